@@ -0,0 +1,105 @@
+//! Azure AD 访问令牌
+//! 以托管标识（实例元数据服务）或应用注册的客户端凭据获取 Azure SQL 的访问令牌，用于建立数据源连接。
+//! 令牌缓存在内存中，距到期不足 `aad.refresh_margin_secs` 时重新获取；已建立的连接不受令牌到期影响，
+//! 之后新建的连接使用新令牌。
+
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+use crate::config::{AadAuthMethod, AadConfig};
+
+/// Azure SQL 的令牌作用域
+const SQL_RESOURCE: &str = "https://database.windows.net/";
+/// 实例元数据服务的令牌地址
+const IMDS_TOKEN_URL: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+
+/// 缓存的令牌
+struct CachedToken {
+    token: String,
+    expires_at: Instant,
+}
+
+/// 访问令牌提供者
+pub struct TokenProvider {
+    config: AadConfig,
+    client: reqwest::Client,
+    cached: tokio::sync::Mutex<Option<CachedToken>>,
+}
+
+impl TokenProvider {
+    pub fn new(config: &AadConfig) -> Self {
+        Self {
+            config: config.clone(),
+            client: reqwest::Client::new(),
+            cached: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// 返回有效的访问令牌，缓存的令牌即将到期时重新获取
+    pub async fn token(&self) -> Result<String> {
+        let mut cached = self.cached.lock().await;
+        let margin = Duration::from_secs(self.config.refresh_margin_secs);
+        if let Some(cached) = cached.as_ref().filter(|c| Instant::now() + margin < c.expires_at) {
+            return Ok(cached.token.clone());
+        }
+
+        let (token, expires_in) = self.fetch().await?;
+        info!("已获取 Azure AD 访问令牌，{} 秒后到期", expires_in);
+        *cached = Some(CachedToken {
+            token: token.clone(),
+            expires_at: Instant::now() + Duration::from_secs(expires_in),
+        });
+        Ok(token)
+    }
+
+    /// 请求新令牌，返回令牌与有效期（秒）
+    async fn fetch(&self) -> Result<(String, u64)> {
+        let request = match self.config.method {
+            AadAuthMethod::ManagedIdentity => {
+                debug!("从实例元数据服务获取托管标识令牌");
+                let mut query = vec![("api-version", "2018-02-01"), ("resource", SQL_RESOURCE)];
+                if !self.config.client_id.is_empty() {
+                    query.push(("client_id", self.config.client_id.as_str()));
+                }
+                self.client.get(IMDS_TOKEN_URL).header("Metadata", "true").query(&query)
+            }
+            AadAuthMethod::ClientCredentials => {
+                debug!("以客户端凭据获取 Azure AD 令牌");
+                let url = format!("https://login.microsoftonline.com/{}/oauth2/v2.0/token", self.config.tenant_id);
+                let scope = format!("{}.default", SQL_RESOURCE);
+                self.client.post(url).form(&[
+                    ("grant_type", "client_credentials"),
+                    ("client_id", self.config.client_id.as_str()),
+                    ("client_secret", self.config.client_secret.as_str()),
+                    ("scope", scope.as_str()),
+                ])
+            }
+        };
+
+        let response = request
+            .timeout(Duration::from_secs(30))
+            .send()
+            .await
+            .map_err(|e| anyhow!("请求 Azure AD 令牌失败: {}", e))?;
+        let status = response.status();
+        let body: Value = response.json().await
+            .map_err(|e| anyhow!("解析 Azure AD 令牌响应失败: {}", e))?;
+        if !status.is_success() {
+            let reason = body.get("error_description").or_else(|| body.get("error")).unwrap_or(&body);
+            return Err(anyhow!("获取 Azure AD 令牌失败（{}）: {}", status, reason));
+        }
+
+        let token = body["access_token"].as_str()
+            .ok_or_else(|| anyhow!("Azure AD 令牌响应中没有 access_token"))?;
+        // 实例元数据服务以字符串返回有效期
+        let expires_in = match &body["expires_in"] {
+            Value::Number(n) => n.as_u64(),
+            Value::String(s) => s.parse().ok(),
+            _ => None,
+        }
+        .ok_or_else(|| anyhow!("Azure AD 令牌响应中没有有效的 expires_in"))?;
+        Ok((token.to_string(), expires_in))
+    }
+}
@@ -0,0 +1,269 @@
+//! 同步告警
+//! 连续 `alerts.failure_threshold` 个更新周期失败时发送 `sync_failing`，缓存最新数据落后当前时间超过
+//! `alerts.lag_threshold_secs` 时发送 `data_lagging`；条件解除时分别发送 `sync_recovered` 与 `lag_recovered`。
+//! 同一条件持续期间只告警一次；启用 `spc.notify` 时每条 SPC 违规事件另发送一条 `spc_violation`，
+//! 定时导出任务失败时发送 `export_failed`（按任务配置在成功时发送 `export_succeeded`），
+//! 数据源熔断与恢复时发送 `circuit_open` 与 `circuit_closed`。
+//! 发送失败只记录日志，不影响同步。
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::config::{AlertConfig, AlertWebhookConfig, AppConfig};
+use crate::spc::SpcEvent;
+
+/// 告警事件
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AlertEvent {
+    SyncFailing,
+    SyncRecovered,
+    DataLagging,
+    LagRecovered,
+    SpcViolation,
+    ExportSucceeded,
+    ExportFailed,
+    CircuitOpen,
+    CircuitClosed,
+}
+
+impl AlertEvent {
+    fn as_str(self) -> &'static str {
+        match self {
+            AlertEvent::SyncFailing => "sync_failing",
+            AlertEvent::SyncRecovered => "sync_recovered",
+            AlertEvent::DataLagging => "data_lagging",
+            AlertEvent::LagRecovered => "lag_recovered",
+            AlertEvent::SpcViolation => "spc_violation",
+            AlertEvent::ExportSucceeded => "export_succeeded",
+            AlertEvent::ExportFailed => "export_failed",
+            AlertEvent::CircuitOpen => "circuit_open",
+            AlertEvent::CircuitClosed => "circuit_closed",
+        }
+    }
+}
+
+/// 一条告警的内容
+struct Alert<'a> {
+    event: AlertEvent,
+    timestamp: DateTime<Utc>,
+    consecutive_failures: u32,
+    lag_secs: Option<i64>,
+    error: Option<&'a str>,
+    /// SPC 违规告警对应的事件
+    spc: Option<&'a SpcEvent>,
+    /// 导出告警的任务名与生成的文件数
+    export: Option<(&'a str, usize)>,
+}
+
+/// 已告警、尚未恢复的条件
+#[derive(Debug, Default)]
+struct AlertState {
+    failing: bool,
+    lagging: bool,
+}
+
+/// 同步告警器
+pub struct Alerter {
+    config: AlertConfig,
+    /// 是否发送 SPC 违规告警
+    spc_notify: bool,
+    state: Mutex<AlertState>,
+    client: reqwest::Client,
+}
+
+impl Alerter {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            config: config.alerts.clone(),
+            spc_notify: config.spc.enabled && config.spc.notify,
+            state: Mutex::new(AlertState::default()),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// 每个更新周期结束后调用：`error` 为本周期的错误（成功时为 None），`latest` 为缓存中最新数据的时间
+    pub async fn after_cycle(&self, consecutive_failures: u32, error: Option<&str>, latest: Option<DateTime<Utc>>) {
+        if self.config.webhooks.is_empty() {
+            return;
+        }
+
+        let now = Utc::now();
+        let lag_secs = latest.map(|latest| (now - latest).num_seconds().max(0));
+        let events = {
+            let mut state = self.state.lock().unwrap();
+            let mut events = Vec::new();
+
+            let failing = consecutive_failures >= self.config.failure_threshold;
+            if failing != state.failing {
+                state.failing = failing;
+                events.push(if failing { AlertEvent::SyncFailing } else { AlertEvent::SyncRecovered });
+            }
+
+            // 缓存为空时无法判断滞后，保持原状态
+            if let (true, Some(lag)) = (self.config.lag_threshold_secs > 0, lag_secs) {
+                let lagging = lag > self.config.lag_threshold_secs as i64;
+                if lagging != state.lagging {
+                    state.lagging = lagging;
+                    events.push(if lagging { AlertEvent::DataLagging } else { AlertEvent::LagRecovered });
+                }
+            }
+            events
+        };
+
+        for event in events {
+            let alert = Alert { event, timestamp: now, consecutive_failures, lag_secs, error, spc: None, export: None };
+            info!("发送同步告警 {}（连续失败 {} 个周期，最新数据落后 {:?} 秒）", event.as_str(), consecutive_failures, lag_secs);
+            self.send_all(&alert).await;
+        }
+    }
+
+    /// 为本周期产生的每条 SPC 违规事件发送 `spc_violation` 告警
+    pub async fn spc_violations(&self, events: &[SpcEvent]) {
+        if !self.spc_notify || self.config.webhooks.is_empty() {
+            return;
+        }
+
+        for event in events {
+            let alert = Alert {
+                event: AlertEvent::SpcViolation,
+                timestamp: event.timestamp,
+                consecutive_failures: 0,
+                lag_secs: None,
+                error: None,
+                spc: Some(event),
+                export: None,
+            };
+            info!("发送 SPC 违规告警: 标签 {} 触发规则 {}", event.tag_name, event.rule.code());
+            self.send_all(&alert).await;
+        }
+    }
+
+    /// 发送定时导出任务的结果：`error` 为 None 时发送 `export_succeeded`，否则发送 `export_failed`
+    pub async fn export_result(&self, job: &str, files: usize, error: Option<&str>) {
+        if self.config.webhooks.is_empty() {
+            return;
+        }
+
+        let event = if error.is_some() { AlertEvent::ExportFailed } else { AlertEvent::ExportSucceeded };
+        let alert = Alert {
+            event,
+            timestamp: Utc::now(),
+            consecutive_failures: 0,
+            lag_secs: None,
+            error,
+            spc: None,
+            export: Some((job, files)),
+        };
+        info!("发送导出告警 {}: 任务 {}", event.as_str(), job);
+        self.send_all(&alert).await;
+    }
+
+    /// 发送数据源熔断（`open` 为真）或恢复告警，`error` 为触发熔断的错误
+    pub async fn circuit(&self, open: bool, consecutive_failures: u32, error: Option<&str>) {
+        if self.config.webhooks.is_empty() {
+            return;
+        }
+
+        let event = if open { AlertEvent::CircuitOpen } else { AlertEvent::CircuitClosed };
+        let alert = Alert {
+            event,
+            timestamp: Utc::now(),
+            consecutive_failures,
+            lag_secs: None,
+            error,
+            spc: None,
+            export: None,
+        };
+        info!("发送熔断告警 {}（连续失败 {} 个周期）", event.as_str(), consecutive_failures);
+        self.send_all(&alert).await;
+    }
+
+    /// 向全部 webhook 发送一条告警
+    async fn send_all(&self, alert: &Alert<'_>) {
+        for webhook in &self.config.webhooks {
+            if let Err(e) = self.send(webhook, alert).await {
+                warn!("发送告警到 {} 失败: {}", webhook.name, e);
+            }
+        }
+    }
+
+    /// 向一个 webhook POST 告警
+    async fn send(&self, webhook: &AlertWebhookConfig, alert: &Alert<'_>) -> Result<()> {
+        let payload = render_payload(webhook, alert)?;
+        let mut request = self.client
+            .post(&webhook.url)
+            .timeout(Duration::from_secs(webhook.timeout_secs))
+            .json(&payload);
+        for (name, value) in &webhook.headers {
+            request = request.header(name, value);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("服务器返回 {}: {}", status, body.chars().take(200).collect::<String>()));
+        }
+        Ok(())
+    }
+}
+
+/// 渲染 JSON 负载
+fn render_payload(webhook: &AlertWebhookConfig, alert: &Alert<'_>) -> Result<Value> {
+    let Some(template) = &webhook.template else {
+        if let Some((job, files)) = alert.export {
+            return Ok(serde_json::json!({
+                "event": alert.event.as_str(),
+                "timestamp": alert.timestamp.to_rfc3339(),
+                "job": job,
+                "files": files,
+                "error": alert.error,
+            }));
+        }
+        if let Some(spc) = alert.spc {
+            return Ok(serde_json::json!({
+                "event": alert.event.as_str(),
+                "timestamp": alert.timestamp.to_rfc3339(),
+                "tag": spc.tag_name,
+                "rule": spc.rule.code(),
+                "value": spc.value,
+                "mean": spc.mean,
+                "sigma": spc.sigma,
+            }));
+        }
+        return Ok(serde_json::json!({
+            "event": alert.event.as_str(),
+            "timestamp": alert.timestamp.to_rfc3339(),
+            "consecutive_failures": alert.consecutive_failures,
+            "lag_secs": alert.lag_secs,
+            "error": alert.error,
+        }));
+    };
+
+    // 字符串按 JSON 字符串转义（去掉两端的引号），模板中放在引号内使用
+    let escape = |value: &str| {
+        let quoted = Value::String(value.to_string()).to_string();
+        quoted[1..quoted.len() - 1].to_string()
+    };
+    let number = |value: Option<f64>| value.map_or("null".to_string(), |v| Value::from(v).to_string());
+    let rendered = template
+        .replace("{{event}}", alert.event.as_str())
+        .replace("{{timestamp}}", &alert.timestamp.to_rfc3339())
+        .replace("{{error}}", &escape(alert.error.unwrap_or_default()))
+        .replace("{{consecutive_failures}}", &alert.consecutive_failures.to_string())
+        .replace("{{lag_secs}}", &alert.lag_secs.map_or("null".to_string(), |lag| lag.to_string()))
+        .replace("{{tag}}", &escape(alert.spc.map_or("", |spc| spc.tag_name.as_str())))
+        .replace("{{rule}}", alert.spc.map_or("", |spc| spc.rule.code()))
+        .replace("{{value}}", &number(alert.spc.map(|spc| spc.value)))
+        .replace("{{mean}}", &number(alert.spc.map(|spc| spc.mean)))
+        .replace("{{sigma}}", &number(alert.spc.map(|spc| spc.sigma)))
+        .replace("{{job}}", &escape(alert.export.map_or("", |(job, _)| job)))
+        .replace("{{files}}", &alert.export.map_or("null".to_string(), |(_, files)| files.to_string()));
+
+    serde_json::from_str(&rendered)
+        .map_err(|e| anyhow!("告警 webhook {} 的模板渲染结果不是合法 JSON: {}", webhook.name, e))
+}
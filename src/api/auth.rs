@@ -0,0 +1,209 @@
+//! API 认证
+//! 认证提供方：Bearer 令牌（admin_token、replication_token 与 api_keys）与 LDAP/AD（HTTP Basic 认证）
+
+use axum::http::{HeaderMap, StatusCode};
+use base64::Engine;
+use ldap3::{LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use subtle::ConstantTimeEq;
+use tracing::{info, warn};
+
+use super::{ApiError, ApiState};
+use crate::config::{ApiConfig, ApiRole, LdapConfig};
+
+/// 认证提供方
+enum AuthProvider<'a> {
+    /// 固定令牌，授予指定角色
+    Token { name: &'a str, token: &'a str, roles: Vec<ApiRole> },
+    /// LDAP/AD 简单绑定，按所属组映射角色
+    Ldap(&'a LdapConfig),
+}
+
+impl AuthProvider<'_> {
+    /// 该提供方是否可能授予指定角色
+    fn can_grant(&self, role: ApiRole) -> bool {
+        match self {
+            AuthProvider::Token { roles, .. } => has_role(roles, role),
+            AuthProvider::Ldap(ldap) => ldap.group_roles.values().any(|r| *r == role || *r == ApiRole::Admin),
+        }
+    }
+}
+
+/// LDAP 认证结果缓存：认证成功后在 `cache_secs` 内以相同账号与密码认证时不再访问服务器
+///
+/// 以账号与密码的摘要为键，不保存明文密码；认证失败不缓存。
+#[derive(Default)]
+pub(super) struct LdapCache {
+    entries: Mutex<HashMap<CacheKey, (Vec<ApiRole>, Instant)>>,
+}
+
+/// 账号与密码的 SHA-256 摘要
+type CacheKey = [u8; 32];
+
+impl LdapCache {
+    fn key(user: &str, password: &str) -> CacheKey {
+        let mut hasher = Sha256::new();
+        hasher.update(user.as_bytes());
+        hasher.update([0]);
+        hasher.update(password.as_bytes());
+        hasher.finalize().into()
+    }
+
+    /// 未过期的缓存角色
+    fn get(&self, key: &CacheKey, ttl: Duration) -> Option<Vec<ApiRole>> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(key)
+            .filter(|(_, cached_at)| cached_at.elapsed() < ttl)
+            .map(|(roles, _)| roles.clone())
+    }
+
+    /// 缓存认证成功的角色，同时清除过期条目
+    fn insert(&self, key: CacheKey, roles: Vec<ApiRole>, ttl: Duration) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (_, cached_at)| cached_at.elapsed() < ttl);
+        entries.insert(key, (roles, Instant::now()));
+    }
+}
+
+/// 角色列表是否包含指定角色（admin 包含全部角色）
+fn has_role(roles: &[ApiRole], role: ApiRole) -> bool {
+    roles.iter().any(|r| *r == role || *r == ApiRole::Admin)
+}
+
+/// 按配置构建认证提供方
+fn providers(config: &ApiConfig) -> Vec<AuthProvider<'_>> {
+    let mut providers = Vec::new();
+    if let Some(token) = config.admin_token.as_deref().filter(|t| !t.is_empty()) {
+        providers.push(AuthProvider::Token { name: "admin_token", token, roles: vec![ApiRole::Admin] });
+    }
+    if let Some(token) = config.replication_token.as_deref().filter(|t| !t.is_empty()) {
+        providers.push(AuthProvider::Token { name: "replication_token", token, roles: vec![ApiRole::Replication] });
+    }
+    for key in config.api_keys.iter().filter(|k| !k.key.is_empty()) {
+        providers.push(AuthProvider::Token { name: &key.name, token: &key.key, roles: key.roles.clone() });
+    }
+    if let Some(ldap) = &config.ldap {
+        providers.push(AuthProvider::Ldap(ldap));
+    }
+    providers
+}
+
+/// 校验请求具有指定角色，返回认证主体名称
+///
+/// `Authorization: Bearer <令牌>` 由令牌提供方校验，`Authorization: Basic <账号:密码>` 由 LDAP/AD 校验；
+/// 没有任何提供方可以授予该角色时接口视为禁用。
+pub(super) async fn authorize(state: &ApiState, headers: &HeaderMap, role: ApiRole) -> Result<String, ApiError> {
+    let providers = providers(&state.config.api);
+    if !providers.iter().any(|p| p.can_grant(role)) {
+        return Err(ApiError {
+            status: StatusCode::FORBIDDEN,
+            message: format!("未配置可授予 {:?} 角色的认证方式，该接口已禁用", role),
+        });
+    }
+
+    let unauthorized = || ApiError {
+        status: StatusCode::UNAUTHORIZED,
+        message: "认证失败".to_string(),
+    };
+    let forbidden = |principal: &str| ApiError {
+        status: StatusCode::FORBIDDEN,
+        message: format!("{} 没有 {:?} 角色", principal, role),
+    };
+
+    let header = headers.get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(unauthorized)?;
+
+    if let Some(token) = header.strip_prefix("Bearer ") {
+        for provider in &providers {
+            if let AuthProvider::Token { name, token: expected, roles } = provider
+                && bool::from(token.as_bytes().ct_eq(expected.as_bytes())) {
+                return if has_role(roles, role) { Ok(name.to_string()) } else { Err(forbidden(name)) };
+            }
+        }
+        return Err(unauthorized());
+    }
+
+    if let Some(encoded) = header.strip_prefix("Basic ") {
+        let ldap = providers.iter()
+            .find_map(|p| match p {
+                AuthProvider::Ldap(ldap) => Some(*ldap),
+                _ => None,
+            })
+            .ok_or_else(unauthorized)?;
+
+        let decoded = base64::engine::general_purpose::STANDARD.decode(encoded.trim()).ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(unauthorized)?;
+        let (user, password) = decoded.split_once(':').ok_or_else(unauthorized)?;
+
+        let ttl = Duration::from_secs(ldap.cache_secs);
+        let key = LdapCache::key(user, password);
+        if let Some(roles) = state.ldap_cache.get(&key, ttl) {
+            return if has_role(&roles, role) { Ok(user.to_string()) } else { Err(forbidden(user)) };
+        }
+
+        let roles = match ldap_roles(ldap, user, password).await {
+            Ok(Some(roles)) => roles,
+            Ok(None) => {
+                warn!(target: "audit", "LDAP 认证失败: 用户 {}", user);
+                return Err(unauthorized());
+            }
+            Err(e) => {
+                return Err(ApiError {
+                    status: StatusCode::BAD_GATEWAY,
+                    message: format!("LDAP 服务器不可用: {}", e),
+                });
+            }
+        };
+
+        info!(target: "audit", "LDAP 认证成功: 用户 {}，角色 {:?}", user, roles);
+        if ldap.cache_secs > 0 {
+            state.ldap_cache.insert(key, roles.clone(), ttl);
+        }
+        return if has_role(&roles, role) { Ok(user.to_string()) } else { Err(forbidden(user)) };
+    }
+
+    Err(unauthorized())
+}
+
+/// 以用户账号绑定 LDAP 并按所属组映射角色，账号或密码错误时返回 None
+async fn ldap_roles(ldap: &LdapConfig, user: &str, password: &str) -> anyhow::Result<Option<Vec<ApiRole>>> {
+    // 空密码会被服务器当作匿名绑定，直接拒绝
+    if user.is_empty() || password.is_empty() {
+        return Ok(None);
+    }
+
+    let timeout = Duration::from_secs(ldap.timeout_secs.max(1));
+    let settings = LdapConnSettings::new().set_conn_timeout(timeout);
+    let (conn, mut client) = LdapConnAsync::with_settings(settings, &ldap.url).await?;
+    ldap3::drive!(conn);
+    client.with_timeout(timeout);
+
+    let bind_dn = ldap.bind_dn_template.replace("{user}", &ldap3::dn_escape(user));
+    if client.simple_bind(&bind_dn, password).await?.success().is_err() {
+        let _ = client.unbind().await;
+        return Ok(None);
+    }
+
+    let filter = ldap.user_filter.replace("{user}", &ldap3::ldap_escape(user));
+    let (entries, _) = client.search(&ldap.base_dn, Scope::Subtree, &filter, vec!["memberOf"]).await?.success()?;
+    let _ = client.unbind().await;
+
+    let mut roles = Vec::new();
+    for entry in entries {
+        let entry = SearchEntry::construct(entry);
+        for group in entry.attrs.get("memberOf").into_iter().flatten() {
+            let mapped = ldap.group_roles.iter()
+                .find(|(dn, _)| dn.eq_ignore_ascii_case(group))
+                .map(|(_, role)| *role);
+            if let Some(role) = mapped.filter(|r| !roles.contains(r)) {
+                roles.push(role);
+            }
+        }
+    }
+    Ok(Some(roles))
+}
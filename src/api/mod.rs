@@ -0,0 +1,1232 @@
+//! HTTP API 模块
+//! 提供基于本地 DuckDB 缓存的查询与分析接口
+
+mod auth;
+mod passthrough;
+mod serialize;
+mod share;
+mod snapshot;
+mod trace;
+
+use anyhow::Result;
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::Notify;
+use tracing::{Instrument, info, error, warn};
+
+use crate::backup;
+use crate::config::{Aggregation, ApiRole, AppConfig, FillMethod, MissingCells, ShedStage, StorageMode, TableShape};
+use crate::database::{self, ChangeLog, ChangesPage, ColdPartition, DatabaseManager, Forecast, LatestValue, QueryInterrupts, RetentionHold, Sparkline, StateReport, SyncCycleStats, TagColumn, TagMeta, TagSeries};
+use crate::degradation::{self, DegradationStatus};
+use crate::energy::{self, DailyConsumption};
+use crate::low_latency::LatencyReport;
+use crate::metrics;
+use crate::schema_doc;
+use crate::stale::StaleTag;
+use crate::sync_service::{ServiceStatus, SyncService};
+use crate::toggles::{self, ToggleState};
+use serialize::Format;
+
+/// API 共享状态
+pub struct ApiState {
+    pub config: Arc<AppConfig>,
+    pub db_manager: Arc<DatabaseManager>,
+    queries: QueryTracker,
+    snapshots: snapshot::SnapshotLimiter,
+    shares: share::ShareLimiter,
+    ldap_cache: auth::LdapCache,
+    sync_trigger: Arc<Notify>,
+    /// 主同步配置的同步服务，跟随模式下为 None
+    sync_service: Option<Arc<SyncService>>,
+}
+
+impl ApiState {
+    /// 创建 API 共享状态
+    pub fn new(
+        config: Arc<AppConfig>,
+        db_manager: Arc<DatabaseManager>,
+        sync_trigger: Arc<Notify>,
+        sync_service: Option<Arc<SyncService>>,
+    ) -> Self {
+        Self {
+            config,
+            db_manager,
+            queries: QueryTracker::default(),
+            snapshots: snapshot::SnapshotLimiter::default(),
+            shares: share::ShareLimiter::default(),
+            ldap_cache: auth::LdapCache::default(),
+            sync_trigger,
+            sync_service,
+        }
+    }
+}
+
+/// 正在执行的查询
+struct RunningQuery {
+    endpoint: &'static str,
+    /// 发起查询的请求 ID
+    request_id: Option<String>,
+    started_at: DateTime<Utc>,
+    started: Instant,
+    interrupts: Arc<QueryInterrupts>,
+}
+
+/// 正在执行的查询登记表
+#[derive(Default)]
+struct QueryTracker {
+    next_id: AtomicU64,
+    running: Mutex<HashMap<u64, RunningQuery>>,
+}
+
+/// 查询登记守卫：请求被丢弃（客户端断开）时中断查询并注销
+struct QueryGuard<'a> {
+    id: u64,
+    tracker: &'a QueryTracker,
+    finished: bool,
+}
+
+impl Drop for QueryGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(query) = self.tracker.running.lock().unwrap().remove(&self.id)
+            && !self.finished {
+            warn!("客户端已断开，取消查询 #{} ({})", self.id, query.endpoint);
+            query.interrupts.interrupt();
+        }
+    }
+}
+
+/// API 错误，转换为带状态码的 JSON 响应
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    /// 请求参数错误
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+        }
+    }
+
+    /// 服务内部错误
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: message.into(),
+        }
+    }
+
+    /// 服务暂时不可用
+    pub fn unavailable(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            message: message.into(),
+        }
+    }
+}
+
+/// 资源压力降级期间拒绝重型查询（Arrow 导出、快照下载、分享导出）
+fn check_heavy_query() -> Result<(), ApiError> {
+    if degradation::get().shedding(ShedStage::HeavyQueries) {
+        return Err(ApiError::unavailable("资源压力降级中，暂停重型查询，请稍后重试"));
+    }
+    Ok(())
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if self.status.is_server_error() {
+            error!("API 请求处理失败: {}", self.message);
+        }
+        (self.status, Json(serde_json::json!({ "error": self.message }))).into_response()
+    }
+}
+
+/// 在阻塞线程池中执行可取消的 DuckDB 操作
+///
+/// 查询在执行期间登记到查询列表；客户端断开导致请求被丢弃或管理员终止时，中断底层 DuckDB 查询。
+async fn run_blocking<T, F>(state: &ApiState, endpoint: &'static str, f: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Box<dyn std::error::Error + Send + Sync>> + Send + 'static,
+{
+    let interrupts = Arc::new(QueryInterrupts::default());
+    let id = state.queries.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    state.queries.running.lock().unwrap().insert(id, RunningQuery {
+        endpoint,
+        request_id: trace::current(),
+        started_at: Utc::now(),
+        started: Instant::now(),
+        interrupts: interrupts.clone(),
+    });
+    let mut guard = QueryGuard { id, tracker: &state.queries, finished: false };
+
+    let task_interrupts = interrupts.clone();
+    // 阻塞线程中的日志沿用请求的 span（带请求 ID）
+    let span = tracing::Span::current();
+    let result = tokio::task::spawn_blocking(move || {
+        span.in_scope(|| database::run_interruptible(task_interrupts, f))
+    }).await;
+    guard.finished = true;
+
+    match result {
+        Err(e) => Err(ApiError::internal(format!("查询任务异常终止: {}", e))),
+        Ok(Err(_)) if interrupts.is_cancelled() => Err(ApiError {
+            status: StatusCode::CONFLICT,
+            message: format!("查询 #{} 已被取消", id),
+        }),
+        Ok(Err(e)) => Err(ApiError::internal(e.to_string())),
+        Ok(Ok(value)) => Ok(value),
+    }
+}
+
+/// 标签时间范围查询参数
+#[derive(Debug, Deserialize)]
+pub struct TagRangeParams {
+    pub tag: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+impl TagRangeParams {
+    /// 校验时间范围
+    fn validate(&self) -> Result<(), ApiError> {
+        if self.to <= self.from {
+            return Err(ApiError::bad_request("参数 to 必须晚于 from"));
+        }
+        Ok(())
+    }
+}
+
+/// 构建 API 路由
+pub fn router(state: Arc<ApiState>) -> Router {
+    Router::new()
+        .route("/analysis/state-report", get(state_report))
+        .route("/energy/consumption", get(energy_consumption))
+        .route("/energy/daily", get(energy_daily))
+        .route("/latest", get(latest_values))
+        .route("/tags/sparklines", get(tag_sparklines))
+        .route("/tags/forecast", get(tag_forecasts))
+        .route("/tags/values", get(tag_values))
+        .route("/tags/aggregate", get(tag_aggregates))
+        .route("/tags/resample", get(tag_resample))
+        .route("/tags/table", get(tag_table))
+        .route("/tags/text", get(tag_text_values))
+        .route("/tags/columns", get(tag_columns))
+        .route("/tags/meta", get(tag_meta))
+        .route("/status", get(service_status))
+        .route("/status/sync-log", get(sync_log))
+        .route("/status/stale-tags", get(stale_tags))
+        .route("/status/latency", get(latency))
+        .route("/status/degradation", get(degradation_status))
+        .route("/metrics", get(prometheus_metrics))
+        .route("/schema-doc", get(schema_doc))
+        .route("/export/arrow", get(export_arrow))
+        .route("/archive/partitions", get(cold_partitions))
+        .route("/replication/log", get(replication_log))
+        .route("/replication/changes", get(replication_changes))
+        .route("/download/snapshot", get(snapshot::download_snapshot))
+        .route("/shared/export", get(share::shared_export))
+        .route("/query/sql", post(passthrough::run_sql))
+        .route("/admin/queries", get(list_queries))
+        .route("/admin/queries/{id}", delete(kill_query))
+        .route("/admin/sync", post(trigger_sync))
+        .route("/admin/backup", post(create_backup))
+        .route("/admin/share", post(share::create_share_link))
+        .route("/admin/share/{id}", delete(share::revoke_share_link))
+        .route("/admin/toggles", get(get_toggles).put(update_toggles))
+        .route("/admin/holds", get(list_holds).post(create_hold))
+        .route("/admin/holds/{id}", delete(release_hold))
+        .layer(axum::middleware::from_fn(trace::middleware))
+        .with_state(state)
+}
+
+/// 启动 HTTP API 服务
+pub async fn serve(state: Arc<ApiState>) -> Result<()> {
+    let bind_addr = state.config.api.bind_addr.clone();
+    let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
+
+    info!("HTTP API 已启动，监听地址: {}", bind_addr);
+    axum::serve(listener, router(state).into_make_service_with_connect_info::<std::net::SocketAddr>()).await?;
+    Ok(())
+}
+
+/// 开关量标签运行状态报告
+async fn state_report(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<TagRangeParams>,
+) -> Result<Json<StateReport>, ApiError> {
+    params.validate()?;
+
+    let db_manager = state.db_manager.clone();
+    let report = run_blocking(&state, "state-report", move || {
+        db_manager.state_report(&params.tag, params.from, params.to)
+    }).await?;
+
+    Ok(Json(report))
+}
+
+/// 时间段能耗响应
+#[derive(Debug, Serialize)]
+struct ConsumptionResponse {
+    tag: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    consumption: f64,
+}
+
+/// 计数型标签在时间段内的消耗量
+async fn energy_consumption(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<TagRangeParams>,
+) -> Result<Json<ConsumptionResponse>, ApiError> {
+    params.validate()?;
+
+    let rollover = state.config.energy.meter(&params.tag).and_then(|m| m.rollover);
+    let db_manager = state.db_manager.clone();
+    let (tag, from, to) = (params.tag.clone(), params.from, params.to);
+    let samples = run_blocking(&state, "energy-consumption", move || db_manager.get_tag_values(&tag, from, to)).await?;
+
+    Ok(Json(ConsumptionResponse {
+        tag: params.tag,
+        from: params.from,
+        to: params.to,
+        consumption: energy::counter_consumption(&samples, rollover),
+    }))
+}
+
+/// 计数型标签的日消耗量报表
+async fn energy_daily(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<TagRangeParams>,
+) -> Result<Json<Vec<DailyConsumption>>, ApiError> {
+    params.validate()?;
+
+    let rollover = state.config.energy.meter(&params.tag).and_then(|m| m.rollover);
+    let db_manager = state.db_manager.clone();
+    let samples = run_blocking(&state, "energy-daily", move || {
+        db_manager.get_tag_values(&params.tag, params.from, params.to)
+    }).await?;
+
+    Ok(Json(energy::daily_consumption(&samples, rollover)))
+}
+
+/// 时间范围查询每个标签最多返回的点数
+const MAX_RANGE_POINTS: usize = 100_000;
+
+/// 时间范围查询参数
+#[derive(Debug, Deserialize)]
+struct TagValuesParams {
+    /// 逗号分隔的标签列表
+    tags: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    /// 降采样的时间桶宽度，如 `10s`、`1m`、`1h`、`1d`，不给出时返回原始值
+    every: Option<String>,
+    /// 时间桶内的聚合方式（avg/min/max/sum/stddev/first/last/count/time_weighted_avg/duration_in_state）
+    #[serde(default)]
+    agg: Aggregation,
+}
+
+/// 标签在时间段内的值，可在服务端按时间桶降采样，按时间升序
+async fn tag_values(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<TagValuesParams>,
+) -> Result<Json<HashMap<String, TagSeries>>, ApiError> {
+    if params.to <= params.from {
+        return Err(ApiError::bad_request("参数 to 必须晚于 from"));
+    }
+    let tags: Vec<String> = params.tags.split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(String::from)
+        .collect();
+    if tags.is_empty() {
+        return Err(ApiError::bad_request("参数 tags 不能为空"));
+    }
+
+    let bucket_secs = match params.every.as_deref() {
+        Some(every) => Some(parse_every(every)
+            .ok_or_else(|| ApiError::bad_request(format!("无效的时间桶宽度: {}（示例: 10s、1m、1h、1d）", every)))?),
+        None => None,
+    };
+    if let Some(secs) = bucket_secs {
+        let buckets = (params.to - params.from).num_seconds() as u64 / secs + 1;
+        if buckets > MAX_RANGE_POINTS as u64 {
+            return Err(ApiError::bad_request(format!("时间桶数超过 {} 个，请增大 every", MAX_RANGE_POINTS)));
+        }
+    }
+
+    let db_manager = state.db_manager.clone();
+    let (from, to, aggregation) = (params.from, params.to, params.agg);
+    // 多取一个点用于判断是否超出上限
+    let points = run_blocking(&state, "tag-values", move || {
+        tags.into_iter()
+            .map(|tag| {
+                let points = db_manager.query_tag_range(&tag, from, to, bucket_secs, aggregation, MAX_RANGE_POINTS + 1)?;
+                Ok((tag, points))
+            })
+            .collect::<Result<Vec<_>, Box<dyn std::error::Error + Send + Sync>>>()
+    }).await?;
+
+    let mut series = HashMap::new();
+    for (tag, points) in points {
+        if points.len() > MAX_RANGE_POINTS {
+            return Err(ApiError::bad_request(format!("标签 {} 在该时间段内超过 {} 个点，请使用 every 降采样", tag, MAX_RANGE_POINTS)));
+        }
+        let (timestamps, values) = points.into_iter().unzip();
+        series.insert(tag, TagSeries { timestamps, values });
+    }
+
+    Ok(Json(series))
+}
+
+/// 解析时间桶宽度（正整数加单位 s/m/h/d），返回秒数
+fn parse_every(every: &str) -> Option<u64> {
+    let every = every.trim();
+    let unit = every.chars().last()?;
+    let count: u64 = every[..every.len() - unit.len_utf8()].parse().ok().filter(|n| *n > 0)?;
+    let multiplier = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86400,
+        _ => return None,
+    };
+    count.checked_mul(multiplier)
+}
+
+/// 窗口聚合参数
+#[derive(Debug, Deserialize)]
+struct TagAggregateParams {
+    /// 逗号分隔的标签列表
+    tags: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    /// 窗口宽度，如 `10s`、`1m`、`1h`、`1d`，窗口从 from 开始对齐
+    window: String,
+    /// 聚合方式（avg/min/max/sum/stddev/first/last/count/time_weighted_avg/duration_in_state）
+    #[serde(default)]
+    func: Aggregation,
+}
+
+/// 标签在 [from, to) 内按固定窗口的聚合值，按时间升序，时间为窗口起始时间
+async fn tag_aggregates(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<TagAggregateParams>,
+) -> Result<Json<HashMap<String, TagSeries>>, ApiError> {
+    if params.to <= params.from {
+        return Err(ApiError::bad_request("参数 to 必须晚于 from"));
+    }
+    let tags: Vec<String> = params.tags.split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(String::from)
+        .collect();
+    if tags.is_empty() {
+        return Err(ApiError::bad_request("参数 tags 不能为空"));
+    }
+    let window_secs = parse_every(&params.window)
+        .ok_or_else(|| ApiError::bad_request(format!("无效的窗口宽度: {}（示例: 10s、1m、1h、1d）", params.window)))?;
+    if (params.to - params.from).num_seconds() as u64 / window_secs >= MAX_RANGE_POINTS as u64 {
+        return Err(ApiError::bad_request(format!("窗口数超过 {} 个，请增大 window", MAX_RANGE_POINTS)));
+    }
+
+    let db_manager = state.db_manager.clone();
+    let (from, to, aggregation) = (params.from, params.to, params.func);
+    let series = run_blocking(&state, "tag-aggregate", move || {
+        tags.into_iter()
+            .map(|tag| {
+                let (timestamps, values) = db_manager.aggregate(&tag, from, to, window_secs, aggregation)?.into_iter().unzip();
+                Ok((tag, TagSeries { timestamps, values }))
+            })
+            .collect::<Result<HashMap<_, _>, Box<dyn std::error::Error + Send + Sync>>>()
+    }).await?;
+
+    Ok(Json(series))
+}
+
+/// 重采样参数
+#[derive(Debug, Deserialize)]
+struct ResampleParams {
+    /// 逗号分隔的标签列表
+    tags: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    /// 网格间隔，如 `10s`、`1m`、`1h`、`1d`，网格从 from 开始
+    step: String,
+    /// 默认取值方式（previous/linear）
+    #[serde(default)]
+    fill: FillMethod,
+    /// 逗号分隔的按线性插值的标签，其余标签按 fill
+    linear: Option<String>,
+}
+
+/// 重采样结果：所有标签共用同一组网格时间，缺少值的网格点为 null
+#[derive(Debug, Serialize)]
+struct ResampledFrame {
+    timestamps: Vec<DateTime<Utc>>,
+    values: HashMap<String, Vec<Option<f64>>>,
+}
+
+/// 将标签重采样到同一固定时间网格，便于对齐采样频率不同的标签
+async fn tag_resample(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<ResampleParams>,
+) -> Result<Json<ResampledFrame>, ApiError> {
+    if params.to <= params.from {
+        return Err(ApiError::bad_request("参数 to 必须晚于 from"));
+    }
+    let split = |list: &str| -> Vec<String> {
+        list.split(',').map(str::trim).filter(|t| !t.is_empty()).map(String::from).collect()
+    };
+    let tags = split(&params.tags);
+    if tags.is_empty() {
+        return Err(ApiError::bad_request("参数 tags 不能为空"));
+    }
+    let linear = params.linear.as_deref().map(split).unwrap_or_default();
+    let step_secs = parse_every(&params.step)
+        .ok_or_else(|| ApiError::bad_request(format!("无效的网格间隔: {}（示例: 10s、1m、1h、1d）", params.step)))?;
+    if (params.to - params.from).num_seconds() as u64 / step_secs >= MAX_RANGE_POINTS as u64 {
+        return Err(ApiError::bad_request(format!("网格点数超过 {} 个，请增大 step", MAX_RANGE_POINTS)));
+    }
+
+    let db_manager = state.db_manager.clone();
+    let (from, to, default_fill) = (params.from, params.to, params.fill);
+    let columns = run_blocking(&state, "tag-resample", move || {
+        tags.into_iter()
+            .map(|tag| {
+                let fill = if linear.contains(&tag) { FillMethod::Linear } else { default_fill };
+                let points = db_manager.resample(&tag, from, to, step_secs, fill)?;
+                Ok((tag, points))
+            })
+            .collect::<Result<Vec<_>, Box<dyn std::error::Error + Send + Sync>>>()
+    }).await?;
+
+    let mut frame = ResampledFrame { timestamps: Vec::new(), values: HashMap::new() };
+    for (tag, points) in columns {
+        let (timestamps, values): (Vec<_>, Vec<_>) = points.into_iter().unzip();
+        if frame.timestamps.is_empty() {
+            frame.timestamps = timestamps;
+        }
+        frame.values.insert(tag, values);
+    }
+
+    Ok(Json(frame))
+}
+
+/// 字符串标签的文本值
+#[derive(Debug, Serialize)]
+struct TextValue {
+    timestamp: DateTime<Utc>,
+    value: String,
+}
+
+/// 字符串标签在时间段内的文本值（如运行状态 "RUNNING"/"STOPPED"），按时间升序
+async fn tag_text_values(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<TagRangeParams>,
+) -> Result<Json<Vec<TextValue>>, ApiError> {
+    params.validate()?;
+
+    let db_manager = state.db_manager.clone();
+    let values = run_blocking(&state, "tag-text", move || {
+        db_manager.get_text_values(&params.tag, params.from, params.to)
+    }).await?;
+
+    Ok(Json(values.into_iter().map(|(timestamp, value)| TextValue { timestamp, value }).collect()))
+}
+
+/// 标签到宽表列名的映射（列名冲突时带消歧后缀）
+async fn tag_columns(State(state): State<Arc<ApiState>>) -> Result<Json<Vec<TagColumn>>, ApiError> {
+    if state.config.storage_mode != StorageMode::Wide {
+        return Err(ApiError::bad_request("窄表模式下没有标签列"));
+    }
+
+    let db_manager = state.db_manager.clone();
+    let columns = run_blocking(&state, "tag-columns", move || db_manager.tag_columns()).await?;
+    Ok(Json(columns))
+}
+
+/// 标签元数据查询参数
+#[derive(Debug, Deserialize)]
+struct TagMetaParams {
+    /// 逗号分隔的标签列表，为空时返回全部
+    tags: Option<String>,
+}
+
+/// 标签的单位、描述、量程与输入/输出标志（每个同步周期从 TagDatabase 更新），供界面标注与缩放图表
+async fn tag_meta(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<TagMetaParams>,
+) -> Result<Json<Vec<TagMeta>>, ApiError> {
+    let tags: Vec<String> = params.tags.unwrap_or_default()
+        .split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    let db_manager = state.db_manager.clone();
+    let meta = run_blocking(&state, "tag-meta", move || db_manager.get_tag_meta(&tags)).await?;
+    Ok(Json(meta))
+}
+
+/// 最新值查询参数
+#[derive(Debug, Deserialize)]
+struct LatestParams {
+    /// 逗号分隔的标签列表
+    tags: String,
+}
+
+/// 各标签最新的非空值与其时间，没有数据的标签不出现在结果中
+async fn latest_values(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<LatestParams>,
+) -> Result<Json<HashMap<String, LatestValue>>, ApiError> {
+    let tags: Vec<String> = params.tags.split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(String::from)
+        .collect();
+    if tags.is_empty() {
+        return Err(ApiError::bad_request("参数 tags 不能为空"));
+    }
+
+    let db_manager = state.db_manager.clone();
+    let latest = run_blocking(&state, "latest", move || db_manager.get_latest_values(&tags)).await?;
+    Ok(Json(latest))
+}
+
+/// 缩略趋势查询参数
+#[derive(Debug, Deserialize)]
+struct SparklineParams {
+    /// 逗号分隔的标签列表，为空时返回全部
+    tags: Option<String>,
+}
+
+/// 预计算的标签缩略趋势
+async fn tag_sparklines(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<SparklineParams>,
+) -> Result<Json<HashMap<String, Sparkline>>, ApiError> {
+    if !state.db_manager.toggles().sparklines() {
+        return Err(ApiError::bad_request("未启用缩略趋势预计算（sparkline.enabled 或运行时开关 sparklines）"));
+    }
+
+    let sparklines = state.db_manager.sparklines();
+    let result = match params.tags.as_deref().filter(|t| !t.is_empty()) {
+        Some(tags) => tags.split(',')
+            .map(str::trim)
+            .filter_map(|tag| sparklines.get(tag).map(|s| (tag.to_string(), s.clone())))
+            .collect(),
+        None => (*sparklines).clone(),
+    };
+
+    Ok(Json(result))
+}
+
+/// 标签的最近一次趋势预测，与实际值叠加显示
+async fn tag_forecasts(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<SparklineParams>,
+) -> Result<Json<HashMap<String, Forecast>>, ApiError> {
+    if !state.config.forecast.enabled {
+        return Err(ApiError::bad_request("未启用趋势预测（forecast.enabled）"));
+    }
+
+    let tags: Vec<String> = params.tags.as_deref()
+        .map(|tags| tags.split(',').map(str::trim).filter(|t| !t.is_empty()).map(String::from).collect())
+        .unwrap_or_default();
+    let db_manager = state.db_manager.clone();
+    let forecasts = run_blocking(&state, "tag-forecast", move || db_manager.forecasts(&tags)).await?;
+    Ok(Json(forecasts))
+}
+
+/// 数据字典查询参数
+#[derive(Debug, Deserialize)]
+struct SchemaDocParams {
+    /// md（默认）或 html
+    format: Option<String>,
+}
+
+/// 缓存数据字典（表、标签、单位与说明、保留策略）
+async fn schema_doc(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<SchemaDocParams>,
+) -> Result<Response, ApiError> {
+    let format: schema_doc::DocFormat = params.format.as_deref().unwrap_or("md").parse()
+        .map_err(ApiError::bad_request)?;
+    let content_type = match format {
+        schema_doc::DocFormat::Markdown => "text/markdown; charset=utf-8",
+        schema_doc::DocFormat::Html => "text/html; charset=utf-8",
+    };
+
+    let db_manager = state.db_manager.clone();
+    let config = state.config.clone();
+    let doc = run_blocking(&state, "schema-doc", move || {
+        schema_doc::generate(&db_manager, &config, format)
+    }).await?;
+
+    Ok(([(axum::http::header::CONTENT_TYPE, content_type)], doc).into_response())
+}
+
+/// Arrow 导出查询参数，时间范围为 [from, to)，未给出的边界不限
+#[derive(Debug, Deserialize)]
+struct ArrowExportParams {
+    /// 逗号分隔的标签列表，为空时为全部标签
+    #[serde(default)]
+    tags: String,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+/// 以 Arrow IPC 流分块返回数据，Python 端可直接用 `pyarrow.ipc.open_stream` 读取
+async fn export_arrow(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<ArrowExportParams>,
+) -> Result<Response, ApiError> {
+    check_heavy_query()?;
+    if let (Some(from), Some(to)) = (params.from, params.to)
+        && to <= from {
+        return Err(ApiError::bad_request("参数 to 必须晚于 from"));
+    }
+    let tags: Vec<String> = params.tags.split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    let db_manager = state.db_manager.clone();
+    let (from, to) = (params.from, params.to);
+    Ok(stream_response(state, "export-arrow", Format::Arrow.content_type(), move |writer| {
+        db_manager.export_arrow(writer, from, to, &tags)?;
+        Ok(())
+    }))
+}
+
+/// 流式响应每个数据块的大小
+const STREAM_CHUNK_SIZE: usize = 256 * 1024;
+
+/// 以 HTTP 分块传输返回阻塞线程中写出的数据，结果不在内存中整体缓存
+///
+/// 查询与 [`run_blocking`] 一样登记到查询列表，写出的数据按块经有界通道交给响应体，客户端读取慢时查询随之等待；
+/// 客户端断开后写入失败，查询随之结束。响应头在查询开始前发出，查询中途失败时响应体异常结束，客户端会收到不完整的流。
+fn stream_response<F>(state: Arc<ApiState>, endpoint: &'static str, content_type: &'static str, f: F) -> Response
+where
+    F: FnOnce(&mut ChunkWriter) -> Result<(), Box<dyn std::error::Error + Send + Sync>> + Send + 'static,
+{
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<std::io::Result<Vec<u8>>>(4);
+    let error_sender = sender.clone();
+    tokio::spawn(async move {
+        let result = run_blocking(&state, endpoint, move || {
+            let mut writer = ChunkWriter { buffer: Vec::with_capacity(STREAM_CHUNK_SIZE), sender };
+            f(&mut writer)?;
+            std::io::Write::flush(&mut writer)?;
+            Ok(())
+        }).await;
+        if let Err(e) = result {
+            warn!("流式查询 {} 失败: {}", endpoint, e.message);
+            let _ = error_sender.send(Err(std::io::Error::other(e.message))).await;
+        }
+    }.instrument(tracing::Span::current()));
+
+    let chunks = futures::stream::poll_fn(move |cx| receiver.poll_recv(cx));
+    ([(header::CONTENT_TYPE, content_type)], Body::from_stream(chunks)).into_response()
+}
+
+/// 按块把写出的数据发送给流式响应体
+struct ChunkWriter {
+    buffer: Vec<u8>,
+    sender: tokio::sync::mpsc::Sender<std::io::Result<Vec<u8>>>,
+}
+
+impl std::io::Write for ChunkWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() >= STREAM_CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(STREAM_CHUNK_SIZE));
+        self.sender.blocking_send(Ok(chunk))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "客户端已断开"))
+    }
+}
+
+/// 多标签表格查询参数，时间范围为 [from, to)
+#[derive(Debug, Deserialize)]
+struct TagTableParams {
+    /// 逗号分隔的标签列表，为空时为全部标签
+    #[serde(default)]
+    tags: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    /// wide（每个标签一列，默认）或 long（DateTime, TagName, Value）
+    #[serde(default)]
+    shape: TableShape,
+    /// 宽表形状中缺少值的单元格的处理方式：null（默认）、previous（沿用之前最后一个值）或 drop（丢弃不完整的行）
+    missing: Option<MissingCells>,
+    /// json、csv、arrow 或 msgpack，未给出时按 Accept 请求头，默认 json
+    format: Option<String>,
+}
+
+/// 以宽表（按时间对齐）或窄表形状返回多个标签的数值，与存储模式无关；Arrow 格式流式返回全部行，其他格式最多 100000 行
+async fn tag_table(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Query(params): Query<TagTableParams>,
+) -> Result<Response, ApiError> {
+    check_heavy_query()?;
+    if params.to <= params.from {
+        return Err(ApiError::bad_request("参数 to 必须晚于 from"));
+    }
+    if params.shape == TableShape::Long && params.missing.is_some() {
+        return Err(ApiError::bad_request("参数 missing 只适用于 shape=wide"));
+    }
+    let format = Format::negotiate(params.format.as_deref(), &headers)?;
+    let tags: Vec<String> = params.tags.split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    let db_manager = state.db_manager.clone();
+    let (from, to, shape, missing) = (params.from, params.to, params.shape, params.missing.unwrap_or_default());
+    if format == Format::Arrow {
+        // Arrow 格式逐批流式返回，不限行数
+        return Ok(stream_response(state, "tag-table", format.content_type(), move |writer| {
+            db_manager.stream_table(writer, &tags, from, to, shape, missing)?;
+            Ok(())
+        }));
+    }
+    let (body, truncated) = run_blocking(&state, "tag-table", move || {
+        let (batches, truncated) = match shape {
+            TableShape::Wide => db_manager.query_frame(&tags, from, to, missing, MAX_RANGE_POINTS)?,
+            TableShape::Long => db_manager.query_table(&tags, from, to, shape, MAX_RANGE_POINTS)?,
+        };
+        Ok((serialize::encode(&batches, format, truncated)?, truncated))
+    }).await?;
+
+    Ok(serialize::response(body, format, truncated))
+}
+
+/// 冷存储目录查询参数
+#[derive(Debug, Deserialize)]
+struct ColdPartitionParams {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    /// 来源表（ts_wide、ts_long、ts_text 或 ts_quality），未给出时不限
+    table: Option<String>,
+}
+
+/// 冷存储目录中与 [from, to) 有交集的 Parquet 分区文件，按时间升序
+async fn cold_partitions(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<ColdPartitionParams>,
+) -> Result<Json<Vec<ColdPartition>>, ApiError> {
+    if !state.config.archive.enabled {
+        return Err(ApiError::bad_request("未启用归档（archive.enabled）"));
+    }
+    if let (Some(from), Some(to)) = (params.from, params.to)
+        && to <= from {
+        return Err(ApiError::bad_request("参数 to 必须晚于 from"));
+    }
+
+    let db_manager = state.db_manager.clone();
+    let partitions = run_blocking(&state, "cold-partitions", move || {
+        db_manager.cold_partitions(params.from, params.to, params.table.as_deref())
+    }).await?;
+    Ok(Json(partitions))
+}
+
+/// 同步周期统计查询参数
+#[derive(Debug, Deserialize)]
+struct SyncLogParams {
+    /// 最多返回的周期数
+    limit: Option<usize>,
+}
+
+/// 最近的同步周期统计，按时间倒序
+async fn sync_log(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<SyncLogParams>,
+) -> Result<Json<Vec<SyncCycleStats>>, ApiError> {
+    let limit = params.limit.unwrap_or(100).clamp(1, 10_000);
+    let db_manager = state.db_manager.clone();
+    let cycles = run_blocking(&state, "sync-log", move || db_manager.recent_sync_cycles(limit)).await?;
+
+    Ok(Json(cycles))
+}
+
+/// 当前停滞的标签与数据源时间过期的标签（需启用 `[stale_tags]` 或源时间戳模式的过期检查）
+async fn stale_tags(State(state): State<Arc<ApiState>>) -> Result<Json<Vec<StaleTag>>, ApiError> {
+    let timestamps = &state.config.timestamps;
+    let source_time_checked = timestamps.use_source_time && timestamps.stale_after_secs > 0;
+    if !state.config.stale_tags.enabled && !source_time_checked {
+        return Err(ApiError::bad_request("未启用停滞标签检测（stale_tags.enabled）或数据源时间过期检查（timestamps.stale_after_secs）"));
+    }
+    Ok(Json(state.db_manager.staleness().stale_tags()))
+}
+
+/// 低延迟模式的延迟统计（需启用 `[low_latency]`）
+async fn latency(State(state): State<Arc<ApiState>>) -> Result<Json<LatencyReport>, ApiError> {
+    if !state.config.low_latency.enabled {
+        return Err(ApiError::bad_request("未启用低延迟模式（low_latency.enabled）"));
+    }
+    Ok(Json(state.db_manager.latency().report()))
+}
+
+/// 服务状态查询参数
+#[derive(Debug, Deserialize)]
+struct StatusParams {
+    /// json（默认）或 text（与定期状态报告相同的文本）
+    format: Option<String>,
+}
+
+/// 主同步配置的服务状态：记录数、同步进度、失败统计、运行时长、文件大小与标签数
+async fn service_status(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<StatusParams>,
+) -> Result<Response, ApiError> {
+    let Some(sync_service) = state.sync_service.clone() else {
+        return Err(ApiError::bad_request("跟随模式（replica）下没有同步状态"));
+    };
+    let text = match params.format.as_deref() {
+        None | Some("json") => false,
+        Some("text") => true,
+        Some(other) => return Err(ApiError::bad_request(format!("不支持的格式: {}（可选 json、text）", other))),
+    };
+
+    let status: ServiceStatus = run_blocking(&state, "status", move || {
+        sync_service.get_status().map_err(Into::into)
+    }).await?;
+
+    Ok(if text {
+        status.to_string().into_response()
+    } else {
+        Json(status).into_response()
+    })
+}
+
+/// 资源压力降级状态：当前停用的阶段与最近一次资源采样
+async fn degradation_status() -> Json<DegradationStatus> {
+    Json(degradation::get().status())
+}
+
+/// 同步流水线内部指标（Prometheus 文本格式）
+async fn prometheus_metrics(State(state): State<Arc<ApiState>>) -> Response {
+    let (frozen, source_time) = state.db_manager.staleness().counts();
+    let mut body = metrics::render();
+    body.push_str(&metrics::render_gauge(
+        "rt_db_stale_tags",
+        "当前停滞（值未变化）或数据源时间过期的标签数",
+        "reason",
+        &[("frozen", frozen as f64), ("source_time", source_time as f64)],
+    ));
+    ([(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")], body).into_response()
+}
+
+/// 校验管理接口权限（admin 角色）
+async fn require_admin(state: &ApiState, headers: &HeaderMap) -> Result<(), ApiError> {
+    let principal = auth::authorize(state, headers, ApiRole::Admin).await?;
+    info!(target: "audit", "管理接口访问: {}", principal);
+    Ok(())
+}
+
+/// 正在执行的查询信息
+#[derive(Debug, Serialize)]
+struct RunningQueryInfo {
+    id: u64,
+    endpoint: &'static str,
+    request_id: Option<String>,
+    started_at: DateTime<Utc>,
+    elapsed_ms: u128,
+}
+
+/// 列出正在执行的查询
+async fn list_queries(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<RunningQueryInfo>>, ApiError> {
+    require_admin(&state, &headers).await?;
+
+    let mut queries: Vec<RunningQueryInfo> = state.queries.running.lock().unwrap()
+        .iter()
+        .map(|(id, query)| RunningQueryInfo {
+            id: *id,
+            endpoint: query.endpoint,
+            request_id: query.request_id.clone(),
+            started_at: query.started_at,
+            elapsed_ms: query.started.elapsed().as_millis(),
+        })
+        .collect();
+    queries.sort_by_key(|q| q.id);
+
+    Ok(Json(queries))
+}
+
+/// 终止指定查询
+async fn kill_query(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, ApiError> {
+    require_admin(&state, &headers).await?;
+
+    let running = state.queries.running.lock().unwrap();
+    let Some(query) = running.get(&id) else {
+        return Err(ApiError {
+            status: StatusCode::NOT_FOUND,
+            message: format!("查询 #{} 不存在或已结束", id),
+        });
+    };
+
+    info!("管理员终止查询 #{} ({})", id, query.endpoint);
+    query.interrupts.interrupt();
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 请求立即执行一次同步，不等待同步完成
+async fn trigger_sync(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    require_admin(&state, &headers).await?;
+
+    info!("管理员请求立即同步");
+    state.sync_trigger.notify_one();
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// 立即备份缓存到 `[backup]` 配置的目录，返回新备份的目录
+async fn create_backup(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    require_admin(&state, &headers).await?;
+    check_heavy_query()?;
+
+    info!("管理员请求备份");
+    let (config, db_manager) = (state.config.clone(), state.db_manager.clone());
+    let target = run_blocking(&state, "backup", move || {
+        let settings = &config.backup;
+        backup::run(&db_manager, &config, std::path::Path::new(&settings.dir), settings.keep)
+            .map_err(Into::into)
+    }).await?;
+    // 上传在后台进行，不阻塞响应
+    let (config, uploading) = (state.config.clone(), target.clone());
+    tokio::spawn(async move {
+        if let Err(e) = backup::upload(&config, &uploading).await {
+            warn!("上传备份 {} 失败: {}", uploading.display(), e);
+        }
+    });
+    Ok(Json(serde_json::json!({ "path": target.to_string_lossy() })))
+}
+
+/// 功能开关修改请求，未给出的项保持不变
+#[derive(Debug, Deserialize)]
+struct ToggleUpdate {
+    deadband: Option<bool>,
+    sparklines: Option<bool>,
+    parse_logging: Option<bool>,
+    /// 推送目标名称（`export:<任务名>`、`integration:<端点名>`）-> 是否启用
+    #[serde(default)]
+    sinks: HashMap<String, bool>,
+}
+
+/// 运行时功能开关当前状态
+async fn get_toggles(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<Json<ToggleState>, ApiError> {
+    require_admin(&state, &headers).await?;
+    Ok(Json(state.db_manager.toggles().state(&state.config)))
+}
+
+/// 修改运行时功能开关，每项修改记录审计日志
+async fn update_toggles(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Json(update): Json<ToggleUpdate>,
+) -> Result<Json<ToggleState>, ApiError> {
+    let principal = auth::authorize(&state, &headers, ApiRole::Admin).await?;
+
+    let known_sinks = toggles::sink_names(&state.config);
+    if let Some(sink) = update.sinks.keys().find(|sink| !known_sinks.contains(sink)) {
+        return Err(ApiError::bad_request(format!("未知的推送目标: {}（可选 {:?}）", sink, known_sinks)));
+    }
+
+    let toggles = state.db_manager.toggles();
+    if update.deadband == Some(true) && !state.config.deadband.enabled {
+        return Err(ApiError::bad_request("配置中未启用死区过滤（deadband.enabled），运行时只能停用"));
+    }
+    if update.sparklines == Some(true) && !state.config.sparkline.enabled {
+        return Err(ApiError::bad_request("配置中未启用缩略趋势（sparkline.enabled），运行时只能停用"));
+    }
+    let mut changes = Vec::new();
+    if let Some(enabled) = update.deadband {
+        changes.push(format!("deadband {} -> {}", toggles.deadband(), enabled));
+        toggles.set_deadband(enabled);
+    }
+    if let Some(enabled) = update.sparklines {
+        changes.push(format!("sparklines {} -> {}", toggles.sparklines(), enabled));
+        toggles.set_sparklines(enabled);
+    }
+    if let Some(enabled) = update.parse_logging {
+        changes.push(format!("parse_logging {} -> {}", toggles.parse_logging(), enabled));
+        toggles.set_parse_logging(enabled);
+    }
+    for (sink, enabled) in &update.sinks {
+        changes.push(format!("{} {} -> {}", sink, toggles.sink_enabled(sink), enabled));
+        toggles.set_sink(sink, *enabled);
+    }
+
+    info!(target: "audit", "修改运行时功能开关: {}，{}", principal,
+          if changes.is_empty() { "无变化".to_string() } else { changes.join("，") });
+    Ok(Json(toggles.state(&state.config)))
+}
+
+/// 保留期豁免请求，标签与时间范围至少给出一项
+#[derive(Debug, Deserialize)]
+struct HoldRequest {
+    /// 标签，缺省时为全部标签
+    tag: Option<String>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    /// 原因（如事故调查编号）
+    reason: String,
+}
+
+/// 当前的保留期豁免
+async fn list_holds(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<RetentionHold>>, ApiError> {
+    require_admin(&state, &headers).await?;
+
+    let db_manager = state.db_manager.clone();
+    let holds = run_blocking(&state, "holds", move || db_manager.retention_holds()).await?;
+    Ok(Json(holds))
+}
+
+/// 添加保留期豁免，命中的数据在解除前不被保留期清理
+async fn create_hold(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Json(request): Json<HoldRequest>,
+) -> Result<(StatusCode, Json<RetentionHold>), ApiError> {
+    let principal = auth::authorize(&state, &headers, ApiRole::Admin).await?;
+
+    let tag = request.tag.filter(|tag| !tag.trim().is_empty());
+    if tag.is_none() && request.from.is_none() && request.to.is_none() {
+        return Err(ApiError::bad_request("tag、from、to 至少给出一项，不支持豁免全部数据"));
+    }
+    if let (Some(from), Some(to)) = (request.from, request.to)
+        && to <= from {
+        return Err(ApiError::bad_request("参数 to 必须晚于 from"));
+    }
+    if request.reason.trim().is_empty() {
+        return Err(ApiError::bad_request("reason 不能为空"));
+    }
+
+    let db_manager = state.db_manager.clone();
+    let created_by = principal.clone();
+    let hold = run_blocking(&state, "holds", move || {
+        db_manager.add_retention_hold(tag, request.from, request.to, request.reason, created_by)
+    }).await?;
+
+    info!(target: "audit", "添加保留期豁免 #{}: {}，标签 {}，范围 {:?} 到 {:?}，原因: {}",
+          hold.id, principal, hold.tag.as_deref().unwrap_or("全部"), hold.from, hold.to, hold.reason);
+    Ok((StatusCode::CREATED, Json(hold)))
+}
+
+/// 解除保留期豁免，之后的清理周期按保留窗口正常清理
+async fn release_hold(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let principal = auth::authorize(&state, &headers, ApiRole::Admin).await?;
+
+    let db_manager = state.db_manager.clone();
+    let released = run_blocking(&state, "holds", move || db_manager.release_retention_hold(id)).await?;
+    let Some(hold) = released else {
+        return Err(ApiError {
+            status: StatusCode::NOT_FOUND,
+            message: format!("保留期豁免 #{} 不存在", id),
+        });
+    };
+
+    info!(target: "audit", "解除保留期豁免 #{}: {}，标签 {}，范围 {:?} 到 {:?}，原因: {}",
+          id, principal, hold.tag.as_deref().unwrap_or("全部"), hold.from, hold.to, hold.reason);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 变更日志查询参数
+#[derive(Debug, Deserialize)]
+struct ChangeLogParams {
+    /// 副本已应用的日志序号，缺省时要求全量拉取
+    after: Option<i64>,
+}
+
+/// 变更日志，供只读副本获取需要拉取的时间范围
+async fn replication_log(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Query(params): Query<ChangeLogParams>,
+) -> Result<Json<ChangeLog>, ApiError> {
+    auth::authorize(&state, &headers, ApiRole::Replication).await?;
+
+    let db_manager = state.db_manager.clone();
+    let log = run_blocking(&state, "replication-log", move || db_manager.change_log(params.after)).await?;
+    Ok(Json(log))
+}
+
+/// 变更流查询参数
+#[derive(Debug, Deserialize)]
+struct ChangesParams {
+    /// 时间范围起点（含），缺省时不限
+    from: Option<DateTime<Utc>>,
+    /// 时间范围终点（含），缺省时不限
+    to: Option<DateTime<Utc>>,
+    /// 分页游标：只返回晚于该时间的数据，取上一页的 `next`
+    after: Option<DateTime<Utc>>,
+    /// 最多返回的时间点数量
+    limit: Option<usize>,
+}
+
+/// 变更流单批的最大时间点数量
+const MAX_CHANGES_LIMIT: usize = 10_000;
+
+/// 变更流，供只读副本拉取增量数据
+async fn replication_changes(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Query(params): Query<ChangesParams>,
+) -> Result<Json<ChangesPage>, ApiError> {
+    auth::authorize(&state, &headers, ApiRole::Replication).await?;
+
+    let limit = params.limit.unwrap_or(1000).clamp(1, MAX_CHANGES_LIMIT);
+    let db_manager = state.db_manager.clone();
+    let page = run_blocking(&state, "replication-changes", move || {
+        db_manager.changes(params.from, params.to, params.after, limit)
+    }).await?;
+
+    Ok(Json(page))
+}
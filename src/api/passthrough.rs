@@ -0,0 +1,342 @@
+//! 只读 SQL 透传
+//! 受信任的客户端（sql 角色）提交只读查询，由持有 DuckDB 文件的本进程执行，避免外部进程打开文件时与写入方冲突。
+//! 只接受以 SELECT、WITH、FROM 等开头的单条查询语句，查询被包装为子查询并限制返回行数；
+//! 读取文件、执行动态 SQL 或连接外部数据库的函数被拒绝，数据库实例不自动加载扩展。
+//! 该接口不是沙箱，sql 角色只应授予受信任的客户端。
+
+use axum::Json;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::Response;
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::info;
+
+use super::serialize::{self, Format};
+use super::{ApiError, ApiState, auth, check_heavy_query, run_blocking};
+use crate::config::ApiRole;
+use crate::sql;
+
+/// 允许的语句开头关键字
+const ALLOWED_STATEMENTS: &[&str] = &["select", "with", "from", "values", "describe", "show", "summarize", "pivot", "unpivot"];
+
+/// 禁止的函数（读取文件、访问环境、执行动态 SQL 或连接外部数据库），
+/// 另外禁止 `read_` 开头与 `_scan`、`_query`、`_attach`、`_execute` 结尾的函数（如 `postgres_query`、`sqlite_attach`）
+const DENIED_FUNCTIONS: &[&str] = &[
+    "glob", "query", "query_table", "sniff_csv", "getenv", "duckdb_secrets",
+    "parquet_metadata", "parquet_schema", "parquet_file_metadata", "parquet_kv_metadata",
+    "json_execute_serialized_sql",
+];
+
+/// 禁止的函数名后缀
+const DENIED_SUFFIXES: &[&str] = &["_scan", "_query", "_attach", "_execute"];
+
+/// 结束 FROM 子句的关键字
+const FROM_CLAUSE_END: &[&str] = &[
+    "where", "group", "having", "qualify", "window", "order", "limit", "offset",
+    "union", "except", "intersect", "on", "using", "select",
+];
+
+/// SQL 透传请求
+#[derive(Debug, Deserialize)]
+pub(super) struct SqlRequest {
+    sql: String,
+    /// 最多返回的行数，不超过 `api.sql_max_rows`
+    limit: Option<usize>,
+    /// json、csv、arrow 或 msgpack，未给出时按 Accept 请求头，默认 json
+    #[serde(default)]
+    format: Option<String>,
+}
+
+/// 执行只读 SQL，结果格式见 [`serialize`]
+pub(super) async fn run_sql(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Json(request): Json<SqlRequest>,
+) -> Result<Response, ApiError> {
+    let principal = auth::authorize(&state, &headers, ApiRole::Sql).await?;
+    check_heavy_query()?;
+
+    let format = Format::negotiate(request.format.as_deref(), &headers)?;
+    let sql = validate(&request.sql).map_err(ApiError::bad_request)?;
+    let limit = request.limit.unwrap_or(state.config.api.sql_max_rows).clamp(1, state.config.api.sql_max_rows);
+    info!(target: "audit", "{} 执行只读 SQL（最多 {} 行）: {}", principal, limit, sql);
+
+    let db_manager = state.db_manager.clone();
+    let (body, truncated) = run_blocking(&state, "sql", move || {
+        let (batches, truncated) = db_manager.query_batches(&sql::Statement::new(sql), limit)?;
+        Ok((serialize::encode(&batches, format, truncated)?, truncated))
+    }).await?;
+
+    Ok(serialize::response(body, format, truncated))
+}
+
+/// 校验查询：单条语句、以允许的关键字开头、不调用禁止的函数、不以字符串字面量作为表（直接读取文件）；
+/// 返回去掉末尾分号的语句
+fn validate(sql: &str) -> Result<String, String> {
+    let sql = sql.trim().trim_end_matches(|c: char| c == ';' || c.is_whitespace());
+    if sql.is_empty() {
+        return Err("SQL 不能为空".to_string());
+    }
+
+    let tokens = tokenize(sql)?;
+    let first = tokens.iter().find_map(|t| match t {
+        Token::Word(word) => Some(word.as_str()),
+        _ => None,
+    });
+    if !first.is_some_and(|w| ALLOWED_STATEMENTS.contains(&w)) {
+        return Err(format!("只允许只读查询（{} 开头的语句）", ALLOWED_STATEMENTS.join("/").to_uppercase()));
+    }
+
+    // FROM 子句中紧跟 FROM、JOIN 或逗号的字符串字面量会被 DuckDB 当作文件读取
+    let mut depth = 0usize;
+    let mut from_depth: Option<usize> = None;
+    let mut previous: Option<&Token> = None;
+    for token in &tokens {
+        match token {
+            Token::Semicolon => return Err("只允许单条语句".to_string()),
+            Token::Open => depth += 1,
+            Token::Close => {
+                if from_depth == Some(depth) {
+                    from_depth = None;
+                }
+                depth = depth.saturating_sub(1);
+            }
+            Token::Word(word) | Token::Quoted(word) => {
+                if DENIED_FUNCTIONS.contains(&word.as_str())
+                    || word.starts_with("read_")
+                    || DENIED_SUFFIXES.iter().any(|suffix| word.ends_with(suffix))
+                {
+                    return Err(format!("不允许调用 {}", word));
+                }
+                if matches!(token, Token::Word(_)) {
+                    if word == "from" || word == "join" {
+                        from_depth = Some(depth);
+                    } else if FROM_CLAUSE_END.contains(&word.as_str()) && from_depth == Some(depth) {
+                        from_depth = None;
+                    }
+                }
+            }
+            Token::Literal => {
+                let table_position = match previous {
+                    Some(Token::Word(w)) => w == "from" || w == "join",
+                    Some(Token::Comma) => from_depth == Some(depth),
+                    _ => false,
+                };
+                if table_position {
+                    return Err("不允许以字符串作为表名（直接读取文件）".to_string());
+                }
+            }
+            Token::Comma | Token::Other => {}
+        }
+        previous = Some(token);
+    }
+
+    Ok(sql.to_string())
+}
+
+/// 词法单元（只区分校验需要的部分）
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// 关键字或标识符（小写）
+    Word(String),
+    /// 双引号标识符（小写）
+    Quoted(String),
+    /// 字符串字面量（含 E'...' 与 $$...$$）
+    Literal,
+    Semicolon,
+    Comma,
+    Open,
+    Close,
+    Other,
+}
+
+/// 按 SQL 词法切分，跳过注释
+fn tokenize(sql: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                let end = (i + 2..chars.len().saturating_sub(1))
+                    .find(|&j| chars[j] == '*' && chars[j + 1] == '/')
+                    .ok_or("注释未结束")?;
+                i = end + 2;
+            }
+            '\'' | '"' => {
+                // 引号内连续两个引号表示转义
+                let start = i;
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err("引号未闭合".to_string()),
+                        Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                            text.push(c);
+                            i += 2;
+                        }
+                        Some(&q) if q == c => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&other) => {
+                            text.push(other);
+                            i += 1;
+                        }
+                    }
+                }
+                if c == '"' {
+                    tokens.push(Token::Quoted(text.to_lowercase()));
+                } else {
+                    // E'...' 转义字符串的前缀与字符串合为一个字面量
+                    let prefixed = start > 0 && chars[start - 1].eq_ignore_ascii_case(&'e');
+                    if prefixed && matches!(tokens.last(), Some(Token::Word(w)) if w == "e") {
+                        tokens.pop();
+                    }
+                    tokens.push(Token::Literal);
+                }
+            }
+            '$' => {
+                // 美元符号引用的字符串：$$...$$ 或 $标记$...$标记$
+                let tag_end = (i + 1..chars.len())
+                    .take_while(|&j| chars[j] == '$' || chars[j].is_alphanumeric() || chars[j] == '_')
+                    .find(|&j| chars[j] == '$');
+                match tag_end {
+                    Some(end) => {
+                        let tag: String = chars[i..=end].iter().collect();
+                        let rest: String = chars[end + 1..].iter().collect();
+                        let close = rest.find(&tag).ok_or("字符串未闭合")?;
+                        i = end + 1 + rest[..close].chars().count() + tag.chars().count();
+                        tokens.push(Token::Literal);
+                    }
+                    None => {
+                        tokens.push(Token::Other);
+                        i += 1;
+                    }
+                }
+            }
+            ';' | ',' | '(' | ')' => {
+                tokens.push(match c {
+                    ';' => Token::Semicolon,
+                    ',' => Token::Comma,
+                    '(' => Token::Open,
+                    _ => Token::Close,
+                });
+                i += 1;
+            }
+            _ if c.is_alphanumeric() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                    i += 1;
+                }
+                tokens.push(Token::Word(chars[start..i].iter().collect::<String>().to_lowercase()));
+            }
+            _ => {
+                tokens.push(Token::Other);
+                i += 1;
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_read_only_queries() {
+        for sql in [
+            "SELECT * FROM ts_wide ORDER BY DateTime DESC LIMIT 10;",
+            "with t AS (SELECT TagName, Value FROM ts_long) SELECT * FROM t",
+            "FROM ts_latest WHERE TagName = 'read_csv(''x'')'",
+            "SELECT \"Tag;1\" FROM ts_wide -- 注释中的 read_csv('x'); 不影响\n",
+            "SELECT a.TagName FROM ts_latest AS a, tag_meta AS b WHERE a.TagName = b.TagName",
+            "SELECT COUNT(*) FROM ts_long WHERE TagName IN ('a', 'b')",
+            "DESCRIBE ts_wide",
+            "SUMMARIZE SELECT * FROM ts_latest",
+        ] {
+            assert!(validate(sql).is_ok(), "应接受: {}", sql);
+        }
+        assert_eq!(validate("  SELECT 1 ;  ").unwrap(), "SELECT 1");
+    }
+
+    #[test]
+    fn rejects_writes_and_multiple_statements() {
+        for sql in [
+            "",
+            "DELETE FROM ts_wide",
+            "INSERT INTO ts_latest VALUES ('a', now(), 1)",
+            "/* SELECT */ DROP TABLE ts_wide",
+            "ATTACH 'other.duckdb'",
+            "COPY ts_wide TO 'out.csv'",
+            "SELECT 1; DROP TABLE ts_wide",
+            "SELECT 1 /* 未结束的注释",
+            "SELECT 'unterminated",
+        ] {
+            assert!(validate(sql).is_err(), "应拒绝: {}", sql);
+        }
+    }
+
+    #[test]
+    fn rejects_denied_functions() {
+        for sql in [
+            "SELECT * FROM read_csv('/etc/passwd')",
+            "SELECT * FROM READ_PARQUET('x.parquet')",
+            "SELECT * FROM \"read_csv_auto\"('x.csv')",
+            "SELECT * FROM parquet_scan('x.parquet')",
+            "SELECT * FROM glob('/*')",
+            "SELECT * FROM query('DROP TABLE ts_wide')",
+            "SELECT * FROM query_table('ts_wide')",
+            "SELECT getenv('HOME')",
+            "SELECT * FROM json_execute_serialized_sql('x')",
+            "SELECT * FROM postgres_query('db', 'SELECT 1')",
+            "SELECT * FROM mysql_query('db', 'SELECT 1')",
+            "SELECT * FROM sqlite_attach('x.db')",
+            "SELECT * FROM postgres_execute('db', 'DROP TABLE t')",
+            "SELECT * FROM /* 注释 */ read_json ('x.json')",
+            "SELECT * FROM ts_wide, read_csv('x.csv')",
+        ] {
+            assert!(validate(sql).is_err(), "应拒绝: {}", sql);
+        }
+    }
+
+    #[test]
+    fn rejects_string_literals_as_tables() {
+        for sql in [
+            "SELECT * FROM 'data.csv'",
+            "FROM 'data.parquet'",
+            "SELECT * FROM ts_wide JOIN 'x.csv' USING (DateTime)",
+            "SELECT * FROM ts_wide, 'x.csv'",
+            "SELECT * FROM (SELECT * FROM E'x.csv')",
+            "SELECT * FROM $$x.csv$$",
+            "SELECT * FROM $tag$x.csv$tag$",
+        ] {
+            assert!(validate(sql).is_err(), "应拒绝: {}", sql);
+        }
+        // 条件与选择列表中的字符串不是表
+        assert!(validate("SELECT 'a', 'b' FROM ts_latest WHERE TagName = 'x.csv'").is_ok());
+        assert!(validate("SELECT * FROM ts_latest WHERE TagName IN ('a', 'b.csv')").is_ok());
+    }
+
+    #[test]
+    fn tokenizes_quotes_and_comments() {
+        let tokens = tokenize("SELECT \"A\"\"b\", 'it''s' -- c\n/* d */ FROM t").unwrap();
+        assert_eq!(tokens, vec![
+            Token::Word("select".to_string()),
+            Token::Quoted("a\"b".to_string()),
+            Token::Comma,
+            Token::Literal,
+            Token::Word("from".to_string()),
+            Token::Word("t".to_string()),
+        ]);
+    }
+}
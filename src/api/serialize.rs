@@ -0,0 +1,109 @@
+//! 查询结果序列化
+//! 表格型查询的结果统一以 Arrow 记录批次表示，按 `format=` 参数或 `Accept` 请求头序列化为
+//! JSON（`{"rows": [...], "truncated": false}`）、CSV、Arrow IPC 流或 MessagePack（结构与 JSON 相同）。
+//! `format=` 优先于 `Accept`，都未给出或无法识别的 `Accept` 时为 JSON。
+
+use arrow::record_batch::RecordBatch;
+use axum::http::{HeaderMap, HeaderValue, header};
+use axum::response::{IntoResponse, Response};
+use std::sync::Arc;
+
+use super::ApiError;
+
+/// 结果是否被截断的响应头
+const TRUNCATED_HEADER: &str = "x-truncated";
+
+/// 响应格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+    Arrow,
+    MessagePack,
+}
+
+impl Format {
+    /// 按 `format=` 参数或 `Accept` 请求头确定响应格式
+    pub fn negotiate(format: Option<&str>, headers: &HeaderMap) -> Result<Self, ApiError> {
+        if let Some(format) = format {
+            return match format {
+                "json" => Ok(Self::Json),
+                "csv" => Ok(Self::Csv),
+                "arrow" => Ok(Self::Arrow),
+                "msgpack" => Ok(Self::MessagePack),
+                other => Err(ApiError::bad_request(format!("不支持的格式: {}（json、csv、arrow 或 msgpack）", other))),
+            };
+        }
+
+        let accept = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()).unwrap_or_default();
+        Ok(accept.split(',')
+            .filter_map(|media| match media.split(';').next().unwrap_or_default().trim() {
+                "application/json" => Some(Self::Json),
+                "text/csv" => Some(Self::Csv),
+                "application/vnd.apache.arrow.stream" => Some(Self::Arrow),
+                "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Self::MessagePack),
+                _ => None,
+            })
+            .next()
+            .unwrap_or(Self::Json))
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Arrow => "application/vnd.apache.arrow.stream",
+            Self::MessagePack => "application/msgpack",
+        }
+    }
+}
+
+/// 按格式编码结果批次；CSV 与 Arrow 不含截断标记，由响应头 `X-Truncated` 表示
+pub fn encode(
+    batches: &[RecordBatch],
+    format: Format,
+    truncated: bool,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    match format {
+        Format::Arrow => {
+            let mut buffer = Vec::new();
+            let schema = batches.first().map(|b| b.schema()).unwrap_or_else(|| Arc::new(arrow::datatypes::Schema::empty()));
+            let mut writer = arrow::ipc::writer::StreamWriter::try_new(&mut buffer, &schema)?;
+            for batch in batches {
+                writer.write(batch)?;
+            }
+            writer.finish()?;
+            drop(writer);
+            Ok(buffer)
+        }
+        Format::Csv => {
+            let mut writer = arrow::csv::WriterBuilder::new().with_header(true).build(Vec::new());
+            for batch in batches {
+                writer.write(batch)?;
+            }
+            Ok(writer.into_inner())
+        }
+        Format::Json => Ok(serde_json::to_vec(&rows_document(batches, truncated)?)?),
+        Format::MessagePack => Ok(rmp_serde::to_vec_named(&rows_document(batches, truncated)?)?),
+    }
+}
+
+/// 以编码后的结果构造响应，响应头 `X-Truncated` 表示结果是否被截断
+pub fn response(body: Vec<u8>, format: Format, truncated: bool) -> Response {
+    let mut response = ([(header::CONTENT_TYPE, format.content_type())], body).into_response();
+    response.headers_mut().insert(TRUNCATED_HEADER, HeaderValue::from_static(if truncated { "true" } else { "false" }));
+    response
+}
+
+/// JSON 与 MessagePack 共用的结果结构：`{"rows": [{列名: 值}...], "truncated": bool}`
+fn rows_document(
+    batches: &[RecordBatch],
+    truncated: bool,
+) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+    let mut writer = arrow::json::ArrayWriter::new(Vec::new());
+    writer.write_batches(&batches.iter().collect::<Vec<_>>())?;
+    writer.finish()?;
+    let rows = writer.into_inner();
+    let rows: serde_json::Value = if rows.is_empty() { serde_json::json!([]) } else { serde_json::from_slice(&rows)? };
+    Ok(serde_json::json!({ "rows": rows, "truncated": truncated }))
+}
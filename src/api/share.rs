@@ -0,0 +1,270 @@
+//! 数据分享签名链接
+//! 管理员为指定标签与时间范围生成带过期时间的签名链接，持有链接即可下载该数据集（CSV），无需账号或令牌。
+//! 链接不在服务端保存，签名使用 `api.share_secret`（HMAC-SHA256），更换密钥即可使全部已发出的链接失效。
+//!
+//! 链接是持有者令牌（bearer token）：有效期内任何拿到链接的人都可以重复下载，不校验下载者身份。
+//! 单个链接可按 ID 吊销（记录在 `share_revocations` 表），同一链接的下载按 `share_min_interval_secs` 限流。
+
+use axum::Json;
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::Response;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::io::ReaderStream;
+use tracing::{info, warn};
+
+use super::{ApiError, ApiState, auth, check_heavy_query, run_blocking, snapshot};
+use crate::config::{ApiRole, ExportLayout};
+
+/// 生成分享链接的请求
+#[derive(Debug, Deserialize)]
+pub(super) struct ShareRequest {
+    /// 分享的标签，为空时分享全部标签
+    #[serde(default)]
+    tags: Vec<String>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    /// 有效期，单位为秒，默认 1 天
+    expires_in_secs: Option<u64>,
+}
+
+/// 生成的分享链接
+#[derive(Debug, Serialize)]
+pub(super) struct ShareLink {
+    /// 链接 ID，用于吊销与审计日志（不能用来下载）
+    id: String,
+    /// 相对路径，拼接在 API 地址之后使用
+    url: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// 分享链接下载参数
+#[derive(Debug, Deserialize)]
+pub(super) struct SharedExportParams {
+    /// 逗号分隔的标签列表，为空时为全部标签
+    #[serde(default)]
+    tags: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    /// 过期时间（Unix 时间戳，秒）
+    expires: i64,
+    sig: String,
+}
+
+/// 默认有效期：1 天
+const DEFAULT_EXPIRES_IN_SECS: u64 = 86400;
+
+/// 分享链接下载限流状态
+#[derive(Default)]
+pub(super) struct ShareLimiter {
+    /// 各链接最近一次下载的时间
+    last_download: Mutex<HashMap<String, Instant>>,
+}
+
+impl ShareLimiter {
+    /// 检查链接是否可以下载，可以时登记本次下载
+    fn acquire(&self, id: &str, min_interval: Duration) -> Result<(), ApiError> {
+        let mut last_download = self.last_download.lock().unwrap();
+        if let Some(elapsed) = last_download.get(id).map(Instant::elapsed)
+            && elapsed < min_interval {
+            return Err(ApiError {
+                status: StatusCode::TOO_MANY_REQUESTS,
+                message: format!("该分享链接下载过于频繁，请在 {} 秒后重试", (min_interval - elapsed).as_secs() + 1),
+            });
+        }
+        last_download.retain(|_, last| last.elapsed() < min_interval);
+        last_download.insert(id.to_string(), Instant::now());
+        Ok(())
+    }
+}
+
+/// 链接 ID：签名的 SHA-256 摘要前 16 位十六进制，不能反推出签名
+fn link_id(sig: &str) -> String {
+    Sha256::digest(sig.as_bytes()).iter().take(8).map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 签名的规范内容：标签、时间范围与过期时间
+fn canonical(tags: &str, from: DateTime<Utc>, to: DateTime<Utc>, expires: i64) -> String {
+    format!("{}\n{}\n{}\n{}", tags, from.to_rfc3339(), to.to_rfc3339(), expires)
+}
+
+fn mac(secret: &str, payload: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC 接受任意长度的密钥");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// 签名密钥，未配置时分享功能禁用
+fn share_secret(state: &ApiState) -> Result<&str, ApiError> {
+    state.config.api.share_secret.as_deref()
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| ApiError {
+            status: StatusCode::FORBIDDEN,
+            message: "未配置 api.share_secret，分享链接已禁用".to_string(),
+        })
+}
+
+/// 生成分享链接（需要 admin 角色）
+pub(super) async fn create_share_link(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Json(request): Json<ShareRequest>,
+) -> Result<Json<ShareLink>, ApiError> {
+    let secret = share_secret(&state)?;
+    let principal = auth::authorize(&state, &headers, ApiRole::Admin).await?;
+
+    if request.to <= request.from {
+        return Err(ApiError::bad_request("参数 to 必须晚于 from"));
+    }
+    if request.tags.iter().any(|t| t.contains(',')) {
+        return Err(ApiError::bad_request("分享的标签名不能包含逗号"));
+    }
+    let expires_in = request.expires_in_secs.unwrap_or(DEFAULT_EXPIRES_IN_SECS);
+    let max_ttl = state.config.api.share_max_ttl_secs;
+    if expires_in == 0 || expires_in > max_ttl {
+        return Err(ApiError::bad_request(format!("有效期必须在 1 到 {} 秒之间", max_ttl)));
+    }
+
+    let tags = request.tags.join(",");
+    let expires_at = Utc::now() + chrono::Duration::seconds(expires_in as i64);
+    let expires = expires_at.timestamp();
+    let signature = mac(secret, &canonical(&tags, request.from, request.to, expires)).finalize().into_bytes();
+    let sig = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(signature);
+    let id = link_id(&sig);
+
+    let url = format!(
+        "/shared/export?tags={}&from={}&to={}&expires={}&sig={}",
+        urlencoding::encode(&tags),
+        urlencoding::encode(&request.from.to_rfc3339()),
+        urlencoding::encode(&request.to.to_rfc3339()),
+        expires,
+        sig
+    );
+
+    info!(target: "audit", "生成分享链接 {}: {}，标签 [{}]，范围 {} 到 {}，过期时间 {}",
+          id, principal, tags, request.from, request.to, expires_at);
+    Ok(Json(ShareLink { id, url, expires_at }))
+}
+
+/// 吊销分享链接（需要 admin 角色），之后以该链接下载返回 410
+pub(super) async fn revoke_share_link(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    share_secret(&state)?;
+    let principal = auth::authorize(&state, &headers, ApiRole::Admin).await?;
+    if id.len() != 16 || !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ApiError::bad_request("分享链接 ID 应为 16 位十六进制"));
+    }
+
+    // 链接的过期时间不在服务端保存，吊销记录按最长有效期保留
+    let expires = Utc::now() + chrono::Duration::seconds(state.config.api.share_max_ttl_secs as i64);
+    let db_manager = state.db_manager.clone();
+    let (revoked_id, revoked_by) = (id.to_ascii_lowercase(), principal.clone());
+    run_blocking(&state, "share", move || db_manager.revoke_share_link(&revoked_id, &revoked_by, expires)).await?;
+
+    info!(target: "audit", "吊销分享链接 {}: {}", id, principal);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 通过分享链接下载数据集：单个文件时返回 CSV，多个文件（分列或含文本值）时打包为 zip
+pub(super) async fn shared_export(
+    State(state): State<Arc<ApiState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<SharedExportParams>,
+) -> Result<Response, ApiError> {
+    let secret = share_secret(&state)?;
+    check_heavy_query()?;
+    let client = addr.ip();
+    let id = link_id(&params.sig);
+
+    let signature = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(&params.sig).unwrap_or_default();
+    let payload = canonical(&params.tags, params.from, params.to, params.expires);
+    if mac(secret, &payload).verify_slice(&signature).is_err() {
+        warn!(target: "audit", "拒绝分享链接下载: 客户端 {}，链接 {} 签名无效", client, id);
+        return Err(ApiError {
+            status: StatusCode::FORBIDDEN,
+            message: "分享链接无效".to_string(),
+        });
+    }
+    if Utc::now().timestamp() > params.expires {
+        warn!(target: "audit", "拒绝分享链接下载: 客户端 {}，链接 {} 已过期", client, id);
+        return Err(ApiError {
+            status: StatusCode::GONE,
+            message: "分享链接已过期".to_string(),
+        });
+    }
+    let db_manager = state.db_manager.clone();
+    let revoked_id = id.clone();
+    if run_blocking(&state, "share", move || db_manager.share_link_revoked(&revoked_id)).await? {
+        warn!(target: "audit", "拒绝分享链接下载: 客户端 {}，链接 {} 已吊销", client, id);
+        return Err(ApiError {
+            status: StatusCode::GONE,
+            message: "分享链接已吊销".to_string(),
+        });
+    }
+    if let Err(e) = state.shares.acquire(&id, Duration::from_secs(state.config.api.share_min_interval_secs)) {
+        warn!(target: "audit", "拒绝分享链接下载: 客户端 {}，链接 {} 下载过于频繁", client, id);
+        return Err(e);
+    }
+
+    let tags: Vec<String> = params.tags.split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    let name = format!("shared_{}", Utc::now().format("%Y%m%d_%H%M%S_%f"));
+    let work_dir = PathBuf::from(&state.config.export.output_dir).join("shared").join(&name);
+
+    let db_manager = state.db_manager.clone();
+    let dir = work_dir.clone();
+    let (from, to) = (params.from, params.to);
+    let result = run_blocking(&state, "shared-export", move || {
+        std::fs::create_dir_all(&dir)?;
+        let (files, rows) = db_manager.export_csv(&dir.join(format!("{}.csv", name)), from, to, &tags, ExportLayout::default())?;
+        let (path, content_type, filename) = if files.len() == 1 {
+            (files[0].clone(), "text/csv", format!("{}.csv", name))
+        } else {
+            let zip = dir.with_extension("zip");
+            snapshot::zip_directory(&dir, &zip, None)?;
+            (zip, "application/zip", format!("{}.zip", name))
+        };
+        let size = std::fs::metadata(&path)?.len();
+        Ok((path, content_type, filename, size, rows))
+    }).await;
+
+    let (path, content_type, filename, size, rows) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&work_dir);
+            warn!(target: "audit", "分享链接导出失败: 客户端 {}，链接 {}，原因: {}", client, id, e.message);
+            return Err(e);
+        }
+    };
+
+    let file = tokio::fs::File::open(&path).await
+        .map_err(|e| ApiError::internal(format!("打开导出文件失败: {}", e)))?;
+    // 文件句柄保持打开即可继续读取
+    let _ = std::fs::remove_dir_all(&work_dir);
+    let _ = std::fs::remove_file(work_dir.with_extension("zip"));
+
+    info!(target: "audit", "分享链接下载: 客户端 {}，链接 {}，标签 [{}]，范围 {} 到 {}，{} 行，{} 字节",
+          client, id, params.tags, params.from, params.to, rows, size);
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, size)
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename))
+        .body(Body::from_stream(ReaderStream::new(file)))
+        .map_err(|e| ApiError::internal(format!("构建响应失败: {}", e)))
+}
@@ -0,0 +1,177 @@
+//! 缓存快照下载
+//! 按需将当前缓存导出为一致性快照（DuckDB EXPORT DATABASE，CSV 格式）并打包为 zip 下载
+
+use axum::body::Body;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{StatusCode, header};
+use axum::response::Response;
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio_util::io::ReaderStream;
+use tracing::{info, warn};
+
+use super::{ApiError, ApiState, check_heavy_query, run_blocking};
+use crate::sql;
+
+/// 快照下载限流状态
+#[derive(Default)]
+pub(super) struct SnapshotLimiter {
+    /// 各客户端最近一次下载的时间
+    last_download: Mutex<HashMap<IpAddr, Instant>>,
+    /// 是否有快照正在生成（同一时间只生成一个）
+    in_progress: AtomicBool,
+}
+
+/// 生成期间的占用标记，离开作用域时释放
+struct InProgress<'a>(&'a AtomicBool);
+
+impl Drop for InProgress<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+impl SnapshotLimiter {
+    /// 检查客户端是否可以下载，可以时登记本次下载并占用生成槽
+    fn acquire(&self, client: IpAddr, min_interval: Duration) -> Result<InProgress<'_>, ApiError> {
+        let mut last_download = self.last_download.lock().unwrap();
+        if let Some(last) = last_download.get(&client) {
+            let elapsed = last.elapsed();
+            if elapsed < min_interval {
+                return Err(ApiError {
+                    status: StatusCode::TOO_MANY_REQUESTS,
+                    message: format!("下载过于频繁，请在 {} 秒后重试", (min_interval - elapsed).as_secs() + 1),
+                });
+            }
+        }
+
+        if self.in_progress.swap(true, Ordering::SeqCst) {
+            return Err(ApiError {
+                status: StatusCode::TOO_MANY_REQUESTS,
+                message: "已有快照正在生成，请稍后重试".to_string(),
+            });
+        }
+
+        last_download.insert(client, Instant::now());
+        Ok(InProgress(&self.in_progress))
+    }
+}
+
+/// 下载当前缓存的 zip 快照
+pub(super) async fn download_snapshot(
+    State(state): State<Arc<ApiState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> Result<Response, ApiError> {
+    if !state.config.api.snapshot_enabled {
+        return Err(ApiError::bad_request("未启用快照下载（api.snapshot_enabled）"));
+    }
+    check_heavy_query()?;
+
+    let client = addr.ip();
+    let min_interval = Duration::from_secs(state.config.api.snapshot_min_interval_secs);
+    let _in_progress = match state.snapshots.acquire(client, min_interval) {
+        Ok(guard) => guard,
+        Err(e) => {
+            warn!(target: "audit", "拒绝快照下载: 客户端 {}，原因: {}", client, e.message);
+            return Err(e);
+        }
+    };
+
+    info!(target: "audit", "开始生成快照: 客户端 {}", client);
+    let started = Instant::now();
+
+    let work_dir = PathBuf::from(&state.config.export.output_dir).join("snapshots");
+    let name = format!("snapshot_{}", chrono::Utc::now().format("%Y%m%d_%H%M%S"));
+    let export_dir = work_dir.join(&name);
+    let zip_path = work_dir.join(format!("{}.zip", name));
+
+    let db_manager = state.db_manager.clone();
+    let watermark = state.config.export.watermark;
+    let (dir, zip) = (export_dir.clone(), zip_path.clone());
+    let result = run_blocking(&state, "snapshot", move || {
+        cleanup_stale(&work_dir);
+        std::fs::create_dir_all(&dir)?;
+        db_manager.export_database(&dir)?;
+        let comment = watermark.then(|| {
+            db_manager.provenance(&sql::Statement::new("EXPORT DATABASE"))
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, value))
+                .collect::<Vec<_>>()
+                .join("\n")
+        });
+        let size = zip_directory(&dir, &zip, comment)?;
+        std::fs::remove_dir_all(&dir)?;
+        Ok(size)
+    }).await;
+
+    let size = match result {
+        Ok(size) => size,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&export_dir);
+            let _ = std::fs::remove_file(&zip_path);
+            warn!(target: "audit", "快照生成失败: 客户端 {}，原因: {}", client, e.message);
+            return Err(e);
+        }
+    };
+
+    let file = tokio::fs::File::open(&zip_path).await
+        .map_err(|e| ApiError::internal(format!("打开快照文件失败: {}", e)))?;
+    // 文件句柄保持打开即可继续读取；不支持删除已打开文件的平台由下次请求清理
+    let _ = std::fs::remove_file(&zip_path);
+
+    info!(target: "audit", "快照已生成: 客户端 {}，文件 {}.zip，大小 {} 字节，耗时 {:.1} 秒",
+          client, name, size, started.elapsed().as_secs_f64());
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/zip")
+        .header(header::CONTENT_LENGTH, size)
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.zip\"", name))
+        .body(Body::from_stream(ReaderStream::new(file)))
+        .map_err(|e| ApiError::internal(format!("构建响应失败: {}", e)))
+}
+
+/// 删除之前遗留的快照文件
+fn cleanup_stale(work_dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(work_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let _ = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+    }
+}
+
+/// 将目录下的文件打包为 zip，返回 zip 文件大小；`comment` 写入 zip 注释（来源信息）
+pub(super) fn zip_directory(dir: &Path, zip_path: &Path, comment: Option<String>) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let file = std::fs::File::create(zip_path)?;
+    let mut zip = zip::ZipWriter::new(file);
+    if let Some(comment) = comment {
+        zip.set_comment(comment);
+    }
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        zip.start_file(name, options)?;
+        let mut source = std::fs::File::open(&path)?;
+        std::io::copy(&mut source, &mut zip)?;
+    }
+
+    zip.finish()?.flush()?;
+    Ok(std::fs::metadata(zip_path)?.len())
+}
@@ -0,0 +1,89 @@
+//! 请求追踪
+//! 为每个 API 请求分配 ID：请求带有 W3C `traceparent` 时沿用其 trace-id，否则沿用 `X-Request-Id`，
+//! 都没有时生成新的 ID。请求的全部日志都在带有该 ID 的 span 中输出（含阻塞线程池中的 DuckDB 查询），
+//! 响应头 `X-Request-Id` 返回该 ID，便于跨系统排查慢查询或失败的请求。
+
+use axum::extract::Request;
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{Instrument, debug, warn};
+
+/// 请求 ID 响应头
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// 超过该耗时的请求记录告警
+const SLOW_REQUEST: Duration = Duration::from_secs(5);
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// 当前请求的 ID，不在请求处理中时为 None
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// 请求追踪中间件
+pub async fn middleware(request: Request, next: Next) -> Response {
+    let id = request_id(&request);
+    let span = tracing::info_span!("request", id = %id, method = %request.method(), path = %request.uri().path());
+
+    let started = Instant::now();
+    let mut response = REQUEST_ID.scope(id.clone(), next.run(request))
+        .instrument(span.clone())
+        .await;
+
+    let elapsed = started.elapsed();
+    span.in_scope(|| {
+        if elapsed >= SLOW_REQUEST {
+            warn!("慢请求: 状态 {}，耗时 {} 毫秒", response.status(), elapsed.as_millis());
+        } else {
+            debug!("请求完成: 状态 {}，耗时 {} 毫秒", response.status(), elapsed.as_millis());
+        }
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// 取请求的追踪 ID：traceparent 的 trace-id、X-Request-Id 或新生成的 ID
+fn request_id(request: &Request) -> String {
+    let header = |name: &str| request.headers().get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+
+    if let Some(trace_id) = header("traceparent").and_then(traceparent_trace_id) {
+        return trace_id.to_string();
+    }
+    if let Some(id) = header(REQUEST_ID_HEADER)
+        .filter(|id| !id.is_empty() && id.len() <= 128 && id.bytes().all(|b| b.is_ascii_graphic())) {
+        return id.to_string();
+    }
+    generate()
+}
+
+/// 解析 `traceparent`（`版本-trace_id-parent_id-标志`），trace-id 须为 32 位非全零的小写十六进制
+fn traceparent_trace_id(value: &str) -> Option<&str> {
+    let mut parts = value.split('-');
+    let (version, trace_id, parent_id) = (parts.next()?, parts.next()?, parts.next()?);
+    parts.next()?;
+
+    let is_hex = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    let valid = is_hex(version, 2) && version != "ff"
+        && is_hex(trace_id, 32) && trace_id.bytes().any(|b| b != b'0')
+        && is_hex(parent_id, 16);
+    valid.then_some(trace_id)
+}
+
+/// 生成新的 ID（32 位十六进制，可直接作为 trace-id 传给下游）
+fn generate() -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    format!("{:016x}{:08x}{:08x}", nanos, std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed))
+}
@@ -0,0 +1,159 @@
+//! 数据归档
+//! 保留窗口清理删除旧数据之前，先将其按 UTC 日期写出为 Parquet 文件（`archive/yyyy=.../mm=.../dd=...`），
+//! 清理后数据不会丢失。写出的每个分区文件登记在冷存储目录表 `cold_partitions` 中（来源表、日期、时间范围与行数），
+//! 长期历史查询据此只读取相关的文件。为避免每个更新周期生成大量小文件，归档与清理按 `interval_secs` 合并执行，
+//! 缓存中的数据因此最多比保留窗口多保留一个间隔；归档失败时不清理，下次重试。
+//!
+//! 配置 `[archive.upload]` 时，归档文件随后上传到 S3 兼容对象存储，用于边缘设备的异地保留。上传失败的文件
+//! 留在待上传列表中，下次归档时重试；待上传的文件全部成功后再上传一份清单（`manifests/` 下的 JSON）。
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::AppConfig;
+use crate::database::DatabaseManager;
+use crate::s3::{Manifest, ManifestEntry, S3Client};
+
+/// 归档器
+pub struct Archiver {
+    enabled: bool,
+    dir: PathBuf,
+    interval: Duration,
+    /// 归档文件名前缀（DuckDB 文件名），区分多个同步配置写入同一归档目录的文件
+    prefix: String,
+    last_run: Mutex<Option<Instant>>,
+    upload: Option<S3Client>,
+    /// 待上传的归档文件
+    pending: Mutex<Vec<PathBuf>>,
+    /// 已上传、尚未写入清单的文件
+    uploaded: Mutex<Vec<ManifestEntry>>,
+}
+
+impl Archiver {
+    pub fn new(config: &AppConfig) -> Self {
+        let prefix = std::path::Path::new(&config.db_file_path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "rt_db".to_string());
+        Self {
+            enabled: config.archive.enabled,
+            dir: PathBuf::from(&config.archive.dir),
+            interval: Duration::from_secs(config.archive.interval_secs),
+            prefix,
+            last_run: Mutex::new(None),
+            upload: config.archive.upload.clone().map(S3Client::new),
+            pending: Mutex::new(Vec::new()),
+            uploaded: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 本次是否执行清理：未启用归档时每次执行，启用时距上次归档超过间隔才执行
+    pub fn due(&self) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        self.last_run.lock().unwrap().is_none_or(|last| last.elapsed() >= self.interval)
+    }
+
+    /// 归档截止时间以前的数据，未启用时不做任何事，返回归档的行数
+    pub fn archive(&self, db_manager: &DatabaseManager, cutoff_time: DateTime<Utc>) -> Result<usize> {
+        if !self.enabled {
+            return Ok(0);
+        }
+
+        // 首次归档前登记目录中已有的归档文件（DuckDB 文件重建后冷存储目录为空）
+        if self.last_run.lock().unwrap().is_none() {
+            match db_manager.catalog_existing_partitions(&self.dir, &self.prefix) {
+                Ok(0) => {}
+                Ok(count) => info!("已将 {} 个已有的归档文件登记到冷存储目录", count),
+                Err(e) => warn!("登记已有的归档文件失败: {}", e),
+            }
+        }
+
+        let (files, rows) = db_manager.archive_before(&self.dir, &self.prefix, cutoff_time)
+            .map_err(|e| anyhow!("归档 {} 以前的数据失败: {}", cutoff_time, e))?;
+        *self.last_run.lock().unwrap() = Some(Instant::now());
+        if rows > 0 {
+            info!("已归档 {} 以前的数据 {} 行到 {}", cutoff_time, rows, self.dir.display());
+        }
+        if self.upload.is_some() {
+            self.pending.lock().unwrap().extend(files);
+        }
+        Ok(rows)
+    }
+
+    /// 上传待上传的归档文件，全部成功后上传清单；未配置上传时不做任何事，返回本次上传的文件数
+    ///
+    /// 失败的文件留待下次重试，本地文件不会删除。
+    pub async fn upload(&self) -> Result<usize> {
+        let Some(client) = &self.upload else {
+            return Ok(0);
+        };
+        let files = std::mem::take(&mut *self.pending.lock().unwrap());
+        if files.is_empty() {
+            return Ok(0);
+        }
+
+        let mut failed = Vec::new();
+        let mut uploaded = Vec::new();
+        for file in files {
+            let relative = file.strip_prefix(&self.dir).unwrap_or(&file);
+            let key = client.key(&relative.to_string_lossy().replace('\\', "/"));
+            let size = match tokio::fs::metadata(&file).await {
+                Ok(metadata) => metadata.len(),
+                Err(e) => {
+                    warn!("归档文件 {} 不可读，跳过上传: {}", file.display(), e);
+                    continue;
+                }
+            };
+            match client.upload_file(&file, &key).await {
+                Ok(sha256) => uploaded.push(ManifestEntry { key, size, sha256 }),
+                Err(e) => {
+                    warn!("{}，下次归档时重试", e);
+                    failed.push(file);
+                }
+            }
+        }
+        let count = uploaded.len();
+        if count > 0 {
+            info!("已上传 {} 个归档文件", count);
+        }
+
+        let entries = {
+            let mut pending = self.pending.lock().unwrap();
+            let mut done = self.uploaded.lock().unwrap();
+            done.extend(uploaded);
+            if !failed.is_empty() {
+                // 新归档的文件排在失败的文件之后
+                failed.append(&mut pending);
+                *pending = failed;
+                return Err(anyhow!("{} 个归档文件上传失败", pending.len()));
+            }
+            if done.is_empty() {
+                return Ok(count);
+            }
+            std::mem::take(&mut *done)
+        };
+
+        let now = Utc::now();
+        let manifest = serde_json::to_vec_pretty(&Manifest {
+            source: &self.prefix,
+            uploaded_at: now,
+            files: &entries,
+        })?;
+        let key = client.key(&format!("manifests/{}_{}.json", self.prefix, now.timestamp_millis()));
+        if let Err(e) = client.put_with_retry(&key, manifest.into()).await {
+            // 清单上传失败时保留条目，随下一批文件一起写入清单
+            let mut done = self.uploaded.lock().unwrap();
+            let mut rest = std::mem::replace(&mut *done, entries);
+            done.append(&mut rest);
+            return Err(anyhow!("上传清单失败: {}", e));
+        }
+        info!("已上传归档清单 {}（{} 个文件）", key, entries.len());
+        Ok(count)
+    }
+}
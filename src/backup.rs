@@ -0,0 +1,246 @@
+//! 缓存备份
+//! 以 DuckDB EXPORT DATABASE（Parquet 格式）将缓存导出为一致性快照目录 `<备份目录>/<DuckDB 文件名>_<UTC 时间>`，
+//! 可在 DuckDB 中用 IMPORT DATABASE 恢复。导出先写入临时目录，完成后再改名，中断的备份不会被当作有效备份；
+//! 每次备份后只保留最近 `keep` 份。配置 `[backup.upload]` 时，备份目录中的文件随后上传到 S3 兼容对象存储的
+//! `backups/<备份目录名>/` 下，全部成功后再上传该备份的清单 `manifest.json`；上传失败时备份仍保留在本地。
+//!
+//! 恢复时先将备份导入临时文件并校验、修复表结构，再与现有的 DuckDB 文件交换（原文件保留为 `.before-restore`）；
+//! 同步检查点设为备份时的同步进度，服务下次启动时从该处回填备份之后的数据。
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::config::AppConfig;
+use crate::database::DatabaseManager;
+use crate::s3::{Manifest, ManifestEntry, S3Client};
+
+/// 备份目录名中的时间格式
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// 执行一次备份并删除超出保留份数的旧备份，返回新备份的目录
+pub fn run(db_manager: &DatabaseManager, config: &AppConfig, dir: &Path, keep: usize) -> Result<PathBuf> {
+    let prefix = prefix(config);
+    std::fs::create_dir_all(dir)?;
+
+    let name = format!("{}_{}", prefix, Utc::now().format(TIMESTAMP_FORMAT));
+    let target = dir.join(&name);
+    if target.exists() {
+        return Err(anyhow!("备份 {} 已存在", target.display()));
+    }
+    let partial = dir.join(format!(".{}.partial", name));
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)?;
+    }
+
+    if let Err(e) = db_manager.backup_to(&partial) {
+        let _ = std::fs::remove_dir_all(&partial);
+        return Err(anyhow!("导出数据库失败: {}", e));
+    }
+    std::fs::rename(&partial, &target)?;
+    info!("已备份到 {}", target.display());
+
+    prune(dir, &prefix, keep)?;
+    Ok(target)
+}
+
+/// 配置 `[backup.upload]` 时上传备份目录中的文件，全部成功后上传清单；未配置时不做任何事，返回上传的文件数
+pub async fn upload(config: &AppConfig, backup: &Path) -> Result<usize> {
+    let Some(settings) = &config.backup.upload else {
+        return Ok(0);
+    };
+    let client = S3Client::new(settings.clone());
+    let name = backup.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow!("无效的备份目录 {}", backup.display()))?;
+
+    let mut files = Vec::new();
+    let mut entries = tokio::fs::read_dir(backup).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_file() {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+
+    let mut uploaded = Vec::new();
+    for file in &files {
+        let key = client.key(&format!("backups/{}/{}", name, file.file_name().unwrap_or_default().to_string_lossy()));
+        let size = tokio::fs::metadata(file).await?.len();
+        let sha256 = client.upload_file(file, &key).await?;
+        uploaded.push(ManifestEntry { key, size, sha256 });
+    }
+
+    let manifest = serde_json::to_vec_pretty(&Manifest {
+        source: &prefix(config),
+        uploaded_at: Utc::now(),
+        files: &uploaded,
+    })?;
+    let key = client.key(&format!("backups/{}/manifest.json", name));
+    client.put_with_retry(&key, manifest.into()).await
+        .map_err(|e| anyhow!("上传备份清单失败: {}", e))?;
+    info!("已上传备份 {}（{} 个文件）到 {}", name, uploaded.len(), key);
+    Ok(uploaded.len())
+}
+
+/// 将备份目录恢复为配置的 DuckDB 文件，返回恢复后的同步检查点时间（数据为空时为 None）
+///
+/// 需在服务停止时运行：现有文件被其他进程打开时拒绝恢复。
+pub fn restore(config: &AppConfig, source: &Path) -> Result<Option<DateTime<Utc>>> {
+    if !config.persist_cache {
+        return Err(anyhow!("恢复需启用 persist_cache，否则服务启动时会删除 DuckDB 文件"));
+    }
+    if !source.join("schema.sql").is_file() {
+        return Err(anyhow!("{} 不是有效的备份目录（缺少 schema.sql）", source.display()));
+    }
+
+    let target = PathBuf::from(&config.db_file_path);
+    if target.exists() {
+        // 服务运行中时文件被锁定，打开失败
+        duckdb::Connection::open(&target)
+            .map_err(|e| anyhow!("无法打开 {}，请先停止服务: {}", target.display(), e))?;
+    }
+
+    let staging = with_suffix(&target, "restoring");
+    remove_database_file(&staging)?;
+    let last_synced = match import(config, &staging, source) {
+        Ok(last_synced) => last_synced,
+        Err(e) => {
+            let _ = remove_database_file(&staging);
+            return Err(e);
+        }
+    };
+
+    if target.exists() {
+        let previous = with_suffix(&target, "before-restore");
+        remove_database_file(&previous)?;
+        std::fs::rename(&target, &previous)?;
+        let wal = with_suffix(&target, "wal");
+        if wal.exists() {
+            std::fs::rename(&wal, with_suffix(&previous, "wal"))?;
+        }
+        info!("原 DuckDB 文件已保留为 {}", previous.display());
+    }
+    std::fs::rename(&staging, &target)?;
+    info!("已从 {} 恢复 {}", source.display(), target.display());
+    Ok(last_synced)
+}
+
+/// 将备份导入临时文件，校验并修复表结构，确保同步检查点存在；返回检查点时间
+fn import(config: &AppConfig, staging: &Path, source: &Path) -> Result<Option<DateTime<Utc>>> {
+    let mut staging_config = config.clone();
+    staging_config.db_file_path = staging.to_string_lossy().to_string();
+    let db_manager = DatabaseManager::new(Arc::new(staging_config));
+
+    db_manager.import_database(source)
+        .map_err(|e| anyhow!("导入备份失败: {}", e))?;
+    db_manager.get_record_count()
+        .map_err(|e| anyhow!("备份中缺少 {:?} 存储模式的数据表，与配置的存储模式不一致: {}", config.storage_mode, e))?;
+    db_manager.initialize()
+        .map_err(|e| anyhow!("校验备份的表结构失败: {}", e))?;
+
+    let checkpoint = db_manager.load_checkpoint()
+        .map_err(|e| anyhow!("读取同步检查点失败: {}", e))?;
+    if let Some(checkpoint) = checkpoint {
+        return Ok(Some(checkpoint.last_synced));
+    }
+
+    // 备份中没有检查点时以最新的数据时间与存储的标签作为同步进度
+    let latest = db_manager.get_latest_timestamp()
+        .map_err(|e| anyhow!("获取最新时间戳失败: {}", e))?;
+    if let Some(latest) = latest {
+        let tags = db_manager.stored_tags()
+            .map_err(|e| anyhow!("读取标签失败: {}", e))?;
+        db_manager.restore_known_tags(&tags);
+        db_manager.save_checkpoint(latest, None)
+            .map_err(|e| anyhow!("写入同步检查点失败: {}", e))?;
+    }
+    Ok(latest)
+}
+
+/// 在文件名后追加后缀：`cache.duckdb` -> `cache.duckdb.<suffix>`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// 删除 DuckDB 文件及其 WAL 文件，不存在时忽略
+fn remove_database_file(path: &Path) -> Result<()> {
+    for file in [path.to_path_buf(), with_suffix(path, "wal")] {
+        if file.exists() {
+            std::fs::remove_file(&file)?;
+        }
+    }
+    Ok(())
+}
+
+/// 启动定时备份任务，未启用时返回 None
+pub fn spawn_scheduler(config: Arc<AppConfig>, db_manager: Arc<DatabaseManager>) -> Option<JoinHandle<()>> {
+    if !config.backup.enabled {
+        return None;
+    }
+    info!("定时备份已启用，间隔 {} 秒，保留 {} 份，目录 {}", config.backup.interval_secs, config.backup.keep, config.backup.dir);
+
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.backup.interval_secs));
+        interval.tick().await; // 跳过第一个立即触发
+
+        loop {
+            interval.tick().await;
+            let (job_config, db_manager) = (config.clone(), db_manager.clone());
+            let result = tokio::task::spawn_blocking(move || {
+                let settings = &job_config.backup;
+                run(&db_manager, &job_config, Path::new(&settings.dir), settings.keep)
+            }).await;
+            match result {
+                Ok(Ok(target)) => {
+                    if let Err(e) = upload(&config, &target).await {
+                        warn!("上传备份 {} 失败: {}", target.display(), e);
+                    }
+                }
+                Ok(Err(e)) => warn!("定时备份失败: {}", e),
+                Err(e) => error!("定时备份任务异常终止: {}", e),
+            }
+        }
+    }))
+}
+
+/// 备份目录名前缀（DuckDB 文件名），区分多个同步配置写入同一备份目录的备份
+fn prefix(config: &AppConfig) -> String {
+    Path::new(&config.db_file_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "rt_db".to_string())
+}
+
+/// 删除本前缀下超出保留份数的旧备份，其他目录与文件不受影响
+fn prune(dir: &Path, prefix: &str, keep: usize) -> Result<()> {
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_backup = path.is_dir()
+            && path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(prefix)?.strip_prefix('_'))
+                .is_some_and(|time| NaiveDateTime::parse_from_str(time, TIMESTAMP_FORMAT).is_ok());
+        if is_backup {
+            backups.push(path);
+        }
+    }
+
+    // 目录名中的时间可按字典序排序，最新的在前
+    backups.sort_by(|a, b| b.cmp(a));
+    for old in backups.iter().skip(keep) {
+        match std::fs::remove_dir_all(old) {
+            Ok(()) => info!("已删除旧备份 {}", old.display()),
+            Err(e) => warn!("删除旧备份 {} 失败: {}", old.display(), e),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,138 @@
+//! 数据源会话录制与回放
+//! 启用 `[capture]` 后，每个常规更新周期从数据源读取到的当前标签集合与最新数据按 JSON Lines 追加到 `capture.path`，
+//! 一行一个周期。测试中以录制的会话代替 SQL Server（见 `SqlServerDataSource::with_replay`），
+//! 经 `SyncService` 的更新周期逐周期回放，并以 golden 文件对比回放后的缓存内容。
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::Write;
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::config::CaptureConfig;
+use crate::database::TimeSeriesRecord;
+
+/// 录制的一个同步周期
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapturedCycle {
+    /// 录制时间
+    pub captured_at: DateTime<Utc>,
+    /// 数据源当前的全部标签，按名称排序
+    pub current_tags: Vec<String>,
+    /// 数据源返回的最新数据
+    pub records: Vec<TimeSeriesRecord>,
+}
+
+/// 会话录制器，写入失败只告警，不影响同步
+pub struct Recorder {
+    file: Mutex<std::fs::File>,
+}
+
+impl Recorder {
+    /// 打开录制文件（追加），未启用或打开失败时返回 None
+    pub fn open(config: &CaptureConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        match std::fs::OpenOptions::new().create(true).append(true).open(&config.path) {
+            Ok(file) => {
+                info!("数据源会话录制已启用: {}", config.path);
+                Some(Self { file: Mutex::new(file) })
+            }
+            Err(e) => {
+                warn!("打开会话录制文件 {} 失败，本次运行不录制: {}", config.path, e);
+                None
+            }
+        }
+    }
+
+    /// 追加一个周期的数据源读取结果
+    pub fn record(&self, current_tags: &HashSet<String>, records: &[TimeSeriesRecord]) {
+        let mut current_tags: Vec<String> = current_tags.iter().cloned().collect();
+        current_tags.sort();
+        let cycle = CapturedCycle {
+            captured_at: Utc::now(),
+            current_tags,
+            records: records.to_vec(),
+        };
+
+        let result = serde_json::to_string(&cycle)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| {
+                let mut file = self.file.lock().unwrap();
+                writeln!(file, "{}", line)?;
+                Ok(file.flush()?)
+            });
+        if let Err(e) = result {
+            warn!("写入会话录制失败: {}", e);
+        }
+    }
+}
+
+/// 录制会话的回放游标（仅测试）
+///
+/// 每个更新周期先检测标签变化（读取当前周期录制的标签集合），再读取最新数据（返回该周期录制的数据并前进到下一周期）；
+/// 读取失败（如注入的断连）时不前进，与真实数据源一样由后续周期读取之后的数据。
+#[cfg(test)]
+pub struct Replay {
+    cycles: Vec<CapturedCycle>,
+    next: Mutex<usize>,
+}
+
+#[cfg(test)]
+impl Replay {
+    pub fn new(cycles: Vec<CapturedCycle>) -> Self {
+        Self { cycles, next: Mutex::new(0) }
+    }
+
+    /// 读取录制的会话文件，跳过空行
+    pub fn open(path: &std::path::Path) -> anyhow::Result<Self> {
+        use anyhow::anyhow;
+        use std::io::BufRead;
+
+        let file = std::fs::File::open(path)
+            .map_err(|e| anyhow!("打开会话录制文件 {} 失败: {}", path.display(), e))?;
+
+        let mut cycles = Vec::new();
+        for (index, line) in std::io::BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let cycle = serde_json::from_str(&line)
+                .map_err(|e| anyhow!("{} 第 {} 行解析失败: {}", path.display(), index + 1, e))?;
+            cycles.push(cycle);
+        }
+        Ok(Self::new(cycles))
+    }
+
+    /// 去掉第 `index` 个周期（从 0 开始）后的会话
+    pub fn without_cycle(mut self, index: usize) -> Self {
+        self.cycles.remove(index);
+        self
+    }
+
+    /// 录制的周期数
+    pub fn cycle_count(&self) -> usize {
+        self.cycles.len()
+    }
+
+    /// 当前周期录制的标签集合
+    pub fn current_tags(&self) -> anyhow::Result<HashSet<String>> {
+        Ok(self.current()?.current_tags.iter().cloned().collect())
+    }
+
+    /// 当前周期录制的最新数据，并前进到下一周期
+    pub fn take_records(&self) -> anyhow::Result<Vec<TimeSeriesRecord>> {
+        let records = self.current()?.records.clone();
+        *self.next.lock().unwrap() += 1;
+        Ok(records)
+    }
+
+    fn current(&self) -> anyhow::Result<&CapturedCycle> {
+        let next = *self.next.lock().unwrap();
+        self.cycles.get(next)
+            .ok_or_else(|| anyhow::anyhow!("录制的 {} 个周期已全部回放", self.cycles.len()))
+    }
+}
@@ -0,0 +1,126 @@
+//! 故障注入（混沌测试模式）
+//! 仅在启用 `chaos` feature 编译时生效，通过环境变量注入数据源断连、查询延迟、数值损坏与磁盘写满，
+//! 用于验证服务在故障下能否自行恢复且不丢数据。未启用 feature 时所有注入点均为空操作。
+//!
+//! - `RT_DB_CHAOS_DROP_CONNECTION_PCT`：建立数据源连接失败的概率（百分比）
+//! - `RT_DB_CHAOS_QUERY_DELAY_MS`：每次数据源查询前的额外延迟（毫秒）
+//! - `RT_DB_CHAOS_CORRUPT_ROWS_PCT`：解析时将数值替换为 NaN 的行比例（百分比）
+//! - `RT_DB_CHAOS_DISK_FULL_PCT`：开始同步周期写入时模拟磁盘已满的概率（百分比）
+
+use std::hash::{BuildHasher, Hasher};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::warn;
+
+/// 故障注入设置
+#[derive(Debug, Default, Clone)]
+pub struct ChaosSettings {
+    pub drop_connection_pct: f64,
+    pub query_delay_ms: u64,
+    pub corrupt_rows_pct: f64,
+    pub disk_full_pct: f64,
+}
+
+static SETTINGS: OnceLock<Option<ChaosSettings>> = OnceLock::new();
+
+#[cfg(test)]
+thread_local! {
+    /// 测试中按线程覆盖的设置，不依赖 feature 与环境变量，并行的测试互不影响
+    static TEST_OVERRIDE: std::cell::Cell<Option<&'static ChaosSettings>> = const { std::cell::Cell::new(None) };
+}
+
+/// 在当前线程上启用给定的故障注入设置（仅测试），`None` 时恢复为未启用
+#[cfg(test)]
+pub fn set_for_test(settings: Option<ChaosSettings>) {
+    let settings = settings.map(|settings| &*Box::leak(Box::new(settings)));
+    TEST_OVERRIDE.with(|current| current.set(settings));
+}
+
+impl ChaosSettings {
+    /// 从环境变量读取，全部为 0 时返回 None
+    fn from_env() -> Option<Self> {
+        let pct = |name: &str| std::env::var(name).ok()
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .unwrap_or(0.0)
+            .clamp(0.0, 100.0);
+        let settings = Self {
+            drop_connection_pct: pct("RT_DB_CHAOS_DROP_CONNECTION_PCT"),
+            query_delay_ms: std::env::var("RT_DB_CHAOS_QUERY_DELAY_MS").ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(0),
+            corrupt_rows_pct: pct("RT_DB_CHAOS_CORRUPT_ROWS_PCT"),
+            disk_full_pct: pct("RT_DB_CHAOS_DISK_FULL_PCT"),
+        };
+
+        let enabled = settings.drop_connection_pct > 0.0
+            || settings.query_delay_ms > 0
+            || settings.corrupt_rows_pct > 0.0
+            || settings.disk_full_pct > 0.0;
+        enabled.then_some(settings)
+    }
+}
+
+/// 当前生效的故障注入设置，未启用时返回 None
+pub fn settings() -> Option<&'static ChaosSettings> {
+    #[cfg(test)]
+    if let Some(settings) = TEST_OVERRIDE.with(std::cell::Cell::get) {
+        return Some(settings);
+    }
+    if !cfg!(feature = "chaos") {
+        return None;
+    }
+    SETTINGS.get_or_init(ChaosSettings::from_env).as_ref()
+}
+
+/// 启动时提示故障注入已启用
+pub fn warn_if_enabled() {
+    if let Some(settings) = settings() {
+        warn!("故障注入已启用，仅用于测试: {:?}", settings);
+    }
+}
+
+/// 以 `pct` 的概率返回 true
+fn roll(pct: f64) -> bool {
+    if pct <= 0.0 {
+        return false;
+    }
+    // 标准库 RandomState 每次使用随机种子，足以满足故障注入的随机性
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    (hasher.finish() % 10_000) as f64 / 100.0 < pct
+}
+
+/// 模拟数据源连接被断开
+pub fn inject_connection_failure() -> anyhow::Result<()> {
+    match settings() {
+        Some(settings) if roll(settings.drop_connection_pct) => {
+            anyhow::bail!("故障注入：数据源连接被断开")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 模拟数据源查询延迟
+pub async fn inject_query_delay() {
+    if let Some(settings) = settings().filter(|s| s.query_delay_ms > 0) {
+        tokio::time::sleep(Duration::from_millis(settings.query_delay_ms)).await;
+    }
+}
+
+/// 模拟数值损坏，损坏的值替换为 NaN
+pub fn corrupt_value(value: Option<f64>) -> Option<f64> {
+    match settings() {
+        Some(settings) if roll(settings.corrupt_rows_pct) => Some(f64::NAN),
+        _ => value,
+    }
+}
+
+/// 模拟磁盘已满
+pub fn inject_disk_full() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match settings() {
+        Some(settings) if roll(settings.disk_full_pct) => {
+            Err("故障注入：No space left on device".into())
+        }
+        _ => Ok(()),
+    }
+}
@@ -1,7 +1,6 @@
 use anyhow::Result;
 use duckdb::Connection;
-use tracing::{info, error};
-use tracing_subscriber;
+use tracing::info;
 
 fn main() -> Result<()> {
     // 初始化日志
@@ -31,7 +30,7 @@ fn main() -> Result<()> {
     }
     
     // 查询最新的10行数据的DateTime列原始值
-        let query = "SELECT DateTime FROM ts_wide ORDER BY DateTime DESC LIMIT 10";
+        let query = "SELECT CAST(DateTime AS VARCHAR) FROM ts_wide ORDER BY DateTime DESC LIMIT 10";
         let mut stmt = conn.prepare(query)?;
         let rows = stmt.query_map([], |row| {
             let datetime_str: String = row.get(0)?;
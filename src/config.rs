@@ -1,21 +1,39 @@
 use anyhow::Result;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
 
 /// 数据库连接方式
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum DatabaseConnectionType {
     /// 使用连接字符串
     ConnectionString,
     /// 使用结构化配置
+    #[default]
     StructuredConfig,
 }
 
-impl Default for DatabaseConnectionType {
-    fn default() -> Self {
-        DatabaseConnectionType::StructuredConfig
-    }
+/// 本地存储模式
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageMode {
+    /// 宽表：每个标签一列 (ts_wide)
+    #[default]
+    Wide,
+    /// 窄表：(DateTime, TagName, Value) 三列 (ts_long)
+    Long,
+}
+
+/// 宽表列命名方式
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnNaming {
+    /// 非字母数字字符替换为下划线（默认）
+    #[default]
+    Sanitized,
+    /// 使用原始标签名作为列名（加引号的标识符，支持中文与任意字符）
+    Original,
 }
 
 /// 应用配置结构体
@@ -34,45 +52,215 @@ pub struct AppConfig {
     pub data_window_days: u32,
     /// 本地 DuckDB 文件路径
     pub db_file_path: String,
+    /// 启动时复用已有的 DuckDB 文件（默认删除重建）
+    #[serde(default)]
+    pub persist_cache: bool,
+    /// DuckDB 已用空间上限（MB），超过时从最旧的数据开始逐步汇总、归档后删除，未设置时不限
+    #[serde(default)]
+    pub max_db_size_mb: Option<u64>,
+    /// 本地存储模式（wide / long）
+    #[serde(default)]
+    pub storage_mode: StorageMode,
+    /// 宽表列命名方式（sanitized / original），只影响新出现的标签，已分配的列名保持不变
+    #[serde(default)]
+    pub column_naming: ColumnNaming,
+    /// 该数据源全部标签的命名空间前缀（如 `unit1.`），存储、API、导出与推送均使用加前缀后的标签名
+    #[serde(default)]
+    pub tag_prefix: String,
+    /// 数据源时间的时区偏移（小时），SQL Server 中的时间按该时区解释并换算为 UTC 存储
+    #[serde(default = "default_source_timezone_offset_hours")]
+    pub source_timezone_offset_hours: i32,
+    /// 缺失或无效的标签值填充为 0.0（旧版行为），默认写入 NULL
+    #[serde(default)]
+    pub zero_fill_missing: bool,
+    /// 只读数据源保护：审计发往 SQL Server 的全部语句，拒绝 SELECT 以外的语句
+    #[serde(default)]
+    pub read_only_source: bool,
+    /// 最小权限模式：启动自检发现账号拥有写权限或 sysadmin/db_owner 角色时拒绝启动
+    #[serde(default)]
+    pub strict_permissions: bool,
     /// 日志级别
     pub log_level: String,
+    /// 按模块（日志 target）覆盖日志级别，如 `sync_service = "debug"`、`tiberius = "warn"`
+    #[serde(default)]
+    pub log_levels: BTreeMap<String, String>,
     /// 表名配置
     pub tables: TableConfig,
     /// 连接配置
     pub connection: ConnectionConfig,
+    /// Azure AD 令牌认证配置（Azure SQL）
+    #[serde(default)]
+    pub aad: AadConfig,
     /// 查询配置
+    #[allow(dead_code)]
     pub query: QueryConfig,
     /// 批量处理配置
     #[serde(default)]
     pub batch: BatchConfig,
+    /// HTTP API 配置
+    #[serde(default)]
+    pub api: ApiConfig,
+    /// SPC 监控配置
+    #[serde(default)]
+    pub spc: SpcConfig,
+    /// 趋势预测配置
+    #[serde(default)]
+    pub forecast: ForecastConfig,
+    /// Kafka 推送配置
+    #[serde(default)]
+    pub kafka: KafkaConfig,
+    /// 内嵌 OPC UA 服务配置
+    #[serde(default)]
+    pub opcua: OpcUaConfig,
+    /// 资源压力下的分级降级配置
+    #[serde(default)]
+    pub degradation: DegradationConfig,
+    /// 能耗计量配置
+    #[serde(default)]
+    pub energy: EnergyConfig,
+    /// 定时导出配置
+    #[serde(default)]
+    pub export: ExportConfig,
+    /// MES/ERP REST 推送配置
+    #[serde(default)]
+    pub integration: IntegrationConfig,
+    /// 数据库维护配置
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+    /// 备份配置
+    #[serde(default)]
+    pub backup: BackupConfig,
+    /// 缩略趋势图预计算配置
+    #[serde(default)]
+    pub sparkline: SparklineConfig,
+    /// 只读副本（跟随模式）配置
+    #[serde(default)]
+    pub replica: ReplicaConfig,
+    /// 数据缺口回填配置
+    #[serde(default)]
+    pub backfill: BackfillConfig,
+    /// 标签轮询分组配置
+    #[serde(default)]
+    pub polling: PollingConfig,
+    /// 死区（仅变化时存储）配置
+    #[serde(default)]
+    pub deadband: DeadbandConfig,
+    /// 数据源熔断配置
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// 数据源会话录制配置
+    #[serde(default)]
+    pub capture: CaptureConfig,
+    /// 同步失败与数据滞后告警配置
+    #[serde(default)]
+    pub alerts: AlertConfig,
+    /// 心跳回写配置
+    #[serde(default)]
+    pub heartbeat: HeartbeatConfig,
+    /// 数据质量（TagQuality）采集配置
+    #[serde(default)]
+    pub quality: QualityConfig,
+    /// 周期拼接时间戳配置
+    #[serde(default)]
+    pub timestamps: TimestampConfig,
+    /// 清理前的 Parquet 归档配置
+    #[serde(default)]
+    pub archive: ArchiveConfig,
+    /// 分级保留：清理前降采样为分钟与小时汇总
+    #[serde(default)]
+    pub rollup: RollupConfig,
+    /// 停滞标签检测配置
+    #[serde(default)]
+    pub stale_tags: StaleTagConfig,
+    /// 启动顺序控制：等待数据盘挂载与数据源就绪
+    #[serde(default)]
+    pub startup: StartupConfig,
+    /// 低延迟模式（亚秒级轮询）配置
+    #[serde(default)]
+    pub low_latency: LowLatencyConfig,
+    /// OpenTelemetry 追踪导出配置
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    /// 重复告警日志限流配置
+    #[serde(default)]
+    pub log_throttle: LogThrottleConfig,
+    /// 慢操作日志配置
+    #[serde(default)]
+    pub slow_log: SlowLogConfig,
+    /// 标签规范化目录（原始标签到规范标签的映射与单位换算），各同步配置共用
+    #[serde(default)]
+    pub normalization: NormalizationConfig,
+    /// 额外的同步配置（每个独立的数据源、表、DuckDB 文件与周期），与主配置在同一进程中运行
+    #[serde(default)]
+    pub pipelines: Vec<PipelineConfig>,
+}
+
+/// 额外同步配置，未设置的项沿用主配置
+#[derive(Debug, Deserialize, Clone)]
+pub struct PipelineConfig {
+    /// 名称，用于日志
+    pub name: String,
+    /// 数据库连接方式
+    pub database_connection_type: Option<DatabaseConnectionType>,
+    /// 数据库连接字符串
+    pub database_url: Option<String>,
+    /// 结构化数据库连接配置
+    pub database: Option<DatabaseConfig>,
+    /// 表名配置
+    pub tables: Option<TableConfig>,
+    /// 本地 DuckDB 文件路径，各配置之间不能相同
+    pub db_file_path: String,
+    /// 本地存储模式
+    pub storage_mode: Option<StorageMode>,
+    /// 增量更新周期，单位为秒
+    pub update_interval_secs: Option<u64>,
+    /// 数据保留窗口，单位为天
+    pub data_window_days: Option<u32>,
+    /// 数据源时间的时区偏移（小时）
+    pub source_timezone_offset_hours: Option<i32>,
+    /// 标签命名空间前缀，不沿用主配置（默认无前缀）
+    pub tag_prefix: Option<String>,
+}
+
+/// 默认数据源时区：北京时间 (UTC+8)
+fn default_source_timezone_offset_hours() -> i32 {
+    8
 }
 
 /// 数据库连接配置
 #[derive(Debug, Deserialize, Clone)]
 pub struct DatabaseConfig {
-    /// 服务器地址
+    /// 服务器地址，命名实例写作 `HOST\INSTANCE`
     pub server: String,
-    /// 端口号
+    /// 端口号（命名实例通过 SQL Browser 查询端口，忽略该项）
     pub port: u16,
     /// 数据库名
     pub database: String,
-    /// 用户名
+    /// 用户名（使用 Azure AD 令牌认证时不需要）
+    #[serde(default)]
     pub user: String,
-    /// 密码
+    /// 密码（使用 Azure AD 令牌认证时不需要）
+    #[serde(default)]
     pub password: String,
     /// 是否信任服务器证书
+    #[allow(dead_code)]
     pub trust_server_certificate: bool,
+    /// 备用服务器（如主/备历史库），格式同 `server`，可写作 `HOST,PORT` 指定端口（默认同 `port`）；
+    /// 当前服务器连接失败时依次轮换
+    #[serde(default)]
+    pub failover_servers: Vec<String>,
 }
 
 impl DatabaseConfig {
     /// 生成数据库连接字符串
+    #[allow(dead_code)]
     pub fn to_connection_string(&self) -> String {
         // 对数据库名、用户名和密码进行URL编码以支持中文字符
         let encoded_database = urlencoding::encode(&self.database);
         let encoded_user = urlencoding::encode(&self.user);
         let encoded_password = urlencoding::encode(&self.password);
         
-        format!(
+        let mut connection_string = format!(
             "server=tcp:{},{};database={};user={};password={};TrustServerCertificate={}",
             self.server,
             self.port,
@@ -80,7 +268,11 @@ impl DatabaseConfig {
             encoded_user,
             encoded_password,
             self.trust_server_certificate
-        )
+        );
+        for server in &self.failover_servers {
+            connection_string.push_str(&format!(";Failover Partner={}", server));
+        }
+        connection_string
     }
     
     /// 从连接字符串解析数据库配置
@@ -91,6 +283,7 @@ impl DatabaseConfig {
         let mut user = String::new();
         let mut password = String::new();
         let mut trust_server_certificate = false;
+        let mut failover_servers = Vec::new();
         
         // 解析连接字符串中的键值对
         for pair in connection_string.split(';') {
@@ -110,8 +303,7 @@ impl DatabaseConfig {
             match key.as_str() {
                 "server" => {
                     // 处理 server=tcp:localhost,1433 格式
-                    if value.starts_with("tcp:") {
-                        let server_part = &value[4..]; // 去掉 "tcp:" 前缀
+                    if let Some(server_part) = value.strip_prefix("tcp:") {
                         if let Some(comma_pos) = server_part.find(',') {
                             server = server_part[..comma_pos].to_string();
                             if let Ok(parsed_port) = server_part[comma_pos + 1..].parse::<u16>() {
@@ -145,6 +337,10 @@ impl DatabaseConfig {
                 "trustservercertificate" => {
                     trust_server_certificate = value.to_lowercase() == "true";
                 }
+                // 可重复出现，每项一台备用服务器
+                "failover partner" | "failoverpartner" => {
+                    failover_servers.push(value.strip_prefix("tcp:").unwrap_or(value).to_string());
+                }
                 _ => {
                     // 忽略未知的键
                 }
@@ -158,6 +354,7 @@ impl DatabaseConfig {
             user,
             password,
             trust_server_certificate,
+            failover_servers,
         };
         
         // 验证解析结果
@@ -166,12 +363,38 @@ impl DatabaseConfig {
         Ok(config)
     }
     
+    /// 全部服务器地址与端口：主服务器在前，其后为备用服务器
+    pub fn servers(&self) -> Vec<(&str, u16)> {
+        std::iter::once((self.server.as_str(), self.port))
+            .chain(self.failover_servers.iter().map(|server| match server.split_once(',') {
+                Some((host, port)) => (host.trim(), port.trim().parse().unwrap_or(self.port)),
+                None => (server.as_str(), self.port),
+            }))
+            .collect()
+    }
+    
     /// 验证数据库配置的有效性
     fn validate(&self) -> Result<()> {
         if self.server.is_empty() {
             anyhow::bail!("数据库服务器地址不能为空");
         }
         
+        for server in &self.failover_servers {
+            if let Some((_, port)) = server.split_once(',')
+                && !matches!(port.trim().parse::<u16>(), Ok(port) if port > 0) {
+                anyhow::bail!("备用服务器 {} 的端口无效", server);
+            }
+        }
+        for (server, _) in self.servers() {
+            if server.is_empty() {
+                anyhow::bail!("备用服务器地址不能为空");
+            }
+            if let (host, Some(instance)) = split_instance(server)
+                && (host.is_empty() || instance.is_empty() || instance.contains('\\')) {
+                anyhow::bail!("数据库服务器地址 {} 无效，命名实例应写作 主机名\\实例名", server);
+            }
+        }
+        
         if self.port == 0 {
             anyhow::bail!("数据库端口号必须大于 0");
         }
@@ -180,6 +403,11 @@ impl DatabaseConfig {
             anyhow::bail!("数据库名不能为空");
         }
         
+        Ok(())
+    }
+    
+    /// 验证 SQL 账号（使用 Azure AD 令牌认证时不需要）
+    fn validate_credentials(&self) -> Result<()> {
         if self.user.is_empty() {
             anyhow::bail!("数据库用户名不能为空");
         }
@@ -195,18 +423,23 @@ impl DatabaseConfig {
 /// 表名配置
 #[derive(Debug, Deserialize, Clone)]
 pub struct TableConfig {
-    /// 历史表名
+    /// 历史表名，可包含日期模板变量 {yyyy}、{yy}、{MM}、{dd}（按月/按日轮换的历史表）
     pub history_table: String,
     /// TagDatabase 表名
     pub tag_database_table: String,
+    /// 数值列名，未配置时通过 INFORMATION_SCHEMA 自动识别（TagVal / Value / Val）
+    #[serde(default)]
+    pub value_column: Option<String>,
 }
 
 /// 查询配置
 #[derive(Debug, Deserialize, Clone)]
 pub struct QueryConfig {
     /// 历史数据查询天数
+    #[allow(dead_code)]
     pub days_back: i32,
     /// 历史数据表名（用于查询）
+    #[allow(dead_code)]
     pub history_table: String,
 }
 
@@ -219,6 +452,29 @@ pub struct ConnectionConfig {
     pub retry_interval_secs: u64,
     /// 连接超时，单位为秒
     pub connection_timeout_secs: u64,
+    /// 单条查询的超时，单位为秒，超时后取消查询并丢弃连接，0 表示不限制
+    #[serde(default = "default_query_timeout_secs")]
+    pub query_timeout_secs: u64,
+    /// TCP keep-alive 空闲时间，单位为秒，0 表示不开启
+    #[serde(default = "default_tcp_keepalive_secs")]
+    pub tcp_keepalive_secs: u64,
+    /// 经代理连接数据源：`socks5://[user:password@]host[:port]` 或 `ssh://[user@]jumphost[:port]`
+    /// （SSH 本地端口转发，需要系统中的 ssh 客户端），未配置时直接连接
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// SSH 隧道使用的私钥文件，未配置时使用 ssh 的默认配置
+    #[serde(default)]
+    pub ssh_identity_file: Option<String>,
+}
+
+/// 默认查询超时：10 分钟，足以完成一个批次的历史表扫描
+fn default_query_timeout_secs() -> u64 {
+    600
+}
+
+/// 默认 TCP keep-alive 空闲时间：60 秒
+fn default_tcp_keepalive_secs() -> u64 {
+    60
 }
 
 impl Default for TableConfig {
@@ -226,7 +482,86 @@ impl Default for TableConfig {
         Self {
             history_table: "History".to_string(),
             tag_database_table: "TagDatabase".to_string(),
+            value_column: None,
+        }
+    }
+}
+
+/// 拆分服务器地址为主机名与命名实例名（`HOST\INSTANCE` 形式）
+pub fn split_instance(server: &str) -> (&str, Option<&str>) {
+    match server.split_once('\\') {
+        Some((host, instance)) => (host, Some(instance)),
+        None => (server, None),
+    }
+}
+
+/// 校验 SQL Server 标识符（表名、列名）
+///
+/// 只接受字母、数字与 `_`、`$`、`#`、`@`，允许 `schema.table` 形式，
+/// 配置中的标识符必须通过校验，拼入语句时由 [`Dialect`](crate::sql::Dialect) 引用。
+pub fn validate_identifier(name: &str) -> Result<()> {
+    let parts: Vec<&str> = name.split('.').collect();
+    if parts.len() > 2 {
+        anyhow::bail!("标识符 {} 最多包含一级架构名", name);
+    }
+    
+    for part in parts {
+        if part.is_empty() || part.chars().count() > 128 {
+            anyhow::bail!("标识符 {} 为空或超过 128 个字符", name);
+        }
+        if !part.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '$' | '#' | '@')) {
+            anyhow::bail!("标识符 {} 包含不允许的字符（只允许字母、数字与 _ $ # @）", name);
+        }
+    }
+    Ok(())
+}
+
+impl TableConfig {
+    /// 校验表名与列名配置
+    fn validate(&self) -> Result<()> {
+        validate_identifier(&self.tag_database_table)
+            .map_err(|e| anyhow::anyhow!("tables.tag_database_table 无效: {}", e))?;
+        // 模板变量替换为日期后再校验
+        let sample = chrono::NaiveDateTime::default();
+        for table in self.history_tables(sample, sample) {
+            validate_identifier(&table)
+                .map_err(|e| anyhow::anyhow!("tables.history_table 无效: {}", e))?;
+        }
+        if let Some(column) = self.value_column.as_deref().filter(|c| !c.is_empty()) {
+            validate_identifier(column)
+                .map_err(|e| anyhow::anyhow!("tables.value_column 无效: {}", e))?;
+        }
+        Ok(())
+    }
+    
+    /// 历史表名是否为日期模板
+    pub fn is_history_template(&self) -> bool {
+        ["{yyyy}", "{yy}", "{MM}", "{dd}"].iter().any(|v| self.history_table.contains(v))
+    }
+    
+    /// 解析历史表名模板，返回覆盖 [start, end]（数据源本地时间）的全部表名，按时间顺序
+    pub fn history_tables(&self, start: chrono::NaiveDateTime, end: chrono::NaiveDateTime) -> Vec<String> {
+        if !self.is_history_template() {
+            return vec![self.history_table.clone()];
+        }
+        
+        let mut tables: Vec<String> = Vec::new();
+        let mut date = start.date();
+        while date <= end.date() {
+            let name = self.history_table
+                .replace("{yyyy}", &date.format("%Y").to_string())
+                .replace("{yy}", &date.format("%y").to_string())
+                .replace("{MM}", &date.format("%m").to_string())
+                .replace("{dd}", &date.format("%d").to_string());
+            if tables.last() != Some(&name) {
+                tables.push(name);
+            }
+            match date.succ_opt() {
+                Some(next) => date = next,
+                None => break,
+            }
         }
+        tables
     }
 }
 
@@ -245,6 +580,10 @@ impl Default for ConnectionConfig {
             max_retries: 3,
             retry_interval_secs: 5,
             connection_timeout_secs: 30,
+            query_timeout_secs: default_query_timeout_secs(),
+            tcp_keepalive_secs: default_tcp_keepalive_secs(),
+            proxy: None,
+            ssh_identity_file: None,
         }
     }
 }
@@ -290,6 +629,7 @@ impl AppConfig {
     
     /// 获取数据库连接字符串
     /// 无论使用哪种配置方式，都返回标准的连接字符串
+    #[allow(dead_code)]
     pub fn get_connection_string(&self) -> Result<String> {
         let db_config = self.get_database_config()?;
         Ok(db_config.to_connection_string())
@@ -297,31 +637,202 @@ impl AppConfig {
     
     /// 验证配置的有效性
     fn validate(&self) -> Result<()> {
-        // 验证数据库配置
-        self.get_database_config()?;
+        // 验证数据库配置（跟随模式不连接 SQL Server）
+        if self.replica.enabled {
+            if self.replica.primary_url.is_empty() {
+                anyhow::bail!("跟随模式下 replica.primary_url 不能为空");
+            }
+        } else {
+            let database_config = self.get_database_config()?;
+            if self.aad.enabled {
+                self.aad.validate()?;
+            } else {
+                database_config.validate_credentials()?;
+            }
+            
+            if let Some(proxy) = &self.connection.proxy {
+                crate::proxy::Proxy::parse(proxy)
+                    .map_err(|e| anyhow::anyhow!("connection.proxy 无效: {}", e))?;
+                for (server, _) in database_config.servers() {
+                    if split_instance(server).1.is_some() {
+                        anyhow::bail!("经代理连接时不支持命名实例 {}（SQL Browser 使用 UDP），请改用实例的固定端口", server);
+                    }
+                }
+            }
+        }
         
         if self.update_interval_secs == 0 {
             anyhow::bail!("update_interval_secs 必须大于 0");
         }
         
+        for (target, level) in &self.log_levels {
+            if target.is_empty() || target.contains([',', '=', ' ']) {
+                anyhow::bail!("log_levels 中的模块名 {:?} 无效", target);
+            }
+            level.parse::<tracing::level_filters::LevelFilter>()
+                .map_err(|_| anyhow::anyhow!("log_levels.{} 的日志级别 {:?} 无效（可选 off、error、warn、info、debug、trace）", target, level))?;
+        }
+        
+        if self.low_latency.enabled {
+            if self.low_latency.interval_ms < 200 {
+                anyhow::bail!("low_latency.interval_ms 不能小于 200");
+            }
+            if self.low_latency.commit_every_cycles == 0 {
+                anyhow::bail!("low_latency.commit_every_cycles 必须大于 0");
+            }
+        }
+
+        if self.alerts.failure_threshold == 0 {
+            anyhow::bail!("alerts.failure_threshold 必须大于 0");
+        }
+        for webhook in &self.alerts.webhooks {
+            if webhook.url.trim().is_empty() {
+                anyhow::bail!("告警 webhook {} 未配置 url", webhook.name);
+            }
+        }
+
+        if self.heartbeat.enabled {
+            if self.read_only_source {
+                anyhow::bail!("心跳回写需要写入数据源，不能与 read_only_source 同时启用");
+            }
+            if self.heartbeat.tag_name.trim().is_empty() {
+                anyhow::bail!("启用 heartbeat 时必须配置 heartbeat.tag_name");
+            }
+            for (name, identifier) in [
+                ("table", self.heartbeat_table()),
+                ("tag_column", self.heartbeat.tag_column.as_str()),
+                ("value_column", self.heartbeat.value_column.as_str()),
+            ] {
+                validate_identifier(identifier)
+                    .map_err(|e| anyhow::anyhow!("heartbeat.{} 无效: {}", name, e))?;
+            }
+        }
+
+        if self.log_throttle.enabled && self.log_throttle.window_secs == 0 {
+            anyhow::bail!("log_throttle.window_secs 必须大于 0");
+        }
+
+        if self.telemetry.enabled {
+            if self.telemetry.endpoint.trim().is_empty() {
+                anyhow::bail!("启用 telemetry 时必须配置 telemetry.endpoint");
+            }
+            if !(0.0..=1.0).contains(&self.telemetry.sample_ratio) {
+                anyhow::bail!("telemetry.sample_ratio 必须在 [0, 1] 范围内");
+            }
+        }
+
+        if self.forecast.enabled {
+            if !(self.forecast.alpha > 0.0 && self.forecast.alpha <= 1.0) {
+                anyhow::bail!("forecast.alpha 必须在 (0, 1] 范围内");
+            }
+            if self.forecast.method == ForecastMethod::Holt && !(self.forecast.beta > 0.0 && self.forecast.beta <= 1.0) {
+                anyhow::bail!("forecast.beta 必须在 (0, 1] 范围内");
+            }
+            if self.forecast.window_size < 2 || self.forecast.horizon_steps == 0 {
+                anyhow::bail!("forecast.window_size 不能小于 2，forecast.horizon_steps 必须大于 0");
+            }
+        }
+
+        if self.degradation.enabled {
+            let stages = &self.degradation.stages;
+            if stages.is_empty() {
+                anyhow::bail!("启用 degradation 时 stages 不能为空");
+            }
+            for (i, stage) in stages.iter().enumerate() {
+                if stages[..i].contains(stage) {
+                    anyhow::bail!("degradation.stages 中 {:?} 重复", stage);
+                }
+            }
+            if self.degradation.check_interval_secs == 0 || self.degradation.sync_slowdown_factor < 2 {
+                anyhow::bail!("degradation.check_interval_secs 必须大于 0，sync_slowdown_factor 不能小于 2");
+            }
+        }
+
+        if self.kafka.enabled && (self.kafka.brokers.is_empty() || self.kafka.topic.trim().is_empty()) {
+            anyhow::bail!("启用 kafka 时必须配置 brokers 与 topic");
+        }
+
+        if self.opcua.enabled && (self.opcua.port == 0 || self.opcua.namespace_uri.trim().is_empty() || self.opcua.history_points == 0) {
+            anyhow::bail!("启用 opcua 时 port 与 history_points 必须大于 0，namespace_uri 不能为空");
+        }
+
+        if let Some(upload) = &self.archive.upload {
+            if upload.endpoint.trim().is_empty() || upload.bucket.trim().is_empty() {
+                anyhow::bail!("archive.upload 的 endpoint 与 bucket 不能为空");
+            }
+            if !self.archive.enabled {
+                tracing::warn!("配置了 archive.upload 但未启用归档（archive.enabled = false），不会上传任何文件");
+            }
+        }
+
         if self.data_window_days == 0 {
             anyhow::bail!("data_window_days 必须大于 0");
         }
+        if self.rollup.enabled
+            && (self.rollup.minute_retention_days < self.data_window_days
+                || self.rollup.hour_retention_days < self.rollup.minute_retention_days) {
+            anyhow::bail!("rollup.minute_retention_days 不能小于 data_window_days，hour_retention_days 不能小于 minute_retention_days");
+        }
+        
+        if self.backup.keep == 0 {
+            anyhow::bail!("backup.keep 必须大于 0");
+        }
+        if self.backup.enabled && self.backup.interval_secs == 0 {
+            anyhow::bail!("backup.interval_secs 必须大于 0");
+        }
+        if let Some(upload) = &self.backup.upload
+            && (upload.endpoint.trim().is_empty() || upload.bucket.trim().is_empty())
+        {
+            anyhow::bail!("backup.upload 的 endpoint 与 bucket 不能为空");
+        }
+        
+        if self.max_db_size_mb == Some(0) {
+            anyhow::bail!("max_db_size_mb 必须大于 0");
+        }
         
         if self.db_file_path.is_empty() {
             anyhow::bail!("db_file_path 不能为空");
         }
         
+        if !(-12..=14).contains(&self.source_timezone_offset_hours) {
+            anyhow::bail!("source_timezone_offset_hours 必须在 -12 到 14 之间");
+        }
+        
+        self.tables.validate()?;
+        if let Some(ldap) = &self.api.ldap
+            && (ldap.url.is_empty() || ldap.base_dn.is_empty()) {
+            anyhow::bail!("api.ldap 的 url 与 base_dn 不能为空");
+        }
+        if self.api.sql_max_rows == 0 {
+            anyhow::bail!("api.sql_max_rows 必须大于 0");
+        }
+        if self.quality.enabled {
+            validate_identifier(&self.quality.column)
+                .map_err(|e| anyhow::anyhow!("quality.column 无效: {}", e))?;
+        }
+        self.normalization.validate()?;
+        
+        // 各同步配置必须写入不同的 DuckDB 文件
+        let mut db_files = std::collections::HashSet::from([self.db_file_path.as_str()]);
+        for pipeline in &self.pipelines {
+            if pipeline.name.is_empty() {
+                anyhow::bail!("pipelines 中的 name 不能为空");
+            }
+            if !db_files.insert(pipeline.db_file_path.as_str()) {
+                anyhow::bail!("同步配置 {} 的 db_file_path 与其他配置重复: {}", pipeline.name, pipeline.db_file_path);
+            }
+        }
+        self.validate_tag_prefixes()?;
+        
         // 验证连接方式和对应配置的一致性
         match self.database_connection_type {
             DatabaseConnectionType::ConnectionString => {
                 if self.database_url.is_none() {
                     anyhow::bail!("选择连接字符串模式时，必须提供 database_url");
                 }
-                if let Some(ref url) = self.database_url {
-                    if url.trim().is_empty() {
-                        anyhow::bail!("database_url 不能为空字符串");
-                    }
+                if let Some(ref url) = self.database_url
+                    && url.trim().is_empty() {
+                    anyhow::bail!("database_url 不能为空字符串");
                 }
             }
             DatabaseConnectionType::StructuredConfig => {
@@ -334,23 +845,129 @@ impl AppConfig {
         Ok(())
     }
     
+    /// 心跳回写的目标表，未配置时为 TagDatabase 表
+    pub fn heartbeat_table(&self) -> &str {
+        if self.heartbeat.table.is_empty() { &self.tables.tag_database_table } else { &self.heartbeat.table }
+    }
+    
+    /// 生成额外同步配置对应的完整配置
+    ///
+    /// 未设置的项沿用主配置；HTTP API、导出、推送、SPC、趋势预测、缩略趋势、快速轮询、心跳回写与跟随模式只属于主配置，在此关闭。
+    pub fn pipeline_config(&self, pipeline: &PipelineConfig) -> Result<AppConfig> {
+        let mut config = self.clone();
+        
+        if let Some(connection_type) = &pipeline.database_connection_type {
+            config.database_connection_type = connection_type.clone();
+        }
+        if pipeline.database_url.is_some() {
+            config.database_url = pipeline.database_url.clone();
+        }
+        if pipeline.database.is_some() {
+            config.database = pipeline.database.clone();
+        }
+        if let Some(tables) = &pipeline.tables {
+            config.tables = tables.clone();
+        }
+        config.db_file_path = pipeline.db_file_path.clone();
+        config.storage_mode = pipeline.storage_mode.unwrap_or(self.storage_mode);
+        config.update_interval_secs = pipeline.update_interval_secs.unwrap_or(self.update_interval_secs);
+        config.data_window_days = pipeline.data_window_days.unwrap_or(self.data_window_days);
+        config.source_timezone_offset_hours = pipeline.source_timezone_offset_hours
+            .unwrap_or(self.source_timezone_offset_hours);
+        config.tag_prefix = pipeline.tag_prefix.clone().unwrap_or_default();
+        
+        config.api.enabled = false;
+        config.export.jobs.clear();
+        config.integration.endpoints.clear();
+        config.spc.enabled = false;
+        config.forecast.enabled = false;
+        config.opcua.enabled = false;
+        config.sparkline.enabled = false;
+        config.polling.fast_tags.clear();
+        config.replica.enabled = false;
+        config.capture.enabled = false;
+        config.heartbeat.enabled = false;
+        config.pipelines.clear();
+        
+        config.validate()
+            .map_err(|e| anyhow::anyhow!("同步配置 {} 无效: {}", pipeline.name, e))?;
+        Ok(config)
+    }
+    
+    /// 校验各数据源的标签命名空间前缀：配置了前缀的数据源之间不能相同，也不能互为前缀（否则不同数据源的标签可能同名）
+    fn validate_tag_prefixes(&self) -> Result<()> {
+        let prefixes: Vec<(&str, &str)> = std::iter::once(("主配置", self.tag_prefix.as_str()))
+            .chain(self.pipelines.iter().map(|p| (p.name.as_str(), p.tag_prefix.as_deref().unwrap_or(""))))
+            .collect();
+        
+        for (i, (name, prefix)) in prefixes.iter().enumerate() {
+            if prefix.contains(',') || prefix.chars().any(char::is_whitespace) {
+                anyhow::bail!("{} 的 tag_prefix \"{}\" 不能包含逗号或空白字符", name, prefix);
+            }
+            if prefix.is_empty() {
+                continue;
+            }
+            for (other_name, other) in prefixes[i + 1..].iter().filter(|(_, p)| !p.is_empty()) {
+                if prefix.starts_with(other) || other.starts_with(prefix) {
+                    anyhow::bail!("{} 的 tag_prefix \"{}\" 与 {} 的 tag_prefix \"{}\" 冲突，不同数据源的标签可能同名",
+                                  name, prefix, other_name, other);
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    /// 将数据源本地时间换算为 UTC
+    pub fn source_to_utc(&self, local: chrono::NaiveDateTime) -> chrono::DateTime<chrono::Utc> {
+        local.and_utc() - chrono::Duration::hours(self.source_timezone_offset_hours as i64)
+    }
+    
+    /// 将 UTC 时间换算为数据源本地时间（用于查询条件）
+    pub fn utc_to_source(&self, utc: chrono::DateTime<chrono::Utc>) -> chrono::NaiveDateTime {
+        (utc + chrono::Duration::hours(self.source_timezone_offset_hours as i64)).naive_utc()
+    }
+    
     /// 获取数据窗口的持续时间（以秒为单位）
     pub fn data_window_duration_secs(&self) -> i64 {
         self.data_window_days as i64 * 24 * 60 * 60
     }
+    
+    /// 日志过滤指令：全局级别、依赖库的默认级别与按模块覆盖的级别
+    ///
+    /// 本程序的模块名（如 `sync_service`）同时生成带 crate 前缀的指令（`rt_db::sync_service`），
+    /// 同名的依赖库或日志 target（如 `tiberius`、`audit`）也能匹配。
+    pub fn log_filter(&self) -> String {
+        let mut directives = vec![self.log_level.clone(), "tiberius=warn".to_string(), "tokio_util=warn".to_string()];
+        for (target, level) in &self.log_levels {
+            directives.push(format!("{}={}", target, level));
+            if !target.contains("::") {
+                directives.push(format!("{}::{}={}", env!("CARGO_CRATE_NAME"), target, level));
+            }
+        }
+        directives.join(",")
+    }
 }
 
 /// 批量处理配置
 #[derive(Debug, Deserialize, Clone)]
 pub struct BatchConfig {
     /// 批量插入大小
+    #[allow(dead_code)]
     pub batch_size: usize,
     /// 最大内存记录数
     pub max_memory_records: usize,
     /// 是否启用并行插入
+    #[allow(dead_code)]
     pub enable_parallel_insert: bool,
     /// 历史数据加载批次大小（按天）
     pub history_load_batch_days: u32,
+    /// 历史数据并行加载的最大连接数
+    #[serde(default = "default_history_load_concurrency")]
+    pub history_load_concurrency: usize,
+}
+
+fn default_history_load_concurrency() -> usize {
+    4
 }
 
 impl Default for BatchConfig {
@@ -360,6 +977,7 @@ impl Default for BatchConfig {
             max_memory_records: 50000,
             enable_parallel_insert: true,
             history_load_batch_days: 1,
+            history_load_concurrency: default_history_load_concurrency(),
         }
     }
 }
@@ -373,11 +991,1419 @@ impl Default for AppConfig {
             update_interval_secs: 60,
             data_window_days: 30,
             db_file_path: "rt_db.duckdb".to_string(),
+            persist_cache: false,
+            max_db_size_mb: None,
+            storage_mode: StorageMode::default(),
+            column_naming: ColumnNaming::default(),
+            tag_prefix: String::new(),
+            source_timezone_offset_hours: default_source_timezone_offset_hours(),
+            zero_fill_missing: false,
+            read_only_source: false,
+            strict_permissions: false,
             log_level: "info".to_string(),
+            log_levels: BTreeMap::new(),
             tables: TableConfig::default(),
             connection: ConnectionConfig::default(),
+            aad: AadConfig::default(),
             query: QueryConfig::default(),
             batch: BatchConfig::default(),
+            api: ApiConfig::default(),
+            spc: SpcConfig::default(),
+            forecast: ForecastConfig::default(),
+            kafka: KafkaConfig::default(),
+            opcua: OpcUaConfig::default(),
+            degradation: DegradationConfig::default(),
+            energy: EnergyConfig::default(),
+            export: ExportConfig::default(),
+            integration: IntegrationConfig::default(),
+            maintenance: MaintenanceConfig::default(),
+            backup: BackupConfig::default(),
+            sparkline: SparklineConfig::default(),
+            replica: ReplicaConfig::default(),
+            backfill: BackfillConfig::default(),
+            polling: PollingConfig::default(),
+            deadband: DeadbandConfig::default(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            capture: CaptureConfig::default(),
+            alerts: AlertConfig::default(),
+            heartbeat: HeartbeatConfig::default(),
+            quality: QualityConfig::default(),
+            timestamps: TimestampConfig::default(),
+            stale_tags: StaleTagConfig::default(),
+            archive: ArchiveConfig::default(),
+            rollup: RollupConfig::default(),
+            startup: StartupConfig::default(),
+            low_latency: LowLatencyConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            log_throttle: LogThrottleConfig::default(),
+            slow_log: SlowLogConfig::default(),
+            normalization: NormalizationConfig::default(),
+            pipelines: Vec::new(),
+        }
+    }
+}
+
+/// HTTP API 配置
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// 是否启用 HTTP API
+    pub enabled: bool,
+    /// 监听地址
+    pub bind_addr: String,
+    /// 管理接口令牌，未配置时管理接口（查询列表与终止）不可用
+    pub admin_token: Option<String>,
+    /// 复制接口令牌，未配置时不对外提供变更流
+    pub replication_token: Option<String>,
+    /// 是否启用快照下载接口
+    pub snapshot_enabled: bool,
+    /// 同一客户端两次快照下载的最小间隔，单位为秒
+    pub snapshot_min_interval_secs: u64,
+    /// 额外的 API Key（Bearer 令牌），各自授予指定角色
+    pub api_keys: Vec<ApiKeyConfig>,
+    /// LDAP/AD 认证（HTTP Basic 认证），未配置时不启用
+    pub ldap: Option<LdapConfig>,
+    /// 分享链接签名密钥，未配置时不能生成或使用分享链接；更换后已发出的链接全部失效
+    pub share_secret: Option<String>,
+    /// 分享链接的最长有效期，单位为秒
+    pub share_max_ttl_secs: u64,
+    /// 同一分享链接两次下载的最小间隔，单位为秒
+    pub share_min_interval_secs: u64,
+    /// 只读 SQL 透传每次最多返回的行数
+    pub sql_max_rows: usize,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_addr: "127.0.0.1:8080".to_string(),
+            admin_token: None,
+            replication_token: None,
+            snapshot_enabled: false,
+            snapshot_min_interval_secs: 600,
+            api_keys: Vec::new(),
+            ldap: None,
+            share_secret: None,
+            share_max_ttl_secs: 7 * 86400,
+            share_min_interval_secs: 60,
+            sql_max_rows: 10_000,
+        }
+    }
+}
+
+/// API 角色
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiRole {
+    /// 管理接口（查询列表与终止、立即同步），同时包含其他全部角色
+    Admin,
+    /// 变更流（只读副本拉取增量数据）
+    Replication,
+    /// 只读 SQL 透传
+    Sql,
+}
+
+/// API Key 配置
+#[derive(Debug, Deserialize, Clone)]
+pub struct ApiKeyConfig {
+    /// 名称，用于审计日志
+    pub name: String,
+    /// 令牌
+    pub key: String,
+    /// 授予的角色
+    pub roles: Vec<ApiRole>,
+}
+
+/// LDAP/AD 认证配置
+///
+/// 客户端以 HTTP Basic 认证提交域账号，服务以该账号简单绑定后查询其所属组（memberOf），
+/// 按 `group_roles` 将组映射为角色。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct LdapConfig {
+    /// 服务器地址，如 `ldaps://dc01.plant.local:636`
+    pub url: String,
+    /// 绑定 DN 模板，`{user}` 替换为按 DN 规则转义后的用户名；AD 可使用 UPN，如 `{user}@plant.local`
+    pub bind_dn_template: String,
+    /// 查询用户的搜索基准 DN
+    pub base_dn: String,
+    /// 查询用户的过滤条件，`{user}` 替换为转义后的用户名
+    pub user_filter: String,
+    /// 组 DN 到角色的映射（组 DN 不区分大小写）
+    pub group_roles: HashMap<String, ApiRole>,
+    /// 连接与查询超时，单位为秒
+    pub timeout_secs: u64,
+    /// 认证成功后缓存角色的时长，单位为秒，期间相同账号与密码不再访问服务器；0 表示不缓存
+    pub cache_secs: u64,
+}
+
+impl Default for LdapConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            bind_dn_template: "{user}".to_string(),
+            base_dn: String::new(),
+            user_filter: "(sAMAccountName={user})".to_string(),
+            group_roles: HashMap::new(),
+            timeout_secs: 10,
+            cache_secs: 60,
+        }
+    }
+}
+
+/// 数据缺口回填配置
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct BackfillConfig {
+    /// 是否在更新周期中检测并回填缺口
+    pub enabled: bool,
+    /// 最后一次成功同步距今超过该时长（秒）时视为缺口，未设置时取 3 倍 `update_interval_secs`
+    pub threshold_secs: Option<u64>,
+    /// 回填时每段查询历史表的时长，单位为小时
+    pub chunk_hours: u32,
+}
+
+impl Default for BackfillConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold_secs: None,
+            chunk_hours: 6,
         }
     }
-}
\ No newline at end of file
+}
+
+impl BackfillConfig {
+    /// 视为缺口的时长（秒）：未配置时取 3 倍更新周期，正常运行中偶尔变慢的周期不会触发回填
+    pub fn threshold_secs(&self, update_interval_secs: u64) -> u64 {
+        self.threshold_secs.unwrap_or(update_interval_secs.saturating_mul(3))
+    }
+}
+
+/// 标签轮询分组配置
+///
+/// 快速组标签按 `fast_interval_secs` 单独轮询，其余标签（慢速组）随常规更新周期按 `update_interval_secs` 轮询。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct PollingConfig {
+    /// 快速组标签，为空时不启用快速轮询
+    pub fast_tags: Vec<String>,
+    /// 快速组轮询间隔，单位为秒
+    pub fast_interval_secs: u64,
+}
+
+impl Default for PollingConfig {
+    fn default() -> Self {
+        Self {
+            fast_tags: Vec::new(),
+            fast_interval_secs: 1,
+        }
+    }
+}
+
+/// 死区（仅变化时存储）配置
+///
+/// 启用后 TagDatabase 的新值与该标签最后写入的值比较，变化超过死区的标签才写入。
+/// 死区取 `absolute` 与 `percent`（相对最后写入值）两者中的较大者，均为 0 时任何变化都写入。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct DeadbandConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 绝对死区
+    pub absolute: f64,
+    /// 百分比死区（相对最后写入值，单位为 %）
+    pub percent: f64,
+    /// 值未变化时也至少每隔该时长（秒）写入一次，避免保留期清理后丢失静态标签的值；0 表示不强制写入
+    pub heartbeat_secs: u64,
+}
+
+impl Default for DeadbandConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            absolute: 0.0,
+            percent: 0.0,
+            heartbeat_secs: 3600,
+        }
+    }
+}
+
+/// 数据质量（TagQuality）采集配置
+///
+/// 启用后随数值一同读取质量列并写入 ts_quality 表；`null_bad_values` 时质量不在 `good_values`
+/// 中的值写入 NULL，不保存不可信的数值。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct QualityConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 质量列名（TagDatabase 与历史表相同）
+    pub column: String,
+    /// 视为良好的质量值（不区分大小写）
+    pub good_values: Vec<String>,
+    /// 质量不良时将数值写入 NULL
+    pub null_bad_values: bool,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            column: "TagQuality".to_string(),
+            good_values: vec!["Good".to_string(), "192".to_string(), "已连接".to_string()],
+            null_bad_values: false,
+        }
+    }
+}
+
+impl QualityConfig {
+    /// 质量值是否为良好
+    pub fn is_good(&self, quality: &str) -> bool {
+        self.good_values.iter().any(|good| good.eq_ignore_ascii_case(quality.trim()))
+    }
+}
+
+/// 周期拼接时间戳配置
+///
+/// 默认每个更新周期以当前时间为全部标签打时间戳；`use_source_time` 时改用 TagDatabase 中各标签的 DataTime，
+/// 存储的时间反映值实际变化的时刻，DataTime 未更新的标签不重复写入。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct TimestampConfig {
+    /// 使用数据源 DataTime 作为时间戳
+    pub use_source_time: bool,
+    /// DataTime 超过该时长（秒）未更新的标签视为过期：记录告警且不写入，与停滞标签一起列出；0 表示不检查
+    pub stale_after_secs: u64,
+    /// DataTime 超前当前时间该时长（秒）以上时视为时钟异常，改用当前时间
+    pub max_future_secs: u64,
+}
+
+impl Default for TimestampConfig {
+    fn default() -> Self {
+        Self {
+            use_source_time: false,
+            stale_after_secs: 3600,
+            max_future_secs: 60,
+        }
+    }
+}
+
+/// 清理前的 Parquet 归档
+///
+/// 保留窗口以前的数据在删除前按 UTC 日期写出为 Parquet 文件（`<dir>/yyyy=.../mm=.../dd=...`）。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 归档目录
+    pub dir: String,
+    /// 归档与清理的最小间隔，单位为秒，避免每个周期生成小文件；缓存数据最多多保留该时长
+    pub interval_secs: u64,
+    /// 归档文件上传到 S3 兼容存储（异地保留），未配置时只保存在本地
+    pub upload: Option<S3Config>,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: "archive".to_string(),
+            interval_secs: 3600,
+            upload: None,
+        }
+    }
+}
+
+/// 分级保留
+///
+/// 保留窗口以前的原始数据在删除前按标签降采样为 1 分钟与 1 小时汇总（平均、最小、最大值与样本数），
+/// 汇总表分别保留 `minute_retention_days` 与 `hour_retention_days` 天。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct RollupConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 1 分钟汇总的保留天数
+    pub minute_retention_days: u32,
+    /// 1 小时汇总的保留天数
+    pub hour_retention_days: u32,
+}
+
+impl Default for RollupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            minute_retention_days: 90,
+            hour_retention_days: 730,
+        }
+    }
+}
+
+/// S3 兼容对象存储（AWS S3、MinIO 等）配置，使用路径风格地址（`<endpoint>/<bucket>/<key>`）
+#[derive(Debug, Deserialize, Clone)]
+pub struct S3Config {
+    /// 服务地址，如 `https://minio.plant.local:9000`、`https://s3.cn-north-1.amazonaws.com.cn`
+    pub endpoint: String,
+    pub bucket: String,
+    /// 签名使用的区域，MinIO 默认为 us-east-1
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// 对象键前缀，如 `plant-a/`
+    #[serde(default)]
+    pub prefix: String,
+    /// 上传失败时的最大重试次数
+    #[serde(default = "default_export_max_retries")]
+    pub max_retries: u32,
+    /// 重试间隔，单位为秒
+    #[serde(default = "default_export_retry_interval_secs")]
+    pub retry_interval_secs: u64,
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
+/// 停滞标签检测
+///
+/// 记录各标签的值最后一次变化的时间，超过阈值未变化的标签视为停滞（仪表冻结或数据源不再刷新），
+/// 在状态报告与 `GET /status/stale-tags` 中列出，可选地不再把冻结的值写入新行。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct StaleTagConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 值超过该时长（秒）未变化的标签视为停滞
+    pub threshold_secs: u64,
+    /// 停滞标签不再写入新行（宽表中为 NULL），值恢复变化后继续写入
+    pub skip_frozen_values: bool,
+}
+
+impl Default for StaleTagConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_secs: 3600,
+            skip_frozen_values: false,
+        }
+    }
+}
+
+/// 启动顺序控制
+///
+/// 工厂上电时 SQL Server 或数据盘可能晚于 rt_db 就绪，启动前按配置等待，超过最长等待时间才放弃启动。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct StartupConfig {
+    /// 数据源连接失败时持续重试，直到可连接或超过 `source_max_wait_secs`
+    pub wait_for_source: bool,
+    /// 等待数据源的最长时间，单位为秒，0 表示一直等待
+    pub source_max_wait_secs: u64,
+    /// 启动前必须存在的路径（如数据盘挂载目录）
+    pub wait_for_paths: Vec<String>,
+    /// 要求 `wait_for_paths` 中的路径是挂载点（与上级目录不在同一设备上，仅 Unix）
+    pub require_mount: bool,
+    /// 等待路径的最长时间，单位为秒，0 表示一直等待
+    pub paths_max_wait_secs: u64,
+    /// 两次检查的间隔，单位为秒
+    pub retry_interval_secs: u64,
+}
+
+impl Default for StartupConfig {
+    fn default() -> Self {
+        Self {
+            wait_for_source: false,
+            source_max_wait_secs: 600,
+            wait_for_paths: Vec::new(),
+            require_mount: false,
+            paths_max_wait_secs: 300,
+            retry_interval_secs: 10,
+        }
+    }
+}
+
+/// 低延迟模式配置
+///
+/// 将缓存作为准实时镜像时，按毫秒级间隔（最低 200 毫秒）轮询 TagDatabase：使用常驻数据源连接与
+/// 只构建一次的查询语句，轮询结果先暂存在内存中，每 `commit_every_cycles` 次轮询在一个事务中批量提交。
+/// 启用后常规更新周期不再读取最新数据，只负责标签变化、缺口回填、汇总与清理。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct LowLatencyConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 轮询间隔，单位为毫秒，不小于 200
+    pub interval_ms: u64,
+    /// 每隔多少次轮询提交一次暂存的数据，1 表示每次轮询都提交
+    pub commit_every_cycles: u32,
+    /// 延迟统计的日志间隔，单位为秒，0 表示不定期记录
+    pub report_interval_secs: u64,
+}
+
+impl Default for LowLatencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: 500,
+            commit_every_cycles: 5,
+            report_interval_secs: 60,
+        }
+    }
+}
+
+/// OpenTelemetry 追踪导出配置
+///
+/// 启用后同步周期及其各步骤（标签变化检测、读取最新数据、写入）记录为 span，经 OTLP/HTTP 导出到采集器。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// OTLP/HTTP 追踪接收地址
+    pub endpoint: String,
+    /// 上报的服务名
+    pub service_name: String,
+    /// 同步周期的采样比例，1.0 表示全部采样
+    pub sample_ratio: f64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "http://localhost:4318/v1/traces".to_string(),
+            service_name: "rt_db".to_string(),
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+/// 重复告警日志限流配置
+///
+/// 同一位置的 warn/error 日志在 `window_secs` 内只输出第一条，窗口结束时汇总被省略的次数。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct LogThrottleConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 限流窗口，单位为秒
+    pub window_secs: u64,
+}
+
+impl Default for LogThrottleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window_secs: 60,
+        }
+    }
+}
+
+/// 慢操作日志配置
+///
+/// SQL Server 查询或 DuckDB 批量写入超过阈值时输出警告（日志 target 为 `slow_op`）。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SlowLogConfig {
+    /// SQL Server 查询耗时阈值，单位为毫秒，0 表示不记录
+    pub source_query_ms: u64,
+    /// DuckDB 每批写入的耗时阈值，单位为毫秒，0 表示不记录
+    pub duckdb_insert_ms: u64,
+}
+
+impl Default for SlowLogConfig {
+    fn default() -> Self {
+        Self {
+            source_query_ms: 5000,
+            duckdb_insert_ms: 2000,
+        }
+    }
+}
+
+/// 标签规范化目录
+///
+/// 多个数据源以不同名称、不同单位上报同一物理测点时，将原始标签映射为规范标签：
+/// 原始值按单位（或 `scale`/`offset`）换算后以规范标签名写入；同一时间点有多个来源时，
+/// 按 `sources` 的顺序取第一个有值的来源。
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct NormalizationConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 丢弃未在目录中的标签（默认按原名保留）
+    pub drop_unmapped: bool,
+    /// 规范标签
+    pub tags: Vec<CanonicalTagConfig>,
+}
+
+/// 规范标签
+#[derive(Debug, Deserialize, Clone)]
+pub struct CanonicalTagConfig {
+    /// 规范标签名
+    pub name: String,
+    /// 规范单位，与来源单位同时配置时自动换算
+    pub unit: Option<String>,
+    /// 说明，写入数据字典
+    pub description: Option<String>,
+    /// 原始标签，按优先级从高到低排列
+    pub sources: Vec<TagSourceConfig>,
+}
+
+/// 规范标签的一个来源
+#[derive(Debug, Deserialize, Clone)]
+pub struct TagSourceConfig {
+    /// 原始标签名
+    pub tag: String,
+    /// 原始单位
+    pub unit: Option<String>,
+    /// 单位换算后再乘的系数
+    #[serde(default = "default_source_scale")]
+    pub scale: f64,
+    /// 单位换算后再加的偏移
+    #[serde(default)]
+    pub offset: f64,
+}
+
+fn default_source_scale() -> f64 {
+    1.0
+}
+
+impl NormalizationConfig {
+    /// 校验目录：名称不能为空，同一原始标签只能映射到一个规范标签，配置的单位必须可以换算
+    pub fn validate(&self) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let mut mapped = HashMap::new();
+        for tag in &self.tags {
+            if tag.name.trim().is_empty() {
+                anyhow::bail!("normalization.tags 中的 name 不能为空");
+            }
+            if tag.sources.is_empty() {
+                anyhow::bail!("规范标签 {} 没有配置来源", tag.name);
+            }
+            for source in &tag.sources {
+                if let Some(previous) = mapped.insert(source.tag.as_str(), tag.name.as_str()) {
+                    anyhow::bail!("原始标签 {} 同时映射到规范标签 {} 与 {}", source.tag, previous, tag.name);
+                }
+                if let (Some(from), Some(to)) = (source.unit.as_deref(), tag.unit.as_deref())
+                    && crate::normalize::unit_conversion(from, to).is_none() {
+                    anyhow::bail!("规范标签 {} 的来源 {} 无法从 {} 换算为 {}", tag.name, source.tag, from, to);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Azure AD 令牌的获取方式
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AadAuthMethod {
+    /// 托管标识（在 Azure 虚拟机等资源上运行时，从实例元数据服务获取）
+    #[default]
+    ManagedIdentity,
+    /// 应用注册的客户端凭据（tenant_id、client_id、client_secret）
+    ClientCredentials,
+}
+
+/// Azure AD 令牌认证配置
+///
+/// 启用后以 Azure AD 访问令牌登录 Azure SQL，不再使用 SQL 账号；令牌缓存到即将到期时重新获取。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct AadConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 令牌获取方式（managed_identity / client_credentials）
+    pub method: AadAuthMethod,
+    /// 租户 ID（客户端凭据方式）
+    pub tenant_id: String,
+    /// 应用（客户端）ID；托管标识方式下为用户分配的托管标识，为空时使用系统分配的标识
+    pub client_id: String,
+    /// 客户端密码（客户端凭据方式）
+    pub client_secret: String,
+    /// 令牌到期前多少秒重新获取
+    pub refresh_margin_secs: u64,
+}
+
+impl Default for AadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            method: AadAuthMethod::ManagedIdentity,
+            tenant_id: String::new(),
+            client_id: String::new(),
+            client_secret: String::new(),
+            refresh_margin_secs: 300,
+        }
+    }
+}
+
+impl AadConfig {
+    fn validate(&self) -> Result<()> {
+        if self.method == AadAuthMethod::ClientCredentials
+            && [&self.tenant_id, &self.client_id, &self.client_secret].iter().any(|v| v.trim().is_empty())
+        {
+            anyhow::bail!("Azure AD 客户端凭据方式需要配置 aad.tenant_id、aad.client_id 与 aad.client_secret");
+        }
+        Ok(())
+    }
+}
+
+/// 数据源熔断配置
+///
+/// 连续多个更新周期失败后熔断：暂停常规同步，改为按较长间隔探测，探测成功后恢复。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 连续失败多少个周期后熔断
+    pub failure_threshold: u32,
+    /// 熔断期间的探测间隔，单位为秒
+    pub probe_interval_secs: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            failure_threshold: 5,
+            probe_interval_secs: 600,
+        }
+    }
+}
+
+/// 数据源会话录制配置
+///
+/// 每个常规更新周期读取到的当前标签与最新数据按 JSON Lines 追加到 `path`，用于回放排查与回归测试。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct CaptureConfig {
+    /// 是否录制
+    pub enabled: bool,
+    /// 录制文件路径（追加写入）
+    pub path: String,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: "capture.jsonl".to_string(),
+        }
+    }
+}
+
+/// 同步告警配置
+///
+/// 连续 `failure_threshold` 个更新周期失败、缓存最新数据落后当前时间超过 `lag_threshold_secs` 时
+/// 向各 webhook 发送告警，条件解除时发送恢复通知。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct AlertConfig {
+    /// 连续失败多少个更新周期后告警
+    pub failure_threshold: u32,
+    /// 最新数据落后当前时间超过该秒数时告警，0 表示不检查
+    pub lag_threshold_secs: u64,
+    /// 告警接收端
+    pub webhooks: Vec<AlertWebhookConfig>,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            lag_threshold_secs: 0,
+            webhooks: Vec::new(),
+        }
+    }
+}
+
+/// 告警 webhook
+#[derive(Debug, Deserialize, Clone)]
+pub struct AlertWebhookConfig {
+    /// 名称（用于日志）
+    pub name: String,
+    /// POST 告警的 URL
+    pub url: String,
+    /// JSON 负载模板，为空时使用默认格式
+    ///
+    /// 支持的占位符：`{{event}}`、`{{timestamp}}`、`{{error}}`（已按 JSON 字符串转义，没有错误时为空）、
+    /// `{{consecutive_failures}}` 与 `{{lag_secs}}`（数值，未知时为 null），以及 SPC 违规告警的
+    /// `{{tag}}`、`{{rule}}`（已按 JSON 字符串转义）与 `{{value}}`、`{{mean}}`、`{{sigma}}`，导出告警的
+    /// `{{job}}`（已按 JSON 字符串转义）与 `{{files}}`（其他告警中为空或 null）。
+    pub template: Option<String>,
+    /// 附加请求头
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// 请求超时，单位为秒
+    #[serde(default = "default_rest_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+/// 心跳值类型
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum HeartbeatMode {
+    /// 每个周期加 1 的计数器（服务重启后从 1 开始）
+    #[default]
+    Counter,
+    /// 当前 Unix 时间（秒）
+    Timestamp,
+}
+
+/// 心跳回写配置
+///
+/// 每个更新周期成功后把心跳值写回 SQL Server 中指定的标签行，rt_db 停止消费数据时心跳不再变化，
+/// DCS 侧可据此报警。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct HeartbeatConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 写入的表，为空时使用 `tables.tag_database_table`
+    pub table: String,
+    /// 标签名列
+    pub tag_column: String,
+    /// 心跳值列
+    pub value_column: String,
+    /// 心跳标签名，表中没有该行时插入
+    pub tag_name: String,
+    /// 心跳值类型（counter / timestamp）
+    pub mode: HeartbeatMode,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            table: String::new(),
+            tag_column: "TagName".to_string(),
+            value_column: "TagVal".to_string(),
+            tag_name: "RT_DB_HEARTBEAT".to_string(),
+            mode: HeartbeatMode::Counter,
+        }
+    }
+}
+
+/// 数据库维护配置（定期 CHECKPOINT，使删除释放的块可被后续写入复用）
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    /// 是否启用维护任务
+    pub enabled: bool,
+    /// 执行间隔，单位为秒
+    pub interval_secs: u64,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 3600,
+        }
+    }
+}
+
+/// 备份配置
+///
+/// 备份为 DuckDB EXPORT DATABASE（Parquet 格式）导出的目录，可用 IMPORT DATABASE 恢复；
+/// `rt_db backup` 命令与定时任务都写入 `dir`，只保留最近 `keep` 份；配置 `upload` 时每份备份随后上传到 S3 兼容存储。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// 是否启用定时备份
+    pub enabled: bool,
+    /// 备份目录
+    pub dir: String,
+    /// 定时备份间隔，单位为秒
+    pub interval_secs: u64,
+    /// 保留的备份份数
+    pub keep: usize,
+    /// 停机时（CHECKPOINT 之后）额外备份一次
+    pub on_shutdown: bool,
+    /// 备份上传到 S3 兼容存储（异地保留），未配置时只保存在本地
+    pub upload: Option<S3Config>,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: "backups".to_string(),
+            interval_secs: 86400,
+            keep: 7,
+            on_shutdown: false,
+            upload: None,
+        }
+    }
+}
+
+/// 只读副本（跟随模式）配置
+///
+/// 启用后不连接 SQL Server，而是通过 HTTP(S) 从主实例的变更流拉取增量数据。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ReplicaConfig {
+    /// 是否以跟随模式运行
+    pub enabled: bool,
+    /// 主实例 HTTP API 地址，如 https://plant-gateway:8080
+    pub primary_url: String,
+    /// 主实例的复制接口令牌（api.replication_token）
+    pub token: Option<String>,
+    /// 拉取间隔，单位为秒
+    pub poll_interval_secs: u64,
+    /// 每批拉取的时间点数量
+    pub batch_size: usize,
+}
+
+impl Default for ReplicaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            primary_url: String::new(),
+            token: None,
+            poll_interval_secs: 60,
+            batch_size: 1000,
+        }
+    }
+}
+
+/// 缩略趋势图（sparkline）预计算配置
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SparklineConfig {
+    /// 是否在每个更新周期后刷新缩略趋势
+    pub enabled: bool,
+    /// 覆盖的时间窗口，单位为小时
+    pub window_hours: u32,
+    /// 每个标签的降采样点数
+    pub points: u32,
+}
+
+impl Default for SparklineConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_hours: 24,
+            points: 200,
+        }
+    }
+}
+
+/// SPC（统计过程控制）监控配置
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SpcConfig {
+    /// 是否启用 SPC 监控
+    pub enabled: bool,
+    /// 参与监控的标签
+    pub tags: Vec<String>,
+    /// 计算控制限的滑动窗口样本数
+    pub window_size: usize,
+    /// 是否将违规事件作为 `spc_violation` 告警发送到 `[[alerts.webhooks]]`
+    pub notify: bool,
+}
+
+impl Default for SpcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tags: Vec::new(),
+            window_size: 100,
+            notify: true,
+        }
+    }
+}
+
+/// 趋势预测方法
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ForecastMethod {
+    /// 指数加权移动平均（单指数平滑），预测值为水平值
+    Ewma,
+    /// Holt 线性趋势（双指数平滑），预测值按趋势外推
+    #[default]
+    Holt,
+}
+
+/// 趋势预测配置
+///
+/// 每个更新周期后对选定标签做短期预测，写入 ts_forecast 表并通过 `GET /tags/forecast` 提供，
+/// 看板可将预测值与实际值叠加显示。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ForecastConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 参与预测的标签
+    pub tags: Vec<String>,
+    /// 预测方法（ewma / holt）
+    pub method: ForecastMethod,
+    /// 水平平滑系数，越大越跟随最新值
+    pub alpha: f64,
+    /// 趋势平滑系数（仅 holt）
+    pub beta: f64,
+    /// 参与拟合的最近样本数
+    pub window_size: usize,
+    /// 预测步数，步长为样本的平均间隔
+    pub horizon_steps: usize,
+}
+
+impl Default for ForecastConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tags: Vec::new(),
+            method: ForecastMethod::Holt,
+            alpha: 0.5,
+            beta: 0.1,
+            window_size: 100,
+            horizon_steps: 10,
+        }
+    }
+}
+
+/// 降级级别，按配置的顺序依次启用
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShedStage {
+    /// 暂停预计算汇总（缩略趋势）刷新
+    Rollups,
+    /// 暂停导出、REST 推送与 Kafka 推送
+    Sinks,
+    /// 拒绝重型 API 查询（Arrow 导出、快照下载、分享导出）
+    HeavyQueries,
+    /// 降低常规同步频率
+    SyncFrequency,
+}
+
+/// 资源压力下的分级降级
+///
+/// 定期检查 CPU、内存与 DuckDB 文件所在磁盘，持续超过阈值时按 `stages` 的顺序逐级停用次要功能，
+/// 压力解除后按相反顺序逐级恢复，保证过载的边缘设备上核心同步仍能运行。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct DegradationConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 检查间隔，单位为秒
+    pub check_interval_secs: u64,
+    /// CPU 使用率阈值（百分比）
+    pub cpu_percent: f32,
+    /// 内存使用率阈值（百分比）
+    pub memory_percent: f32,
+    /// 磁盘剩余空间阈值（百分比），低于该值视为压力
+    pub disk_free_percent: f32,
+    /// 降级顺序
+    pub stages: Vec<ShedStage>,
+    /// 连续多少次检查有压力后升一级
+    pub escalate_after_checks: u32,
+    /// 连续多少次检查无压力后降一级
+    pub recover_after_checks: u32,
+    /// 降低同步频率时，每多少个更新周期执行一次
+    pub sync_slowdown_factor: u32,
+}
+
+impl Default for DegradationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            check_interval_secs: 30,
+            cpu_percent: 90.0,
+            memory_percent: 90.0,
+            disk_free_percent: 10.0,
+            stages: vec![ShedStage::Rollups, ShedStage::Sinks, ShedStage::HeavyQueries, ShedStage::SyncFrequency],
+            escalate_after_checks: 2,
+            recover_after_checks: 5,
+            sync_slowdown_factor: 4,
+        }
+    }
+}
+
+/// Kafka 消息格式
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum KafkaFormat {
+    #[default]
+    Json,
+    /// 不带头部的 Avro 二进制编码，模式见 src/kafka.rs
+    Avro,
+}
+
+/// Kafka 推送配置
+///
+/// 写入缓存的每条新记录以标签名为键发布到主题，供数据湖消费。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct KafkaConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// Broker 地址，如 `kafka1:9092`
+    pub brokers: Vec<String>,
+    /// 主题（需预先创建）
+    pub topic: String,
+    /// 消息格式（json / avro）
+    pub format: KafkaFormat,
+    pub client_id: String,
+    /// 发布队列容量（记录批次数），Kafka 不可用时超出的批次被丢弃
+    pub queue_batches: usize,
+    /// 连接或发布失败后的重试间隔，单位为秒
+    pub retry_interval_secs: u64,
+}
+
+impl Default for KafkaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            brokers: Vec::new(),
+            topic: "rt_db.records".to_string(),
+            format: KafkaFormat::Json,
+            client_id: "rt_db".to_string(),
+            queue_batches: 1000,
+            retry_interval_secs: 5,
+        }
+    }
+}
+
+/// 内嵌 OPC UA 服务配置
+///
+/// 按缓存中的标签生成只读地址空间（最新值与最近若干个数值样本），供 OPC 客户端浏览与订阅。
+/// 仅提供匿名、无安全策略的端点，应部署在受信任的网络中。
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct OpcUaConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 监听地址
+    pub host: String,
+    /// 监听端口
+    pub port: u16,
+    /// 命名空间 URI，同时作为应用 URI
+    pub namespace_uri: String,
+    /// 服务器证书目录，证书不存在时自动生成
+    pub pki_dir: String,
+    /// 每个标签保留的最近数值样本数
+    pub history_points: usize,
+}
+
+impl Default for OpcUaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "0.0.0.0".to_string(),
+            port: 4840,
+            namespace_uri: "urn:rt_db".to_string(),
+            pki_dir: "./pki".to_string(),
+            history_points: 20,
+        }
+    }
+}
+
+/// 能耗计量配置
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct EnergyConfig {
+    /// 计量表（累计计数型标签）列表
+    #[serde(default)]
+    pub meters: Vec<MeterConfig>,
+}
+
+impl EnergyConfig {
+    /// 查找标签对应的计量表配置
+    pub fn meter(&self, tag: &str) -> Option<&MeterConfig> {
+        self.meters.iter().find(|m| m.tag == tag)
+    }
+}
+
+/// 单个计量表配置
+#[derive(Debug, Deserialize, Clone)]
+pub struct MeterConfig {
+    /// 累计值标签名
+    pub tag: String,
+    /// 计数器量程（达到后回绕到 0），未配置时读数回落按换表处理
+    pub rollover: Option<f64>,
+}
+
+/// 定时导出配置
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ExportConfig {
+    /// 导出文件的本地暂存目录，文件投递到任务的全部目标后删除
+    pub output_dir: String,
+    /// 在导出文件中写入来源信息（站点、导出时间、版本、查询摘要）
+    pub watermark: bool,
+    /// 站点标识，写入来源信息
+    pub site_id: Option<String>,
+    /// 定时导出任务
+    pub jobs: Vec<ExportJobConfig>,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            output_dir: "exports".to_string(),
+            watermark: false,
+            site_id: None,
+            jobs: Vec::new(),
+        }
+    }
+}
+
+/// 单个定时导出任务
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExportJobConfig {
+    /// 任务名称（用作文件名前缀）
+    pub name: String,
+    /// 执行间隔，单位为秒（如每日导出为 86400）
+    pub interval_secs: u64,
+    /// 导出的时间范围，单位为秒，默认等于执行间隔
+    pub lookback_secs: Option<u64>,
+    /// 导出的标签，为空时导出全部
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 投递目标
+    #[serde(default)]
+    pub destinations: Vec<DestinationConfig>,
+    /// 宽表列数处理方式
+    #[serde(flatten)]
+    pub layout: ExportLayout,
+    /// 投递失败时的最大重试次数
+    #[serde(default = "default_export_max_retries")]
+    pub max_retries: u32,
+    /// 投递重试间隔，单位为秒
+    #[serde(default = "default_export_retry_interval_secs")]
+    pub retry_interval_secs: u64,
+    /// 成功时也发送 `export_succeeded` 告警（失败时总是发送 `export_failed`）
+    #[serde(default)]
+    pub notify_success: bool,
+}
+
+/// 宽表导出的列数处理方式
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+pub struct ExportLayout {
+    /// 单个文件的最大标签列数，未配置时不限制
+    #[serde(default)]
+    pub max_columns: Option<usize>,
+    /// 超过最大列数时的处理方式
+    #[serde(default)]
+    pub wide_overflow: WideOverflow,
+    /// DateTime 列的时间格式
+    #[serde(default)]
+    pub timestamps: ExportTimestamps,
+}
+
+/// CSV 导出中 DateTime 列的时间格式
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportTimestamps {
+    /// DuckDB 默认格式（UTC，带时区后缀，如 `2024-01-01 00:00:00+00`）
+    #[default]
+    Native,
+    /// UTC 时间，`YYYY-MM-DD HH:MM:SS`（Excel 可直接识别）
+    Utc,
+    /// 数据源本地时间（按 `source_timezone_offset_hours`），`YYYY-MM-DD HH:MM:SS`
+    Local,
+}
+
+/// 宽表导出超过最大列数时的处理方式
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WideOverflow {
+    /// 按列拆分为多个文件，每个文件都包含 DateTime 列
+    #[default]
+    Split,
+    /// 转为窄表格式 (DateTime, TagName, Value)
+    Long,
+}
+
+fn default_export_max_retries() -> u32 {
+    3
+}
+
+fn default_export_retry_interval_secs() -> u64 {
+    30
+}
+
+/// 导出投递目标
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DestinationConfig {
+    /// 本地目录
+    Local {
+        path: String,
+    },
+    /// SMB 共享（UNC 路径或已挂载的共享目录）
+    Smb {
+        path: String,
+    },
+    /// FTP 服务器（被动模式）
+    Ftp {
+        host: String,
+        #[serde(default = "default_ftp_port")]
+        port: u16,
+        user: String,
+        password: String,
+        #[serde(default)]
+        remote_dir: String,
+    },
+    /// SFTP 服务器（调用系统 OpenSSH 的 scp，使用密钥认证；未安装 scp 时投递失败）
+    Sftp {
+        host: String,
+        #[serde(default = "default_sftp_port")]
+        port: u16,
+        user: String,
+        #[serde(default)]
+        remote_dir: String,
+        identity_file: Option<String>,
+    },
+}
+
+fn default_ftp_port() -> u16 {
+    21
+}
+
+fn default_sftp_port() -> u16 {
+    22
+}
+
+/// MES/ERP REST 推送配置
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct IntegrationConfig {
+    /// 推送端点
+    pub endpoints: Vec<RestEndpointConfig>,
+}
+
+/// 单个 REST 推送端点
+#[derive(Debug, Deserialize, Clone)]
+pub struct RestEndpointConfig {
+    /// 端点名称（用于日志与投递记录）
+    pub name: String,
+    /// 目标 URL
+    pub url: String,
+    /// 推送间隔，单位为秒
+    pub interval_secs: u64,
+    /// 聚合窗口，单位为秒，默认等于推送间隔
+    pub window_secs: Option<u64>,
+    /// 推送的标签
+    pub tags: Vec<String>,
+    /// 聚合方式
+    #[serde(default)]
+    pub aggregation: Aggregation,
+    /// JSON 负载模板，为空时使用默认格式
+    ///
+    /// 支持的占位符：`{{timestamp}}`、`{{start}}`、`{{end}}`、`{{values}}`（标签到值的 JSON 对象）
+    /// 以及 `{{value:标签名}}`（单个标签的值，缺失时为 null）。
+    pub payload_template: Option<String>,
+    /// 认证方式
+    #[serde(default)]
+    pub auth: RestAuthConfig,
+    /// 附加请求头
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// 请求超时，单位为秒
+    #[serde(default = "default_rest_timeout_secs")]
+    pub timeout_secs: u64,
+    /// 推送失败时的最大重试次数
+    #[serde(default = "default_export_max_retries")]
+    pub max_retries: u32,
+    /// 推送重试间隔，单位为秒
+    #[serde(default = "default_export_retry_interval_secs")]
+    pub retry_interval_secs: u64,
+}
+
+fn default_rest_timeout_secs() -> u64 {
+    10
+}
+
+/// 时间窗口内的聚合方式
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregation {
+    /// 平均值
+    #[default]
+    Avg,
+    /// 最小值
+    Min,
+    /// 最大值
+    Max,
+    /// 求和
+    Sum,
+    /// 样本标准差
+    Stddev,
+    /// 窗口内第一个值
+    First,
+    /// 窗口内最后一个值
+    Last,
+    /// 非空值个数
+    Count,
+    /// 时间加权平均值：每个值保持到下一个采样点（阶梯保持），适用于不等间隔采样的标签
+    TimeWeightedAvg,
+    /// 开关量处于非零（运行）状态的持续秒数，按阶梯保持计算
+    DurationInState,
+}
+
+/// 重采样到固定时间网格时的取值方式
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FillMethod {
+    /// 沿用网格点之前（含）的最后一个值
+    #[default]
+    Previous,
+    /// 按网格点前后两个值线性插值，不外推
+    Linear,
+}
+
+/// 多标签查询结果的形状，与存储模式无关
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TableShape {
+    /// 每个标签一列（DateTime, 标签1, 标签2, ...）
+    #[default]
+    Wide,
+    /// 每个值一行（DateTime, TagName, Value）
+    Long,
+}
+
+/// 时间对齐的多标签结果中缺少值的单元格的处理方式
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingCells {
+    /// 保留为空
+    #[default]
+    Null,
+    /// 沿用该标签在查询范围内之前最后一个值，范围内第一个值之前仍为空
+    Previous,
+    /// 丢弃任一标签缺少值的行
+    Drop,
+}
+
+/// REST 认证方式
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RestAuthConfig {
+    /// 无认证
+    #[default]
+    None,
+    /// HTTP Basic 认证
+    Basic {
+        user: String,
+        password: String,
+    },
+    /// Bearer Token
+    Bearer {
+        token: String,
+    },
+    /// 通过请求头传递 API Key
+    ApiKey {
+        header: String,
+        key: String,
+    },
+}
@@ -1,13 +1,23 @@
 use anyhow::{Result, Context};
 use chrono::{DateTime, Utc, Local, NaiveDateTime};
-use tiberius::{Client, Config, Row};
+use tiberius::{Client, Config, Row, SqlBrowser};
 use tokio::net::TcpStream;
 use tokio_util::compat::{TokioAsyncWriteCompatExt, Compat};
 use tracing::{info, debug, warn, error};
-use crate::database::TimeSeriesRecord;
-use crate::config::AppConfig;
-use std::time::Duration;
+use crate::aad::TokenProvider;
+use crate::database::{TagMeta, TimeSeriesRecord};
+use crate::config::{AppConfig, split_instance, validate_identifier};
+use crate::metrics;
+use crate::normalize::TagNormalizer;
+use crate::proxy::ProxyConnector;
+use crate::slow_log::{self, SlowOpKind};
+use crate::toggles::FeatureToggles;
+use crate::sql::{Dialect, Op, Select, Statement};
+use std::time::{Duration, Instant};
 use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use futures::stream::{self, StreamExt};
 
 /// 标签变化信息
 #[derive(Debug, Clone)]
@@ -20,34 +30,273 @@ pub struct TagChanges {
     pub current_tags: std::collections::HashSet<String>,
 }
 
+impl TagChanges {
+    /// 对比已知标签与数据源当前的标签，计算新增和删除的标签
+    pub fn between(known_tags: &std::collections::HashSet<String>, current_tags: std::collections::HashSet<String>) -> Self {
+        let added_tags = current_tags.difference(known_tags).cloned().collect();
+        let removed_tags = known_tags.difference(&current_tags).cloned().collect();
+        Self { added_tags, removed_tags, current_tags }
+    }
+}
+
+/// 自动识别数值列时按顺序尝试的列名
+const VALUE_COLUMN_CANDIDATES: [&str; 3] = ["TagVal", "Value", "Val"];
+
+/// TagDatabase 中的标签元数据列，不存在的列对应字段为空
+const META_COLUMNS: [&str; 5] = ["TagUnit", "TagDescrip", "TagMinVal", "TagMaxVal", "InOrOutFlag"];
+
 /// SQL Server 数据源管理器
 pub struct SqlServerDataSource {
     config: AppConfig,
+    /// 各表识别出的值列名与 SQL 类型
+    value_columns: std::sync::Mutex<std::collections::HashMap<String, (String, String)>>,
+    /// 以录制的会话代替 SQL Server（仅测试）
+    #[cfg(test)]
+    replay: Option<crate::capture::Replay>,
+    /// 标签规范化目录
+    normalizer: TagNormalizer,
+    /// 该数据源全部标签的命名空间前缀
+    tag_prefix: String,
+    /// 低延迟模式轮询使用的常驻连接，查询出错时丢弃，下次轮询重新连接
+    persistent_client: tokio::sync::Mutex<Option<Client<Compat<TcpStream>>>>,
+    /// 最新值查询语句，构建一次后复用（语句文本不变，SQL Server 复用执行计划）
+    latest_sql: std::sync::Mutex<Option<String>>,
+    /// 标签元数据查询语句，按 TagDatabase 实际存在的列构建一次后复用
+    meta_sql: std::sync::Mutex<Option<String>>,
+    /// 当前使用的服务器在 `DatabaseConfig::servers` 中的序号，连接失败时轮换
+    active_server: AtomicUsize,
+    /// Azure AD 访问令牌（启用 `[aad]` 时以令牌代替 SQL 账号登录）
+    aad: Option<TokenProvider>,
+    /// 经 SOCKS5 代理或 SSH 隧道连接（配置了 `connection.proxy` 时）
+    proxy: Option<ProxyConnector>,
+    /// 运行时功能开关（解析详细日志），与同一配置的缓存共享
+    toggles: Arc<FeatureToggles>,
 }
 
 impl SqlServerDataSource {
     /// 创建新的数据源管理器
     pub fn new(config: AppConfig) -> Self {
-        Self { config }
+        Self {
+            normalizer: TagNormalizer::new(&config.normalization),
+            tag_prefix: config.tag_prefix.clone(),
+            aad: config.aad.enabled.then(|| TokenProvider::new(&config.aad)),
+            proxy: ProxyConnector::new(&config.connection),
+            toggles: Arc::new(FeatureToggles::new(&config)),
+            config,
+            value_columns: std::sync::Mutex::new(std::collections::HashMap::new()),
+            #[cfg(test)]
+            replay: None,
+            persistent_client: tokio::sync::Mutex::new(None),
+            latest_sql: std::sync::Mutex::new(None),
+            meta_sql: std::sync::Mutex::new(None),
+            active_server: AtomicUsize::new(0),
+        }
+    }
+    
+    /// 使用同一配置的缓存持有的功能开关，使管理接口的修改对数据源生效
+    pub fn with_toggles(mut self, toggles: Arc<FeatureToggles>) -> Self {
+        self.toggles = toggles;
+        self
+    }
+    
+    /// 以录制的会话代替 SQL Server：标签检测与最新数据读取逐周期返回录制的结果，历史表与标签元数据为空（仅测试）
+    #[cfg(test)]
+    pub fn with_replay(mut self, replay: crate::capture::Replay) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+    
+    /// 回放模式下代替连接数据源，与真实连接一样经过断连与查询延迟的故障注入
+    #[cfg(test)]
+    async fn replay_source(&self) -> Result<Option<&crate::capture::Replay>> {
+        let Some(replay) = &self.replay else {
+            return Ok(None);
+        };
+        crate::chaos::inject_connection_failure()?;
+        crate::chaos::inject_query_delay().await;
+        Ok(Some(replay))
+    }
+    
+    /// 原始标签对应的存储标签名：规范标签名（未启用规范化或不在目录中时为原名）加命名空间前缀
+    pub fn canonical_tag_name(&self, tag: &str) -> String {
+        format!("{}{}", self.tag_prefix, self.normalizer.canonical_name(tag))
+    }
+    
+    /// 将数据源记录映射为存储标签：规范化后加命名空间前缀
+    fn map_tags(&self, records: Vec<TimeSeriesRecord>) -> Vec<TimeSeriesRecord> {
+        let mut records = self.normalizer.normalize(records);
+        if !self.tag_prefix.is_empty() {
+            for record in &mut records {
+                record.tag_name.insert_str(0, &self.tag_prefix);
+            }
+        }
+        records
+    }
+    
+    /// 构建发往数据源的查询
+    ///
+    /// 启用 `read_only_source` 时审计每条 SQL：只允许单条 SELECT 语句，其余一律拒绝，
+    /// 并在审计日志中记录语句摘要。
+    pub fn checked_query<'a>(&self, sql: impl Into<std::borrow::Cow<'a, str>>) -> Result<tiberius::Query<'a>> {
+        let sql = sql.into();
+        
+        if self.config.read_only_source {
+            let digest = sql_digest(&sql);
+            if let Err(reason) = check_read_only(&sql) {
+                error!(target: "audit", "拒绝发往数据源的语句 {}: {}", digest, reason);
+                anyhow::bail!("只读数据源模式拒绝执行语句 {}: {}", digest, reason);
+            }
+            debug!(target: "audit", "数据源语句 {}: {}", digest, sql);
+        }
+        
+        Ok(tiberius::Query::new(sql))
+    }
+    
+    /// 构建发往数据源的查询并按顺序绑定参数
+    fn statement_query(&self, statement: Statement) -> Result<tiberius::Query<'static>> {
+        let mut query = self.checked_query(statement.sql)?;
+        for param in statement.params {
+            param.bind_to(&mut query);
+        }
+        Ok(query)
+    }
+    
+    /// 在 `connection.query_timeout_secs` 内完成数据源操作，超时返回错误（0 表示不限制）
+    ///
+    /// 超时后查询被取消，连接停留在未读完的结果上，调用方随错误一起丢弃该连接（常驻连接同样在出错时丢弃），
+    /// 下次查询重新建立连接。
+    async fn with_query_timeout<T>(&self, future: impl Future<Output = tiberius::Result<T>>) -> Result<T> {
+        let timeout_secs = self.config.connection.query_timeout_secs;
+        if timeout_secs == 0 {
+            return Ok(future.await?);
+        }
+        match tokio::time::timeout(Duration::from_secs(timeout_secs), future).await {
+            Ok(result) => Ok(result?),
+            Err(_) => anyhow::bail!("数据源查询超过 {} 秒未完成，已取消", timeout_secs),
+        }
+    }
+    
+    /// 执行查询并读取第一个结果集
+    async fn fetch_rows(&self, client: &mut Client<Compat<TcpStream>>, query: tiberius::Query<'_>) -> Result<Vec<Row>> {
+        self.with_query_timeout(async { query.query(client).await?.into_first_result().await }).await
+    }
+    
+    /// 执行查询并读取第一行
+    async fn fetch_row(&self, client: &mut Client<Compat<TcpStream>>, query: tiberius::Query<'_>) -> Result<Option<Row>> {
+        self.with_query_timeout(async { query.query(client).await?.into_row().await }).await
+    }
+    
+    /// 获取表的值列查询表达式：数值列（统一转换为 FLOAT）、文本列与质量列三个表达式
+    ///
+    /// 优先使用配置的 `tables.value_column`，否则通过 INFORMATION_SCHEMA 识别并缓存。
+    /// 值列为字符类型时，无法转换为数值的值（如 "RUNNING"）作为文本值返回，其余情况文本列为 NULL；
+    /// 未启用质量采集时质量列为 NULL。
+    async fn value_expr(&self, client: &mut Client<Compat<TcpStream>>, table: &str) -> Result<String> {
+        if let Some((column, data_type)) = self.value_columns.lock().unwrap().get(table) {
+            return Ok(format!("{}, {}", value_select(column, data_type), self.quality_expr()));
+        }
+        
+        let mut query = self.checked_query(
+            "SELECT COLUMN_NAME, DATA_TYPE FROM INFORMATION_SCHEMA.COLUMNS WHERE TABLE_NAME = @P1"
+        )?;
+        query.bind(table);
+        let rows = self.fetch_rows(client, query).await?;
+        
+        let columns: Vec<(String, String)> = rows.iter()
+            .map(|row| (
+                row.get::<&str, _>(0).unwrap_or("").to_string(),
+                row.get::<&str, _>(1).unwrap_or("").to_string(),
+            ))
+            .collect();
+        
+        let detected = match self.config.tables.value_column.as_deref().filter(|c| !c.is_empty()) {
+            // 配置的列名以配置为准，查不到类型时按数值列处理
+            Some(configured) => columns.iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(configured))
+                .cloned()
+                .or_else(|| Some((configured.to_string(), String::new()))),
+            None => VALUE_COLUMN_CANDIDATES.iter()
+                .find_map(|candidate| columns.iter().find(|(name, _)| name.eq_ignore_ascii_case(candidate)))
+                .cloned(),
+        };
+        
+        let Some((column, data_type)) = detected else {
+            let names: Vec<&str> = columns.iter().map(|(name, _)| name.as_str()).collect();
+            anyhow::bail!("无法识别表 {} 的数值列（尝试 {:?}），现有列: {:?}，请配置 tables.value_column",
+                          table, VALUE_COLUMN_CANDIDATES, names);
+        };
+        
+        info!("表 {} 识别到值列 {} (类型 {})", table, column, data_type);
+        let select = format!("{}, {}", value_select(&column, &data_type), self.quality_expr());
+        self.value_columns.lock().unwrap().insert(table.to_string(), (column, data_type));
+        Ok(select)
+    }
+    
+    /// 质量列查询表达式，未启用质量采集时为 NULL
+    fn quality_expr(&self) -> String {
+        if self.config.quality.enabled {
+            format!("CAST({} AS NVARCHAR(64))", Dialect::SqlServer.quote(&self.config.quality.column))
+        } else {
+            "CAST(NULL AS NVARCHAR(64))".to_string()
+        }
+    }
+    
+    /// 最新值查询附加的DataTime列（仅 `timestamps.use_source_time` 时查询）
+    fn source_time_column(&self, select: Select) -> Select {
+        if self.config.timestamps.use_source_time { select.column("DataTime") } else { select }
+    }
+    
+    /// 是否因质量不良丢弃数值：启用 `quality.null_bad_values` 且质量不在良好值列表中
+    fn is_bad_quality(&self, quality: Option<&str>) -> bool {
+        self.config.quality.null_bad_values
+            && quality.is_some_and(|q| !self.config.quality.is_good(q))
     }
     
     /// 创建数据库连接
     async fn create_connection(&self) -> Result<Client<Compat<TcpStream>>> {
         let database_config = self.config.get_database_config()?;
+        crate::chaos::inject_connection_failure()?;
     
-        debug!("正在连接数据库: {}:{}", database_config.server, database_config.port);
+        let servers = database_config.servers();
+        let (server, port) = servers[self.active_server.load(Ordering::Relaxed) % servers.len()];
         
         // 使用与简化版相同的连接方式
         let mut tiberius_config = Config::new();
-        tiberius_config.host(&database_config.server);
-        tiberius_config.port(database_config.port);
+        let (host, instance) = split_instance(server);
+        tiberius_config.host(host);
+        match instance {
+            // 命名实例的端口由 SQL Browser（UDP 1434）返回
+            Some(instance) => {
+                debug!("正在连接数据库: {} 的命名实例 {}", host, instance);
+                tiberius_config.instance_name(instance);
+            }
+            None => {
+                debug!("正在连接数据库: {}:{}", host, port);
+                tiberius_config.port(port);
+            }
+        }
         tiberius_config.database(&database_config.database);
-        tiberius_config.authentication(tiberius::AuthMethod::sql_server(&database_config.user, &database_config.password));
+        let auth = match &self.aad {
+            Some(aad) => tiberius::AuthMethod::aad_token(aad.token().await?),
+            None => tiberius::AuthMethod::sql_server(&database_config.user, &database_config.password),
+        };
+        tiberius_config.authentication(auth);
         tiberius_config.trust_cert();
         
-        let tcp = tokio::net::TcpStream::connect(tiberius_config.get_addr())
-            .await
-            .context("无法连接到SQL Server")?;
+        let tcp = match &self.proxy {
+            Some(proxy) => proxy.connect(host, port).await.context("无法经代理连接到SQL Server")?,
+            None => tokio::net::TcpStream::connect_named(&tiberius_config)
+                .await
+                .context("无法连接到SQL Server")?,
+        };
+        
+        // 开启 TCP keep-alive，使空闲的常驻连接不被防火墙回收，并能及时发现已断开的连接
+        let keepalive_secs = self.config.connection.tcp_keepalive_secs;
+        if keepalive_secs > 0 {
+            let keepalive = socket2::TcpKeepalive::new().with_time(Duration::from_secs(keepalive_secs));
+            socket2::SockRef::from(&tcp).set_tcp_keepalive(&keepalive)
+                .context("设置 TCP keep-alive 失败")?;
+        }
         
         let client = Client::connect(tiberius_config, tcp.compat_write())
             .await
@@ -71,7 +320,9 @@ impl SqlServerDataSource {
                 }
                 Err(e) => {
                     last_error = Some(e);
+                    self.failover();
                     if attempt < self.config.connection.max_retries {
+                        metrics::add(&metrics::RETRIES, "source_connect", 1);
                         warn!("第 {} 次连接失败，{} 秒后重试: {}", 
                               attempt, self.config.connection.retry_interval_secs, last_error.as_ref().unwrap());
                         tokio::time::sleep(Duration::from_secs(self.config.connection.retry_interval_secs)).await;
@@ -83,83 +334,198 @@ impl SqlServerDataSource {
         Err(last_error.unwrap())
     }
     
+    /// 连接失败后切换到下一台服务器，只配置了一台服务器时不做任何事
+    fn failover(&self) {
+        let Ok(database_config) = self.config.get_database_config() else {
+            return;
+        };
+        let servers = database_config.servers();
+        if servers.len() < 2 {
+            return;
+        }
+        
+        let current = self.active_server.load(Ordering::Relaxed) % servers.len();
+        let next = (current + 1) % servers.len();
+        self.active_server.store(next, Ordering::Relaxed);
+        warn!("数据源服务器 {} 连接失败，切换到 {}", servers[current].0, servers[next].0);
+    }
+    
+    /// 当前使用的数据源服务器（`主机:端口`，命名实例为 `主机\实例`）
+    pub fn active_server(&self) -> Option<String> {
+        let database_config = self.config.get_database_config().ok()?;
+        let servers = database_config.servers();
+        let (server, port) = servers[self.active_server.load(Ordering::Relaxed) % servers.len()];
+        Some(match split_instance(server) {
+            (_, Some(_)) => server.to_string(),
+            (host, None) => format!("{}:{}", host, port),
+        })
+    }
+    
     /// 从历史表加载初始数据 - 只查询DateTime、TagName、TagVal三个字段
+    #[allow(dead_code)]
     pub async fn load_initial_data(&self, start_time: DateTime<Utc>) -> Result<Vec<TimeSeriesRecord>> {
         debug!("开始从历史表加载初始数据，起始时间: {}", start_time);
         
         let mut client = self.create_connection_with_retry().await?;
+        let records = self.query_history(&mut client, start_time, None).await?;
         
-        let sql = format!(
-            "SELECT * FROM [{}] WHERE [DateTime] >= @P1 ORDER BY [DateTime]",
-            self.config.tables.history_table
+        debug!("从历史表加载了 {} 条记录", records.len());
+        Ok(records)
+    }
+    
+    /// 查询历史表中 [start_time, end_time) 的数据，end_time 为 None 时不限上界
+    ///
+    /// 历史表名为日期模板时，依次查询时间范围覆盖的各张表，跳过尚不存在的表。
+    async fn query_history(
+        &self,
+        client: &mut Client<Compat<TcpStream>>,
+        start_time: DateTime<Utc>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<Vec<TimeSeriesRecord>> {
+        // 数据源时间为本地时间，按配置时区换算查询条件
+        let _timer = metrics::timer(&metrics::SOURCE_QUERY_SECONDS, "history");
+        let local_start = self.config.utc_to_source(start_time);
+        let local_end = end_time.map(|t| self.config.utc_to_source(t));
+        let tables = self.config.tables.history_tables(
+            local_start,
+            local_end.unwrap_or_else(|| self.config.utc_to_source(Utc::now())),
         );
         
-        let mut query = tiberius::Query::new(sql);
-        query.bind(start_time);
-        
-        let stream = query.query(&mut client).await?;
-        let rows = stream.into_first_result().await?;
-        
         let mut records = Vec::new();
-        
-        for row in rows {
-            if let Some(record) = self.parse_tagdb_row(row)? {
-                records.push(record);
+        for table in tables {
+            if self.config.tables.is_history_template() && !self.table_exists(client, &table).await? {
+                debug!("历史表 {} 不存在，跳过", table);
+                continue;
+            }
+            
+            let value_expr = self.value_expr(client, &table).await?;
+            // 模板生成的表名同样按标识符规则校验
+            validate_identifier(&table)?;
+            let mut select = Select::from(Dialect::SqlServer, &table)
+                .column("DateTime")
+                .column("TagName")
+                .expr(value_expr)
+                .filter("DateTime", Op::Ge, local_start);
+            if let Some(local_end) = local_end {
+                select = select.filter("DateTime", Op::Lt, local_end);
+            }
+            let statement = select.order_by("DateTime").build();
+            let sql = statement.sql.clone();
+            let query = self.statement_query(statement)?;
+            
+            let started = Instant::now();
+            crate::chaos::inject_query_delay().await;
+            let rows = self.fetch_rows(client, query).await?;
+            slow_log::check(&self.config.slow_log, SlowOpKind::SourceQuery, "history", &sql, rows.len(), started);
+            
+            for row in rows {
+                if let Some(record) = self.parse_tagdb_row(row)? {
+                    records.push(record);
+                }
             }
         }
         
-        debug!("从历史表加载了 {} 条记录", records.len());
-        Ok(records)
+        Ok(self.map_tags(records))
+    }
+    
+    /// 判断表是否存在
+    async fn table_exists(&self, client: &mut Client<Compat<TcpStream>>, table: &str) -> Result<bool> {
+        let mut query = self.checked_query("SELECT COUNT(*) FROM INFORMATION_SCHEMA.TABLES WHERE TABLE_NAME = @P1")?;
+        query.bind(table);
+        let row = self.fetch_row(client, query).await?;
+        Ok(row.and_then(|row| row.get::<i32, _>(0)).unwrap_or(0) > 0)
+    }
+    
+    /// 历史表是否存在；历史表名为日期模板时按时间范围逐表跳过不存在的表，视为存在
+    pub async fn has_history_table(&self) -> Result<bool> {
+        if self.config.tables.is_history_template() {
+            return Ok(true);
+        }
+        
+        let mut client = self.create_connection_with_retry().await?;
+        // INFORMATION_SCHEMA 中的表名不含架构名
+        let table = self.config.tables.history_table.rsplit('.').next().unwrap_or_default();
+        self.table_exists(&mut client, table).await
     }
     
     /// 按时间范围从历史表加载数据（分批加载优化）
+    ///
+    /// 按 `history_load_batch_days` 将时间范围拆分为多个子范围，在多个连接上并行加载
+    /// （并发数为 `history_load_concurrency`），结果按时间顺序合并。
     pub async fn load_data_in_range(&self, start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> Result<Vec<TimeSeriesRecord>> {
-        debug!("按时间范围加载数据: {} 到 {}", start_time, end_time);
+        #[cfg(test)]
+        if self.replay_source().await?.is_some() {
+            return Ok(Vec::new());
+        }
         
-        let mut client = self.create_connection_with_retry().await?;
+        let chunk = chrono::Duration::days(i64::from(self.config.batch.history_load_batch_days.max(1)));
         
-        let sql = format!(
-            "SELECT * FROM [{}] WHERE [DateTime] >= @P1 AND [DateTime] < @P2 ORDER BY [DateTime]",
-            self.config.tables.history_table
-        );
+        let mut ranges = Vec::new();
+        let mut chunk_start = start_time;
+        while chunk_start < end_time {
+            let chunk_end = (chunk_start + chunk).min(end_time);
+            ranges.push((chunk_start, chunk_end));
+            chunk_start = chunk_end;
+        }
         
-        let mut query = tiberius::Query::new(sql);
-        query.bind(start_time);
-        query.bind(end_time);
+        if ranges.len() <= 1 {
+            return self.load_range(start_time, end_time).await;
+        }
         
-        let stream = query.query(&mut client).await?;
-        let rows = stream.into_first_result().await?;
+        let concurrency = self.config.batch.history_load_concurrency.max(1);
+        info!("将 {} 到 {} 拆分为 {} 个子范围并行加载，并发数 {}", start_time, end_time, ranges.len(), concurrency);
         
-        let mut records = Vec::new();
+        // buffered 保持输入顺序，保证按时间顺序合并
+        let results: Vec<Result<Vec<TimeSeriesRecord>>> = stream::iter(ranges)
+            .map(|(start, end)| self.load_range(start, end))
+            .buffered(concurrency)
+            .collect()
+            .await;
         
-        for row in rows {
-            if let Some(record) = self.parse_tagdb_row(row)? {
-                records.push(record);
-            }
+        let mut records = Vec::new();
+        for result in results {
+            records.extend(result?);
         }
         
+        debug!("并行加载完成，共 {} 条记录", records.len());
+        Ok(records)
+    }
+    
+    /// 在单个连接上加载一个时间范围的历史数据
+    async fn load_range(&self, start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> Result<Vec<TimeSeriesRecord>> {
+        debug!("按时间范围加载数据: {} 到 {}", start_time, end_time);
+        
+        let mut client = self.create_connection_with_retry().await?;
+        let records = self.query_history(&mut client, start_time, Some(end_time)).await?;
+        
         debug!("按时间范围加载了 {} 条记录", records.len());
         Ok(records)
     }
     
     /// 从TagDatabase表获取增量数据 - 只查询DateTime、TagName、TagVal三个字段
+    #[allow(dead_code)]
     pub async fn get_incremental_data(&self, last_timestamp: DateTime<Utc>) -> Result<Vec<TimeSeriesRecord>> {
         debug!("获取增量数据，上次时间戳: {}", last_timestamp);
         
         let mut client = self.create_connection_with_retry().await?;
-        
-        // 将DateTime转换为SQL Server兼容的字符串格式
-        let timestamp_str = last_timestamp.format("%Y-%m-%d %H:%M:%S%.3f").to_string();
-        
-        let sql = format!(
-            "SELECT [DataTime], [TagName], [TagVal] FROM [{}] WHERE [DataTime] > '{}' ORDER BY [DataTime]",
-            self.config.tables.tag_database_table, timestamp_str
-        );
-        
-        let query = tiberius::Query::new(sql);
-        
-        let stream = query.query(&mut client).await?;
-        let rows = stream.into_first_result().await?;
+        let _timer = metrics::timer(&metrics::SOURCE_QUERY_SECONDS, "incremental");
+        let value_expr = self.value_expr(&mut client, &self.config.tables.tag_database_table).await?;
+        
+        // 时间戳按数据源本地时间绑定
+        let statement = Select::from(Dialect::SqlServer, &self.config.tables.tag_database_table)
+            .column("DataTime")
+            .column("TagName")
+            .expr(value_expr)
+            .filter("DataTime", Op::Gt, self.config.utc_to_source(last_timestamp))
+            .order_by("DataTime")
+            .build();
+        let sql = statement.sql.clone();
+        let query = self.statement_query(statement)?;
+        
+        let started = Instant::now();
+        crate::chaos::inject_query_delay().await;
+        let rows = self.fetch_rows(&mut client, query).await?;
+        slow_log::check(&self.config.slow_log, SlowOpKind::SourceQuery, "incremental", &sql, rows.len(), started);
         
         let mut records = Vec::new();
         
@@ -169,6 +535,7 @@ impl SqlServerDataSource {
             }
         }
         
+        let records = self.map_tags(records);
         if !records.is_empty() {
             debug!("获取到 {} 条增量数据", records.len());
         }
@@ -176,25 +543,75 @@ impl SqlServerDataSource {
         Ok(records)
     }
     
-    /// 获取TagDatabase表的最新数据（忽略DataTime，使用当前时间）
+    /// 获取TagDatabase表的最新数据（默认忽略DataTime使用当前时间，`timestamps.use_source_time` 时使用DataTime）
+    #[tracing::instrument(skip_all)]
     pub async fn get_latest_tagdb_data(&self) -> Result<Vec<TimeSeriesRecord>> {
         debug!("开始查询TagDatabase表的最新数据");
         
+        #[cfg(test)]
+        if let Some(replay) = self.replay_source().await? {
+            return Ok(replay.take_records()?.into_iter()
+                .map(|record| TimeSeriesRecord { value: self.normalize_value(record.value), ..record })
+                .collect());
+        }
+        
         let mut client = self.create_connection_with_retry().await?;
+        let records = self.query_latest_tagdb(&mut client).await?;
+        debug!("从TagDatabase表获取到 {} 条最新数据", records.len());
         
-        // 查询TagDatabase表的TagName和TagVal，忽略DataTime
-        let sql = format!(
-            "SELECT [TagName], [TagVal] FROM [{}]",
-            self.config.tables.tag_database_table
-        );
+        Ok(records)
+    }
+    
+    /// 低延迟模式轮询TagDatabase表的最新数据：复用常驻连接
+    ///
+    /// 常驻连接的会话已断开（查询返回连接层错误）时立即重新连接并重试一次；最新值查询每次读取完整快照，
+    /// 重试即补上被中断的查询。其余失败不重试，丢弃连接后由下次轮询重新连接。
+    pub async fn poll_latest_tagdb_data(&self) -> Result<Vec<TimeSeriesRecord>> {
+        let mut persistent = self.persistent_client.lock().await;
+        let (mut client, reused) = match persistent.take() {
+            Some(client) => (client, true),
+            None => (self.create_connection().await?, false),
+        };
         
-        let query = tiberius::Query::new(sql);
+        let mut result = self.query_latest_tagdb(&mut client).await;
+        if reused && result.as_ref().is_err_and(is_connection_error) {
+            if let Err(e) = &result {
+                info!("低延迟轮询的常驻连接已断开，重新连接: {}", e);
+            }
+            client = self.create_connection().await?;
+            result = self.query_latest_tagdb(&mut client).await;
+        }
+        if result.is_ok() {
+            *persistent = Some(client);
+        }
+        result
+    }
+    
+    /// 在给定连接上查询TagDatabase表的TagName和数值列
+    async fn query_latest_tagdb(&self, client: &mut Client<Compat<TcpStream>>) -> Result<Vec<TimeSeriesRecord>> {
+        let _timer = metrics::timer(&metrics::SOURCE_QUERY_SECONDS, "latest");
+        let cached = self.latest_sql.lock().unwrap().clone();
+        let sql = match cached {
+            Some(sql) => sql,
+            None => {
+                let value_expr = self.value_expr(client, &self.config.tables.tag_database_table).await?;
+                let statement = self.source_time_column(
+                    Select::from(Dialect::SqlServer, &self.config.tables.tag_database_table)
+                        .column("TagName")
+                        .expr(value_expr)
+                ).build();
+                *self.latest_sql.lock().unwrap() = Some(statement.sql.clone());
+                statement.sql
+            }
+        };
         
-        let stream = query.query(&mut client).await?;
-        let rows = stream.into_first_result().await?;
+        let query = self.checked_query(sql.as_str())?;
+        let started = Instant::now();
+        let rows = self.fetch_rows(client, query).await?;
+        slow_log::check(&self.config.slow_log, SlowOpKind::SourceQuery, "latest", &sql, rows.len(), started);
         
         let mut records = Vec::new();
-        // 直接使用UTC时间，database.rs中会自动转换为北京时间显示
+        // 统一使用UTC时间，展示时再转换时区
         let current_time = Utc::now();
         
         for row in rows {
@@ -203,47 +620,132 @@ impl SqlServerDataSource {
             }
         }
         
-        debug!("从TagDatabase表获取到 {} 条最新数据", records.len());
+        Ok(self.map_tags(records))
+    }
+    
+    /// 读取TagDatabase表的标签元数据（单位、描述、量程、输入/输出标志），按规范标签名排序
+    pub async fn get_tag_metadata(&self) -> Result<Vec<TagMeta>> {
+        #[cfg(test)]
+        if self.replay_source().await?.is_some() {
+            return Ok(Vec::new());
+        }
         
-        Ok(records)
+        let mut client = self.create_connection_with_retry().await?;
+        
+        let cached = self.meta_sql.lock().unwrap().clone();
+        let sql = match cached {
+            Some(sql) => sql,
+            None => {
+                let table = &self.config.tables.tag_database_table;
+                let mut query = self.checked_query(
+                    "SELECT COLUMN_NAME FROM INFORMATION_SCHEMA.COLUMNS WHERE TABLE_NAME = @P1"
+                )?;
+                query.bind(table.as_str());
+                let rows = self.fetch_rows(&mut client, query).await?;
+                let existing: Vec<String> = rows.iter()
+                    .filter_map(|row| row.get::<&str, _>(0).map(str::to_string))
+                    .collect();
+                let has = |column: &str| existing.iter().any(|name| name.eq_ignore_ascii_case(column));
+                
+                let missing: Vec<&str> = META_COLUMNS.iter().copied().filter(|column| !has(column)).collect();
+                if !missing.is_empty() {
+                    info!("表 {} 没有元数据列 {:?}，对应字段为空", table, missing);
+                }
+                
+                let text = |column: &str| if has(column) {
+                    format!("CAST({} AS NVARCHAR(4000))", Dialect::SqlServer.quote(column))
+                } else {
+                    "CAST(NULL AS NVARCHAR(4000))".to_string()
+                };
+                let number = |column: &str| if has(column) {
+                    format!("TRY_CAST({} AS FLOAT)", Dialect::SqlServer.quote(column))
+                } else {
+                    "CAST(NULL AS FLOAT)".to_string()
+                };
+                let statement = Select::from(Dialect::SqlServer, table)
+                    .column("TagName")
+                    .expr(text("TagUnit"))
+                    .expr(text("TagDescrip"))
+                    .expr(number("TagMinVal"))
+                    .expr(number("TagMaxVal"))
+                    .expr(text("InOrOutFlag"))
+                    .not_null("TagName")
+                    .build();
+                *self.meta_sql.lock().unwrap() = Some(statement.sql.clone());
+                statement.sql
+            }
+        };
+        
+        let query = self.checked_query(sql)?;
+        let rows = self.fetch_rows(&mut client, query).await?;
+        
+        let text = |row: &Row, index: usize| row.get::<&str, _>(index)
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string);
+        let mut meta: Vec<TagMeta> = Vec::new();
+        let mut seen = HashSet::new();
+        for row in rows {
+            let Some(tag_name) = row.get::<&str, _>(0).map(str::trim) else {
+                continue;
+            };
+            if !self.normalizer.keeps(tag_name) {
+                continue;
+            }
+            // 多个来源映射到同一规范标签时取第一个
+            let tag_name = self.canonical_tag_name(tag_name);
+            if !seen.insert(tag_name.clone()) {
+                continue;
+            }
+            meta.push(TagMeta {
+                tag_name,
+                unit: text(&row, 1),
+                description: text(&row, 2),
+                min_value: row.get::<f64, _>(3),
+                max_value: row.get::<f64, _>(4),
+                in_out_flag: text(&row, 5),
+            });
+        }
+        meta.sort_by(|a, b| a.tag_name.cmp(&b.tag_name));
+        
+        debug!("从TagDatabase表读取到 {} 个标签的元数据", meta.len());
+        Ok(meta)
     }
     
     /// 检测TagDatabase表的标签变化（加点/少点）
+    #[tracing::instrument(skip_all, fields(known_tags = known_tags.len()))]
     pub async fn detect_tag_changes(&self, known_tags: &std::collections::HashSet<String>) -> Result<TagChanges> {
         debug!("开始检测TagDatabase表的标签变化");
         
+        #[cfg(test)]
+        if let Some(replay) = self.replay_source().await? {
+            return Ok(TagChanges::between(known_tags, replay.current_tags()?));
+        }
+        
         let mut client = self.create_connection_with_retry().await?;
         
         // 查询TagDatabase表中所有唯一的TagName
-        let sql = format!(
-            "SELECT DISTINCT [TagName] FROM [{}] WHERE [TagName] IS NOT NULL",
-            self.config.tables.tag_database_table
-        );
-        
-        let query = tiberius::Query::new(sql);
-        let stream = query.query(&mut client).await?;
-        let rows = stream.into_first_result().await?;
+        let query = self.statement_query(
+            Select::from(Dialect::SqlServer, &self.config.tables.tag_database_table)
+                .distinct()
+                .column("TagName")
+                .not_null("TagName")
+                .build()
+        )?;
+        let rows = self.fetch_rows(&mut client, query).await?;
         
         let mut current_tags = std::collections::HashSet::new();
         for row in rows {
             if let Some(tag_name) = row.get::<&str, _>(0) {
-                current_tags.insert(tag_name.trim().to_string());
+                // 按规范标签名比较，多个来源映射到同一规范标签时只算一个标签
+                let tag_name = tag_name.trim();
+                if self.normalizer.keeps(tag_name) {
+                    current_tags.insert(self.canonical_tag_name(tag_name));
+                }
             }
         }
         
-        // 计算新增和删除的标签
-        let added_tags: Vec<String> = current_tags.difference(known_tags)
-            .cloned()
-            .collect();
-        let removed_tags: Vec<String> = known_tags.difference(&current_tags)
-            .cloned()
-            .collect();
-        
-        let changes = TagChanges {
-            added_tags,
-            removed_tags,
-            current_tags,
-        };
+        let changes = TagChanges::between(known_tags, current_tags);
         
         if !changes.added_tags.is_empty() {
             info!("检测到新增标签: {:?}", changes.added_tags);
@@ -264,25 +766,19 @@ impl SqlServerDataSource {
         debug!("开始查询指定标签的最新数据: {:?}", tag_names);
         
         let mut client = self.create_connection_with_retry().await?;
+        let value_expr = self.value_expr(&mut client, &self.config.tables.tag_database_table).await?;
         
-        // 构建IN子句
-        let tag_placeholders: Vec<String> = (1..=tag_names.len())
-            .map(|i| format!("@P{}", i))
-            .collect();
-        let in_clause = tag_placeholders.join(", ");
-        
-        let sql = format!(
-            "SELECT [TagName], [TagVal] FROM [{}] WHERE [TagName] IN ({})",
-            self.config.tables.tag_database_table, in_clause
-        );
+        let query = self.statement_query(
+            self.source_time_column(
+                Select::from(Dialect::SqlServer, &self.config.tables.tag_database_table)
+                    .column("TagName")
+                    .expr(value_expr)
+            )
+            .filter_in("TagName", tag_names)
+            .build()
+        )?;
         
-        let mut query = tiberius::Query::new(sql);
-        for tag_name in tag_names {
-            query.bind(tag_name.as_str());
-        }
-        
-        let stream = query.query(&mut client).await?;
-        let rows = stream.into_first_result().await?;
+        let rows = self.fetch_rows(&mut client, query).await?;
         
         let mut records = Vec::new();
         let current_time = Utc::now();
@@ -293,11 +789,13 @@ impl SqlServerDataSource {
             }
         }
         
+        let records = self.map_tags(records);
         debug!("获取到 {} 条指定标签数据", records.len());
         Ok(records)
     }
     
     /// 解析日期时间字符串 (格式: "21/5/2024 10:15:01")
+    #[allow(dead_code)]
     fn parse_datetime_string(&self, datetime_str: &str) -> Result<DateTime<Utc>> {
         // 尝试解析 DD/M/YYYY HH:MM:SS 格式
         if let Ok(naive_dt) = NaiveDateTime::parse_from_str(datetime_str, "%d/%m/%Y %H:%M:%S") {
@@ -314,45 +812,39 @@ impl SqlServerDataSource {
     }
     
     /// 解析简化的数据库行为时序记录 (DateTime, TagName, TagVal)
+    #[allow(dead_code)]
     fn parse_simplified_row(&self, row: Row) -> Result<Option<TimeSeriesRecord>> {
         // SQL Server的datetime类型应该使用NaiveDateTime获取，然后转换为UTC
         let timestamp: Option<NaiveDateTime> = row.get(0);
         let tag_name: Option<&str> = row.get(1);
         
-        // 尝试获取f64，如果失败则尝试f32并转换
-        let value: Option<f64> = match row.try_get::<f64, _>(2) {
-            Ok(val) => val,
-            Err(_) => {
-                // 如果f64失败，尝试f32并转换为f64
-                match row.try_get::<f32, _>(2) {
-                    Ok(Some(f32_val)) => Some(f32_val as f64),
-                    Ok(None) => None,
-                    Err(e) => {
-                        warn!("无法解析数值字段: {}", e);
-                        None
-                    }
-                }
-            }
-        };
+        // 数值列可能为 float/real/int/bit/decimal 等类型，统一解码为 f64
+        let value = decode_value(&row, 2);
+        // 字符类型值列中无法转换为数值的值作为文本值（字符串标签）
+        let text = if value.is_none() { decode_text(&row, 3) } else { None };
+        let quality = decode_text(&row, 4);
         
         match (timestamp, tag_name) {
             (Some(naive_ts), Some(tag)) => {
-                // 处理None值为0.0，保持总行数不变
-                let val = value.unwrap_or(0.0);
-                
-                // 过滤无效数值，将其设为0.0
-                let final_val = if val.is_finite() { val } else { 0.0 };
+                // 缺失或无效数值按配置写入NULL（或补0），保持总行数不变；文本值不补0
+                let final_val = if text.is_some() || self.is_bad_quality(quality.as_deref()) {
+                    None
+                } else {
+                    self.normalize_value(value)
+                };
                 
-                // 假设SQL Server中的时间是北京时间，需要转换为UTC存储
-                // 将NaiveDateTime转换为UTC DateTime，然后减去8小时
-                let utc_timestamp = naive_ts.and_utc();
-                let beijing_timestamp = utc_timestamp - chrono::Duration::hours(8);
+                // SQL Server中的时间为数据源本地时间，按配置时区换算为UTC存储
+                let utc_timestamp = self.config.source_to_utc(naive_ts);
                 
-                Ok(Some(TimeSeriesRecord {
+                let record = TimeSeriesRecord {
                     tag_name: tag.trim().to_string(), // 去除标签名的空格
-                    timestamp: beijing_timestamp,
+                    timestamp: utc_timestamp,
                     value: final_val,
-                }))
+                    text,
+                    quality,
+                };
+                self.log_parsed(&naive_ts, value, &record);
+                Ok(Some(record))
             }
             _ => {
                 warn!("跳过不完整的数据行: timestamp={:?}, tag={:?}, value={:?}", 
@@ -362,46 +854,57 @@ impl SqlServerDataSource {
         }
     }
     
+    /// 规整标签值：NaN/Inf 视为缺失，缺失值按配置写入NULL或补0
+    fn normalize_value(&self, value: Option<f64>) -> Option<f64> {
+        let value = crate::chaos::corrupt_value(value);
+        match value.filter(|v| v.is_finite()) {
+            Some(v) => Some(v),
+            None if self.config.zero_fill_missing => Some(0.0),
+            None => None,
+        }
+    }
+    
+    /// 解析详细日志（运行时开关 `parse_logging`）：记录每行的原始时间、原始值与解析结果
+    fn log_parsed(&self, raw_time: &dyn std::fmt::Display, raw_value: Option<f64>, record: &TimeSeriesRecord) {
+        if self.toggles.parse_logging() {
+            info!("解析数据行: tag={}, 原始时间={}, 原始值={:?}, 时间={}, 值={:?}, 文本={:?}, 质量={:?}",
+                  record.tag_name, raw_time, raw_value, record.timestamp, record.value, record.text, record.quality);
+        }
+    }
+    
     /// 解析TagDatabase表的行为时序记录 (DateTime, 标签名, 数值)
     fn parse_tagdb_row(&self, row: Row) -> Result<Option<TimeSeriesRecord>> {
         // SQL Server的datetime类型应该使用NaiveDateTime获取
         let timestamp: Option<NaiveDateTime> = row.get(0);
         let tag_name: Option<&str> = row.get(1);
         
-        // 尝试获取f64，如果失败则尝试f32并转换
-        let value: Option<f64> = match row.try_get::<f64, _>(2) {
-            Ok(val) => val,
-            Err(_) => {
-                // 如果f64失败，尝试f32并转换为f64
-                match row.try_get::<f32, _>(2) {
-                    Ok(Some(f32_val)) => Some(f32_val as f64),
-                    Ok(None) => None,
-                    Err(e) => {
-                        warn!("无法解析数值字段: {}", e);
-                        None
-                    }
-                }
-            }
-        };
+        // 数值列可能为 float/real/int/bit/decimal 等类型，统一解码为 f64
+        let value = decode_value(&row, 2);
+        // 字符类型值列中无法转换为数值的值作为文本值（字符串标签）
+        let text = if value.is_none() { decode_text(&row, 3) } else { None };
+        let quality = decode_text(&row, 4);
         
         match (timestamp, tag_name) {
             (Some(naive_ts), Some(tag)) => {
-                // 处理None值为0.0，保持总行数不变
-                let val = value.unwrap_or(0.0);
-                
-                // 过滤无效数值，将其设为0.0
-                let final_val = if val.is_finite() { val } else { 0.0 };
+                // 缺失或无效数值按配置写入NULL（或补0），保持总行数不变；文本值不补0
+                let final_val = if text.is_some() || self.is_bad_quality(quality.as_deref()) {
+                    None
+                } else {
+                    self.normalize_value(value)
+                };
                 
-                // 假设SQL Server中的时间是北京时间，需要转换为UTC存储
-                // 将NaiveDateTime转换为UTC DateTime，然后减去8小时
-                let utc_timestamp = naive_ts.and_utc();
-                let beijing_timestamp = utc_timestamp - chrono::Duration::hours(8);
+                // SQL Server中的时间为数据源本地时间，按配置时区换算为UTC存储
+                let utc_timestamp = self.config.source_to_utc(naive_ts);
                 
-                Ok(Some(TimeSeriesRecord {
+                let record = TimeSeriesRecord {
                     tag_name: tag.trim().to_string(), // 去除标签名的空格
-                    timestamp: beijing_timestamp,
+                    timestamp: utc_timestamp,
                     value: final_val,
-                }))
+                    text,
+                    quality,
+                };
+                self.log_parsed(&naive_ts, value, &record);
+                Ok(Some(record))
             }
             _ => {
                 warn!("跳过不完整的数据行: timestamp={:?}, tag={:?}, value={:?}", 
@@ -411,39 +914,42 @@ impl SqlServerDataSource {
         }
     }
     
-    /// 解析TagDatabase表当前数据行（只有TagName和TagVal，使用当前时间）
+    /// 解析TagDatabase表当前数据行（TagName与数值，使用当前时间；使用源时间戳时末列为DataTime）
     fn parse_tagdb_current_row(&self, row: Row, current_time: DateTime<Utc>) -> Result<Option<TimeSeriesRecord>> {
         let tag_name: Option<&str> = row.get(0);
-        
-        // 尝试获取f64，如果失败则尝试f32并转换
-        let value: Option<f64> = match row.try_get::<f64, _>(1) {
-            Ok(val) => val,
-            Err(_) => {
-                // 如果f64失败，尝试f32并转换为f64
-                match row.try_get::<f32, _>(1) {
-                    Ok(Some(f32_val)) => Some(f32_val as f64),
-                    Ok(None) => None,
-                    Err(e) => {
-                        warn!("无法解析数值字段: {}", e);
-                        None
-                    }
-                }
-            }
+        // DataTime 缺失或类型不符时退回当前时间
+        let timestamp = if self.config.timestamps.use_source_time {
+            row.try_get::<NaiveDateTime, _>(4).ok().flatten()
+                .map(|naive_ts| self.config.source_to_utc(naive_ts))
+                .unwrap_or(current_time)
+        } else {
+            current_time
         };
         
+        // 数值列可能为 float/real/int/bit/decimal 等类型，统一解码为 f64
+        let value = decode_value(&row, 1);
+        // 字符类型值列中无法转换为数值的值作为文本值（字符串标签）
+        let text = if value.is_none() { decode_text(&row, 2) } else { None };
+        let quality = decode_text(&row, 3);
+        
         match tag_name {
             Some(tag) => {
-                // 处理None值为0.0，保持总行数不变
-                let val = value.unwrap_or(0.0);
-                
-                // 过滤无效数值，将其设为0.0
-                let final_val = if val.is_finite() { val } else { 0.0 };
+                // 缺失或无效数值按配置写入NULL（或补0），保持总行数不变；文本值不补0
+                let final_val = if text.is_some() || self.is_bad_quality(quality.as_deref()) {
+                    None
+                } else {
+                    self.normalize_value(value)
+                };
                 
-                Ok(Some(TimeSeriesRecord {
+                let record = TimeSeriesRecord {
                     tag_name: tag.trim().to_string(), // 去除标签名的空格
-                    timestamp: current_time,
+                    timestamp,
                     value: final_val,
-                }))
+                    text,
+                    quality,
+                };
+                self.log_parsed(&timestamp, value, &record);
+                Ok(Some(record))
             }
             _ => {
                 warn!("跳过不完整的数据行: tag={:?}, value={:?}", 
@@ -454,42 +960,29 @@ impl SqlServerDataSource {
     }
     
     /// 解析数据库行为时序记录 (保留兼容性)
+    #[allow(dead_code)]
     fn parse_row(&self, row: Row) -> Result<Option<TimeSeriesRecord>> {
         let tag_name: Option<&str> = row.get(0);
         // SQL Server的datetime类型应该使用NaiveDateTime获取
         let timestamp: Option<NaiveDateTime> = row.get(1);
         
-        // 尝试获取f64，如果失败则尝试f32并转换
-        let value: Option<f64> = match row.try_get::<f64, _>(2) {
-            Ok(val) => val,
-            Err(_) => {
-                // 如果f64失败，尝试f32并转换为f64
-                match row.try_get::<f32, _>(2) {
-                    Ok(Some(f32_val)) => Some(f32_val as f64),
-                    Ok(None) => None,
-                    Err(e) => {
-                        warn!("无法解析数值字段: {}", e);
-                        None
-                    }
-                }
-            }
-        };
+        // 数值列可能为 float/real/int/bit/decimal 等类型，统一解码为 f64
+        let value = decode_value(&row, 2);
         
         match (tag_name, timestamp) {
             (Some(tag), Some(naive_ts)) => {
-                // 处理None值为0.0，保持总行数不变
-                let val = value.unwrap_or(0.0);
+                // 缺失或无效数值按配置写入NULL（或补0），保持总行数不变
+                let final_val = self.normalize_value(value);
                 
-                // 过滤无效数值，将其设为0.0
-                let final_val = if val.is_finite() { val } else { 0.0 };
-                
-                // 将NaiveDateTime转换为UTC DateTime
-                let utc_timestamp = naive_ts.and_utc();
+                // 将数据源本地时间换算为UTC DateTime
+                let utc_timestamp = self.config.source_to_utc(naive_ts);
                 
                 Ok(Some(TimeSeriesRecord {
                     tag_name: tag.trim().to_string(), // 去除标签名的空格
                     timestamp: utc_timestamp,
                     value: final_val,
+                    text: None,
+                    quality: None,
                 }))
             }
             _ => {
@@ -501,6 +994,7 @@ impl SqlServerDataSource {
     }
     
     /// 查询历史数据
+    #[allow(dead_code)]
     pub async fn query_history_data(&self, table: &str, days: i32) -> Result<Vec<TimeSeriesRecord>> {
         info!("开始查询历史数据，表: {}, 天数: {}", table, days);
         
@@ -510,35 +1004,32 @@ impl SqlServerDataSource {
         let end_date = Local::now().date_naive();
         let start_date = end_date - chrono::Duration::days(days as i64);
         
-        let query = format!(
-            "SELECT * FROM [{}] WHERE CAST([DateTime] AS DATE) >= '{}' AND CAST([DateTime] AS DATE) <= '{}' ORDER BY [DateTime]",
-            table, start_date, end_date
-        );
+        // 表名由调用方传入，先按标识符规则校验
+        validate_identifier(table)?;
+        let date_expr = format!("CAST({} AS DATE)", Dialect::SqlServer.quote("DateTime"));
+        let statement = Select::from(Dialect::SqlServer, table)
+            .filter_expr(date_expr.clone(), Op::Ge, start_date)
+            .filter_expr(date_expr, Op::Le, end_date)
+            .order_by("DateTime")
+            .build();
         
-        info!("执行历史数据查询: {}", query);
+        info!("执行历史数据查询: {}（{} 到 {}）", statement.sql, start_date, end_date);
         
-        let stream = tiberius::Query::new(query)
-            .query(&mut client)
+        let rows = self.fetch_rows(&mut client, self.statement_query(statement)?)
             .await
             .context("历史数据查询失败")?;
         
-        let rows = stream.into_first_result().await?;
-        
         if rows.is_empty() {
             warn!("未找到历史数据，请检查:");
             warn!("  - 表名是否正确: {}", table);
             warn!("  - 时间范围: {} 到 {}", start_date, end_date);
             
             // 尝试查询表的总记录数
-            let count_query = format!("SELECT COUNT(*) FROM {}", table);
-            match tiberius::Query::new(count_query).query(&mut client).await {
-                Ok(count_stream) => {
-                    if let Ok(count_rows) = count_stream.into_first_result().await {
-                        if let Some(count_row) = count_rows.into_iter().next() {
-                            if let Some(count) = count_row.get::<i32, _>(0) {
-                                warn!("  - 表 {} 总记录数: {}", table, count);
-                            }
-                        }
+            let count_query = Select::from(Dialect::SqlServer, table).expr("COUNT(*)").build();
+            match self.fetch_row(&mut client, self.statement_query(count_query)?).await {
+                Ok(count_row) => {
+                    if let Some(count) = count_row.and_then(|row| row.get::<i32, _>(0)) {
+                        warn!("  - 表 {} 总记录数: {}", table, count);
                     }
                 }
                 Err(e) => warn!("无法查询表记录数: {}", e),
@@ -553,30 +1044,19 @@ impl SqlServerDataSource {
             }
         }
         
+        let records = self.map_tags(records);
         info!("查询到 {} 条历史记录", records.len());
         Ok(records)
     }
     
     /// 解析历史数据行
+    #[allow(dead_code)]
     fn parse_history_row(&self, row: Row) -> Result<Option<TimeSeriesRecord>> {
         let tag_name: Option<&str> = row.get(0);
         let timestamp: Option<DateTime<Utc>> = row.get(1);
         
-        // 尝试获取f64，如果失败则尝试f32并转换
-        let value: Option<f64> = match row.try_get::<f64, _>(2) {
-            Ok(val) => val,
-            Err(_) => {
-                // 如果f64失败，尝试f32并转换为f64
-                match row.try_get::<f32, _>(2) {
-                    Ok(Some(f32_val)) => Some(f32_val as f64),
-                    Ok(None) => None,
-                    Err(e) => {
-                        warn!("无法解析数值字段: {}", e);
-                        None
-                    }
-                }
-            }
-        };
+        // 数值列可能为 float/real/int/bit/decimal 等类型，统一解码为 f64
+        let value = decode_value(&row, 2);
         let _quality: Option<&str> = row.get(3);
         
         match (tag_name, timestamp, value) {
@@ -585,7 +1065,9 @@ impl SqlServerDataSource {
                     Ok(Some(TimeSeriesRecord {
                         tag_name: tag.to_string(),
                         timestamp: ts,
-                        value: val,
+                        value: Some(val),
+                        text: None,
+                        quality: None,
                     }))
                 } else {
                     debug!("跳过无效数值: tag={}, value={}", tag, val);
@@ -600,15 +1082,282 @@ impl SqlServerDataSource {
         }
     }
 
+    /// 权限自检：校验账号对配置表的 SELECT 权限，并检查是否拥有超出需要的权限
+    ///
+    /// 缺少 SELECT 权限时报告需要执行的 GRANT 语句并返回错误；拥有写权限或 sysadmin/db_owner
+    /// 角色时给出警告，启用 `strict_permissions` 时同样视为错误。仅快照模式（`snapshot_only`）不检查历史表。
+    pub async fn check_permissions(&self, snapshot_only: bool) -> Result<()> {
+        debug!("开始检查数据源账号权限");
+        let mut client = self.create_connection_with_retry().await?;
+        
+        let query = self.checked_query("SELECT SUSER_SNAME(), IS_SRVROLEMEMBER('sysadmin'), IS_ROLEMEMBER('db_owner')")?;
+        let row = self.fetch_row(&mut client, query).await?
+            .ok_or_else(|| anyhow::anyhow!("权限查询没有返回结果"))?;
+        let login = row.get::<&str, _>(0).unwrap_or("").to_string();
+        let mut excessive = Vec::new();
+        if row.get::<i32, _>(1) == Some(1) {
+            excessive.push("sysadmin 服务器角色".to_string());
+        }
+        if row.get::<i32, _>(2) == Some(1) {
+            excessive.push("db_owner 数据库角色".to_string());
+        }
+        
+        // 模板历史表只检查当前时间对应的表
+        let now = self.config.utc_to_source(Utc::now());
+        let mut tables = if snapshot_only { Vec::new() } else { self.config.tables.history_tables(now, now) };
+        tables.push(self.config.tables.tag_database_table.clone());
+        
+        let mut missing = Vec::new();
+        for table in &tables {
+            // `schema.table` 各部分分别引用（QUOTENAME 整体引用会得到 [dbo.Table]）
+            let object = Dialect::SqlServer.quote_qualified(table);
+            let mut query = self.checked_query(
+                "SELECT HAS_PERMS_BY_NAME(@P1, 'OBJECT', 'SELECT'), \
+                        HAS_PERMS_BY_NAME(@P1, 'OBJECT', 'INSERT'), \
+                        HAS_PERMS_BY_NAME(@P1, 'OBJECT', 'UPDATE'), \
+                        HAS_PERMS_BY_NAME(@P1, 'OBJECT', 'DELETE')"
+            )?;
+            query.bind(object.as_str());
+            let Some(row) = self.fetch_row(&mut client, query).await? else {
+                continue;
+            };
+            
+            match row.get::<i32, _>(0) {
+                Some(1) => {}
+                // 对象不存在或不可见时返回 NULL
+                None => missing.push(format!("表 {} 不存在或当前账号不可见", object)),
+                Some(_) => missing.push(format!("GRANT SELECT ON {} TO {};", object, Dialect::SqlServer.quote(&login))),
+            }
+            
+            // 心跳回写需要目标表的 INSERT/UPDATE 权限
+            let heartbeat = self.config.heartbeat.enabled && table == self.config.heartbeat_table();
+            let writes: Vec<&str> = ["INSERT", "UPDATE", "DELETE"].iter().enumerate()
+                .filter(|(i, _)| row.get::<i32, _>(i + 1) == Some(1))
+                .map(|(_, perm)| *perm)
+                .filter(|perm| !(heartbeat && matches!(*perm, "INSERT" | "UPDATE")))
+                .collect();
+            if !writes.is_empty() {
+                excessive.push(format!("表 {} 的 {} 权限", object, writes.join("/")));
+            }
+        }
+        
+        if !missing.is_empty() {
+            for item in &missing {
+                error!("数据源账号 {} 缺少权限: {}", login, item);
+            }
+            anyhow::bail!("数据源账号 {} 缺少 {} 项必要权限，详见日志", login, missing.len());
+        }
+        
+        if !excessive.is_empty() {
+            for item in &excessive {
+                warn!("数据源账号 {} 拥有超出需要的权限: {}", login, item);
+            }
+            if self.config.strict_permissions {
+                anyhow::bail!("最小权限模式下数据源账号 {} 不应拥有 {} 项额外权限，详见日志", login, excessive.len());
+            }
+        }
+        
+        info!("数据源账号 {} 权限检查通过", login);
+        Ok(())
+    }
+    
+    /// 将心跳值写回数据源：更新心跳标签所在行，行不存在时插入
+    pub async fn write_heartbeat(&self, value: f64) -> Result<()> {
+        let heartbeat = &self.config.heartbeat;
+        let table = Dialect::SqlServer.quote_qualified(self.config.heartbeat_table());
+        let tag_column = Dialect::SqlServer.quote(&heartbeat.tag_column);
+        let value_column = Dialect::SqlServer.quote(&heartbeat.value_column);
+        let mut client = self.create_connection_with_retry().await?;
+        
+        let mut query = self.checked_query(format!(
+            "UPDATE {} SET {} = @P1 WHERE {} = @P2", table, value_column, tag_column
+        ))?;
+        query.bind(value);
+        query.bind(heartbeat.tag_name.clone());
+        if self.with_query_timeout(query.execute(&mut client)).await?.total() > 0 {
+            return Ok(());
+        }
+        
+        let mut query = self.checked_query(format!(
+            "INSERT INTO {} ({}, {}) VALUES (@P1, @P2)", table, tag_column, value_column
+        ))?;
+        query.bind(heartbeat.tag_name.clone());
+        query.bind(value);
+        self.with_query_timeout(query.execute(&mut client)).await?;
+        info!("数据源表 {} 中没有心跳标签 {}，已插入", table, heartbeat.tag_name);
+        Ok(())
+    }
+    
     /// 测试数据库连接
     pub async fn test_connection(&self) -> Result<()> {
         debug!("测试 SQL Server 连接");
         let mut client = self.create_connection_with_retry().await?;
         
-        let stream = tiberius::Query::new("SELECT 1 as test").query(&mut client).await?;
-        let _rows = stream.into_first_result().await?;
+        let query = self.checked_query("SELECT 1 as test")?;
+        let _rows = self.fetch_rows(&mut client, query).await?;
         
         info!("SQL Server 连接成功");
         Ok(())
     }
-}
\ No newline at end of file
+}
+/// 是否为连接层错误（会话断开、连接被重置等），而不是 SQL 执行错误
+fn is_connection_error(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause.is::<std::io::Error>()
+            || matches!(cause.downcast_ref::<tiberius::error::Error>(), Some(tiberius::error::Error::Io { .. }))
+    })
+}
+
+/// 只读模式下禁止出现的关键字（SELECT ... INTO 会建表，同样禁止）
+const WRITE_KEYWORDS: [&str; 16] = [
+    "INSERT", "UPDATE", "DELETE", "MERGE", "DROP", "ALTER", "CREATE", "TRUNCATE",
+    "EXEC", "EXECUTE", "GRANT", "REVOKE", "DENY", "INTO", "BULK", "OPENROWSET",
+];
+
+/// 值列的查询表达式：数值表达式与文本表达式，以逗号分隔
+fn value_select(column: &str, data_type: &str) -> String {
+    let column = Dialect::SqlServer.quote(column);
+    let is_text = ["char", "varchar", "nchar", "nvarchar", "text", "ntext", "sql_variant"]
+        .iter()
+        .any(|t| data_type.eq_ignore_ascii_case(t));
+    
+    if is_text {
+        format!(
+            "TRY_CAST({0} AS FLOAT), CASE WHEN TRY_CAST({0} AS FLOAT) IS NULL THEN CAST({0} AS NVARCHAR(4000)) END",
+            column
+        )
+    } else {
+        format!("CAST({} AS FLOAT), CAST(NULL AS NVARCHAR(4000))", column)
+    }
+}
+
+
+/// 读取文本值列，列不存在或为 NULL 时返回 None
+fn decode_text(row: &Row, index: usize) -> Option<String> {
+    row.try_get::<&str, _>(index).ok().flatten()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// 将数值列解码为 f64
+///
+/// 兼容 float/real、decimal/numeric、bigint/int/smallint/tinyint、bit 以及数字字符串；
+/// NULL 或无法识别的类型返回 None。
+fn decode_value(row: &Row, index: usize) -> Option<f64> {
+    if let Ok(value) = row.try_get::<f64, _>(index) {
+        return value;
+    }
+    if let Ok(value) = row.try_get::<f32, _>(index) {
+        return value.map(f64::from);
+    }
+    if let Ok(value) = row.try_get::<tiberius::numeric::Numeric, _>(index) {
+        return value.map(f64::from);
+    }
+    if let Ok(value) = row.try_get::<i64, _>(index) {
+        return value.map(|v| v as f64);
+    }
+    if let Ok(value) = row.try_get::<i32, _>(index) {
+        return value.map(f64::from);
+    }
+    if let Ok(value) = row.try_get::<i16, _>(index) {
+        return value.map(f64::from);
+    }
+    if let Ok(value) = row.try_get::<u8, _>(index) {
+        return value.map(f64::from);
+    }
+    if let Ok(value) = row.try_get::<bool, _>(index) {
+        return value.map(|v| if v { 1.0 } else { 0.0 });
+    }
+    match row.try_get::<&str, _>(index) {
+        Ok(value) => value.and_then(|v| v.trim().parse().ok()),
+        Err(e) => {
+            warn!("无法解析数值字段: {}", e);
+            None
+        }
+    }
+}
+
+/// 校验 SQL 为单条只读 SELECT 语句
+///
+/// 忽略字符串字面量、方括号标识符与注释中的内容后，要求首个关键字为 SELECT 或 WITH，
+/// 不含写操作关键字，且除末尾外不含分号。
+fn check_read_only(sql: &str) -> std::result::Result<(), String> {
+    // 去掉字面量、标识符与注释，只保留语句结构
+    let mut stripped = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                // 字符串字面量，'' 为转义的单引号
+                while let Some(c) = chars.next() {
+                    if c == '\'' {
+                        if chars.peek() == Some(&'\'') {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                stripped.push_str(" '' ");
+            }
+            '[' => {
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                }
+                stripped.push_str(" [] ");
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                stripped.push(' ');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                stripped.push(' ');
+            }
+            _ => stripped.push(c),
+        }
+    }
+    
+    if stripped.trim().trim_end_matches(';').contains(';') {
+        return Err("包含多条语句".to_string());
+    }
+    
+    let keywords: Vec<String> = stripped
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '@'))
+        .filter(|word| !word.is_empty() && !word.starts_with('@'))
+        .map(str::to_uppercase)
+        .collect();
+    
+    match keywords.first().map(String::as_str) {
+        Some("SELECT") | Some("WITH") => {}
+        Some(other) => return Err(format!("不是 SELECT 语句（{}）", other)),
+        None => return Err("空语句".to_string()),
+    }
+    
+    if let Some(keyword) = keywords.iter().find(|word| WRITE_KEYWORDS.contains(&word.as_str())) {
+        return Err(format!("包含写操作关键字 {}", keyword));
+    }
+    
+    Ok(())
+}
+
+/// 语句摘要（FNV-1a 64 位），用于审计日志与导出来源信息中标识语句
+pub fn sql_digest(sql: &str) -> String {
+    let hash = sql.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
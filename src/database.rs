@@ -1,455 +1,4380 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use duckdb::Connection;
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::sql::{self, Dialect, Insert, Param};
+use crate::low_latency::LatencyTracker;
+use crate::metrics;
+use crate::stale::StaleTracker;
+use crate::toggles::FeatureToggles;
+use crate::slow_log::{self, SlowOpKind};
+use crate::config::{AppConfig, Aggregation, ColumnNaming, ExportLayout, ExportTimestamps, FillMethod, MissingCells, StorageMode, TableShape, WideOverflow};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
 use tracing::{info, debug, error, warn};
 
+/// 分级保留的汇总表：1 分钟与 1 小时
+pub const ROLLUP_TABLES: [&str; 2] = ["ts_rollup_1m", "ts_rollup_1h"];
+
+/// 查询返回的标签数据点（时间，值）
+pub type Point<T> = (DateTime<Utc>, T);
+/// 阶梯保持区间（起始时间，结束时间，数值）
+type Span = (DateTime<Utc>, DateTime<Utc>, f64);
+/// 各标签最后写入的值与写入时间
+type LastWritten = std::collections::HashMap<String, (Option<f64>, DateTime<Utc>)>;
+
 /// 时序数据记录
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TimeSeriesRecord {
     pub tag_name: String,
     pub timestamp: DateTime<Utc>,
-    pub value: f64,
+    /// 标签值，None 表示缺失或无效
+    pub value: Option<f64>,
+    /// 文本值（字符串标签，如 "RUNNING"），数值标签为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// 数据质量（TagQuality），未启用质量采集时为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>,
 }
 
 /// 宽表格式的时序数据记录
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 pub struct WideTimeSeriesRecord {
     pub timestamp: DateTime<Utc>,
-    pub tag_values: std::collections::HashMap<String, f64>,
+    pub tag_values: std::collections::HashMap<String, Option<f64>>,
+}
+
+/// 同步检查点
+#[derive(Debug, Clone)]
+pub struct SyncCheckpoint {
+    /// 最后成功同步的时间
+    pub last_synced: DateTime<Utc>,
+    /// 已知标签基线
+    pub known_tags: Vec<String>,
+}
+
+/// 一次同步周期的统计，记录在 sync_log 表
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncCycleStats {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// 从数据源获取的记录数（含缺口回填）
+    pub rows_fetched: usize,
+    /// 写入缓存的记录数（死区过滤后）
+    pub rows_written: usize,
+    /// 本周期新增的标签（宽表中的新列）数量
+    pub new_columns: usize,
+    /// 周期失败时的错误信息
+    pub error: Option<String>,
+}
+
+/// 保留期豁免（法律保全）：命中的数据在保留期清理、归档与已删除标签清理时保留，直到通过管理接口解除
+#[derive(Debug, Clone, Serialize)]
+pub struct RetentionHold {
+    pub id: i64,
+    /// 标签，None 表示全部标签
+    pub tag: Option<String>,
+    /// 时间范围 [from, to)，未给出的边界不限
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub reason: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 复制变更日志：`after` 之后写入过数据的时间范围，供只读副本按序号增量拉取（含回填的较早数据）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeLog {
+    /// 最新的日志序号，副本下次以此为 `after`
+    pub seq: i64,
+    /// 写入过数据的时间范围 [from, to]，按起始时间排序，相邻的范围已合并
+    pub ranges: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+    /// 未给出 `after` 或其后的日志已被清理，副本需重新拉取整个数据窗口
+    pub full: bool,
+}
+
+/// 变更流的一页
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangesPage {
+    pub records: Vec<TimeSeriesRecord>,
+    /// 本页达到时间点数量上限时为最后一个时间点，作为下一页的 `after`
+    pub next: Option<DateTime<Utc>>,
+}
+
+/// 标签的缩略趋势（降采样后的桶均值序列）
+#[derive(Debug, Clone, Serialize)]
+pub struct Sparkline {
+    /// 各桶起始时间
+    pub timestamps: Vec<DateTime<Utc>>,
+    /// 各桶均值
+    pub values: Vec<f64>,
+}
+
+/// 标签元数据（来自 TagDatabase），TagDatabase 中没有对应列或值为空时为 None
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TagMeta {
+    pub tag_name: String,
+    /// 工程单位（TagUnit）
+    pub unit: Option<String>,
+    /// 描述（TagDescrip）
+    pub description: Option<String>,
+    /// 量程下限（TagMinVal）
+    pub min_value: Option<f64>,
+    /// 量程上限（TagMaxVal）
+    pub max_value: Option<f64>,
+    /// 输入/输出标志（InOrOutFlag），原样保留
+    pub in_out_flag: Option<String>,
+}
+
+/// 冷存储目录中的一个 Parquet 分区文件
+#[derive(Debug, Clone, Serialize)]
+pub struct ColdPartition {
+    pub path: String,
+    /// 来源表（ts_wide、ts_long、ts_text 或 ts_quality）
+    pub table_name: String,
+    /// 分区日期（UTC）
+    pub day: chrono::NaiveDate,
+    pub min_time: DateTime<Utc>,
+    pub max_time: DateTime<Utc>,
+    pub row_count: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 标签的最新值
+#[derive(Debug, Clone, Serialize)]
+pub struct LatestValue {
+    /// 最新非空值的数据时间
+    pub timestamp: DateTime<Utc>,
+    pub value: f64,
+}
+
+/// 标签在时间范围内的值序列
+#[derive(Debug, Clone, Serialize)]
+pub struct TagSeries {
+    /// 各点时间（降采样时为时间桶起始时间）
+    pub timestamps: Vec<DateTime<Utc>>,
+    pub values: Vec<f64>,
+}
+
+/// 标签的趋势预测
+#[derive(Debug, Clone, Serialize)]
+pub struct Forecast {
+    /// 预测时间
+    pub issued_at: DateTime<Utc>,
+    /// 各预测点时间
+    pub timestamps: Vec<DateTime<Utc>>,
+    /// 各预测值
+    pub values: Vec<f64>,
+}
+
+/// 标签在宽表中的列名
+#[derive(Debug, Clone, Serialize)]
+pub struct TagColumn {
+    pub tag: String,
+    pub column: String,
+    /// 列名与其他标签冲突，已追加后缀消歧
+    pub disambiguated: bool,
+}
+
+/// 数据表压缩结果
+#[derive(Debug, Clone)]
+pub struct CompactReport {
+    /// 删除的全空列（宽表模式）
+    pub dropped_columns: Vec<String>,
+    /// 重写的行数
+    pub rows: usize,
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+/// 开关量标签运行状态报告
+#[derive(Debug, Clone, Serialize)]
+pub struct StateReport {
+    pub tag_name: String,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    /// 运行（非零状态）总时长，单位为秒
+    pub run_time_secs: f64,
+    /// 启动次数（0 -> 非0）
+    pub starts: u32,
+    /// 停止次数（非0 -> 0）
+    pub stops: u32,
+    /// 最长连续运行时长，单位为秒
+    pub longest_run_secs: f64,
+    /// 各状态持续时间 (状态值, 秒)
+    pub state_durations: Vec<(f64, f64)>,
+}
+
+/// 可取消查询的中断句柄集合，登记当前查询过程中打开的全部连接
+#[derive(Default)]
+pub struct QueryInterrupts {
+    handles: std::sync::Mutex<Vec<Arc<duckdb::InterruptHandle>>>,
+    cancelled: std::sync::atomic::AtomicBool,
+}
+
+impl QueryInterrupts {
+    /// 中断正在执行的查询，之后该查询不能再获取新连接
+    pub fn interrupt(&self) {
+        self.cancelled.store(true, std::sync::atomic::Ordering::SeqCst);
+        for handle in self.handles.lock().unwrap().iter() {
+            handle.interrupt();
+        }
+    }
+
+    /// 查询是否已被取消
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    fn register(&self, handle: Arc<duckdb::InterruptHandle>) {
+        if self.is_cancelled() {
+            handle.interrupt();
+        }
+        self.handles.lock().unwrap().push(handle);
+    }
+}
+
+thread_local! {
+    /// 当前线程上执行的可取消查询
+    static CURRENT_QUERY: std::cell::RefCell<Option<Arc<QueryInterrupts>>> = const { std::cell::RefCell::new(None) };
+}
+
+/// 在当前线程上执行可取消的数据库操作，期间 get_connection 打开的连接均可通过 `interrupts` 中断
+pub fn run_interruptible<T>(interrupts: Arc<QueryInterrupts>, f: impl FnOnce() -> T) -> T {
+    /// 离开作用域（包括 panic）时清除当前线程的查询登记
+    struct Reset;
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            CURRENT_QUERY.with(|current| *current.borrow_mut() = None);
+        }
+    }
+
+    CURRENT_QUERY.with(|current| *current.borrow_mut() = Some(interrupts));
+    let _reset = Reset;
+    f()
 }
 
 /// DuckDB 数据库管理器
 pub struct DatabaseManager {
+    config: Arc<AppConfig>,
     db_path: String,
     known_tags: std::sync::Mutex<std::collections::HashSet<String>>,
+    /// 宽表现有列缓存（None 表示尚未从目录加载）
+    wide_columns: std::sync::Mutex<Option<std::collections::HashSet<String>>>,
+    /// 标签到宽表列名的映射缓存（None 表示尚未从 tag_columns 表加载）
+    tag_columns: std::sync::Mutex<Option<std::collections::HashMap<String, String>>>,
+    /// 共享的数据库实例连接，其余连接均由其克隆，避免同一进程重复打开文件
+    database: std::sync::Mutex<Option<Connection>>,
+    /// 当前同步周期的事务连接（begin_cycle 与 commit_cycle 之间有效）
+    cycle_conn: std::sync::Mutex<Option<Connection>>,
+    /// 写入变更日志到提交完成期间持有，保证日志序号按提交顺序分配
+    change_log_lock: std::sync::Mutex<()>,
+    /// 预计算的缩略趋势，按标签名索引
+    sparklines: std::sync::RwLock<Arc<std::collections::HashMap<String, Sparkline>>>,
+    /// 当前缩略趋势的窗口参数（小时数, 桶数）与刷新时间，None 表示下次刷新需重新计算整个窗口
+    sparkline_params: std::sync::Mutex<Option<(u32, u32, DateTime<Utc>)>>,
+    /// 死区模式下各标签最后写入的值与写入时间
+    last_written: std::sync::Mutex<LastWritten>,
+    /// 停滞检测与源时间戳模式下的数据源时间（最后写入的 DataTime 与过期标签）
+    staleness: StaleTracker,
+    /// 低延迟模式的延迟统计
+    latency: LatencyTracker,
+    /// 该同步配置的运行时功能开关
+    toggles: Arc<FeatureToggles>,
+}
+
+/// 同步周期句柄，由 begin_cycle 返回：写操作传入句柄时进入周期事务，其他写入方（API 写入、保持、开关审计等）不传句柄，使用独立连接
+#[must_use = "同步周期需要通过 commit_cycle 或 rollback_cycle 结束"]
+pub struct Cycle {
+    /// 本周期对已知标签集合的修改（标签, 是否为新增），回滚时逆序撤销
+    tag_changes: std::sync::Mutex<Vec<(String, bool)>>,
+    /// 本周期写入数据的时间范围，提交时记入变更日志
+    written: std::sync::Mutex<Option<(DateTime<Utc>, DateTime<Utc>)>>,
+}
+
+/// 写操作使用的连接：传入同步周期句柄时为周期事务连接，否则为独立连接
+enum WriteConnection<'a> {
+    Cycle(std::sync::MutexGuard<'a, Option<Connection>>),
+    Standalone(Connection),
+}
+
+impl std::ops::Deref for WriteConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        match self {
+            WriteConnection::Cycle(guard) => guard.as_ref().expect("周期事务连接不存在"),
+            WriteConnection::Standalone(conn) => conn,
+        }
+    }
 }
 
 impl DatabaseManager {
     /// 创建新的数据库管理器
-    pub fn new(db_path: String) -> Self {
+    pub fn new(config: Arc<AppConfig>) -> Self {
         Self { 
-            db_path,
+            db_path: config.db_file_path.clone(),
+            toggles: Arc::new(FeatureToggles::new(&config)),
+            config,
             known_tags: std::sync::Mutex::new(std::collections::HashSet::new()),
+            wide_columns: std::sync::Mutex::new(None),
+            tag_columns: std::sync::Mutex::new(None),
+            database: std::sync::Mutex::new(None),
+            cycle_conn: std::sync::Mutex::new(None),
+            change_log_lock: std::sync::Mutex::new(()),
+            sparklines: std::sync::RwLock::new(Arc::new(std::collections::HashMap::new())),
+            sparkline_params: std::sync::Mutex::new(None),
+            last_written: std::sync::Mutex::new(std::collections::HashMap::new()),
+            staleness: StaleTracker::default(),
+            latency: LatencyTracker::default(),
         }
     }
     
-    /// 初始化数据库（删除旧文件并创建新的数据库结构）
+    /// 初始化数据库
+    ///
+    /// 默认删除旧文件并创建新的数据库结构；启用 `persist_cache` 时复用已有文件，
+    /// 校验并修复表结构。
     pub fn initialize(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         info!("初始化数据库: {}", self.db_path);
-        
+
+        if self.config.persist_cache && Path::new(&self.db_path).exists() {
+            return self.open_existing();
+        }
+
         // 删除已存在的数据库文件
         if Path::new(&self.db_path).exists() {
             std::fs::remove_file(&self.db_path)?;
             info!("已删除旧的数据库文件");
         }
-        
+
         // 创建新的数据库连接
-        let conn = Connection::open(&self.db_path)?;
-        
-        // 只创建宽表
-        self.create_wide_table(&conn)?;
-        
-        // 创建索引
-        self.create_wide_table_index(&conn)?;
-        
+        let conn = self.get_connection()?;
+
+        match self.config.storage_mode {
+            StorageMode::Wide => {
+                // 只创建宽表
+                self.create_wide_table(&conn)?;
+                
+                // 创建索引
+                self.create_wide_table_index(&conn)?;
+                
+                // 新建的宽表只有时间列
+                *self.wide_columns.lock().unwrap() = Some(std::iter::once("DateTime".to_string()).collect());
+                *self.tag_columns.lock().unwrap() = Some(std::collections::HashMap::new());
+            }
+            StorageMode::Long => {
+                self.create_long_table(&conn)?;
+            }
+        }
+
+        self.create_checkpoint_table(&conn)?;
+        self.create_sync_log_table(&conn)?;
+        self.create_text_table(&conn)?;
+        self.create_quality_table(&conn)?;
+        self.create_tag_columns_table(&conn)?;
+        self.create_holds_table(&conn)?;
+        self.create_forecast_table(&conn)?;
+        self.create_latest_table(&conn)?;
+        self.create_tag_meta_table(&conn)?;
+        self.create_rollup_tables(&conn)?;
+        self.create_cold_partitions_table(&conn)?;
+        self.create_change_log_tables(&conn)?;
+        self.create_share_revocations_table(&conn)?;
+        self.create_spc_events_table(&conn)?;
+        self.create_deliveries_table(&conn)?;
+        self.create_size_cap_table(&conn)?;
+
         info!("数据库初始化完成");
         Ok(())
     }
     
-    /// 创建宽表格式的时序数据表
-    fn create_wide_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let sql = r#"
-            CREATE TABLE ts_wide (
-                DateTime TIMESTAMP PRIMARY KEY
-            )
-        "#;
-        
-        conn.execute(sql, [])?;
-        info!("已创建 ts_wide 宽表");
-        Ok(())
+    /// 当前存储模式对应的数据表名
+    fn data_table(&self) -> &'static str {
+        match self.config.storage_mode {
+            StorageMode::Wide => "ts_wide",
+            StorageMode::Long => "ts_long",
+        }
     }
     
-    /// 创建宽表索引
-    fn create_wide_table_index(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let sql = "CREATE INDEX idx_datetime ON ts_wide (DateTime)";
-        conn.execute(sql, [])?;
-        info!("已创建 idx_datetime 索引");
+    /// 复用已有数据库文件，校验并修复表结构
+    fn open_existing(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        info!("persist_cache 已启用，复用已有数据库文件");
+
+        let table = self.data_table();
+        let conn = self.get_connection()?;
+
+        let table_count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM information_schema.tables WHERE table_name = ?",
+            [table],
+            |row| row.get(0),
+        )?;
+
+        if table_count == 0 {
+            warn!("已有数据库文件中缺少 {} 表，重新创建", table);
+            match self.config.storage_mode {
+                StorageMode::Wide => self.create_wide_table(&conn)?,
+                StorageMode::Long => self.create_long_table(&conn)?,
+            }
+        }
+
+        // 校验时间列
+        let datetime_type: Option<String> = conn.query_row(
+            "SELECT data_type FROM information_schema.columns WHERE table_name = ? AND column_name = 'DateTime'",
+            [table],
+            |row| row.get(0),
+        ).ok();
+
+        match datetime_type.as_deref() {
+            Some("TIMESTAMP WITH TIME ZONE") => {}
+            Some("TIMESTAMP") => {
+                self.migrate_legacy_timestamps(&conn, table)?;
+            }
+            Some(other) => {
+                return Err(format!("{}.DateTime 列类型异常: {}，请删除数据库文件后重启", table, other).into());
+            }
+            None => {
+                return Err(format!("{} 表缺少 DateTime 列，请删除数据库文件后重启", table).into());
+            }
+        }
+
+        self.create_checkpoint_table(&conn)?;
+        self.create_sync_log_table(&conn)?;
+        self.create_text_table(&conn)?;
+        self.create_quality_table(&conn)?;
+        self.create_tag_columns_table(&conn)?;
+        self.create_holds_table(&conn)?;
+        self.create_forecast_table(&conn)?;
+        self.create_latest_table(&conn)?;
+        self.create_tag_meta_table(&conn)?;
+        self.create_rollup_tables(&conn)?;
+        self.create_cold_partitions_table(&conn)?;
+        self.create_change_log_tables(&conn)?;
+        self.create_share_revocations_table(&conn)?;
+        self.create_spc_events_table(&conn)?;
+        self.create_deliveries_table(&conn)?;
+        self.create_size_cap_table(&conn)?;
+
+        // 修复缺失的索引
+        match self.config.storage_mode {
+            StorageMode::Wide => {
+                conn.execute("CREATE INDEX IF NOT EXISTS idx_datetime ON ts_wide (DateTime)", [])?;
+                drop(conn);
+                
+                // 加载列缓存
+                let columns = self.load_wide_columns()?;
+                info!("已复用数据库文件，宽表共有 {} 列", columns.len());
+                *self.wide_columns.lock().unwrap() = Some(columns);
+            }
+            StorageMode::Long => {
+                conn.execute("CREATE INDEX IF NOT EXISTS idx_long_tag_datetime ON ts_long (TagName, DateTime)", [])?;
+                info!("已复用数据库文件 (窄表模式)");
+            }
+        }
+
+        self.rebuild_latest_values()?;
+
         Ok(())
     }
     
-    /// 获取数据库连接
-    pub fn get_connection(&self) -> Result<Connection, Box<dyn std::error::Error + Send + Sync>> {
-        Ok(Connection::open(&self.db_path)?)
-    }
-    
-    /// 重构历史数据为宽表格式并插入
-    pub fn convert_and_insert_wide(&self, records: &[TimeSeriesRecord]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        if records.is_empty() {
+    /// 最新值表为空时（由未维护该表的版本创建的数据库文件）从数据表重建
+    fn rebuild_latest_values(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let existing: i64 = conn.query_row("SELECT COUNT(*) FROM ts_latest", [], |row| row.get(0))?;
+        if existing > 0 {
             return Ok(());
         }
-        
-        // 按时间戳分组数据
-        let mut grouped_data: std::collections::HashMap<DateTime<Utc>, std::collections::HashMap<String, f64>> = std::collections::HashMap::new();
-        
-        for record in records {
-            grouped_data
-                .entry(record.timestamp)
-                .or_insert_with(std::collections::HashMap::new)
-                .insert(record.tag_name.clone(), record.value);
+
+        let selects: Vec<sql::Statement> = match self.config.storage_mode {
+            StorageMode::Long => vec![sql::Statement::new(
+                "SELECT TagName, max(DateTime), arg_max(Value, DateTime) FROM ts_long WHERE Value IS NOT NULL GROUP BY TagName",
+            )],
+            StorageMode::Wide => {
+                let wide_columns = self.wide_columns.lock().unwrap().clone().unwrap_or_default();
+                self.tag_columns()?
+                    .into_iter()
+                    .filter(|c| wide_columns.contains(&c.column))
+                    .map(|c| {
+                        let column = Dialect::DuckDb.quote(&c.column);
+                        sql::Statement {
+                            sql: format!(
+                                "SELECT CAST(? AS VARCHAR) AS TagName, max(DateTime), arg_max({}, DateTime) FROM ts_wide WHERE {} IS NOT NULL HAVING count(*) > 0",
+                                column, column
+                            ),
+                            params: vec![Param::from(c.tag)],
+                        }
+                    })
+                    .collect()
+            }
+        };
+
+        let mut rebuilt = 0;
+        for chunk in selects.chunks(100) {
+            let select = sql::Statement::union_all(chunk.iter().cloned());
+            rebuilt += conn.execute(&format!("INSERT INTO ts_latest {}", select.sql), duckdb::params_from_iter(select.params.iter()))?;
+        }
+        if rebuilt > 0 {
+            info!("已从数据表重建 {} 个标签的最新值", rebuilt);
         }
-        
-        // 获取所有唯一的标签名
-        let all_tags: std::collections::HashSet<String> = records.iter()
-            .map(|r| r.tag_name.clone())
-            .collect();
-        
-        // 动态添加列到宽表
-        self.add_columns_to_wide_table(&all_tags)?;
-        
-        // 插入宽表数据
-        self.insert_wide_data(&grouped_data, &all_tags)?;
-        
-        debug!("重构并插入 {} 个时间点的历史数据到宽表", grouped_data.len());
         Ok(())
     }
     
-    /// 将TagDatabase的最新数据拼接到宽表
-    pub fn append_latest_tagdb_data(&self, records: &[TimeSeriesRecord]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        if records.is_empty() {
-            return Ok(());
-        }
-        
-        // 使用北京时间作为时间戳 (UTC+8)
-        let current_time = Utc::now() + chrono::Duration::hours(8);
-        
-        // 将所有记录按当前时间分组
-        let mut tag_values = std::collections::HashMap::new();
-        for record in records {
-            tag_values.insert(record.tag_name.clone(), record.value);
-        }
-        
-        // 获取所有标签名
-        let all_tags: std::collections::HashSet<String> = records.iter()
-            .map(|r| r.tag_name.clone())
-            .collect();
-        
-        // 动态添加列到宽表
-        self.add_columns_to_wide_table(&all_tags)?;
-        
-        // 创建分组数据
-        let mut grouped_data = std::collections::HashMap::new();
-        grouped_data.insert(current_time, tag_values);
-        
-        // 插入宽表数据
-        self.insert_wide_data(&grouped_data, &all_tags)?;
-        
-        debug!("拼接 {} 个标签的最新数据到宽表，时间戳: {}", records.len(), current_time);
+    /// 创建文本值表（字符串标签），两种存储模式共用窄表结构
+    fn create_text_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS ts_text (
+                DateTime TIMESTAMPTZ NOT NULL,
+                TagName VARCHAR NOT NULL,
+                Value VARCHAR,
+                PRIMARY KEY (DateTime, TagName)
+            )",
+            [],
+        )?;
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_text_tag_datetime ON ts_text (TagName, DateTime)", [])?;
         Ok(())
     }
     
-    /// 处理标签变化（加点/少点）
-    pub fn handle_tag_changes(&self, tag_changes: &crate::data_source::TagChanges) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        // 处理新增标签（加点）
-        if !tag_changes.added_tags.is_empty() {
-            info!("处理新增标签: {:?}", tag_changes.added_tags);
-            let new_tags: std::collections::HashSet<String> = tag_changes.added_tags.iter().cloned().collect();
-            self.add_columns_to_wide_table(&new_tags)?;
-            
-            // 更新已知标签集合
-            {
-                let mut known_tags = self.known_tags.lock().unwrap();
-                for tag in &tag_changes.added_tags {
-                    known_tags.insert(tag.clone());
-                }
-            }
-        }
-        
-        // 处理删除标签（少点）
-        if !tag_changes.removed_tags.is_empty() {
-            warn!("检测到删除的标签: {:?}", tag_changes.removed_tags);
-            
-            // 对于删除的标签，我们可以选择：
-            // 1. 保留列但标记为已删除（推荐，保持数据完整性）
-            // 2. 物理删除列（可能导致数据丢失）
-            // 这里采用方案1，只是从已知标签集合中移除，但保留数据库列
-            {
-                let mut known_tags = self.known_tags.lock().unwrap();
-                for tag in &tag_changes.removed_tags {
-                    known_tags.remove(tag);
-                }
-            }
-            
-            // 记录删除的标签信息，便于后续处理
-            info!("已从已知标签集合中移除: {:?}，但保留历史数据列", tag_changes.removed_tags);
-        }
-        
+    /// 创建标签列名映射表（宽表模式下标签到列名的分配，含列名冲突时的消歧后缀）
+    fn create_tag_columns_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS tag_columns (
+                TagName VARCHAR PRIMARY KEY,
+                ColumnName VARCHAR NOT NULL
+            )",
+            [],
+        )?;
         Ok(())
     }
     
-    /// 获取当前已知的标签列表
-    pub fn get_known_tags(&self) -> std::collections::HashSet<String> {
-        self.known_tags.lock().unwrap().clone()
+    /// 创建数据质量表
+    fn create_quality_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS ts_quality (
+                DateTime TIMESTAMPTZ NOT NULL,
+                TagName VARCHAR NOT NULL,
+                Quality VARCHAR,
+                PRIMARY KEY (DateTime, TagName)
+            )",
+            [],
+        )?;
+        Ok(())
     }
     
-    /// 清理已删除标签的空值数据（可选的维护操作）
-    pub fn cleanup_removed_tag_data(&self, removed_tags: &[String]) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
-        if removed_tags.is_empty() {
-            return Ok(0);
-        }
-        
-        let conn = self.get_connection()?;
-        let mut total_cleaned = 0;
-        
-        for tag in removed_tags {
-            let safe_column_name = self.sanitize_column_name(tag);
-            
-            // 检查列是否存在
-            let column_exists_sql = format!(
-                "SELECT COUNT(*) FROM pragma_table_info('ts_wide') WHERE name = '{}'",
-                safe_column_name
+    /// 创建同步检查点表（单行）
+    fn create_checkpoint_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_checkpoint (
+                Id INTEGER PRIMARY KEY,
+                LastSynced TIMESTAMPTZ NOT NULL,
+                KnownTags VARCHAR NOT NULL,
+                UpdatedAt TIMESTAMPTZ DEFAULT current_timestamp
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+    
+    /// 创建保留期豁免表
+    fn create_holds_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS retention_holds (
+                Id BIGINT PRIMARY KEY,
+                TagName VARCHAR,
+                FromTime TIMESTAMPTZ,
+                ToTime TIMESTAMPTZ,
+                Reason VARCHAR NOT NULL,
+                CreatedBy VARCHAR NOT NULL,
+                CreatedAt TIMESTAMPTZ NOT NULL
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+    
+    /// 创建趋势预测表，每个标签只保留最近一次预测
+    fn create_forecast_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS ts_forecast (
+                DateTime TIMESTAMPTZ NOT NULL,
+                TagName VARCHAR NOT NULL,
+                Forecast DOUBLE NOT NULL,
+                IssuedAt TIMESTAMPTZ NOT NULL,
+                PRIMARY KEY (DateTime, TagName)
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+    
+    /// 创建最新值表，每个标签一行，随写入维护，最新值查询不必扫描数据表
+    fn create_latest_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS ts_latest (
+                TagName VARCHAR PRIMARY KEY,
+                DateTime TIMESTAMPTZ NOT NULL,
+                Value DOUBLE NOT NULL
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+    
+    /// 创建标签元数据表（每个同步周期从 TagDatabase 整体替换）
+    fn create_tag_meta_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS tag_meta (
+                TagName VARCHAR PRIMARY KEY,
+                Unit VARCHAR,
+                Description VARCHAR,
+                MinValue DOUBLE,
+                MaxValue DOUBLE,
+                InOutFlag VARCHAR
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+    
+    /// 创建复制变更日志表与副本拉取位置表（单行）
+    fn create_change_log_tables(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS change_log (
+                Seq BIGINT PRIMARY KEY,
+                DataFrom TIMESTAMPTZ NOT NULL,
+                DataTo TIMESTAMPTZ NOT NULL,
+                LoggedAt TIMESTAMPTZ NOT NULL
             );
-            
-            let column_count: i64 = conn.query_row(&column_exists_sql, [], |row| row.get(0))?;
-            
-            if column_count > 0 {
-                // 将该列的所有值设为NULL（软删除）
-                let update_sql = format!(
-                    "UPDATE ts_wide SET {} = NULL",
-                    safe_column_name
-                );
-                
-                let updated_rows = conn.execute(&update_sql, [])?;
-                total_cleaned += updated_rows;
-                
-                info!("已清理标签 {} 的 {} 条数据记录", tag, updated_rows);
+            CREATE TABLE IF NOT EXISTS replica_position (
+                Id INTEGER PRIMARY KEY,
+                Seq BIGINT NOT NULL
+            );"
+        )?;
+        Ok(())
+    }
+    
+    /// 创建分享链接吊销表，过期后的记录在下次吊销时清除
+    fn create_share_revocations_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS share_revocations (
+                Id VARCHAR PRIMARY KEY,
+                RevokedBy VARCHAR NOT NULL,
+                RevokedAt TIMESTAMPTZ NOT NULL,
+                Expires TIMESTAMPTZ NOT NULL
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+    
+    /// 创建大小上限清理状态表（单行），记录删除数据后已用空间仍超过上限的情况
+    fn create_size_cap_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS size_cap_stall (
+                Id INTEGER PRIMARY KEY,
+                CapBytes UBIGINT NOT NULL,
+                UsedBytes UBIGINT NOT NULL,
+                StalledAt TIMESTAMPTZ NOT NULL
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+    
+    /// 创建 SPC 违规事件表
+    fn create_spc_events_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS spc_events (
+                DateTime TIMESTAMPTZ NOT NULL,
+                TagName VARCHAR NOT NULL,
+                Rule VARCHAR NOT NULL,
+                Value DOUBLE,
+                Mean DOUBLE,
+                Sigma DOUBLE,
+                CreatedAt TIMESTAMPTZ DEFAULT current_timestamp
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+    
+    /// 创建 REST 推送投递记录表
+    fn create_deliveries_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS integration_deliveries (
+                Endpoint VARCHAR NOT NULL,
+                WindowEnd TIMESTAMPTZ NOT NULL,
+                Attempts INTEGER NOT NULL,
+                StatusCode INTEGER,
+                Success BOOLEAN NOT NULL,
+                Error VARCHAR,
+                CreatedAt TIMESTAMPTZ DEFAULT current_timestamp
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+    
+    /// 创建冷存储目录表，记录归档写出的每个 Parquet 分区文件
+    fn create_cold_partitions_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS cold_partitions (
+                Path VARCHAR PRIMARY KEY,
+                TableName VARCHAR NOT NULL,
+                Day DATE NOT NULL,
+                MinTime TIMESTAMPTZ NOT NULL,
+                MaxTime TIMESTAMPTZ NOT NULL,
+                RowCount BIGINT NOT NULL,
+                CreatedAt TIMESTAMPTZ NOT NULL
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+    
+    /// 创建分级保留的汇总表（1 分钟与 1 小时）及各表已汇总到的截止时间
+    fn create_rollup_tables(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS rollup_watermarks (
+                TableName VARCHAR PRIMARY KEY,
+                Cutoff TIMESTAMPTZ NOT NULL
+            )",
+            [],
+        )?;
+        for table in ROLLUP_TABLES {
+            conn.execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} (
+                        TagName VARCHAR NOT NULL,
+                        DateTime TIMESTAMPTZ NOT NULL,
+                        Avg DOUBLE,
+                        Min DOUBLE,
+                        Max DOUBLE,
+                        Count BIGINT NOT NULL,
+                        TwAvg DOUBLE,
+                        OnSecs DOUBLE,
+                        PRIMARY KEY (TagName, DateTime)
+                    )",
+                    table
+                ),
+                [],
+            )?;
+            // 早期版本创建的汇总表没有时间加权列
+            for column in ["TwAvg", "OnSecs"] {
+                conn.execute(&format!("ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} DOUBLE", table, column), [])?;
             }
         }
-        
-        Ok(total_cleaned)
+        Ok(())
     }
     
-    /// 删除给定时间以前的数据
-    pub fn delete_data_before_time(&self, cutoff_time: DateTime<Utc>) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
-        let conn = self.get_connection()?;
-        
-        let sql = "DELETE FROM ts_wide WHERE DateTime < ?";
-        let cutoff_str = cutoff_time.format("%Y-%m-%d %H:%M:%S%.3f").to_string();
-        
-        let deleted_rows = conn.execute(sql, [&cutoff_str])?;
-        
-        if deleted_rows > 0 {
-            info!("删除了 {} 条给定时间前的数据，截止时间: {}", deleted_rows, cutoff_str);
-        }
-        
-        Ok(deleted_rows)
+    /// 创建同步周期统计表
+    fn create_sync_log_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_log (
+                StartedAt TIMESTAMPTZ NOT NULL,
+                FinishedAt TIMESTAMPTZ NOT NULL,
+                RowsFetched BIGINT NOT NULL,
+                RowsWritten BIGINT NOT NULL,
+                NewColumns INTEGER NOT NULL,
+                Error VARCHAR
+            )",
+            [],
+        )?;
+        Ok(())
     }
     
-    /// 插入宽表数据（批量优化版本）
-    fn insert_wide_data(
-        &self,
-        grouped_data: &std::collections::HashMap<DateTime<Utc>, std::collections::HashMap<String, f64>>,
-        all_tags: &std::collections::HashSet<String>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        if grouped_data.is_empty() {
-            return Ok(());
-        }
+    /// 记录一次同步周期的统计，并删除超过保留期的旧记录
+    ///
+    /// 使用独立连接写入，不受周期事务回滚影响。
+    pub fn record_sync_cycle(&self, stats: &SyncCycleStats) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // sync_log 保留天数
+        const SYNC_LOG_RETENTION_DAYS: i64 = 30;
 
         let conn = self.get_connection()?;
-        
-        // 构建列名列表
-        let mut columns = vec!["DateTime".to_string()];
-        for tag in all_tags {
-            let safe_column_name = self.sanitize_column_name(tag);
-            columns.push(safe_column_name);
-        }
-        
-        let columns_str = columns.join(", ");
-        let placeholder = format!("({})", vec!["?"; columns.len()].join(", "));
-        
-        // 将数据转换为向量以便分批处理
-        let mut data_rows: Vec<_> = grouped_data.iter().collect();
-        data_rows.sort_by_key(|(timestamp, _)| *timestamp);
-        
-        // 分批插入数据
-        const BATCH_SIZE: usize = 1000;
-        for chunk in data_rows.chunks(BATCH_SIZE) {
-            // 构建批量插入SQL
-            let placeholders = vec![placeholder.clone(); chunk.len()].join(", ");
-            let sql = format!(
-                "INSERT OR REPLACE INTO ts_wide ({}) VALUES {}",
-                columns_str, placeholders
-            );
+        conn.execute(
+            "INSERT INTO sync_log (StartedAt, FinishedAt, RowsFetched, RowsWritten, NewColumns, Error) VALUES (?, ?, ?, ?, ?, ?)",
+            duckdb::params![
+                format_timestamp(&stats.started_at),
+                format_timestamp(&stats.finished_at),
+                stats.rows_fetched as i64,
+                stats.rows_written as i64,
+                stats.new_columns as i64,
+                stats.error,
+            ],
+        )?;
+
+        let cutoff = Utc::now() - chrono::Duration::days(SYNC_LOG_RETENTION_DAYS);
+        conn.execute("DELETE FROM sync_log WHERE StartedAt < ?", [format_timestamp(&cutoff)])?;
+        Ok(())
+    }
+    
+    /// 按时间倒序读取最近的同步周期统计
+    pub fn recent_sync_cycles(&self, limit: usize) -> Result<Vec<SyncCycleStats>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT CAST(StartedAt AS TIMESTAMP), CAST(FinishedAt AS TIMESTAMP), RowsFetched, RowsWritten, NewColumns, Error
+             FROM sync_log ORDER BY StartedAt DESC LIMIT ?",
+        )?;
+        let rows = stmt.query_map([limit as i64], |row| {
+            Ok(SyncCycleStats {
+                started_at: row.get::<_, chrono::NaiveDateTime>(0)?.and_utc(),
+                finished_at: row.get::<_, chrono::NaiveDateTime>(1)?.and_utc(),
+                rows_fetched: row.get::<_, i64>(2)? as usize,
+                rows_written: row.get::<_, i64>(3)? as usize,
+                new_columns: row.get::<_, i64>(4)? as usize,
+                error: row.get(5)?,
+            })
+        })?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+    
+    /// 保存同步检查点：最后成功同步的时间与当前已知标签基线
+    ///
+    /// 同步周期进行中时写入周期事务，与数据一同提交。
+    pub fn save_checkpoint(&self, last_synced: DateTime<Utc>, cycle: Option<&Cycle>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut known_tags: Vec<String> = self.get_known_tags().into_iter().collect();
+        known_tags.sort();
+        let known_tags_json = serde_json::to_string(&known_tags)?;
+
+        let conn = self.write_connection(cycle)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO sync_checkpoint (Id, LastSynced, KnownTags, UpdatedAt) VALUES (1, ?, ?, current_timestamp)",
+            [format_timestamp(&last_synced), known_tags_json],
+        )?;
+
+        debug!("已保存同步检查点: {}，已知标签 {} 个", last_synced, known_tags.len());
+        Ok(())
+    }
+    
+    /// 读取同步检查点，不存在时返回 None
+    pub fn load_checkpoint(&self) -> Result<Option<SyncCheckpoint>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let row: Option<(chrono::NaiveDateTime, String)> = conn.query_row(
+            "SELECT CAST(LastSynced AS TIMESTAMP), KnownTags FROM sync_checkpoint WHERE Id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).ok();
+
+        let Some((last_synced, known_tags_json)) = row else {
+            return Ok(None);
+        };
+
+        Ok(Some(SyncCheckpoint {
+            last_synced: last_synced.and_utc(),
+            known_tags: serde_json::from_str(&known_tags_json)?,
+        }))
+    }
+    
+    /// 用检查点中的标签基线恢复已知标签集合
+    pub fn restore_known_tags(&self, tags: &[String]) {
+        let mut known_tags = self.known_tags.lock().unwrap();
+        known_tags.extend(tags.iter().cloned());
+    }
+    
+    /// 将旧版本的 TIMESTAMP 列迁移为 TIMESTAMPTZ
+    ///
+    /// 旧文件中的时间按数据源本地时间（source_timezone_offset_hours）存储，
+    /// 迁移时换算为 UTC。整个迁移在一个事务内完成。
+    fn migrate_legacy_timestamps(&self, conn: &Connection, table: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let offset_hours = self.config.source_timezone_offset_hours;
+        warn!("检测到旧版本 TIMESTAMP 时间列，开始迁移 {} 为 TIMESTAMPTZ (按 UTC{:+} 换算)", table, offset_hours);
+
+        let legacy = format!("{}_legacy", table);
+
+        conn.execute_batch("BEGIN TRANSACTION")?;
+        let result = (|| -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            conn.execute_batch(&format!(
+                "DROP INDEX IF EXISTS idx_datetime;
+                 DROP INDEX IF EXISTS idx_long_tag_datetime;
+                 ALTER TABLE {} RENAME TO {};",
+                table, legacy
+            ))?;
             
-            // 准备参数
-            let mut params = Vec::new();
-            for (timestamp, tag_values) in chunk {
-                // 添加时间戳
-                params.push(timestamp.format("%Y-%m-%d %H:%M:%S%.3f").to_string());
-                
-                // 添加标签值
-                for tag in all_tags {
-                    let value = tag_values.get(tag).unwrap_or(&0.0);
-                    params.push(value.to_string());
+            match self.config.storage_mode {
+                StorageMode::Wide => {
+                    self.create_wide_table(conn)?;
+                    
+                    // 按旧表结构补齐标签列
+                    let mut stmt = conn.prepare(&format!("DESCRIBE {}", legacy))?;
+                    let columns = stmt.query_map([], |row| {
+                        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                    })?.collect::<Result<Vec<_>, _>>()?;
+                    
+                    for (name, column_type) in columns {
+                        if name != "DateTime" {
+                            conn.execute(&format!("ALTER TABLE ts_wide ADD COLUMN {} {}", Dialect::DuckDb.quote(&name), column_type), [])?;
+                        }
+                    }
                 }
+                StorageMode::Long => self.create_long_table(conn)?,
             }
             
-            // 执行批量插入
-            conn.execute(&sql, duckdb::params_from_iter(params.iter()))?;
+            let migrated = conn.execute(&format!(
+                "INSERT INTO {} BY NAME
+                 SELECT * REPLACE (CAST(DateTime - INTERVAL ({}) HOUR AS TIMESTAMPTZ) AS DateTime)
+                 FROM {}",
+                table, offset_hours, legacy
+            ), [])?;
+            
+            conn.execute(&format!("DROP TABLE {}", legacy), [])?;
+            info!("已迁移 {} 行到 TIMESTAMPTZ", migrated);
+            Ok(())
+        })();
+
+        // 表被重建，无论成功与否都需重新加载列缓存
+        self.invalidate_schema_cache("迁移时间列");
+
+        match result {
+            Ok(()) => {
+                conn.execute_batch("COMMIT")?;
+                Ok(())
+            }
+            Err(e) => {
+                let _ = conn.execute_batch("ROLLBACK");
+                Err(format!("迁移时间列失败: {}", e).into())
+            }
         }
-        
+    }
+    
+    /// 创建窄表格式的时序数据表及索引
+    fn create_long_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let sql = r#"
+            CREATE TABLE IF NOT EXISTS ts_long (
+                DateTime TIMESTAMPTZ NOT NULL,
+                TagName VARCHAR NOT NULL,
+                Value DOUBLE,
+                PRIMARY KEY (DateTime, TagName)
+            )
+        "#;
+
+        conn.execute(sql, [])?;
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_long_tag_datetime ON ts_long (TagName, DateTime)", [])?;
+        info!("已创建 ts_long 窄表及 idx_long_tag_datetime 索引");
         Ok(())
     }
     
-    /// 动态添加列到宽表
-    fn add_columns_to_wide_table(&self, tags: &std::collections::HashSet<String>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let conn = self.get_connection()?;
-        
-        // 获取现有列 - 使用DuckDB的DESCRIBE语法
-        let mut existing_columns = std::collections::HashSet::new();
-        let mut stmt = conn.prepare("DESCRIBE ts_wide")?;
-        let rows = stmt.query_map([], |row| {
-            let column_name: String = row.get(0)?; // DuckDB的DESCRIBE返回列名在第0列
-            Ok(column_name)
-        })?;
-        
-        for row in rows {
-            existing_columns.insert(row?);
+    /// 创建宽表格式的时序数据表
+    fn create_wide_table(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let sql = r#"
+            CREATE TABLE ts_wide (
+                DateTime TIMESTAMPTZ PRIMARY KEY
+            )
+        "#;
+
+        conn.execute(sql, [])?;
+        info!("已创建 ts_wide 宽表");
+        Ok(())
+    }
+    
+    /// 创建宽表索引
+    fn create_wide_table_index(&self, conn: &Connection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let sql = "CREATE INDEX idx_datetime ON ts_wide (DateTime)";
+        conn.execute(sql, [])?;
+        info!("已创建 idx_datetime 索引");
+        Ok(())
+    }
+    
+    /// 获取数据库连接
+    ///
+    /// 首次调用时打开数据库文件，之后的连接均克隆自同一数据库实例。
+    pub fn get_connection(&self) -> Result<Connection, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = {
+            let mut database = self.database.lock().unwrap();
+            if database.is_none() {
+                let conn = Connection::open(&self.db_path)?;
+                // 不自动安装或加载扩展（Parquet 已静态链接），SQL 透传因此无法经 postgres、mysql、sqlite 等扩展访问外部；
+                // 这两项设置与 enable_external_access 都只能在整个数据库实例上设置，后者会同时禁止导出与归档写文件，不在此关闭
+                conn.execute_batch("SET autoinstall_known_extensions = false; SET autoload_known_extensions = false")?;
+                *database = Some(conn);
+            }
+            database.as_ref().unwrap().try_clone()?
+        };
+
+        // 在可取消查询中打开的连接登记中断句柄
+        let cancelled = CURRENT_QUERY.with(|current| {
+            current.borrow().as_ref().map(|query| {
+                query.register(conn.interrupt_handle());
+                query.is_cancelled()
+            })
+        });
+        if cancelled == Some(true) {
+            return Err("查询已取消".into());
         }
-        
-        // 更新已知标签集合
-        {
-            let mut known_tags = self.known_tags.lock().unwrap();
-            for tag in tags {
-                known_tags.insert(tag.clone());
+
+        Ok(conn)
+    }
+    
+    /// 获取写连接：传入同步周期句柄时写入周期事务，否则使用独立连接，不受周期回滚影响
+    fn write_connection(&self, cycle: Option<&Cycle>) -> Result<WriteConnection<'_>, Box<dyn std::error::Error + Send + Sync>> {
+        if cycle.is_some() {
+            let cycle_conn = self.cycle_conn.lock().unwrap();
+            if cycle_conn.is_none() {
+                // 周期事务已被停机流程回滚，不能退回独立连接写入，否则这部分写入会脱离周期提交
+                return Err("同步周期事务已结束".into());
             }
+            return Ok(WriteConnection::Cycle(cycle_conn));
         }
-        
-        // 添加新列
+        Ok(WriteConnection::Standalone(self.get_connection()?))
+    }
+    
+    /// 将标签加入已知标签集合，周期内的新增在回滚时撤销
+    fn insert_known_tags<'a>(&self, tags: impl IntoIterator<Item = &'a String>, cycle: Option<&Cycle>) {
+        let mut known_tags = self.known_tags.lock().unwrap();
+        let mut changes = cycle.map(|cycle| cycle.tag_changes.lock().unwrap());
         for tag in tags {
-            let safe_column_name = self.sanitize_column_name(tag);
-            if !existing_columns.contains(&safe_column_name) {
-                let sql = format!("ALTER TABLE ts_wide ADD COLUMN {} DOUBLE", safe_column_name);
-                conn.execute(&sql, [])?;
-                debug!("添加新列: {}", safe_column_name);
+            if known_tags.insert(tag.clone()) && let Some(changes) = changes.as_mut() {
+                changes.push((tag.clone(), true));
             }
         }
-        
-        Ok(())
     }
     
-    /// 清理列名，确保SQL安全
-    fn sanitize_column_name(&self, tag_name: &str) -> String {
-        let mut result = tag_name
-            .chars()
-            .map(|c| if c.is_alphanumeric() || c == '_' { c } else { '_' })
-            .collect::<String>()
-            .trim_matches('_')
-            .to_string();
-        
-        // 确保列名不以数字开头
-        if result.chars().next().map_or(false, |c| c.is_ascii_digit()) {
-            result = format!("tag_{}", result);
+    /// 从已知标签集合移除标签，周期内的移除在回滚时撤销
+    fn remove_known_tags<'a>(&self, tags: impl IntoIterator<Item = &'a String>, cycle: Option<&Cycle>) {
+        let mut known_tags = self.known_tags.lock().unwrap();
+        let mut changes = cycle.map(|cycle| cycle.tag_changes.lock().unwrap());
+        for tag in tags {
+            if known_tags.remove(tag) && let Some(changes) = changes.as_mut() {
+                changes.push((tag.clone(), false));
+            }
         }
-        
-        // 确保列名不为空
-        if result.is_empty() {
-            result = "unknown_tag".to_string();
+    }
+    
+    /// 结束同步周期：`undo` 为真时撤销本周期对已知标签集合的修改
+    fn end_cycle(&self, cycle: Cycle, undo: bool) {
+        let changes = cycle.tag_changes.into_inner().unwrap();
+        if undo {
+            let mut known_tags = self.known_tags.lock().unwrap();
+            for (tag, added) in changes.into_iter().rev() {
+                if added {
+                    known_tags.remove(&tag);
+                } else {
+                    known_tags.insert(tag);
+                }
+            }
         }
-        
-        result
     }
     
+    /// 重写数据表以回收空间并恢复扫描性能：宽表模式删除已移除标签（不在已知标签与 tag_meta 中）的全空列及其列名映射，
+    /// 两种模式都按时间（窄表按标签与时间）重新排序写入，最后执行 CHECKPOINT
+    ///
+    /// 重写在单个事务内完成，失败时数据表保持不变。
+    pub fn compact(&self) -> Result<CompactReport, Box<dyn std::error::Error + Send + Sync>> {
+        let size_before = self.file_size()?;
+        let live_columns = match self.config.storage_mode {
+            StorageMode::Wide => self.live_columns()?,
+            StorageMode::Long => std::collections::HashSet::new(),
+        };
+        let conn = self.get_connection()?;
+
+        conn.execute_batch("BEGIN TRANSACTION")?;
+        let result = match self.config.storage_mode {
+            StorageMode::Wide => self.compact_wide(&conn, &live_columns),
+            StorageMode::Long => self.compact_long(&conn).map(|rows| (Vec::new(), rows)),
+        };
+        let (dropped_columns, rows) = match result {
+            Ok(result) => {
+                conn.execute_batch("COMMIT")?;
+                result
+            }
+            Err(e) => {
+                conn.execute_batch("ROLLBACK")?;
+                return Err(e);
+            }
+        };
+        drop(conn);
+        self.invalidate_schema_cache("数据表压缩");
 
+        let (_, size_after) = self.checkpoint()?;
+        info!("数据表压缩完成: 重写 {} 行，删除 {} 个全空列", rows, dropped_columns.len());
+        Ok(CompactReport { dropped_columns, rows, size_before, size_after })
+    }
     
-    /// 根据标签删除最旧的数据
-    pub fn delete_oldest_by_tag(&self, tag_name: &str, keep_count: usize) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
-        let conn = self.get_connection()?;
-        let safe_column_name = self.sanitize_column_name(tag_name);
-        
-        // 获取该标签的总记录数
-        let count_sql = format!(
-            "SELECT COUNT(*) FROM ts_wide WHERE {} IS NOT NULL",
-            safe_column_name
-        );
-        let total_count: i64 = conn.query_row(&count_sql, [], |row| row.get(0))?;
-        
-        if total_count <= keep_count as i64 {
-            return Ok(0); // 不需要删除
+    /// 按标签与时间重新排序写入 ts_long，返回行数
+    fn compact_long(&self, conn: &Connection) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        conn.execute_batch(
+            "CREATE TEMP TABLE ts_long_sorted AS SELECT * FROM ts_long ORDER BY TagName, DateTime;
+             DELETE FROM ts_long;
+             INSERT INTO ts_long SELECT * FROM ts_long_sorted ORDER BY TagName, DateTime;
+             DROP TABLE ts_long_sorted;"
+        )?;
+        let rows: i64 = conn.query_row("SELECT COUNT(*) FROM ts_long", [], |row| row.get(0))?;
+        Ok(rows as usize)
+    }
+    
+    /// 仍在使用的宽表列名（小写）：已知标签（内存中与同步检查点中）及 tag_meta 中标签对应的列
+    ///
+    /// 这些标签可能只是暂时没有数据（保留期清理后尚未上报、或值被清空），其列与映射不能删除。
+    fn live_columns(&self) -> Result<std::collections::HashSet<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut tags = self.get_known_tags();
+        if let Some(checkpoint) = self.load_checkpoint()? {
+            tags.extend(checkpoint.known_tags);
         }
-        
-        let delete_count = total_count - keep_count as i64;
-        
-        // 删除最旧的记录（将对应列设为NULL）
-        let delete_sql = format!(
-            "UPDATE ts_wide SET {} = NULL WHERE DateTime IN (
-                SELECT DateTime FROM ts_wide 
-                WHERE {} IS NOT NULL 
-                ORDER BY DateTime ASC 
-                LIMIT {}
-            )",
-            safe_column_name, safe_column_name, delete_count
-        );
-        
-        let updated_rows = conn.execute(&delete_sql, [])?;
-        
-        if updated_rows > 0 {
-            info!("标签 {} 删除了 {} 条最旧数据", tag_name, updated_rows);
+        tags.extend(self.get_tag_meta(&[])?.into_iter().map(|meta| meta.tag_name));
+
+        let mut columns = std::collections::HashSet::new();
+        for tag in &tags {
+            if let Some(column) = self.column_for(tag)? {
+                columns.insert(column.to_lowercase());
+            }
         }
-        
-        Ok(updated_rows)
+        Ok(columns)
     }
     
-    /// 删除指定天数前的数据以维持数据库大小
-    pub fn delete_data_older_than_days(&self, days: u32) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
-        let conn = self.get_connection()?;
-        
-        // 计算截止时间
-        let cutoff_time = Utc::now() - chrono::Duration::days(days as i64);
-        let cutoff_str = cutoff_time.format("%Y-%m-%d %H:%M:%S").to_string();
-        
-        // 删除ts_wide表中的旧数据
-        let delete_sql = "DELETE FROM ts_wide WHERE DateTime < ?";
-        let deleted_rows = conn.execute(delete_sql, [&cutoff_str])?;
-        
-        if deleted_rows > 0 {
-            info!("删除了{}天前的数据: {}条", days, deleted_rows);
+    /// 以保留的列重建 ts_wide（按时间排序写入），返回删除的列与行数；
+    /// 只删除全空且不属于 `live_columns`（已知标签或 tag_meta 中的标签）的列
+    fn compact_wide(
+        &self,
+        conn: &Connection,
+        live_columns: &std::collections::HashSet<String>,
+    ) -> Result<(Vec<String>, usize), Box<dyn std::error::Error + Send + Sync>> {
+        let mut stmt = conn.prepare(
+            "SELECT column_name, data_type FROM information_schema.columns \
+             WHERE table_name = 'ts_wide' AND column_name <> 'DateTime' ORDER BY ordinal_position"
+        )?;
+        let columns: Vec<(String, String)> = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+
+        let mut kept = Vec::new();
+        let mut dropped = Vec::new();
+        if !columns.is_empty() {
+            let counts = columns.iter()
+                .map(|(name, _)| format!("COUNT({})", Dialect::DuckDb.quote(name)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let counts: Vec<i64> = conn.query_row(&format!("SELECT {} FROM ts_wide", counts), [], |row| {
+                (0..columns.len()).map(|i| row.get(i)).collect()
+            })?;
+            for ((name, data_type), count) in columns.into_iter().zip(counts) {
+                if count == 0 && !live_columns.contains(&name.to_lowercase()) {
+                    dropped.push(name);
+                } else {
+                    kept.push((name, data_type));
+                }
+            }
         }
-        
-        Ok(deleted_rows)
+
+        let definitions: String = kept.iter()
+            .map(|(name, data_type)| format!(", {} {}", Dialect::DuckDb.quote(name), data_type))
+            .collect();
+        let selected: String = kept.iter()
+            .map(|(name, _)| format!(", {}", Dialect::DuckDb.quote(name)))
+            .collect();
+        conn.execute_batch(&format!(
+            "CREATE TABLE ts_wide_compact (DateTime TIMESTAMPTZ PRIMARY KEY{definitions});
+             INSERT INTO ts_wide_compact SELECT DateTime{selected} FROM ts_wide ORDER BY DateTime;
+             DROP TABLE ts_wide;
+             ALTER TABLE ts_wide_compact RENAME TO ts_wide;
+             CREATE INDEX idx_datetime ON ts_wide (DateTime);"
+        ))?;
+        for column in &dropped {
+            conn.execute("DELETE FROM tag_columns WHERE ColumnName = ?", [column])?;
+            debug!("已删除全空列: {}", column);
+        }
+
+        let rows: i64 = conn.query_row("SELECT COUNT(*) FROM ts_wide", [], |row| row.get(0))?;
+        Ok((dropped, rows as usize))
     }
     
-    /// 获取数据库中的记录总数
-    pub fn get_record_count(&self) -> Result<i64, Box<dyn std::error::Error + Send + Sync>> {
+    /// 记录写入数据的时间范围：周期内并入周期的范围，提交时记入变更日志；否则直接记入变更日志
+    fn log_change(&self, records: &[TimeSeriesRecord], cycle: Option<&Cycle>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let Some(from) = records.iter().map(|r| r.timestamp).min() else {
+            return Ok(());
+        };
+        let to = records.iter().map(|r| r.timestamp).max().unwrap_or(from);
+
+        if let Some(cycle) = cycle {
+            let mut written = cycle.written.lock().unwrap();
+            *written = Some(match *written {
+                Some((start, end)) => (start.min(from), end.max(to)),
+                None => (from, to),
+            });
+            return Ok(());
+        }
+
         let conn = self.get_connection()?;
-        let mut stmt = conn.prepare("SELECT COUNT(*) FROM ts_wide")?;
-        let count: i64 = stmt.query_row([], |row| row.get(0))?;
-        Ok(count)
+        let _change_log = self.change_log_lock.lock().unwrap();
+        self.append_change_log(&conn, (from, to))
     }
     
-    /// 获取最新的时间戳
-    pub fn get_latest_timestamp(&self) -> Result<Option<DateTime<Utc>>, Box<dyn std::error::Error + Send + Sync>> {
+    /// 追加一条变更日志并清理超过数据窗口的旧日志（保留最新一条以延续序号），调用方需持有 change_log_lock
+    fn append_change_log(&self, conn: &Connection, (from, to): (DateTime<Utc>, DateTime<Utc>)) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let now = Utc::now();
+        conn.execute(
+            "INSERT INTO change_log (Seq, DataFrom, DataTo, LoggedAt)
+             SELECT COALESCE(MAX(Seq), 0) + 1, ?, ?, ? FROM change_log",
+            [format_timestamp(&from), format_timestamp(&to), format_timestamp(&now)],
+        )?;
+
+        let cutoff = now - chrono::Duration::days(i64::from(self.config.data_window_days));
+        conn.execute(
+            "DELETE FROM change_log WHERE LoggedAt < ? AND Seq < (SELECT MAX(Seq) FROM change_log)",
+            [format_timestamp(&cutoff)],
+        )?;
+        Ok(())
+    }
+    
+    /// 读取序号 `after` 之后的变更日志
+    pub fn change_log(&self, after: Option<i64>) -> Result<ChangeLog, Box<dyn std::error::Error + Send + Sync>> {
+        // 间隔不超过该秒数的范围合并为一个，减少副本的请求次数
+        const MERGE_GAP_SECS: i64 = 60;
+
         let conn = self.get_connection()?;
-        let mut stmt = conn.prepare("SELECT MAX(DateTime) FROM ts_wide")?;
-        
-        let result = stmt.query_row([], |row| {
-            let ts_str: Option<String> = row.get(0)?;
-            Ok(ts_str)
-        });
-        
-        match result {
-            Ok(Some(ts_str)) => {
-                let timestamp = DateTime::parse_from_str(&ts_str, "%Y-%m-%d %H:%M:%S%.3f")
-                    .or_else(|_| DateTime::parse_from_str(&ts_str, "%Y-%m-%d %H:%M:%S"))?;
-                Ok(Some(timestamp.with_timezone(&Utc)))
+        let (min_seq, max_seq): (Option<i64>, Option<i64>) = conn.query_row(
+            "SELECT MIN(Seq), MAX(Seq) FROM change_log",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let seq = max_seq.unwrap_or(0);
+        let full = match (after, min_seq) {
+            (None, _) => true,
+            // 日志被清理过（序号不连续）或主实例的数据库已重建（序号回退）
+            (Some(after), Some(min_seq)) => after + 1 < min_seq || after > seq,
+            (Some(after), None) => after > seq,
+        };
+        if full {
+            return Ok(ChangeLog { seq, ranges: Vec::new(), full });
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT CAST(DataFrom AS TIMESTAMP), CAST(DataTo AS TIMESTAMP) FROM change_log
+             WHERE Seq > ? ORDER BY DataFrom"
+        )?;
+        let rows = stmt.query_map([after.unwrap_or(0)], |row| {
+            let from: chrono::NaiveDateTime = row.get(0)?;
+            let to: chrono::NaiveDateTime = row.get(1)?;
+            Ok((from.and_utc(), to.and_utc()))
+        })?;
+
+        let mut ranges: Vec<(DateTime<Utc>, DateTime<Utc>)> = Vec::new();
+        for row in rows {
+            let (from, to) = row?;
+            match ranges.last_mut() {
+                Some(last) if from <= last.1 + chrono::Duration::seconds(MERGE_GAP_SECS) => last.1 = last.1.max(to),
+                _ => ranges.push((from, to)),
             }
-            Ok(None) => Ok(None),
-            Err(e) => {
-                error!("获取最新时间戳失败: {}", e);
-                Ok(None)
+        }
+
+        Ok(ChangeLog { seq, ranges, full })
+    }
+    
+    /// 副本已应用的主实例变更日志序号
+    pub fn replica_position(&self) -> Result<Option<i64>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let seq = conn.query_row("SELECT Seq FROM replica_position WHERE Id = 1", [], |row| row.get(0));
+        match seq {
+            Ok(seq) => Ok(Some(seq)),
+            Err(duckdb::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+    
+    /// 保存副本已应用的主实例变更日志序号
+    pub fn save_replica_position(&self, seq: i64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        conn.execute("INSERT OR REPLACE INTO replica_position (Id, Seq) VALUES (1, ?)", [seq])?;
+        Ok(())
+    }
+    
+    /// 是否有进行中的同步周期事务
+    pub fn cycle_in_progress(&self) -> bool {
+        self.cycle_conn.lock().unwrap().is_some()
+    }
+    
+    /// 停机前保存缓存：回滚未完成的同步周期事务（缓存与同步检查点保持一致），再执行 CHECKPOINT 将 WAL 合并到数据库文件
+    pub fn shutdown_checkpoint(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.cycle_in_progress() {
+            warn!("停机时同步周期尚未完成，本周期的写入将在下次启动时从检查点重新同步");
+            if let Some(conn) = self.cycle_conn.lock().unwrap().take() {
+                conn.execute_batch("ROLLBACK")?;
             }
         }
+
+        let conn = self.get_connection()?;
+        conn.execute_batch("CHECKPOINT")?;
+        info!("已执行 CHECKPOINT，数据库文件 {:.2} MB", self.file_size()? as f64 / 1024.0 / 1024.0);
+        Ok(())
+    }
+    
+    /// DuckDB 文件当前的大小（字节）
+    pub fn file_size(&self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(std::fs::metadata(&self.db_path)?.len())
+    }
+    
+    /// DuckDB 文件中已使用的块与尚未合并的 WAL 占用的空间（字节）
+    ///
+    /// 删除释放的块在 CHECKPOINT 后计为空闲，文件本身通常不会缩小，大小上限按已用空间计算。
+    pub fn used_size(&self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let used: i64 = conn.query_row(
+            "SELECT CAST(used_blocks * block_size AS BIGINT) FROM pragma_database_size() WHERE database_name = current_database()",
+            [],
+            |row| row.get(0),
+        )?;
+        let wal = std::fs::metadata(format!("{}.wal", self.db_path)).map(|m| m.len()).unwrap_or(0);
+        Ok(used.max(0) as u64 + wal)
+    }
+    
+    /// 上次大小上限清理未能降到上限以下时记录的上限与已用空间（字节）
+    pub fn size_cap_stall(&self) -> Result<Option<(u64, u64)>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let stall = conn.query_row(
+            "SELECT CapBytes, UsedBytes FROM size_cap_stall WHERE Id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        );
+        match stall {
+            Ok(stall) => Ok(Some(stall)),
+            Err(duckdb::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+    
+    /// 记录（`Some`）或清除（`None`）大小上限清理未能降到上限以下的状态
+    pub fn set_size_cap_stall(&self, stall: Option<(u64, u64)>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        match stall {
+            Some((cap, used)) => conn.execute(
+                "INSERT OR REPLACE INTO size_cap_stall (Id, CapBytes, UsedBytes, StalledAt) VALUES (1, ?, ?, now())",
+                [cap, used],
+            )?,
+            None => conn.execute("DELETE FROM size_cap_stall", [])?,
+        };
+        Ok(())
+    }
+    
+    /// 执行 CHECKPOINT，返回执行前后的文件大小（字节）
+    ///
+    /// DuckDB 的 VACUUM 不回收磁盘空间；CHECKPOINT 将 WAL 合并到数据库文件，并把删除释放的块标记为可复用，
+    /// 之后的写入优先复用这些块，文件只在末尾的块全部空闲时才会截短。同步周期事务进行中时 CHECKPOINT 会失败，
+    /// 不使用 FORCE CHECKPOINT，以免中止周期事务。
+    pub fn checkpoint(&self) -> Result<(u64, u64), Box<dyn std::error::Error + Send + Sync>> {
+        let size_before = std::fs::metadata(&self.db_path)?.len();
+
+        let conn = self.get_connection()?;
+        conn.execute_batch("CHECKPOINT")?;
+
+        let size_after = std::fs::metadata(&self.db_path)?.len();
+        Ok((size_before, size_after))
+    }
+    
+    /// 开始一个同步周期，传入返回句柄的列变更与数据写入在同一事务中执行
+    pub fn begin_cycle(&self) -> Result<Cycle, Box<dyn std::error::Error + Send + Sync>> {
+        let mut cycle_conn = self.cycle_conn.lock().unwrap();
+        if cycle_conn.is_some() {
+            return Err("同步周期事务已在进行中".into());
+        }
+
+        crate::chaos::inject_disk_full()?;
+        let conn = self.get_connection()?;
+        conn.execute_batch("BEGIN TRANSACTION")?;
+        *cycle_conn = Some(conn);
+
+        debug!("同步周期事务已开始");
+        Ok(Cycle {
+            tag_changes: std::sync::Mutex::new(Vec::new()),
+            written: std::sync::Mutex::new(None),
+        })
+    }
+    
+    /// 提交同步周期的事务
+    pub fn commit_cycle(&self, cycle: Cycle) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let Some(conn) = self.cycle_conn.lock().unwrap().take() else {
+            // 事务已被停机流程回滚
+            self.rollback_cycle(cycle)?;
+            return Err("没有进行中的同步周期事务".into());
+        };
+
+        // 变更日志与数据在同一事务中提交
+        let _change_log = self.change_log_lock.lock().unwrap();
+        let written = *cycle.written.lock().unwrap();
+        let result = match written {
+            Some(range) => self.append_change_log(&conn, range),
+            None => Ok(()),
+        }.and_then(|_| conn.execute_batch("COMMIT").map_err(Into::into));
+        if result.is_err() {
+            let _ = conn.execute_batch("ROLLBACK");
+        }
+        self.end_cycle(cycle, result.is_err());
+        if let Err(e) = result {
+            // 提交失败时事务已中止，列缓存可能包含未落盘的新列，死区基准值与源时间也未落盘，已知标签的修改已撤销
+            self.invalidate_schema_cache("同步周期事务提交失败");
+            self.last_written.lock().unwrap().clear();
+            self.staleness.reset();
+            return Err(e);
+        }
+
+        debug!("同步周期事务已提交");
+        Ok(())
+    }
+    
+    /// 回滚同步周期的事务，事务已被停机流程回滚时只撤销内存状态
+    pub fn rollback_cycle(&self, cycle: Cycle) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.cycle_conn.lock().unwrap().take();
+
+        // 回滚撤销了本周期新增的列与写入的值，需撤销已知标签的修改、重新加载列缓存并重置死区基准值、源时间与停滞检测
+        self.end_cycle(cycle, true);
+        self.invalidate_schema_cache("同步周期事务回滚");
+        self.last_written.lock().unwrap().clear();
+        self.staleness.reset();
+        if let Some(conn) = conn {
+            conn.execute_batch("ROLLBACK")?;
+        }
+
+        warn!("同步周期事务已回滚");
+        Ok(())
     }
     
+    /// 重构历史数据为宽表格式并插入
+    #[tracing::instrument(skip_all, fields(records = records.len()))]
+    pub fn convert_and_insert_wide(&self, records: &[TimeSeriesRecord], cycle: Option<&Cycle>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let _timer = metrics::timer(&metrics::DUCKDB_INSERT_SECONDS, "history");
+        self.insert_quality_data(records, cycle)?;
+
+        // 文本值写入 ts_text；历史数据量大，只有存在文本值时才拆分
+        let numeric_records: Vec<TimeSeriesRecord>;
+        let records = if records.iter().any(|r| r.text.is_some()) {
+            let text_records: Vec<TimeSeriesRecord>;
+            (text_records, numeric_records) = records.iter().cloned().partition(|r| r.text.is_some());
+            self.insert_text_data(&text_records, cycle)?;
+            self.log_change(&text_records, cycle)?;
+            &numeric_records[..]
+        } else {
+            records
+        };
+
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        self.log_change(records, cycle)?;
+        self.update_latest_values(records, cycle)?;
 
-}
\ No newline at end of file
+        if self.config.storage_mode == StorageMode::Long {
+            self.insert_long_data(records, cycle)?;
+            debug!("插入 {} 条历史数据到窄表", records.len());
+            return Ok(());
+        }
+
+        // 按时间戳分组数据
+        let mut grouped_data: std::collections::HashMap<DateTime<Utc>, std::collections::HashMap<String, Option<f64>>> = std::collections::HashMap::new();
+
+        for record in records {
+            grouped_data
+                .entry(record.timestamp)
+                .or_default()
+                .insert(record.tag_name.clone(), record.value);
+        }
+
+        // 获取所有唯一的标签名
+        let all_tags: std::collections::HashSet<String> = records.iter()
+            .map(|r| r.tag_name.clone())
+            .collect();
+
+        // 动态添加列到宽表
+        self.add_columns_to_wide_table(&all_tags, cycle)?;
+
+        // 插入宽表数据
+        self.insert_wide_data(&grouped_data, &all_tags, cycle)?;
+
+        debug!("重构并插入 {} 个时间点的历史数据到宽表", grouped_data.len());
+        Ok(())
+    }
+    
+    /// 将TagDatabase的最新数据拼接到宽表，返回实际写入的标签值数量
+    #[tracing::instrument(skip_all, fields(records = records.len()))]
+    pub fn append_latest_tagdb_data(&self, records: &[TimeSeriesRecord], cycle: Option<&Cycle>) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let _timer = metrics::timer(&metrics::DUCKDB_INSERT_SECONDS, "latest");
+        // 统一使用UTC时间戳，仅在查询/展示时转换时区
+        let current_time = Utc::now();
+
+        // 默认以当前时间为全部标签打时间戳；源时间戳模式下保留各标签的DataTime
+        let stamped: Vec<TimeSeriesRecord> = if self.config.timestamps.use_source_time {
+            self.filter_source_time(records, current_time)
+        } else {
+            records.iter()
+                .map(|r| TimeSeriesRecord { timestamp: current_time, ..r.clone() })
+                .collect()
+        };
+
+        // 文本值写入 ts_text，不参与死区过滤
+        let (text_records, numeric_records): (Vec<TimeSeriesRecord>, Vec<TimeSeriesRecord>) = stamped.into_iter()
+            .partition(|r| r.text.is_some());
+        self.insert_text_data(&text_records, cycle)?;
+        self.log_change(&text_records, cycle)?;
+        let records = &numeric_records[..];
+
+        let unfrozen;
+        let records = if self.config.stale_tags.enabled {
+            unfrozen = self.filter_frozen(records, current_time);
+            &unfrozen[..]
+        } else {
+            records
+        };
+
+        let filtered;
+        let records = if self.toggles.deadband() {
+            filtered = self.filter_deadband(records, current_time);
+            if filtered.len() < records.len() {
+                debug!("死区过滤: {} 个标签中 {} 个有变化", records.len(), filtered.len());
+            }
+            &filtered[..]
+        } else {
+            records
+        };
+
+        self.insert_quality_data(&text_records, cycle)?;
+        self.insert_quality_data(records, cycle)?;
+
+        if records.is_empty() {
+            return Ok(text_records.len());
+        }
+
+        self.log_change(records, cycle)?;
+        self.update_latest_values(records, cycle)?;
+
+        if self.config.storage_mode == StorageMode::Long {
+            self.insert_long_data(records, cycle)?;
+            
+            self.insert_known_tags(records.iter().map(|record| &record.tag_name), cycle);
+            
+            debug!("拼接 {} 个标签的最新数据到窄表，时间戳: {}", records.len(), current_time);
+            return Ok(records.len() + text_records.len());
+        }
+
+        // 将记录按时间戳分组（默认模式下只有当前时间一组）
+        let mut grouped_data: std::collections::HashMap<DateTime<Utc>, std::collections::HashMap<String, Option<f64>>> =
+            std::collections::HashMap::new();
+        for record in records {
+            grouped_data.entry(record.timestamp).or_default()
+                .insert(record.tag_name.clone(), record.value);
+        }
+
+        // 获取所有标签名
+        let all_tags: std::collections::HashSet<String> = records.iter()
+            .map(|r| r.tag_name.clone())
+            .collect();
+
+        // 动态添加列到宽表
+        self.add_columns_to_wide_table(&all_tags, cycle)?;
+
+        // 每个时间点只写入该时间点有值的标签列，已存在的行中其他标签的值保持不变
+        for (timestamp, tag_values) in grouped_data {
+            let tags: std::collections::HashSet<String> = tag_values.keys().cloned().collect();
+            let group = std::collections::HashMap::from([(timestamp, tag_values)]);
+            self.insert_wide_data(&group, &tags, cycle)?;
+        }
+
+        debug!("拼接 {} 个标签的最新数据到宽表，时间戳: {}", records.len(), current_time);
+        Ok(records.len() + text_records.len())
+    }
+    
+    /// 停滞检测：更新各标签值最后变化的时间，标签开始或结束停滞时记录日志；
+    /// 配置了 `skip_frozen_values` 时丢弃停滞标签的记录
+    fn filter_frozen(&self, records: &[TimeSeriesRecord], now: DateTime<Utc>) -> Vec<TimeSeriesRecord> {
+        let config = &self.config.stale_tags;
+        let threshold = chrono::Duration::seconds(config.threshold_secs as i64);
+        records.iter()
+            .filter(|record| {
+                let frozen = self.staleness.observe_value(&record.tag_name, record.value, record.timestamp, now, threshold);
+                !(frozen && config.skip_frozen_values)
+            })
+            .cloned()
+            .collect()
+    }
+    
+    /// 停滞与过期标签跟踪器
+    pub fn staleness(&self) -> &StaleTracker {
+        &self.staleness
+    }
+    
+    /// 低延迟模式的延迟统计
+    pub fn latency(&self) -> &LatencyTracker {
+        &self.latency
+    }
+    
+    /// 该同步配置的运行时功能开关
+    pub fn toggles(&self) -> &Arc<FeatureToggles> {
+        &self.toggles
+    }
+    
+    /// 源时间戳过滤：丢弃DataTime未更新（已写入过）或已过期的记录，超前当前时间过多的DataTime改用当前时间
+    fn filter_source_time(&self, records: &[TimeSeriesRecord], now: DateTime<Utc>) -> Vec<TimeSeriesRecord> {
+        let config = &self.config.timestamps;
+        let max_future = chrono::Duration::seconds(config.max_future_secs as i64);
+        let stale_after = (config.stale_after_secs > 0).then(|| chrono::Duration::seconds(config.stale_after_secs as i64));
+        let mut skewed = 0;
+        let mut fresh = Vec::with_capacity(records.len());
+
+        for record in records {
+            let mut record = record.clone();
+            if record.timestamp > now + max_future {
+                skewed += 1;
+                record.timestamp = now;
+            }
+            
+            if self.staleness.observe_source_time(&record.tag_name, record.timestamp, now, stale_after) {
+                fresh.push(record);
+            }
+        }
+
+        if skewed > 0 {
+            warn!("{} 个标签的数据源时间超前当前时间 {} 秒以上，已改用当前时间", skewed, config.max_future_secs);
+        }
+        debug!("源时间戳过滤: {} 个标签中 {} 个有新数据，{} 个已过期", records.len(), fresh.len(), self.staleness.counts().1);
+        fresh
+    }
+    
+    /// 死区过滤：只保留相对最后写入值变化超过死区（或超过心跳间隔未写入）的记录，并更新最后写入值
+    fn filter_deadband(&self, records: &[TimeSeriesRecord], now: DateTime<Utc>) -> Vec<TimeSeriesRecord> {
+        let deadband = &self.config.deadband;
+        let heartbeat = chrono::Duration::seconds(deadband.heartbeat_secs as i64);
+        let mut last_written = self.last_written.lock().unwrap();
+
+        records.iter()
+            .filter(|record| {
+                let changed = match last_written.get(&record.tag_name) {
+                    None => true,
+                    Some((_, written_at)) if deadband.heartbeat_secs > 0 && now - *written_at >= heartbeat => true,
+                    Some((Some(last), _)) => match record.value {
+                        Some(value) => {
+                            let threshold = deadband.absolute.max(last.abs() * deadband.percent / 100.0);
+                            let diff = (value - last).abs();
+                            if threshold > 0.0 { diff > threshold } else { diff != 0.0 }
+                        }
+                        None => true,
+                    },
+                    Some((None, _)) => record.value.is_some(),
+                };
+                if changed {
+                    last_written.insert(record.tag_name.clone(), (record.value, now));
+                }
+                changed
+            })
+            .cloned()
+            .collect()
+    }
+    
+    /// 处理标签变化（加点/少点）
+    pub fn handle_tag_changes(&self, tag_changes: &crate::data_source::TagChanges, cycle: Option<&Cycle>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // 处理新增标签（加点）
+        if !tag_changes.added_tags.is_empty() {
+            info!("处理新增标签: {:?}", tag_changes.added_tags);
+            if self.config.storage_mode == StorageMode::Wide {
+                let new_tags: std::collections::HashSet<String> = tag_changes.added_tags.iter().cloned().collect();
+                self.add_columns_to_wide_table(&new_tags, cycle)?;
+            }
+            
+            // 更新已知标签集合
+            self.insert_known_tags(&tag_changes.added_tags, cycle);
+        }
+
+        // 处理删除标签（少点）
+        if !tag_changes.removed_tags.is_empty() {
+            warn!("检测到删除的标签: {:?}", tag_changes.removed_tags);
+            
+            // 对于删除的标签，我们可以选择：
+            // 1. 保留列但标记为已删除（推荐，保持数据完整性）
+            // 2. 物理删除列（可能导致数据丢失）
+            // 这里采用方案1，只是从已知标签集合中移除，但保留数据库列
+            self.remove_known_tags(&tag_changes.removed_tags, cycle);
+            
+            // 记录删除的标签信息，便于后续处理
+            info!("已从已知标签集合中移除: {:?}，但保留历史数据列", tag_changes.removed_tags);
+        }
+
+        Ok(())
+    }
+    
+    /// 获取当前已知的标签列表
+    pub fn get_known_tags(&self) -> std::collections::HashSet<String> {
+        self.known_tags.lock().unwrap().clone()
+    }
+    
+    /// 清理已删除标签的空值数据（可选的维护操作）
+    pub fn cleanup_removed_tag_data(&self, removed_tags: &[String], cycle: Option<&Cycle>) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        if removed_tags.is_empty() {
+            return Ok(0);
+        }
+
+        let holds = self.retention_holds()?;
+        {
+            let unheld = self.unheld_filter("ts_latest", &holds)?;
+            let conn = self.write_connection(cycle)?;
+            for tag in removed_tags {
+                let params = std::iter::once(Param::from(tag)).chain(unheld.params.iter().cloned());
+                conn.execute(&format!("DELETE FROM ts_latest WHERE TagName = ?{}", unheld.sql), duckdb::params_from_iter(params))?;
+            }
+        }
+
+        if self.config.storage_mode == StorageMode::Long {
+            let unheld = self.unheld_filter("ts_long", &holds)?;
+            let conn = self.write_connection(cycle)?;
+            let mut total_cleaned = 0;
+            for tag in removed_tags {
+                let params = std::iter::once(Param::from(tag)).chain(unheld.params.iter().cloned());
+                let deleted_rows = conn.execute(&format!("DELETE FROM ts_long WHERE TagName = ?{}", unheld.sql), duckdb::params_from_iter(params))?;
+                total_cleaned += deleted_rows;
+                info!("已清理标签 {} 的 {} 条数据记录", tag, deleted_rows);
+            }
+            return Ok(total_cleaned);
+        }
+
+        // 保留期豁免命中的行不清理
+        let unheld = self.unheld_filter("ts_wide", &holds)?;
+
+        // 检查列是否存在
+        let mut existing = Vec::new();
+        for tag in removed_tags {
+            let Some(safe_column_name) = self.column_for(tag)? else {
+                continue;
+            };
+            if self.wide_column_exists(&safe_column_name)? {
+                existing.push((tag, safe_column_name));
+            }
+        }
+
+        let conn = self.write_connection(cycle)?;
+        let mut total_cleaned = 0;
+
+        for (tag, safe_column_name) in existing {
+            // 将该列的所有值设为NULL（软删除）
+            let update_sql = format!(
+                "UPDATE ts_wide SET {} = NULL WHERE TRUE{}",
+                Dialect::DuckDb.quote(&safe_column_name),
+                unheld.sql
+            );
+            
+            let updated_rows = conn.execute(&update_sql, duckdb::params_from_iter(unheld.params.iter()))?;
+            total_cleaned += updated_rows;
+            
+            info!("已清理标签 {} 的 {} 条数据记录", tag, updated_rows);
+        }
+
+        Ok(total_cleaned)
+    }
+    
+    /// 删除给定时间以前的数据
+    pub fn delete_data_before_time(&self, cutoff_time: DateTime<Utc>) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let holds = self.retention_holds()?;
+        let conn = self.get_connection()?;
+        let cutoff_str = format_timestamp(&cutoff_time);
+
+        let mut deleted_rows = 0;
+        for table in [self.data_table(), "ts_text", "ts_quality", "ts_latest"] {
+            let unheld = self.unheld_filter(table, &holds)?;
+            let params = std::iter::once(Param::from(&cutoff_str)).chain(unheld.params);
+            deleted_rows += conn.execute(&format!("DELETE FROM {} WHERE DateTime < ?{}", table, unheld.sql), duckdb::params_from_iter(params))?;
+        }
+
+        if deleted_rows > 0 {
+            info!("删除了 {} 条给定时间前的数据，截止时间: {}", deleted_rows, cutoff_str);
+        }
+
+        Ok(deleted_rows)
+    }
+    
+    /// 将上次汇总的截止时间与本次截止时间之间的原始数值按 `bucket_secs` 宽的时间桶汇总到汇总表，返回写入的行数
+    ///
+    /// 截止时间应对齐到桶宽度，使写入的每个桶都包含完整的原始数据。每个汇总表的截止时间记入 rollup_watermarks，
+    /// 保留期豁免留在表中的更早数据不再参与汇总，已完成的桶不会被这部分数据的汇总覆盖；已存在的桶不重写。
+    pub fn roll_up_before(
+        &self,
+        table: &str,
+        bucket_secs: u64,
+        cutoff_time: DateTime<Utc>,
+    ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let watermark = match conn.query_row(
+            "SELECT CAST(Cutoff AS TIMESTAMP) FROM rollup_watermarks WHERE TableName = ?",
+            [table],
+            |row| row.get::<_, chrono::NaiveDateTime>(0),
+        ) {
+            Ok(watermark) => Some(watermark.and_utc()),
+            Err(duckdb::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(e.into()),
+        };
+        if watermark.is_some_and(|watermark| watermark >= cutoff_time) {
+            return Ok(0);
+        }
+
+        let cutoff = format_timestamp(&cutoff_time);
+        let mut range = "DateTime < CAST(? AS TIMESTAMPTZ)".to_string();
+        // 截止时间依次出现在 spans 与数据源子查询中
+        let mut params = vec![Param::from(&cutoff), Param::from(&cutoff)];
+        if let Some(watermark) = &watermark {
+            range.push_str(" AND DateTime >= CAST(? AS TIMESTAMPTZ)");
+            params.push(Param::from(format_timestamp(watermark)));
+        }
+        let source = match self.config.storage_mode {
+            StorageMode::Long => {
+                format!("SELECT DateTime, TagName, Value FROM ts_long WHERE {} AND Value IS NOT NULL", range)
+            }
+            StorageMode::Wide => {
+                let columns = self.export_columns(&[])?;
+                if columns.is_empty() {
+                    return Ok(0);
+                }
+                // 宽表列名按 tag_columns 映射回标签名；UNPIVOT 默认跳过空值
+                format!(
+                    "SELECT u.DateTime, c.TagName, u.Value FROM \
+                     (UNPIVOT (SELECT DateTime, {} FROM ts_wide WHERE {}) ON COLUMNS(* EXCLUDE (DateTime)) INTO NAME ColumnName VALUE Value) AS u \
+                     JOIN tag_columns AS c ON c.ColumnName = u.ColumnName",
+                    columns.join(", "), range
+                )
+            }
+        };
+
+        // 时间加权列按阶梯保持计算：每个值保持到同一标签的下一个值（最后一个值保持到截止时间），
+        // 跨越桶边界的区间按重叠时长分配到各桶，只有保持值没有样本的桶 Count 为 0
+        let sql = format!(
+            "INSERT OR IGNORE INTO {table} (TagName, DateTime, Avg, Min, Max, Count, TwAvg, OnSecs) \
+             WITH spans AS ( \
+                 SELECT TagName, CAST(DateTime AS TIMESTAMP) AS ts, Value AS v, \
+                        CAST(COALESCE(LEAD(DateTime) OVER (PARTITION BY TagName ORDER BY DateTime), CAST(? AS TIMESTAMPTZ)) AS TIMESTAMP) AS until \
+                 FROM ({source}) \
+             ), pieces AS ( \
+                 SELECT TagName, ts, v, until, UNNEST(generate_series( \
+                     time_bucket(INTERVAL {bucket_secs} SECOND, ts), \
+                     GREATEST(until - INTERVAL 1 MICROSECOND, ts), \
+                     INTERVAL {bucket_secs} SECOND)) AS bucket \
+                 FROM spans \
+             ), weighted AS ( \
+                 SELECT *, date_diff('millisecond', GREATEST(ts, bucket), LEAST(until, bucket + INTERVAL {bucket_secs} SECOND)) / 1000.0 AS secs \
+                 FROM pieces \
+             ) \
+             SELECT TagName, bucket, \
+                    AVG(v) FILTER (WHERE ts >= bucket), MIN(v) FILTER (WHERE ts >= bucket), MAX(v) FILTER (WHERE ts >= bucket), \
+                    COUNT(v) FILTER (WHERE ts >= bucket), \
+                    SUM(v * secs) / NULLIF(SUM(secs), 0), COALESCE(SUM(secs) FILTER (WHERE v <> 0), 0) \
+             FROM weighted GROUP BY TagName, bucket"
+        );
+        conn.execute_batch("BEGIN TRANSACTION")?;
+        let result = conn.execute(&sql, duckdb::params_from_iter(params.iter())).and_then(|rows| {
+            conn.execute(
+                "INSERT OR REPLACE INTO rollup_watermarks (TableName, Cutoff) VALUES (?, ?)",
+                [table, cutoff.as_str()],
+            )?;
+            Ok(rows)
+        });
+        let rows = match result {
+            Ok(rows) => {
+                conn.execute_batch("COMMIT")?;
+                rows
+            }
+            Err(e) => {
+                conn.execute_batch("ROLLBACK")?;
+                return Err(e.into());
+            }
+        };
+        debug!("已将 {} 以前的数据汇总到 {}: {} 行", cutoff, table, rows);
+        Ok(rows)
+    }
+    
+    /// 删除汇总表中截止时间以前的汇总，返回删除的行数
+    pub fn delete_rollups_before(&self, table: &str, cutoff_time: DateTime<Utc>) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let rows = conn.execute(&format!("DELETE FROM {} WHERE DateTime < ?", table), [format_timestamp(&cutoff_time)])?;
+        Ok(rows)
+    }
+    
+    /// 当前的保留期豁免，按编号排序
+    pub fn retention_holds(&self) -> Result<Vec<RetentionHold>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT Id, TagName, CAST(FromTime AS TIMESTAMP), CAST(ToTime AS TIMESTAMP), Reason, CreatedBy, CAST(CreatedAt AS TIMESTAMP)
+             FROM retention_holds ORDER BY Id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(RetentionHold {
+                id: row.get(0)?,
+                tag: row.get(1)?,
+                from: row.get::<_, Option<chrono::NaiveDateTime>>(2)?.map(|t| t.and_utc()),
+                to: row.get::<_, Option<chrono::NaiveDateTime>>(3)?.map(|t| t.and_utc()),
+                reason: row.get(4)?,
+                created_by: row.get(5)?,
+                created_at: row.get::<_, chrono::NaiveDateTime>(6)?.and_utc(),
+            })
+        })?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+    
+    /// 添加保留期豁免，返回新建的豁免
+    pub fn add_retention_hold(
+        &self,
+        tag: Option<String>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        reason: String,
+        created_by: String,
+    ) -> Result<RetentionHold, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let id: i64 = conn.query_row("SELECT COALESCE(MAX(Id), 0) + 1 FROM retention_holds", [], |row| row.get(0))?;
+        let hold = RetentionHold { id, tag, from, to, reason, created_by, created_at: Utc::now() };
+        conn.execute(
+            "INSERT INTO retention_holds (Id, TagName, FromTime, ToTime, Reason, CreatedBy, CreatedAt) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            duckdb::params![
+                hold.id,
+                hold.tag,
+                hold.from.as_ref().map(format_timestamp),
+                hold.to.as_ref().map(format_timestamp),
+                hold.reason,
+                hold.created_by,
+                format_timestamp(&hold.created_at),
+            ],
+        )?;
+        Ok(hold)
+    }
+    
+    /// 解除保留期豁免，返回该豁免（不存在时为 None）
+    pub fn release_retention_hold(&self, id: i64) -> Result<Option<RetentionHold>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(hold) = self.retention_holds()?.into_iter().find(|hold| hold.id == id) else {
+            return Ok(None);
+        };
+        let conn = self.get_connection()?;
+        conn.execute("DELETE FROM retention_holds WHERE Id = ?", [id])?;
+        Ok(Some(hold))
+    }
+    
+    /// 吊销分享链接，`expires` 之后链接本身已过期，吊销记录随之清除
+    pub fn revoke_share_link(&self, id: &str, revoked_by: &str, expires: DateTime<Utc>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let now = format_timestamp(&Utc::now());
+        conn.execute("DELETE FROM share_revocations WHERE Expires < ?", [&now])?;
+        conn.execute(
+            "INSERT OR REPLACE INTO share_revocations (Id, RevokedBy, RevokedAt, Expires) VALUES (?, ?, ?, ?)",
+            duckdb::params![id, revoked_by, now, format_timestamp(&expires)],
+        )?;
+        Ok(())
+    }
+    
+    /// 分享链接是否已被吊销
+    pub fn share_link_revoked(&self, id: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM share_revocations WHERE Id = ?", [id], |row| row.get(0))?;
+        Ok(count > 0)
+    }
+    
+    /// 排除保留期豁免命中行的附加条件（` AND NOT (...)`）及其参数，没有适用于该表的豁免时为空
+    ///
+    /// 宽表中标签豁免命中该标签列有值的行（整行保留），其余表按 TagName 匹配。
+    fn unheld_filter(&self, table: &str, holds: &[RetentionHold]) -> Result<sql::Statement, Box<dyn std::error::Error + Send + Sync>> {
+        let mut held = Vec::new();
+        let mut params = Vec::new();
+        for hold in holds {
+            let mut conditions = Vec::new();
+            let mut hold_params = Vec::new();
+            if let Some(from) = &hold.from {
+                conditions.push("DateTime >= CAST(? AS TIMESTAMPTZ)".to_string());
+                hold_params.push(Param::from(format_timestamp(from)));
+            }
+            if let Some(to) = &hold.to {
+                conditions.push("DateTime < CAST(? AS TIMESTAMPTZ)".to_string());
+                hold_params.push(Param::from(format_timestamp(to)));
+            }
+            if let Some(tag) = &hold.tag {
+                if table == "ts_wide" {
+                    match self.column_for(tag)? {
+                        Some(column) if self.wide_column_exists(&column)? => {
+                            conditions.push(format!("{} IS NOT NULL", Dialect::DuckDb.quote(&column)));
+                        }
+                        // 宽表中没有该标签的数据
+                        _ => continue,
+                    }
+                } else {
+                    conditions.push("TagName = ?".to_string());
+                    hold_params.push(Param::from(tag));
+                }
+            }
+            held.push(if conditions.is_empty() { "TRUE".to_string() } else { format!("({})", conditions.join(" AND ")) });
+            params.extend(hold_params);
+        }
+
+        let sql = if held.is_empty() { String::new() } else { format!(" AND NOT ({})", held.join(" OR ")) };
+        Ok(sql::Statement { sql, params })
+    }
+    
+    /// 插入宽表数据（批量优化版本）
+    ///
+    /// 写入因表结构与列缓存不一致而失败时（例如大量列变更之后），失效列缓存；
+    /// 不在同步周期事务中时重新补齐列并用新连接重试一次，周期事务中则由回滚后的下一周期重建。
+    fn insert_wide_data(
+        &self,
+        grouped_data: &std::collections::HashMap<DateTime<Utc>, std::collections::HashMap<String, Option<f64>>>,
+        all_tags: &std::collections::HashSet<String>,
+        cycle: Option<&Cycle>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if grouped_data.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.write_connection(cycle)?;
+        let error = match self.execute_wide_insert(&conn, grouped_data, all_tags) {
+            Err(e) if is_schema_error(e.as_ref()) => e,
+            result => return result,
+        };
+
+        self.invalidate_schema_cache("写入宽表时表结构与列缓存不一致");
+        if let WriteConnection::Cycle(_) = conn {
+            return Err(error);
+        }
+        drop(conn);
+
+        warn!("宽表结构已变化，重建列缓存后重试写入: {}", error);
+        metrics::add(&metrics::RETRIES, "wide_insert", 1);
+        self.add_columns_to_wide_table(all_tags, cycle)?;
+        let conn = self.write_connection(cycle)?;
+        self.execute_wide_insert(&conn, grouped_data, all_tags)
+    }
+    
+    /// 按批次执行宽表插入
+    fn execute_wide_insert(
+        &self,
+        conn: &Connection,
+        grouped_data: &std::collections::HashMap<DateTime<Utc>, std::collections::HashMap<String, Option<f64>>>,
+        all_tags: &std::collections::HashSet<String>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // 构建列名列表
+        let mut insert = Insert::into(Dialect::DuckDb, "ts_wide").or_replace().column("DateTime");
+        for tag in all_tags {
+            let safe_column_name = self.column_for(tag)?
+                .ok_or_else(|| format!("标签 {} 没有分配宽表列", tag))?;
+            insert = insert.column(&safe_column_name);
+        }
+
+        // 将数据转换为向量以便分批处理
+        let mut data_rows: Vec<_> = grouped_data.iter().collect();
+        data_rows.sort_by_key(|(timestamp, _)| *timestamp);
+
+        // 分批插入数据
+        const BATCH_SIZE: usize = 1000;
+        for chunk in data_rows.chunks(BATCH_SIZE) {
+            // 准备参数（None 绑定为 NULL）
+            let mut params: Vec<Param> = Vec::new();
+            for (timestamp, tag_values) in chunk {
+                // 添加时间戳
+                params.push(format_timestamp(timestamp).into());
+                
+                // 添加标签值，该时间点缺失的标签写入NULL（或按配置补0）
+                for tag in all_tags {
+                    let value = tag_values.get(tag).copied().flatten()
+                        .or(if self.config.zero_fill_missing { Some(0.0) } else { None });
+                    params.push(value.into());
+                }
+            }
+            
+            // 执行批量插入
+            let sql = insert.sql(chunk.len());
+            let started = Instant::now();
+            conn.execute(&sql, duckdb::params_from_iter(params.iter()))?;
+            slow_log::check(&self.config.slow_log, SlowOpKind::DuckDbInsert, "ts_wide", &sql, chunk.len(), started);
+        }
+
+        Ok(())
+    }
+    
+    /// 插入窄表数据（批量）
+    fn insert_long_data(&self, records: &[TimeSeriesRecord], cycle: Option<&Cycle>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.write_connection(cycle)?;
+
+        const BATCH_SIZE: usize = 1000;
+        let insert = Insert::into(Dialect::DuckDb, "ts_long").or_replace().columns(["DateTime", "TagName", "Value"]);
+        for chunk in records.chunks(BATCH_SIZE) {
+            let mut params: Vec<Param> = Vec::with_capacity(chunk.len() * 3);
+            for record in chunk {
+                params.push(format_timestamp(&record.timestamp).into());
+                params.push((&record.tag_name).into());
+                params.push(record.value.into());
+            }
+            
+            let sql = insert.sql(chunk.len());
+            let started = Instant::now();
+            conn.execute(&sql, duckdb::params_from_iter(params.iter()))?;
+            slow_log::check(&self.config.slow_log, SlowOpKind::DuckDbInsert, "ts_long", &sql, chunk.len(), started);
+        }
+
+        Ok(())
+    }
+    
+    /// 插入文本值（批量），相同时间与标签的值覆盖
+    fn insert_text_data(&self, records: &[TimeSeriesRecord], cycle: Option<&Cycle>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.write_connection(cycle)?;
+
+        const BATCH_SIZE: usize = 1000;
+        let insert = Insert::into(Dialect::DuckDb, "ts_text").or_replace().columns(["DateTime", "TagName", "Value"]);
+        for chunk in records.chunks(BATCH_SIZE) {
+            let mut params: Vec<Param> = Vec::with_capacity(chunk.len() * 3);
+            for record in chunk {
+                params.push(format_timestamp(&record.timestamp).into());
+                params.push((&record.tag_name).into());
+                params.push(record.text.clone().into());
+            }
+            
+            let sql = insert.sql(chunk.len());
+            let started = Instant::now();
+            conn.execute(&sql, duckdb::params_from_iter(params.iter()))?;
+            slow_log::check(&self.config.slow_log, SlowOpKind::DuckDbInsert, "ts_text", &sql, chunk.len(), started);
+        }
+
+        debug!("插入 {} 条文本值", records.len());
+        Ok(())
+    }
+    
+    /// 插入数据质量（批量），只写入带有质量值的记录
+    fn insert_quality_data(&self, records: &[TimeSeriesRecord], cycle: Option<&Cycle>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let records: Vec<&TimeSeriesRecord> = records.iter().filter(|r| r.quality.is_some()).collect();
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.write_connection(cycle)?;
+
+        const BATCH_SIZE: usize = 1000;
+        let insert = Insert::into(Dialect::DuckDb, "ts_quality").or_replace().columns(["DateTime", "TagName", "Quality"]);
+        for chunk in records.chunks(BATCH_SIZE) {
+            let mut params: Vec<Param> = Vec::with_capacity(chunk.len() * 3);
+            for record in chunk {
+                params.push(format_timestamp(&record.timestamp).into());
+                params.push((&record.tag_name).into());
+                params.push(record.quality.clone().into());
+            }
+            
+            conn.execute(&insert.sql(chunk.len()), duckdb::params_from_iter(params.iter()))?;
+        }
+
+        Ok(())
+    }
+    
+    /// 更新最新值表：每个标签保留最新的非空数值，不早于已有记录时才覆盖
+    fn update_latest_values(&self, records: &[TimeSeriesRecord], cycle: Option<&Cycle>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut latest: std::collections::HashMap<&str, (DateTime<Utc>, f64)> = std::collections::HashMap::new();
+        for record in records {
+            let Some(value) = record.value else {
+                continue;
+            };
+            let entry = latest.entry(&record.tag_name).or_insert((record.timestamp, value));
+            if record.timestamp >= entry.0 {
+                *entry = (record.timestamp, value);
+            }
+        }
+        if latest.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.write_connection(cycle)?;
+
+        // 同一语句中每个标签只出现一次，否则 ON CONFLICT 更新会失败
+        const BATCH_SIZE: usize = 1000;
+        let latest: Vec<(&str, (DateTime<Utc>, f64))> = latest.into_iter().collect();
+        for chunk in latest.chunks(BATCH_SIZE) {
+            let sql = format!(
+                "INSERT INTO ts_latest (TagName, DateTime, Value) VALUES {} \
+                 ON CONFLICT (TagName) DO UPDATE SET DateTime = excluded.DateTime, Value = excluded.Value \
+                 WHERE excluded.DateTime >= ts_latest.DateTime",
+                vec!["(?, ?, ?)"; chunk.len()].join(", ")
+            );
+            let mut params: Vec<Param> = Vec::with_capacity(chunk.len() * 3);
+            for (tag, (timestamp, value)) in chunk {
+                params.push((*tag).into());
+                params.push(format_timestamp(timestamp).into());
+                params.push((*value).into());
+            }
+            
+            conn.execute(&sql, duckdb::params_from_iter(params.iter()))?;
+        }
+
+        Ok(())
+    }
+    
+    /// 动态添加列到宽表
+    ///
+    /// 新标签在此分配列名：清理后的列名已被其他标签占用（如 `FIC-101` 与 `FIC_101`，列名不区分大小写）时
+    /// 追加 `_2`、`_3` 等后缀，分配结果写入 tag_columns 表，重启后保持不变。
+    fn add_columns_to_wide_table(&self, tags: &std::collections::HashSet<String>, cycle: Option<&Cycle>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // 更新已知标签集合
+        self.insert_known_tags(tags, cycle);
+
+        let mut wide_columns = self.wide_columns.lock().unwrap();
+        let existing_columns = self.ensure_wide_columns(&mut wide_columns)?;
+        let mut tag_columns = self.tag_columns.lock().unwrap();
+        let assigned = self.ensure_tag_columns(&mut tag_columns)?;
+
+        let mut new_tags: Vec<&String> = tags.iter()
+            .filter(|tag| !assigned.contains_key(*tag))
+            .collect();
+        if new_tags.is_empty() {
+            return Ok(());
+        }
+        // 按标签名排序，保证同一批新标签的分配结果确定
+        new_tags.sort();
+
+        // 已占用的列名（小写）及其所属标签
+        let mut claimed: std::collections::HashMap<String, String> = assigned.iter()
+            .map(|(tag, column)| (column.to_lowercase(), tag.clone()))
+            .collect();
+        claimed.insert("datetime".to_string(), "DateTime".to_string());
+
+        let mut assignments = Vec::with_capacity(new_tags.len());
+        for tag in new_tags {
+            let base = self.base_column_name(tag);
+            let mut column = base.clone();
+            let mut suffix = 1;
+            while claimed.contains_key(&column.to_lowercase()) {
+                suffix += 1;
+                column = format!("{}_{}", base, suffix);
+            }
+            if column != base {
+                warn!("标签 {} 与标签 {} 的列名 {} 冲突，改用列名 {}",
+                      tag, claimed[&base.to_lowercase()], base, column);
+            }
+            claimed.insert(column.to_lowercase(), tag.clone());
+            assignments.push((tag.clone(), column));
+        }
+
+        // 仅在确实出现新标签时才访问数据库；已存在的同名列（旧版本创建）直接沿用
+        let conn = self.write_connection(cycle)?;
+        for (tag, column) in &assignments {
+            if !existing_columns.iter().any(|c| c.eq_ignore_ascii_case(column)) {
+                let sql = format!("ALTER TABLE ts_wide ADD COLUMN {} DOUBLE", Dialect::DuckDb.quote(column));
+                conn.execute(&sql, [])?;
+                debug!("添加新列: {}", column);
+                metrics::add(&metrics::COLUMN_ADDS, "ts_wide", 1);
+                existing_columns.insert(column.clone());
+            }
+            conn.execute("INSERT OR REPLACE INTO tag_columns (TagName, ColumnName) VALUES (?, ?)", [tag, column])?;
+        }
+        assigned.extend(assignments);
+
+        Ok(())
+    }
+    
+    /// 标签对应的宽表列名：已分配的列名优先；未分配时为清理后的列名，
+    /// 该列名已分配给其他标签时返回 None（避免读到其他标签的数据）
+    fn column_for(&self, tag_name: &str) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut tag_columns = self.tag_columns.lock().unwrap();
+        let assigned = self.ensure_tag_columns(&mut tag_columns)?;
+        if let Some(column) = assigned.get(tag_name) {
+            return Ok(Some(column.clone()));
+        }
+
+        let column = self.base_column_name(tag_name);
+        if assigned.values().any(|c| c.eq_ignore_ascii_case(&column)) {
+            return Ok(None);
+        }
+        Ok(Some(column))
+    }
+    
+    /// 当前的标签到宽表列名映射，按标签名排序
+    pub fn tag_columns(&self) -> Result<Vec<TagColumn>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut tag_columns = self.tag_columns.lock().unwrap();
+        let mut columns: Vec<TagColumn> = self.ensure_tag_columns(&mut tag_columns)?
+            .iter()
+            .map(|(tag, column)| TagColumn {
+                disambiguated: *column != self.base_column_name(tag),
+                tag: tag.clone(),
+                column: column.clone(),
+            })
+            .collect();
+        columns.sort_by(|a, b| a.tag.cmp(&b.tag));
+        Ok(columns)
+    }
+    
+    /// 确保标签列名映射已加载
+    fn ensure_tag_columns<'a>(
+        &self,
+        tag_columns: &'a mut Option<std::collections::HashMap<String, String>>,
+    ) -> Result<&'a mut std::collections::HashMap<String, String>, Box<dyn std::error::Error + Send + Sync>> {
+        if tag_columns.is_none() {
+            let conn = self.get_connection()?;
+            let mut stmt = conn.prepare("SELECT TagName, ColumnName FROM tag_columns")?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+            let mut map = std::collections::HashMap::new();
+            for row in rows {
+                let (tag, column) = row?;
+                map.insert(tag, column);
+            }
+            *tag_columns = Some(map);
+        }
+        Ok(tag_columns.as_mut().unwrap())
+    }
+    
+    /// 失效表结构缓存，下次访问时从数据库目录重新加载
+    ///
+    /// 所有可能使列缓存与实际表结构不一致的操作（事务回滚、表重建、写入时发现结构不一致）都应调用。
+    fn invalidate_schema_cache(&self, reason: &str) {
+        if self.wide_columns.lock().unwrap().take().is_some() {
+            debug!("已失效宽表列缓存: {}", reason);
+        }
+        self.tag_columns.lock().unwrap().take();
+    }
+    
+    /// 判断宽表中是否存在指定列（使用列缓存）
+    fn wide_column_exists(&self, column_name: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let mut wide_columns = self.wide_columns.lock().unwrap();
+        Ok(self.ensure_wide_columns(&mut wide_columns)?.contains(column_name))
+    }
+    
+    /// 确保列缓存已加载，只有缓存为空时才查询目录
+    fn ensure_wide_columns<'a>(
+        &self,
+        wide_columns: &'a mut Option<std::collections::HashSet<String>>,
+    ) -> Result<&'a mut std::collections::HashSet<String>, Box<dyn std::error::Error + Send + Sync>> {
+        if wide_columns.is_none() {
+            *wide_columns = Some(self.load_wide_columns()?);
+        }
+        Ok(wide_columns.as_mut().unwrap())
+    }
+    
+    /// 从数据库目录加载宽表现有列
+    fn load_wide_columns(&self) -> Result<std::collections::HashSet<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+
+        // 获取现有列 - 使用DuckDB的DESCRIBE语法
+        let mut existing_columns = std::collections::HashSet::new();
+        let mut stmt = conn.prepare("DESCRIBE ts_wide")?;
+        let rows = stmt.query_map([], |row| {
+            let column_name: String = row.get(0)?; // DuckDB的DESCRIBE返回列名在第0列
+            Ok(column_name)
+        })?;
+
+        for row in rows {
+            existing_columns.insert(row?);
+        }
+
+        debug!("已加载宽表列缓存: {} 列", existing_columns.len());
+        Ok(existing_columns)
+    }
+    
+    /// 标签的默认列名：`original` 模式下为原始标签名（去掉首尾空白），否则为清理后的列名
+    fn base_column_name(&self, tag_name: &str) -> String {
+        match self.config.column_naming {
+            ColumnNaming::Original if !tag_name.trim().is_empty() => tag_name.trim().to_string(),
+            _ => self.sanitize_column_name(tag_name),
+        }
+    }
+    
+    /// 清理列名，确保SQL安全
+    fn sanitize_column_name(&self, tag_name: &str) -> String {
+        let mut result = tag_name
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '_' { c } else { '_' })
+            .collect::<String>()
+            .trim_matches('_')
+            .to_string();
+
+        // 确保列名不以数字开头
+        if result.chars().next().is_some_and(|c| c.is_ascii_digit()) {
+            result = format!("tag_{}", result);
+        }
+
+        // 确保列名不为空
+        if result.is_empty() {
+            result = "unknown_tag".to_string();
+        }
+
+        result
+    }
+    
+
+    
+    /// 根据标签删除最旧的数据
+    #[allow(dead_code)]
+    pub fn delete_oldest_by_tag(&self, tag_name: &str, keep_count: usize) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+
+        if self.config.storage_mode == StorageMode::Long {
+            let total_count: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM ts_long WHERE TagName = ? AND Value IS NOT NULL",
+                [tag_name],
+                |row| row.get(0),
+            )?;
+            
+            if total_count <= keep_count as i64 {
+                return Ok(0);
+            }
+            
+            let delete_sql = format!(
+                "DELETE FROM ts_long WHERE TagName = ? AND DateTime IN (
+                    SELECT DateTime FROM ts_long
+                    WHERE TagName = ? AND Value IS NOT NULL
+                    ORDER BY DateTime ASC
+                    LIMIT {}
+                )",
+                total_count - keep_count as i64
+            );
+            let deleted_rows = conn.execute(&delete_sql, [tag_name, tag_name])?;
+            
+            if deleted_rows > 0 {
+                info!("标签 {} 删除了 {} 条最旧数据", tag_name, deleted_rows);
+            }
+            return Ok(deleted_rows);
+        }
+        let Some(safe_column_name) = self.column_for(tag_name)? else {
+            return Ok(0);
+        };
+        let safe_column_name = Dialect::DuckDb.quote(&safe_column_name);
+
+        // 获取该标签的总记录数
+        let count_sql = format!(
+            "SELECT COUNT(*) FROM ts_wide WHERE {} IS NOT NULL",
+            safe_column_name
+        );
+        let total_count: i64 = conn.query_row(&count_sql, [], |row| row.get(0))?;
+
+        if total_count <= keep_count as i64 {
+            return Ok(0); // 不需要删除
+        }
+
+        let delete_count = total_count - keep_count as i64;
+
+        // 删除最旧的记录（将对应列设为NULL）
+        let delete_sql = format!(
+            "UPDATE ts_wide SET {} = NULL WHERE DateTime IN (
+                SELECT DateTime FROM ts_wide 
+                WHERE {} IS NOT NULL 
+                ORDER BY DateTime ASC 
+                LIMIT {}
+            )",
+            safe_column_name, safe_column_name, delete_count
+        );
+
+        let updated_rows = conn.execute(&delete_sql, [])?;
+
+        if updated_rows > 0 {
+            info!("标签 {} 删除了 {} 条最旧数据", tag_name, updated_rows);
+        }
+
+        Ok(updated_rows)
+    }
+    
+    /// 将给定时间以前的数据（数据表、文本值与质量）按 UTC 日期写出为 Parquet 文件，返回写出的文件与行数
+    ///
+    /// 每个表、每天一个文件：`<dir>/yyyy=2024/mm=01/dd=05/<文件名前缀>_<表名>_<截止时间毫秒>.parquet`，
+    /// 多次归档同一天的数据时生成多个文件。写出的文件登记到冷存储目录表 cold_partitions。
+    pub fn archive_before(&self, dir: &Path, prefix: &str, cutoff_time: DateTime<Utc>) -> Result<(Vec<PathBuf>, usize), Box<dyn std::error::Error + Send + Sync>> {
+        let holds = self.retention_holds()?;
+        let conn = self.get_connection()?;
+        let cutoff = format_timestamp(&cutoff_time);
+        let mut archived = 0;
+        let mut files = Vec::new();
+
+        for table in [self.data_table(), "ts_text", "ts_quality"] {
+            // 保留期豁免的数据不清理，也不归档，解除后随下次清理归档
+            let unheld = self.unheld_filter(table, &holds)?;
+            let oldest: Option<chrono::NaiveDateTime> = conn.query_row(
+                &format!("SELECT MIN(DateTime) FROM {} WHERE DateTime < CAST(? AS TIMESTAMPTZ){}", table, unheld.sql),
+                duckdb::params_from_iter(std::iter::once(Param::from(&cutoff)).chain(unheld.params.iter().cloned())),
+                |row| row.get(0),
+            )?;
+            let Some(oldest) = oldest else {
+                continue;
+            };
+            
+            let mut day_start = oldest.date().and_hms_opt(0, 0, 0).unwrap_or(oldest).and_utc();
+            while day_start < cutoff_time {
+                let day_end = (day_start + chrono::Duration::days(1)).min(cutoff_time);
+                let partition = dir.join(day_start.format("yyyy=%Y/mm=%m/dd=%d").to_string());
+                std::fs::create_dir_all(&partition)?;
+                let file = partition.join(format!("{}_{}_{}.parquet", prefix, table, cutoff_time.timestamp_millis()));
+                
+                let params = [Param::from(format_timestamp(&day_start)), Param::from(format_timestamp(&day_end))]
+                    .into_iter()
+                    .chain(unheld.params.iter().cloned());
+                let rows = conn.execute(&format!(
+                    "COPY (SELECT * FROM {} WHERE DateTime >= CAST(? AS TIMESTAMPTZ) AND DateTime < CAST(? AS TIMESTAMPTZ){} ORDER BY DateTime) TO {} (FORMAT PARQUET, COMPRESSION ZSTD)",
+                    table,
+                    unheld.sql,
+                    sql::literal(&file.to_string_lossy())
+                ), duckdb::params_from_iter(params))?;
+                if rows == 0 {
+                    std::fs::remove_file(&file)?;
+                } else {
+                    self.catalog_parquet(&conn, &file, table)?;
+                    debug!("已归档 {} 行到 {}", rows, file.display());
+                    archived += rows;
+                    files.push(file);
+                }
+                day_start = day_end;
+            }
+        }
+
+        Ok((files, archived))
+    }
+    
+    /// 将 Parquet 文件登记到冷存储目录（时间范围与行数读取自文件），已登记的路径被覆盖
+    fn catalog_parquet(&self, conn: &Connection, file: &Path, table: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let path = file.to_string_lossy().to_string();
+        conn.execute(
+            &format!(
+                "INSERT OR REPLACE INTO cold_partitions (Path, TableName, Day, MinTime, MaxTime, RowCount, CreatedAt) \
+                 SELECT ?, ?, CAST(CAST(MIN(DateTime) AS TIMESTAMP) AS DATE), MIN(DateTime), MAX(DateTime), COUNT(*), now() \
+                 FROM read_parquet({}) HAVING COUNT(*) > 0",
+                sql::literal(&path)
+            ),
+            duckdb::params![path, table],
+        )?;
+        Ok(())
+    }
+    
+    /// 登记目录下尚未登记的归档文件（`<文件名前缀>_<表名>_<毫秒>.parquet`），返回新登记的文件数
+    ///
+    /// 未启用 `persist_cache` 时 DuckDB 文件在启动时重建，由此从已有的归档文件恢复冷存储目录。
+    pub fn catalog_existing_partitions(&self, dir: &Path, prefix: &str) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let known: std::collections::HashSet<String> = {
+            let mut stmt = conn.prepare("SELECT Path FROM cold_partitions")?;
+            stmt.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?
+        };
+
+        let mut pending = vec![dir.to_path_buf()];
+        let mut registered = 0;
+        while let Some(current) = pending.pop() {
+            let entries = match std::fs::read_dir(&current) {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            for entry in entries {
+                let path = entry?.path();
+                if path.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                let Some(table) = path.file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| stem.strip_prefix(prefix)?.strip_prefix('_'))
+                    .and_then(|rest| rest.rsplit_once('_'))
+                    .map(|(table, _)| table.to_string())
+                else {
+                    continue;
+                };
+                if path.extension().is_some_and(|ext| ext == "parquet") && !known.contains(path.to_string_lossy().as_ref()) {
+                    self.catalog_parquet(&conn, &path, &table)?;
+                    registered += 1;
+                }
+            }
+        }
+        Ok(registered)
+    }
+    
+    /// 冷存储目录中与 [start_time, end_time) 有交集的分区文件，未给出的边界不限，`table` 为空时不限来源表；按时间升序
+    pub fn cold_partitions(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        table: Option<&str>,
+    ) -> Result<Vec<ColdPartition>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut select = sql::Select::from(Dialect::DuckDb, "cold_partitions")
+            .expr("Path, TableName, Day, CAST(MinTime AS TIMESTAMP), CAST(MaxTime AS TIMESTAMP), RowCount, CAST(CreatedAt AS TIMESTAMP)");
+        if let Some(start_time) = start_time {
+            select = select.filter("MaxTime", sql::Op::Ge, format_timestamp(&start_time));
+        }
+        if let Some(end_time) = end_time {
+            select = select.filter("MinTime", sql::Op::Lt, format_timestamp(&end_time));
+        }
+        if let Some(table) = table {
+            select = select.filter("TableName", sql::Op::Eq, table);
+        }
+        let statement = select.order_by("MinTime").order_by("TableName").order_by("Path").build();
+
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(&statement.sql)?;
+        let partitions = stmt.query_map(duckdb::params_from_iter(statement.params.iter()), |row| {
+            Ok(ColdPartition {
+                path: row.get(0)?,
+                table_name: row.get(1)?,
+                day: row.get(2)?,
+                min_time: row.get::<_, chrono::NaiveDateTime>(3)?.and_utc(),
+                max_time: row.get::<_, chrono::NaiveDateTime>(4)?.and_utc(),
+                row_count: row.get(5)?,
+                created_at: row.get::<_, chrono::NaiveDateTime>(6)?.and_utc(),
+            })
+        })?.collect::<Result<Vec<_>, _>>()?;
+        Ok(partitions)
+    }
+    
+    /// 各标签在 [start_time, end_time) 内的非空数值样本数，没有样本的标签不出现在结果中
+    pub fn sample_counts(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<std::collections::HashMap<String, u64>, Box<dyn std::error::Error + Send + Sync>> {
+        let in_range = |select: sql::Select| {
+            select
+                .filter("DateTime", sql::Op::Ge, format_timestamp(&start_time))
+                .filter("DateTime", sql::Op::Lt, format_timestamp(&end_time))
+        };
+        let conn = self.get_connection()?;
+        let mut counts = std::collections::HashMap::new();
+
+        match self.config.storage_mode {
+            StorageMode::Wide => {
+                let mut columns = Vec::new();
+                for tag_column in self.tag_columns()? {
+                    if self.wide_column_exists(&tag_column.column)? {
+                        columns.push(tag_column);
+                    }
+                }
+                if columns.is_empty() {
+                    return Ok(counts);
+                }
+                let selected = columns.iter()
+                    .map(|c| format!("COUNT({})", Dialect::DuckDb.quote(&c.column)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let statement = in_range(sql::Select::from(Dialect::DuckDb, "ts_wide").expr(selected)).build();
+                let values: Vec<i64> = conn.query_row(&statement.sql, duckdb::params_from_iter(statement.params.iter()), |row| {
+                    (0..columns.len()).map(|i| row.get(i)).collect()
+                })?;
+                for (column, count) in columns.into_iter().zip(values) {
+                    if count > 0 {
+                        counts.insert(column.tag, count as u64);
+                    }
+                }
+            }
+            StorageMode::Long => {
+                let statement = in_range(sql::Select::from(Dialect::DuckDb, "ts_long").column("TagName").expr("COUNT(Value)"))
+                    .not_null("Value")
+                    .group_by("TagName")
+                    .build();
+                let mut stmt = conn.prepare(&statement.sql)?;
+                let rows = stmt.query_map(duckdb::params_from_iter(statement.params.iter()), |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?;
+                for row in rows {
+                    let (tag, count) = row?;
+                    counts.insert(tag, count as u64);
+                }
+            }
+        }
+        Ok(counts)
+    }
+    
+    /// 获取数据库中的记录总数
+    pub fn get_record_count(&self) -> Result<i64, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(&format!("SELECT COUNT(*) FROM {}", self.data_table()))?;
+        let count: i64 = stmt.query_row([], |row| row.get(0))?;
+        Ok(count)
+    }
+    
+    /// 获取最早的时间戳
+    pub fn get_earliest_timestamp(&self) -> Result<Option<DateTime<Utc>>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let ts: Option<chrono::NaiveDateTime> = conn.query_row(
+            &format!("SELECT CAST(MIN(DateTime) AS TIMESTAMP) FROM {}", self.data_table()),
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(ts.map(|ts| ts.and_utc()))
+    }
+    
+    /// 获取最新的时间戳
+    pub fn get_latest_timestamp(&self) -> Result<Option<DateTime<Utc>>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(&format!("SELECT MAX(DateTime) FROM {}", self.data_table()))?;
+
+        let result = stmt.query_row([], |row| {
+            let ts: Option<chrono::NaiveDateTime> = row.get(0)?;
+            Ok(ts)
+        });
+
+        match result {
+            Ok(Some(ts)) => Ok(Some(ts.and_utc())),
+            Ok(None) => Ok(None),
+            Err(e) => {
+                error!("获取最新时间戳失败: {}", e);
+                Ok(None)
+            }
+        }
+    }
+
+    /// 计算标签在时间范围内的时间加权平均值
+    ///
+    /// 采用阶梯保持（sample-and-hold）语义：每个采样值一直保持到下一个采样点，
+    /// 区间起点取起始时间之前的最后一个值，适用于不等间隔采样的标签。
+    pub fn time_weighted_average(
+        &self,
+        tag_name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Option<f64>, Box<dyn std::error::Error + Send + Sync>> {
+        let spans = self.query_value_spans(tag_name, start_time, end_time)?;
+
+        let total_secs: f64 = spans.iter().map(|(_, secs)| secs).sum();
+        if total_secs <= 0.0 {
+            return Ok(None);
+        }
+
+        let weighted_sum: f64 = spans.iter().map(|(value, secs)| value * secs).sum();
+        Ok(Some(weighted_sum / total_secs))
+    }
+
+    /// 计算开关量标签在时间范围内各状态的持续时间（秒）
+    ///
+    /// 返回按状态值排序的 (状态值, 持续秒数) 列表。
+    pub fn duration_in_state(
+        &self,
+        tag_name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<(f64, f64)>, Box<dyn std::error::Error + Send + Sync>> {
+        let spans = self.query_value_spans(tag_name, start_time, end_time)?;
+        Ok(sum_durations_by_state(&spans))
+    }
+
+    /// 生成开关量标签的运行状态报告（运行时长、启停次数、最长连续运行）
+    ///
+    /// 非零值视为运行状态，零值视为停止状态。
+    pub fn state_report(
+        &self,
+        tag_name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<StateReport, Box<dyn std::error::Error + Send + Sync>> {
+        let spans = self.query_value_spans(tag_name, start_time, end_time)?;
+
+        let mut run_time_secs = 0.0;
+        let mut starts = 0;
+        let mut stops = 0;
+        let mut longest_run_secs: f64 = 0.0;
+        let mut current_run_secs = 0.0;
+        let mut previous_running: Option<bool> = None;
+
+        for (value, secs) in &spans {
+            let running = *value != 0.0;
+
+            match (previous_running, running) {
+                (Some(false), true) => starts += 1,
+                (Some(true), false) => stops += 1,
+                _ => {}
+            }
+
+            if running {
+                run_time_secs += secs;
+                current_run_secs += secs;
+                longest_run_secs = longest_run_secs.max(current_run_secs);
+            } else {
+                current_run_secs = 0.0;
+            }
+
+            previous_running = Some(running);
+        }
+
+        let state_durations = sum_durations_by_state(&spans);
+
+        Ok(StateReport {
+            tag_name: tag_name.to_string(),
+            start_time,
+            end_time,
+            run_time_secs,
+            starts,
+            stops,
+            longest_run_secs,
+            state_durations,
+        })
+    }
+
+    /// 获取标签在 [start_time, end_time] 内的非空值，按时间升序返回
+    pub fn get_tag_values(
+        &self,
+        tag_name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<Point<f64>>, Box<dyn std::error::Error + Send + Sync>> {
+        let series = match self.tag_series_in_range(tag_name, start_time, end_time)? {
+            Some(series) => series,
+            None => return Ok(Vec::new()),
+        };
+
+        let conn = self.get_connection()?;
+        let sql = format!(
+            "SELECT ts, v FROM ({}) WHERE ts >= CAST(? AS TIMESTAMPTZ) AND ts <= CAST(? AS TIMESTAMPTZ) ORDER BY ts",
+            series.sql
+        );
+
+        let mut params = series.params;
+        params.extend([format_timestamp(&start_time), format_timestamp(&end_time)].map(Param::from));
+
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(duckdb::params_from_iter(params.iter()), |row| {
+            let ts: chrono::NaiveDateTime = row.get(0)?;
+            Ok((ts.and_utc(), row.get::<_, f64>(1)?))
+        })?;
+
+        let mut values = Vec::new();
+        for row in rows {
+            values.push(row?);
+        }
+
+        Ok(values)
+    }
+
+    /// 获取标签在 [start_time, end_time] 内的值，按时间升序返回，最多 `limit` 个点
+    ///
+    /// 给出 `bucket_secs` 时在 DuckDB 中按该宽度的时间桶聚合（桶按 UTC 对齐），每个桶返回桶起始时间与聚合值；
+    /// 否则返回原始的非空值。
+    pub fn query_tag_range(
+        &self,
+        tag_name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        bucket_secs: Option<u64>,
+        aggregation: Aggregation,
+        limit: usize,
+    ) -> Result<Vec<Point<f64>>, Box<dyn std::error::Error + Send + Sync>> {
+        if let (Some(secs), None) = (bucket_secs, aggregate_expr(aggregation)) {
+            let spans = self.query_timed_spans(tag_name, start_time, end_time)?;
+            let origin = DateTime::from_timestamp(TIME_BUCKET_ORIGIN_SECS, 0).unwrap_or_default();
+            let mut values = time_weighted_buckets(&spans, origin, secs, aggregation);
+            values.truncate(limit);
+            return Ok(values);
+        }
+        let series = match self.tag_series_in_range(tag_name, start_time, end_time)? {
+            Some(series) => series,
+            None => return Ok(Vec::new()),
+        };
+
+        let time_filter = "ts >= CAST(? AS TIMESTAMPTZ) AND ts <= CAST(? AS TIMESTAMPTZ)";
+        let sql = match (bucket_secs, aggregate_expr(aggregation)) {
+            (Some(secs), Some(value)) => format!(
+                "SELECT time_bucket(INTERVAL {} SECOND, CAST(ts AS TIMESTAMP)) AS bucket, {value} FROM ({}) WHERE {} \
+                 GROUP BY bucket HAVING {value} IS NOT NULL ORDER BY bucket LIMIT {}",
+                secs, series.sql, time_filter, limit
+            ),
+            _ => format!(
+                "SELECT CAST(ts AS TIMESTAMP), v FROM ({}) WHERE {} ORDER BY ts LIMIT {}",
+                series.sql, time_filter, limit
+            ),
+        };
+
+        let mut params = series.params;
+        params.extend([format_timestamp(&start_time), format_timestamp(&end_time)].map(Param::from));
+
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(duckdb::params_from_iter(params.iter()), |row| {
+            let ts: chrono::NaiveDateTime = row.get(0)?;
+            Ok((ts.and_utc(), row.get::<_, f64>(1)?))
+        })?;
+
+        let mut values = Vec::new();
+        for row in rows {
+            values.push(row?);
+        }
+
+        Ok(values)
+    }
+
+    /// 按固定宽度的窗口聚合标签在 [start_time, end_time) 内的值，按时间升序返回每个窗口的起始时间与聚合值
+    ///
+    /// 窗口从 `start_time` 开始对齐，没有数据（或样本不足以计算标准差）的窗口不返回。
+    /// 两种存储模式下的用法相同，调用方无需针对动态的宽表结构编写 SQL。
+    pub fn aggregate(
+        &self,
+        tag_name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        window_secs: u64,
+        aggregation: Aggregation,
+    ) -> Result<Vec<Point<f64>>, Box<dyn std::error::Error + Send + Sync>> {
+        if window_secs == 0 {
+            return Err("聚合窗口必须大于 0 秒".into());
+        }
+        let Some(value) = aggregate_expr(aggregation) else {
+            let spans = self.query_timed_spans(tag_name, start_time, end_time)?;
+            return Ok(time_weighted_buckets(&spans, start_time, window_secs, aggregation));
+        };
+        let series = match self.tag_series_in_range(tag_name, start_time, end_time)? {
+            Some(series) => series,
+            None => return Ok(Vec::new()),
+        };
+
+        // 序列放在 CTE 中，其参数先于桶起点与时间范围绑定
+        let sql = format!(
+            "WITH series AS ({}) \
+             SELECT time_bucket(INTERVAL {} SECOND, CAST(ts AS TIMESTAMP), CAST(CAST(? AS TIMESTAMPTZ) AS TIMESTAMP)) AS bucket, \
+             {value} FROM series WHERE ts >= CAST(? AS TIMESTAMPTZ) AND ts < CAST(? AS TIMESTAMPTZ) \
+             GROUP BY bucket HAVING {value} IS NOT NULL ORDER BY bucket",
+            series.sql, window_secs
+        );
+
+        let start_str = format_timestamp(&start_time);
+        let mut params = series.params;
+        params.extend([start_str.clone(), start_str, format_timestamp(&end_time)].map(Param::from));
+
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(duckdb::params_from_iter(params.iter()), |row| {
+            let ts: chrono::NaiveDateTime = row.get(0)?;
+            Ok((ts.and_utc(), row.get::<_, f64>(1)?))
+        })?;
+
+        let mut values = Vec::new();
+        for row in rows {
+            values.push(row?);
+        }
+
+        Ok(values)
+    }
+
+    /// 将标签的不规则采样重采样到从 `start_time` 开始、间隔 `step_secs` 的固定时间网格（含 `end_time`）
+    ///
+    /// 每个网格点返回一个值：`Previous` 取该点之前（含）的最后一个值，`Linear` 按前后两个样本线性插值；
+    /// 第一个样本之前（线性插值时还包括最后一个样本之后）的网格点为 None。
+    pub fn resample(
+        &self,
+        tag_name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        step_secs: u64,
+        fill: FillMethod,
+    ) -> Result<Vec<Point<Option<f64>>>, Box<dyn std::error::Error + Send + Sync>> {
+        if step_secs == 0 {
+            return Err("重采样间隔必须大于 0 秒".into());
+        }
+        let series = match self.tag_series_in_range(tag_name, start_time, end_time)? {
+            Some(series) => series,
+            None => sql::Statement {
+                sql: "SELECT CAST(NULL AS TIMESTAMPTZ) AS ts, CAST(NULL AS DOUBLE) AS v LIMIT 0".to_string(),
+                params: Vec::new(),
+            },
+        };
+
+        let value = match fill {
+            FillMethod::Previous => "prev.v",
+            FillMethod::Linear => "CASE WHEN prev.ts = grid.ts THEN prev.v \
+                 WHEN next.ts IS NULL THEN NULL \
+                 ELSE prev.v + (next.v - prev.v) * date_diff('millisecond', prev.ts, grid.ts) / date_diff('millisecond', prev.ts, next.ts) END",
+        };
+        // 网格点之前（含）与之后（含）最近的样本通过 ASOF JOIN 取得
+        let sql = format!(
+            "WITH samples AS (SELECT CAST(ts AS TIMESTAMP) AS ts, v FROM ({})),
+            grid AS (
+                SELECT ts FROM generate_series(
+                    CAST(CAST(? AS TIMESTAMPTZ) AS TIMESTAMP),
+                    CAST(CAST(? AS TIMESTAMPTZ) AS TIMESTAMP),
+                    INTERVAL {step_secs} SECOND
+                ) AS g(ts)
+            )
+            SELECT grid.ts, {value}
+            FROM grid
+            ASOF LEFT JOIN samples AS prev ON grid.ts >= prev.ts
+            ASOF LEFT JOIN samples AS next ON grid.ts <= next.ts
+            ORDER BY grid.ts",
+            series.sql
+        );
+
+        let mut params = series.params;
+        params.extend([format_timestamp(&start_time), format_timestamp(&end_time)].map(Param::from));
+
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(duckdb::params_from_iter(params.iter()), |row| {
+            let ts: chrono::NaiveDateTime = row.get(0)?;
+            Ok((ts.and_utc(), row.get::<_, Option<f64>>(1)?))
+        })?;
+
+        let mut values = Vec::new();
+        for row in rows {
+            values.push(row?);
+        }
+
+        Ok(values)
+    }
+
+    /// 获取字符串标签在 [start_time, end_time] 内的文本值，按时间升序返回
+    pub fn get_text_values(
+        &self,
+        tag_name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<Point<String>>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT CAST(DateTime AS TIMESTAMP), Value FROM ts_text
+             WHERE TagName = ? AND DateTime >= CAST(? AS TIMESTAMPTZ) AND DateTime <= CAST(? AS TIMESTAMPTZ)
+               AND Value IS NOT NULL
+             ORDER BY DateTime"
+        )?;
+
+        let start_str = format_timestamp(&start_time);
+        let end_str = format_timestamp(&end_time);
+        let rows = stmt.query_map([tag_name, &start_str, &end_str], |row| {
+            let ts: chrono::NaiveDateTime = row.get(0)?;
+            Ok((ts.and_utc(), row.get::<_, String>(1)?))
+        })?;
+
+        let mut values = Vec::new();
+        for row in rows {
+            values.push(row?);
+        }
+
+        Ok(values)
+    }
+
+    /// 宽表导出时选中的标签列（已加引号，不含 DateTime），`tags` 为空时为全部列
+    fn export_columns(&self, tags: &[String]) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut wide_columns = self.wide_columns.lock().unwrap();
+        let existing = self.ensure_wide_columns(&mut wide_columns)?;
+
+        if tags.is_empty() {
+            let mut columns: Vec<String> = existing.iter()
+                .filter(|c| c.as_str() != "DateTime")
+                .cloned()
+                .collect();
+            columns.sort();
+            return Ok(columns.iter().map(|c| Dialect::DuckDb.quote(c)).collect());
+        }
+
+        let mut columns = Vec::new();
+        for tag in tags {
+            match self.column_for(tag)? {
+                Some(column) if existing.contains(&column) => columns.push(Dialect::DuckDb.quote(&column)),
+                _ => warn!("导出时跳过不存在的标签: {}", tag),
+            }
+        }
+        Ok(columns)
+    }
+
+    /// 导出查询：宽表模式查询 ts_wide（`tags` 为空时为全部列），窄表模式查询 ts_long，时间范围为 [start_time, end_time)，
+    /// 未给出的边界不限；文本值与质量不导出
+    fn export_query(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        tags: &[String],
+    ) -> Result<sql::Statement, Box<dyn std::error::Error + Send + Sync>> {
+        let mut select = match self.config.storage_mode {
+            StorageMode::Wide => {
+                let columns = self.export_columns(tags)?;
+                if columns.is_empty() {
+                    return Err("没有可导出的标签列".into());
+                }
+                sql::Select::from(Dialect::DuckDb, "ts_wide").column("DateTime").expr(columns.join(", "))
+            }
+            StorageMode::Long => {
+                let select = sql::Select::from(Dialect::DuckDb, "ts_long").column("DateTime").column("TagName").column("Value");
+                if tags.is_empty() { select } else { select.filter_in("TagName", tags) }
+            }
+        };
+        if let Some(start_time) = start_time {
+            select = select.filter("DateTime", sql::Op::Ge, format_timestamp(&start_time));
+        }
+        if let Some(end_time) = end_time {
+            select = select.filter("DateTime", sql::Op::Lt, format_timestamp(&end_time));
+        }
+        select = select.order_by("DateTime");
+        if self.config.storage_mode == StorageMode::Long {
+            select = select.order_by("TagName");
+        }
+        Ok(select.build())
+    }
+
+    /// 导出数据为 Parquet 文件，返回导出的行数，查询范围见 [`export_query`](Self::export_query)；
+    /// 启用 `export.watermark` 时来源信息写入文件的键值元数据
+    pub fn export_parquet(
+        &self,
+        path: &Path,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        tags: &[String],
+    ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let query = self.export_query(start_time, end_time, tags)?;
+        let conn = self.get_connection()?;
+        let target = sql::literal(&path.to_string_lossy());
+        let metadata = if self.config.export.watermark {
+            let entries: Vec<String> = self.provenance(&query).iter()
+                .map(|(key, value)| format!("{}: {}", sql::literal(key), sql::literal(value)))
+                .collect();
+            format!(", KV_METADATA {{{}}}", entries.join(", "))
+        } else {
+            String::new()
+        };
+        let rows = conn.execute(
+            &format!("COPY ({}) TO {} (FORMAT PARQUET, COMPRESSION ZSTD{})", query.sql, target, metadata),
+            duckdb::params_from_iter(query.params.iter()),
+        )?;
+        debug!("已导出 {} 行到 {}", rows, path.display());
+        Ok(rows)
+    }
+
+    /// 以 Arrow IPC 流格式写出数据（`pyarrow.ipc.open_stream` 可直接读取），返回导出的行数，
+    /// 查询范围见 [`export_query`](Self::export_query)；启用 `export.watermark` 时来源信息写入 schema 元数据
+    pub fn export_arrow<W: std::io::Write>(
+        &self,
+        writer: W,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        tags: &[String],
+    ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let query = self.export_query(start_time, end_time, tags)?;
+        let metadata = if self.config.export.watermark {
+            self.provenance(&query).into_iter().map(|(key, value)| (key.to_string(), value)).collect()
+        } else {
+            std::collections::HashMap::new()
+        };
+        let rows = self.write_arrow_stream(&query, metadata, writer)?;
+        debug!("已导出 {} 行 Arrow 数据", rows);
+        Ok(rows)
+    }
+
+    /// 逐批写出查询结果的 Arrow IPC 流，`metadata` 写入 schema 元数据，返回写出的行数；结果不在内存中整体缓存
+    fn write_arrow_stream<W: std::io::Write>(
+        &self,
+        query: &sql::Statement,
+        metadata: std::collections::HashMap<String, String>,
+        writer: W,
+    ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(&query.sql)?;
+        let batches = stmt.stream_arrow(duckdb::params_from_iter(query.params.iter()))?;
+
+        let schema = Arc::new(batches.get_schema().as_ref().clone().with_metadata(metadata));
+        let mut writer = arrow::ipc::writer::StreamWriter::try_new(writer, &schema)?;
+        let mut rows = 0;
+        for batch in batches {
+            rows += batch.num_rows();
+            writer.write(&batch.with_schema(schema.clone())?)?;
+        }
+        writer.finish()?;
+        Ok(rows)
+    }
+
+    /// 执行只读查询（由调用方校验），最多返回 `limit` 行，返回结果批次与是否被截断
+    pub fn query_batches(
+        &self,
+        query: &sql::Statement,
+        limit: usize,
+    ) -> Result<(Vec<arrow::record_batch::RecordBatch>, bool), Box<dyn std::error::Error + Send + Sync>> {
+        // 包装为子查询：只有查询语句可以作为子查询，同时由 DuckDB 限制行数；多取一行用于判断是否截断
+        let sql = format!("SELECT * FROM (\n{}\n) AS passthrough LIMIT {}", query.sql, limit + 1);
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(&sql)?;
+
+        let mut batches = Vec::new();
+        let mut rows = 0;
+        let mut truncated = false;
+        for batch in stmt.query_arrow(duckdb::params_from_iter(query.params.iter()))? {
+            if rows + batch.num_rows() > limit {
+                truncated = true;
+                batches.push(batch.slice(0, limit - rows));
+                break;
+            }
+            rows += batch.num_rows();
+            batches.push(batch);
+        }
+
+        Ok((batches, truncated))
+    }
+    
+    /// 查询标签在 [start_time, end_time) 内的数值，按 `shape` 返回宽表或窄表形状的结果批次，与存储模式无关；
+    /// `tags` 为空时为全部已知标签，最多返回 `limit` 行，返回结果批次与是否被截断
+    ///
+    /// 宽表形状的列名为标签名（而非宽表中的列名），没有数据的标签为全空列；窄表形状的列为 DateTime、TagName、Value。
+    pub fn query_table(
+        &self,
+        tags: &[String],
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        shape: TableShape,
+        limit: usize,
+    ) -> Result<(Vec<arrow::record_batch::RecordBatch>, bool), Box<dyn std::error::Error + Send + Sync>> {
+        let query = self.table_query(tags, start_time, end_time, shape, MissingCells::Null)?;
+        self.query_batches(&query, limit)
+    }
+
+    /// 查询标签在 [start_time, end_time) 内按时间对齐的数值：每个时间戳一行、每个标签一列（宽表模式直接读取 ts_wide，
+    /// 窄表模式按时间转置），缺少值的单元格按 `missing` 处理；`tags` 为空时为全部已知标签，最多返回 `limit` 行
+    pub fn query_frame(
+        &self,
+        tags: &[String],
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        missing: MissingCells,
+        limit: usize,
+    ) -> Result<(Vec<arrow::record_batch::RecordBatch>, bool), Box<dyn std::error::Error + Send + Sync>> {
+        let query = self.table_query(tags, start_time, end_time, TableShape::Wide, missing)?;
+        self.query_batches(&query, limit)
+    }
+
+    /// 与 [`query_table`](Self::query_table)/[`query_frame`](Self::query_frame) 相同的查询，不限行数，
+    /// 以 Arrow IPC 流逐批写出，返回写出的行数；用于长时间范围、大量标签的查询
+    pub fn stream_table<W: std::io::Write>(
+        &self,
+        writer: W,
+        tags: &[String],
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        shape: TableShape,
+        missing: MissingCells,
+    ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let query = self.table_query(tags, start_time, end_time, shape, missing)?;
+        let rows = self.write_arrow_stream(&query, std::collections::HashMap::new(), writer)?;
+        debug!("已流式返回 {} 行 Arrow 数据", rows);
+        Ok(rows)
+    }
+
+    /// 多标签表格查询，`missing` 只作用于宽表形状
+    fn table_query(
+        &self,
+        tags: &[String],
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        shape: TableShape,
+        missing: MissingCells,
+    ) -> Result<sql::Statement, Box<dyn std::error::Error + Send + Sync>> {
+        let tags = self.resolve_query_tags(tags)?;
+        if shape == TableShape::Long {
+            return self.long_shape_query(&tags, start_time, end_time);
+        }
+
+        let frame = self.wide_shape_query(&tags, start_time, end_time)?;
+        let columns: Vec<String> = tags.iter().map(|t| Dialect::DuckDb.quote(t)).collect();
+        Ok(match missing {
+            MissingCells::Null => frame,
+            MissingCells::Previous => {
+                let filled: Vec<String> = columns.iter()
+                    .map(|c| format!("LAST_VALUE({c} IGNORE NULLS) OVER (ORDER BY DateTime ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW) AS {c}"))
+                    .collect();
+                sql::Select::from_query(Dialect::DuckDb, frame)
+                    .column("DateTime")
+                    .expr(filled.join(", "))
+                    .order_by("DateTime")
+                    .build()
+            }
+            MissingCells::Drop => {
+                columns.iter()
+                    .fold(sql::Select::from_query(Dialect::DuckDb, frame), |select, c| select.condition(format!("{} IS NOT NULL", c)))
+                    .order_by("DateTime")
+                    .build()
+            }
+        })
+    }
+
+    /// 多标签查询的标签列表，为空时为全部已知标签（按名称排序）
+    fn resolve_query_tags(&self, tags: &[String]) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut tags = tags.to_vec();
+        if tags.is_empty() {
+            tags = self.get_known_tags().into_iter().collect();
+            tags.sort();
+        }
+        if tags.is_empty() {
+            return Err("没有可查询的标签".into());
+        }
+        Ok(tags)
+    }
+
+    /// 宽表形状的查询：宽表模式直接选取并以标签名重命名列，窄表模式用 PIVOT 转置
+    fn wide_shape_query(
+        &self,
+        tags: &[String],
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<sql::Statement, Box<dyn std::error::Error + Send + Sync>> {
+        let in_range = |select: sql::Select| {
+            select
+                .filter("DateTime", sql::Op::Ge, format_timestamp(&start_time))
+                .filter("DateTime", sql::Op::Lt, format_timestamp(&end_time))
+        };
+
+        Ok(match self.config.storage_mode {
+            StorageMode::Wide => {
+                let mut select = vec!["DateTime".to_string()];
+                let mut present = Vec::new();
+                for tag in tags {
+                    match self.column_for(tag)? {
+                        Some(column) if self.wide_column_exists(&column)? => {
+                            let column = Dialect::DuckDb.quote(&column);
+                            select.push(format!("{} AS {}", column, Dialect::DuckDb.quote(tag)));
+                            present.push(format!("{} IS NOT NULL", column));
+                        }
+                        _ => select.push(format!("CAST(NULL AS DOUBLE) AS {}", Dialect::DuckDb.quote(tag))),
+                    }
+                }
+                // 只保留所选标签中至少有一个值的行
+                let present = if present.is_empty() { "FALSE".to_string() } else { format!("({})", present.join(" OR ")) };
+                in_range(sql::Select::from(Dialect::DuckDb, "ts_wide").expr(select.join(", ")))
+                    .condition(present)
+                    .order_by("DateTime")
+                    .build()
+            }
+            StorageMode::Long => {
+                // 按时间分组、每个标签一列（与 PIVOT 相同），标签名作为参数绑定
+                let columns: Vec<String> = tags.iter()
+                    .map(|t| format!("FIRST(Value) FILTER (WHERE TagName = ?) AS {}", Dialect::DuckDb.quote(t)))
+                    .collect();
+                in_range(sql::Select::from(Dialect::DuckDb, "ts_long").column("DateTime").expr_bound(columns.join(", "), tags))
+                    .filter_in("TagName", tags)
+                    .not_null("Value")
+                    .group_by("DateTime")
+                    .order_by("DateTime")
+                    .build()
+            }
+        })
+    }
+
+    /// 窄表形状的查询：窄表模式直接查询 ts_long，宽表模式将各标签列以标签名展开为行
+    fn long_shape_query(
+        &self,
+        tags: &[String],
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<sql::Statement, Box<dyn std::error::Error + Send + Sync>> {
+        let in_range = |select: sql::Select| {
+            select
+                .filter("DateTime", sql::Op::Ge, format_timestamp(&start_time))
+                .filter("DateTime", sql::Op::Lt, format_timestamp(&end_time))
+        };
+
+        let source = match self.config.storage_mode {
+            StorageMode::Wide => {
+                let mut parts = Vec::new();
+                for tag in tags {
+                    let Some(column) = self.column_for(tag)? else {
+                        continue;
+                    };
+                    if !self.wide_column_exists(&column)? {
+                        continue;
+                    }
+                    let select = sql::Select::from(Dialect::DuckDb, "ts_wide")
+                        .column("DateTime")
+                        .expr_bound("CAST(? AS VARCHAR) AS TagName", [tag])
+                        .expr(format!("{} AS Value", Dialect::DuckDb.quote(&column)));
+                    parts.push(in_range(select).not_null(&column).build());
+                }
+                if parts.is_empty() {
+                    sql::Statement::new(
+                        "SELECT CAST(NULL AS TIMESTAMPTZ) AS DateTime, CAST(NULL AS VARCHAR) AS TagName, CAST(NULL AS DOUBLE) AS Value LIMIT 0",
+                    )
+                } else {
+                    sql::Statement::union_all(parts)
+                }
+            }
+            StorageMode::Long => {
+                in_range(sql::Select::from(Dialect::DuckDb, "ts_long").column("DateTime").column("TagName").column("Value"))
+                    .filter_in("TagName", tags)
+                    .not_null("Value")
+                    .build()
+            }
+        };
+        Ok(sql::Select::from_query(Dialect::DuckDb, source).order_by("DateTime").order_by("TagName").build())
+    }
+
+    /// 导出数据为 CSV 文件，返回生成的文件及总行数，时间范围为 [start_time, end_time)
+    ///
+    /// `tags` 为空时导出全部标签。宽表模式下列数超过 `layout.max_columns` 时，
+    /// 按 `layout.wide_overflow` 将列拆分到多个文件（`_part1`、`_part2` ...）或转为窄表格式导出。
+    pub fn export_csv(
+        &self,
+        path: &Path,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        tags: &[String],
+        layout: ExportLayout,
+    ) -> Result<(Vec<std::path::PathBuf>, usize), Box<dyn std::error::Error + Send + Sync>> {
+        let time_column = self.csv_time_column(layout.timestamps);
+        let in_range = |select: sql::Select| {
+            select
+                .filter("DateTime", sql::Op::Ge, format_timestamp(&start_time))
+                .filter("DateTime", sql::Op::Lt, format_timestamp(&end_time))
+        };
+        let with_tags = |select: sql::Select| if tags.is_empty() { select } else { select.filter_in("TagName", tags) };
+        let long_query = |select: sql::Select| {
+            select.expr(time_column.clone()).column("TagName").column("Value").order_by("DateTime").order_by("TagName").build()
+        };
+
+        let mut queries: Vec<(std::path::PathBuf, sql::Statement)> = match self.config.storage_mode {
+            StorageMode::Long => {
+                vec![(path.to_path_buf(), long_query(with_tags(in_range(sql::Select::from(Dialect::DuckDb, "ts_long")))))]
+            }
+            StorageMode::Wide => {
+                let columns = self.export_columns(tags)?;
+                let max_columns = layout.max_columns.unwrap_or(usize::MAX).max(1);
+
+                if columns.len() <= max_columns {
+                    let select = sql::Select::from(Dialect::DuckDb, "ts_wide").expr(time_column.clone()).expr(columns.join(", "));
+                    vec![(path.to_path_buf(), in_range(select).order_by("DateTime").build())]
+                } else {
+                    match layout.wide_overflow {
+                        WideOverflow::Long => {
+                            info!("导出列数 {} 超过阈值 {}，转为窄表格式", columns.len(), max_columns);
+                            let wide = in_range(sql::Select::from(Dialect::DuckDb, "ts_wide").column("DateTime").expr(columns.join(", "))).build();
+                            let source = sql::Statement {
+                                sql: format!("UNPIVOT ({}) ON COLUMNS(* EXCLUDE (DateTime)) INTO NAME TagName VALUE Value", wide.sql),
+                                params: wide.params,
+                            };
+                            vec![(path.to_path_buf(), long_query(sql::Select::from_query(Dialect::DuckDb, source)))]
+                        }
+                        WideOverflow::Split => {
+                            info!("导出列数 {} 超过阈值 {}，按列拆分为多个文件", columns.len(), max_columns);
+                            let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                            let extension = path.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_else(|| "csv".to_string());
+                            columns.chunks(max_columns)
+                                .enumerate()
+                                .map(|(i, chunk)| {
+                                    let file = path.with_file_name(format!("{}_part{}.{}", stem, i + 1, extension));
+                                    let select = sql::Select::from(Dialect::DuckDb, "ts_wide").expr(time_column.clone()).expr(chunk.join(", "));
+                                    (file, in_range(select).order_by("DateTime").build())
+                                })
+                                .collect()
+                        }
+                    }
+                }
+            }
+        };
+
+        let conn = self.get_connection()?;
+
+        // 字符串标签的文本值单独导出为 <文件名>_text.csv（窄表格式）
+        let text_count = with_tags(in_range(sql::Select::from(Dialect::DuckDb, "ts_text").expr("COUNT(*)"))).build();
+        let text_rows: i64 = conn.query_row(&text_count.sql, duckdb::params_from_iter(text_count.params.iter()), |row| row.get(0))?;
+        if text_rows > 0 {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            queries.push((
+                path.with_file_name(format!("{}_text.csv", stem)),
+                long_query(with_tags(in_range(sql::Select::from(Dialect::DuckDb, "ts_text")))),
+            ));
+        }
+
+        let mut files = Vec::with_capacity(queries.len());
+        let mut total_rows = 0;
+        for (file, query) in queries {
+            let target = sql::literal(&file.to_string_lossy());
+            let rows = conn.execute(
+                &format!("COPY ({}) TO {} (HEADER, DELIMITER ',')", query.sql, target),
+                duckdb::params_from_iter(query.params.iter()),
+            )?;
+            if self.config.export.watermark {
+                prepend_csv_comment(&file, &self.provenance(&query))?;
+            }
+            debug!("已导出 {} 行到 {}", rows, file.display());
+            total_rows += rows;
+            files.push(file);
+        }
+
+        Ok((files, total_rows))
+    }
+
+    /// CSV 导出中 DateTime 列的表达式；UTC 与本地时间格式化为 Excel 可识别的 `YYYY-MM-DD HH:MM:SS`
+    fn csv_time_column(&self, timestamps: ExportTimestamps) -> String {
+        let offset_hours = match timestamps {
+            ExportTimestamps::Native => return "DateTime".to_string(),
+            ExportTimestamps::Utc => 0,
+            ExportTimestamps::Local => self.config.source_timezone_offset_hours,
+        };
+        format!(
+            "strftime(CAST(DateTime AS TIMESTAMP) + INTERVAL ({}) HOUR, '%Y-%m-%d %H:%M:%S') AS DateTime",
+            offset_hours
+        )
+    }
+
+    /// 导出文件的来源信息：站点、导出时间、rt_db 版本与查询摘要（语句与绑定的参数）
+    pub fn provenance(&self, query: &sql::Statement) -> Vec<(&'static str, String)> {
+        let digest = if query.params.is_empty() {
+            crate::data_source::sql_digest(&query.sql)
+        } else {
+            crate::data_source::sql_digest(&format!("{}\n{:?}", query.sql, query.params))
+        };
+        vec![
+            ("site_id", self.config.export.site_id.clone().unwrap_or_default()),
+            ("exported_at", Utc::now().to_rfc3339()),
+            ("rt_db_version", env!("CARGO_PKG_VERSION").to_string()),
+            ("query_hash", digest),
+        ]
+    }
+
+    /// 将整个数据库导出到目录（EXPORT DATABASE，CSV 格式），导出在单个事务内完成，结果一致
+    pub fn export_database(&self, dir: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let target = sql::literal(&dir.to_string_lossy());
+        let conn = self.get_connection()?;
+        conn.execute_batch(&format!("EXPORT DATABASE {} (FORMAT CSV, HEADER)", target))?;
+        debug!("数据库已导出到 {}", dir.display());
+        Ok(())
+    }
+
+    /// 将整个数据库备份到目录（EXPORT DATABASE，Parquet 格式），导出在单个事务内完成，可用 IMPORT DATABASE 恢复
+    pub fn backup_to(&self, dir: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let target = sql::literal(&dir.to_string_lossy());
+        let conn = self.get_connection()?;
+        conn.execute_batch(&format!("EXPORT DATABASE {} (FORMAT PARQUET, COMPRESSION ZSTD)", target))?;
+        Ok(())
+    }
+
+    /// 从备份目录导入（IMPORT DATABASE），用于恢复到新的空数据库文件
+    pub fn import_database(&self, dir: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let source = sql::literal(&dir.to_string_lossy());
+        let conn = self.get_connection()?;
+        conn.execute_batch(&format!("IMPORT DATABASE {}", source))?;
+        Ok(())
+    }
+
+    /// 数据表中存储的全部标签（宽表模式取自 tag_columns），按名称排序
+    pub fn stored_tags(&self) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let sql = match self.config.storage_mode {
+            StorageMode::Wide => "SELECT TagName FROM tag_columns ORDER BY TagName",
+            StorageMode::Long => "SELECT DISTINCT TagName FROM ts_long ORDER BY TagName",
+        };
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(sql)?;
+        let tags = stmt.query_map([], |row| row.get(0))?.collect::<Result<Vec<String>, _>>()?;
+        Ok(tags)
+    }
+
+    /// 获取标签最近的 N 个非空值，按时间升序返回
+    pub fn get_recent_values(&self, tag_name: &str, limit: usize) -> Result<Vec<Point<f64>>, Box<dyn std::error::Error + Send + Sync>> {
+        let series = match self.tag_series_sql(tag_name)? {
+            Some(series) => series,
+            None => return Ok(Vec::new()),
+        };
+
+        let conn = self.get_connection()?;
+        let sql = format!(
+            "SELECT ts, v FROM ({}) ORDER BY ts DESC LIMIT {}",
+            series.sql, limit
+        );
+
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(duckdb::params_from_iter(series.params.iter()), |row| {
+            let ts: chrono::NaiveDateTime = row.get(0)?;
+            Ok((ts.and_utc(), row.get::<_, f64>(1)?))
+        })?;
+
+        let mut values = Vec::new();
+        for row in rows {
+            values.push(row?);
+        }
+        values.reverse();
+
+        Ok(values)
+    }
+
+    /// 替换标签的趋势预测
+    pub fn replace_forecast(
+        &self,
+        tag_name: &str,
+        issued_at: DateTime<Utc>,
+        points: &[(DateTime<Utc>, f64)],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        conn.execute("DELETE FROM ts_forecast WHERE TagName = ?", [tag_name])?;
+
+        let mut stmt = conn.prepare(
+            "INSERT INTO ts_forecast (DateTime, TagName, Forecast, IssuedAt) VALUES (?, ?, ?, ?)"
+        )?;
+        let issued_at = format_timestamp(&issued_at);
+        for (timestamp, value) in points {
+            stmt.execute(duckdb::params![format_timestamp(timestamp), tag_name, value, issued_at])?;
+        }
+
+        Ok(())
+    }
+    
+    /// 读取标签的最新非空值（来自随写入维护的最新值表），没有数据的标签不出现在结果中
+    pub fn get_latest_values(&self, tags: &[String]) -> Result<std::collections::HashMap<String, LatestValue>, Box<dyn std::error::Error + Send + Sync>> {
+        if tags.is_empty() {
+            return Ok(std::collections::HashMap::new());
+        }
+
+        let conn = self.get_connection()?;
+        let statement = sql::Select::from(Dialect::DuckDb, "ts_latest")
+            .column("TagName")
+            .expr("CAST(DateTime AS TIMESTAMP)")
+            .column("Value")
+            .filter_in("TagName", tags)
+            .build();
+
+        let mut stmt = conn.prepare(&statement.sql)?;
+        let rows = stmt.query_map(duckdb::params_from_iter(statement.params.iter()), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                LatestValue {
+                    timestamp: row.get::<_, chrono::NaiveDateTime>(1)?.and_utc(),
+                    value: row.get(2)?,
+                },
+            ))
+        })?;
+
+        let mut latest = std::collections::HashMap::new();
+        for row in rows {
+            let (tag, value) = row?;
+            latest.insert(tag, value);
+        }
+
+        Ok(latest)
+    }
+    
+    /// 以 TagDatabase 的当前元数据替换 tag_meta 表，在同步周期事务中调用时随周期一起提交
+    pub fn replace_tag_meta(&self, meta: &[TagMeta], cycle: Option<&Cycle>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.write_connection(cycle)?;
+        conn.execute("DELETE FROM tag_meta", [])?;
+
+        const BATCH_SIZE: usize = 1000;
+        let insert = Insert::into(Dialect::DuckDb, "tag_meta")
+            .columns(["TagName", "Unit", "Description", "MinValue", "MaxValue", "InOutFlag"]);
+        for chunk in meta.chunks(BATCH_SIZE) {
+            let mut params: Vec<Param> = Vec::with_capacity(chunk.len() * 6);
+            for tag in chunk {
+                params.push((&tag.tag_name).into());
+                params.push(tag.unit.clone().into());
+                params.push(tag.description.clone().into());
+                params.push(tag.min_value.into());
+                params.push(tag.max_value.into());
+                params.push(tag.in_out_flag.clone().into());
+            }
+            
+            conn.execute(&insert.sql(chunk.len()), duckdb::params_from_iter(params.iter()))?;
+        }
+
+        debug!("已更新 {} 个标签的元数据", meta.len());
+        Ok(())
+    }
+    
+    /// 读取标签元数据，按标签名排序，`tags` 为空时返回全部标签
+    pub fn get_tag_meta(&self, tags: &[String]) -> Result<Vec<TagMeta>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let mut select = sql::Select::from(Dialect::DuckDb, "tag_meta")
+            .column("TagName")
+            .column("Unit")
+            .column("Description")
+            .column("MinValue")
+            .column("MaxValue")
+            .column("InOutFlag");
+        if !tags.is_empty() {
+            select = select.filter_in("TagName", tags);
+        }
+        let statement = select.order_by("TagName").build();
+
+        let mut stmt = conn.prepare(&statement.sql)?;
+        let rows = stmt.query_map(duckdb::params_from_iter(statement.params.iter()), |row| {
+            Ok(TagMeta {
+                tag_name: row.get(0)?,
+                unit: row.get(1)?,
+                description: row.get(2)?,
+                min_value: row.get(3)?,
+                max_value: row.get(4)?,
+                in_out_flag: row.get(5)?,
+            })
+        })?;
+
+        let mut meta = Vec::new();
+        for row in rows {
+            meta.push(row?);
+        }
+
+        Ok(meta)
+    }
+    
+    /// 读取标签的趋势预测，`tags` 为空时返回全部标签
+    pub fn forecasts(&self, tags: &[String]) -> Result<std::collections::HashMap<String, Forecast>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        let mut query = sql::Select::from(Dialect::DuckDb, "ts_forecast")
+            .column("TagName")
+            .expr("CAST(DateTime AS TIMESTAMP)")
+            .column("Forecast")
+            .expr("CAST(IssuedAt AS TIMESTAMP)");
+        if !tags.is_empty() {
+            query = query.filter_in("TagName", tags);
+        }
+        let statement = query.order_by("TagName").order_by("DateTime").build();
+
+        let mut stmt = conn.prepare(&statement.sql)?;
+        let rows = stmt.query_map(duckdb::params_from_iter(statement.params.iter()), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, chrono::NaiveDateTime>(1)?.and_utc(),
+                row.get::<_, f64>(2)?,
+                row.get::<_, chrono::NaiveDateTime>(3)?.and_utc(),
+            ))
+        })?;
+
+        let mut forecasts: std::collections::HashMap<String, Forecast> = std::collections::HashMap::new();
+        for row in rows {
+            let (tag, timestamp, value, issued_at) = row?;
+            let forecast = forecasts.entry(tag).or_insert_with(|| Forecast {
+                issued_at,
+                timestamps: Vec::new(),
+                values: Vec::new(),
+            });
+            forecast.timestamps.push(timestamp);
+            forecast.values.push(value);
+        }
+
+        Ok(forecasts)
+    }
+    
+    /// 写入 SPC 违规事件到 spc_events 表
+    pub fn insert_spc_events(&self, events: &[crate::spc::SpcEvent]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(
+            "INSERT INTO spc_events (DateTime, TagName, Rule, Value, Mean, Sigma) VALUES (?, ?, ?, ?, ?, ?)"
+        )?;
+        for event in events {
+            stmt.execute(duckdb::params![
+                format_timestamp(&event.timestamp),
+                event.tag_name,
+                event.rule.code(),
+                event.value,
+                event.mean,
+                event.sigma,
+            ])?;
+        }
+
+        Ok(())
+    }
+
+    /// 按聚合方式计算标签在 [start_time, end_time) 内的值，无数据时为 None
+    pub fn aggregate_tag_value(
+        &self,
+        tag_name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        aggregation: Aggregation,
+    ) -> Result<Option<f64>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(value) = aggregate_expr(aggregation) else {
+            if aggregation == Aggregation::TimeWeightedAvg {
+                return self.time_weighted_average(tag_name, start_time, end_time);
+            }
+            // 非零（运行）状态的秒数，区间内没有任何值时为 None
+            let durations = self.duration_in_state(tag_name, start_time, end_time)?;
+            if durations.is_empty() {
+                return Ok(None);
+            }
+            return Ok(Some(durations.iter().filter(|(state, _)| *state != 0.0).map(|(_, secs)| secs).sum()));
+        };
+        let series = match self.tag_series_sql(tag_name)? {
+            Some(series) => series,
+            None => return Ok(None),
+        };
+
+        let sql = format!(
+            "SELECT {} FROM ({}) WHERE ts >= CAST(? AS TIMESTAMPTZ) AND ts < CAST(? AS TIMESTAMPTZ)",
+            value, series.sql
+        );
+
+        let mut params = series.params;
+        params.extend([format_timestamp(&start_time), format_timestamp(&end_time)].map(Param::from));
+
+        let conn = self.get_connection()?;
+        let value: Option<f64> = conn.query_row(&sql, duckdb::params_from_iter(params.iter()), |row| row.get(0))?;
+
+        Ok(value)
+    }
+
+    /// 记录一次 REST 推送的投递结果到 integration_deliveries 表
+    pub fn record_delivery(
+        &self,
+        endpoint: &str,
+        window_end: DateTime<Utc>,
+        attempts: u32,
+        status_code: Option<u16>,
+        error: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.get_connection()?;
+        conn.execute(
+            "INSERT INTO integration_deliveries (Endpoint, WindowEnd, Attempts, StatusCode, Success, Error) VALUES (?, ?, ?, ?, ?, ?)",
+            duckdb::params![
+                endpoint,
+                format_timestamp(&window_end),
+                attempts,
+                status_code,
+                error.is_none(),
+                error,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// 刷新全部标签最近 `window_hours` 小时的缩略趋势，返回标签数量
+    ///
+    /// `since` 为本周期写入数据的最早时间：只重新计算其与上次刷新时间中较早者所在的桶及之后的桶，并剔除移出窗口的桶；
+    /// 首次刷新、窗口参数变化或调用过 `reset_sparklines` 后重新计算整个窗口。
+    pub fn refresh_sparklines(&self, window_hours: u32, points: u32, since: DateTime<Utc>) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let window_secs = i64::from(window_hours) * 3600;
+        let bucket_secs = (window_secs / i64::from(points.max(1))).max(1);
+        let now = Utc::now();
+        let window_start = now - chrono::Duration::seconds(window_secs);
+        let first_bucket = window_start.timestamp().div_euclid(bucket_secs) * bucket_secs;
+
+        let mut params = self.sparkline_params.lock().unwrap();
+        // 上次刷新之后快速组、低延迟轮询与 API 写入的数据也需重新计算
+        let last_refreshed = params.filter(|(hours, count, _)| (*hours, *count) == (window_hours, points))
+            .map(|(_, _, refreshed)| refreshed);
+        let incremental = last_refreshed.is_some();
+        let (from, recompute_from) = match last_refreshed {
+            Some(refreshed) => {
+                let since = since.min(refreshed);
+                let recompute_from = since.timestamp().div_euclid(bucket_secs) * bucket_secs;
+                let from = DateTime::<Utc>::from_timestamp(recompute_from, 0).unwrap_or(since).max(window_start);
+                (from, recompute_from)
+            }
+            None => (window_start, i64::MIN),
+        };
+
+        // 宽表先 UNPIVOT 为 (DateTime, TagName, Value)，列名按 tag_columns 映射回标签名，与窄表统一处理；UNPIVOT 会丢弃 NULL
+        let source = match self.config.storage_mode {
+            StorageMode::Wide => "SELECT u.DateTime, c.TagName, u.Value FROM
+                 (UNPIVOT (SELECT * FROM ts_wide WHERE DateTime >= CAST(? AS TIMESTAMPTZ))
+                  ON COLUMNS(* EXCLUDE (DateTime)) INTO NAME ColumnName VALUE Value) AS u
+                 JOIN tag_columns AS c ON c.ColumnName = u.ColumnName",
+            StorageMode::Long => "SELECT DateTime, TagName, Value FROM ts_long
+                 WHERE DateTime >= CAST(? AS TIMESTAMPTZ) AND Value IS NOT NULL",
+        };
+
+        let sql = format!(
+            "SELECT TagName, CAST(FLOOR(epoch(CAST(DateTime AS TIMESTAMP)) / {bucket}) AS BIGINT) * {bucket} AS bucket, AVG(Value)
+             FROM ({source})
+             GROUP BY TagName, bucket
+             ORDER BY TagName, bucket",
+            bucket = bucket_secs,
+            source = source
+        );
+
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map([format_timestamp(&from)], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, f64>(2)?))
+        })?;
+
+        // 保留窗口内、重新计算范围之前的桶
+        let mut sparklines: std::collections::HashMap<String, Sparkline> = std::collections::HashMap::new();
+        if incremental {
+            for (tag, sparkline) in self.sparklines().iter() {
+                let (timestamps, values) = sparkline.timestamps.iter().zip(&sparkline.values)
+                    .filter(|(timestamp, _)| (first_bucket..recompute_from).contains(&timestamp.timestamp()))
+                    .unzip();
+                sparklines.insert(tag.clone(), Sparkline { timestamps, values });
+            }
+        }
+        for row in rows {
+            let (tag, bucket, value) = row?;
+            let Some(timestamp) = DateTime::<Utc>::from_timestamp(bucket, 0) else {
+                continue;
+            };
+            let sparkline = sparklines.entry(tag).or_insert_with(|| Sparkline {
+                timestamps: Vec::new(),
+                values: Vec::new(),
+            });
+            sparkline.timestamps.push(timestamp);
+            sparkline.values.push(value);
+        }
+        sparklines.retain(|_, sparkline| !sparkline.timestamps.is_empty());
+
+        let count = sparklines.len();
+        *self.sparklines.write().unwrap() = Arc::new(sparklines);
+        *params = Some((window_hours, points, now));
+        Ok(count)
+    }
+
+    /// 使下次刷新重新计算整个窗口的缩略趋势（回填了较早的数据或删除了标签数据后调用）
+    pub fn reset_sparklines(&self) {
+        *self.sparkline_params.lock().unwrap() = None;
+    }
+
+    /// 获取最近一次预计算的缩略趋势
+    pub fn sparklines(&self) -> Arc<std::collections::HashMap<String, Sparkline>> {
+        self.sparklines.read().unwrap().clone()
+    }
+
+    /// 读取时间范围 [from, to] 内晚于 `after` 的最多 `limit` 个有数据的时间点的全部非空数据（变更流），按时间升序
+    ///
+    /// 包含数值、字符串标签的文本值（ts_text）与数据质量（ts_quality），副本据此完整复现主实例的数据。
+    /// 按完整时间点分页，未给出的边界不限；返回的 `next` 作为下一页的 `after`。
+    pub fn changes(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        after: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> Result<ChangesPage, Box<dyn std::error::Error + Send + Sync>> {
+        let mut filters = vec!["TRUE"];
+        let mut params = Vec::new();
+        for (bound, filter) in [
+            (from, "DateTime >= CAST(? AS TIMESTAMPTZ)"),
+            (to, "DateTime <= CAST(? AS TIMESTAMPTZ)"),
+            (after, "DateTime > CAST(? AS TIMESTAMPTZ)"),
+        ] {
+            if let Some(bound) = bound {
+                filters.push(filter);
+                params.push(format_timestamp(&bound));
+            }
+        }
+        let filter = filters.join(" AND ");
+
+        let numeric = match self.config.storage_mode {
+            // 宽表列名按 tag_columns 映射回标签名；UNPIVOT 不输出空值
+            StorageMode::Wide => format!(
+                "SELECT u.DateTime, c.TagName, u.Value FROM
+                 (UNPIVOT (SELECT * FROM ts_wide WHERE {filter}) ON COLUMNS(* EXCLUDE (DateTime)) INTO NAME ColumnName VALUE Value) AS u
+                 JOIN tag_columns AS c ON c.ColumnName = u.ColumnName"
+            ),
+            StorageMode::Long => format!(
+                "SELECT DateTime, TagName, Value FROM ts_long WHERE {filter} AND Value IS NOT NULL"
+            ),
+        };
+        // 数量上限作用于有数据的时间点，全为空值的行不占用；数据质量按时间与标签并入对应的数值或文本值
+        let sql = format!(
+            "WITH v AS (
+                SELECT DateTime, TagName, Value, CAST(NULL AS VARCHAR) AS Text FROM ({numeric})
+                UNION ALL
+                SELECT DateTime, TagName, NULL, Value FROM ts_text WHERE {filter}
+             ),
+             r AS (
+                SELECT COALESCE(v.DateTime, q.DateTime) AS DateTime, COALESCE(v.TagName, q.TagName) AS TagName,
+                       v.Value, v.Text, q.Quality
+                FROM v FULL OUTER JOIN (SELECT * FROM ts_quality WHERE {filter}) AS q
+                  ON q.DateTime = v.DateTime AND q.TagName = v.TagName
+             ),
+             points AS (SELECT DISTINCT DateTime FROM r ORDER BY DateTime LIMIT {limit})
+             SELECT CAST(r.DateTime AS TIMESTAMP), r.TagName, r.Value, r.Text, r.Quality FROM r
+             JOIN points AS p ON p.DateTime = r.DateTime
+             ORDER BY r.DateTime, r.TagName"
+        );
+        // 过滤条件依次出现在数值、文本值与数据质量三处
+        let params = params.iter().cycle().take(params.len() * 3);
+
+        let conn = self.get_connection()?;
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(duckdb::params_from_iter(params), |row| {
+            let ts: chrono::NaiveDateTime = row.get(0)?;
+            Ok(TimeSeriesRecord {
+                timestamp: ts.and_utc(),
+                tag_name: row.get(1)?,
+                value: row.get(2)?,
+                text: row.get(3)?,
+                quality: row.get(4)?,
+            })
+        })?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            records.push(row?);
+        }
+
+        let mut points = records.iter().map(|r| r.timestamp).collect::<Vec<_>>();
+        points.dedup();
+        let next = (points.len() >= limit).then(|| points.last().copied()).flatten();
+        Ok(ChangesPage { records, next })
+    }
+
+    /// 生成单个标签 (ts, v) 序列的子查询，与存储模式无关
+    ///
+    /// 窄表模式下标签名作为绑定参数，调用方将子查询放在其余占位符之前并先绑定其参数；宽表模式下标签列不存在时返回 None。
+    fn tag_series_sql(&self, tag_name: &str) -> Result<Option<sql::Statement>, Box<dyn std::error::Error + Send + Sync>> {
+        match self.config.storage_mode {
+            StorageMode::Wide => {
+                let Some(column) = self.column_for(tag_name)? else {
+                    return Ok(None);
+                };
+                if !self.wide_column_exists(&column)? {
+                    return Ok(None);
+                }
+                Ok(Some(sql::Statement {
+                    sql: format!(
+                        "SELECT DateTime AS ts, {col} AS v FROM ts_wide WHERE {col} IS NOT NULL",
+                        col = Dialect::DuckDb.quote(&column)
+                    ),
+                    params: Vec::new(),
+                }))
+            }
+            StorageMode::Long => Ok(Some(sql::Statement {
+                sql: "SELECT DateTime AS ts, Value AS v FROM ts_long WHERE TagName = ? AND Value IS NOT NULL".to_string(),
+                params: vec![Param::from(tag_name)],
+            })),
+        }
+    }
+
+    /// 生成单个标签在 [start_time, end_time] 内的 (ts, v) 序列子查询：热数据（DuckDB 表）与时间范围相交的
+    /// 冷数据（已归档的 Parquet 分区）合并，未启用归档或没有相交的分区时与 [`Self::tag_series_sql`] 相同
+    ///
+    /// 归档后的数据已从热表中删除，两部分没有重叠；标签在热表与冷数据中都不存在时返回 None。
+    fn tag_series_in_range(
+        &self,
+        tag_name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Option<sql::Statement>, Box<dyn std::error::Error + Send + Sync>> {
+        let hot = self.tag_series_sql(tag_name)?;
+        if !self.config.archive.enabled {
+            return Ok(hot);
+        }
+
+        let files: Vec<String> = self.cold_partitions(Some(start_time), Some(end_time + chrono::Duration::milliseconds(1)), Some(self.data_table()))?
+            .into_iter()
+            .map(|partition| partition.path)
+            .filter(|path| Path::new(path).exists())
+            .collect();
+        if files.is_empty() {
+            return Ok(hot);
+        }
+
+        // 不同时期的宽表文件列可能不同，按列名合并
+        let scan = format!(
+            "read_parquet([{}], union_by_name = true)",
+            files.iter().map(|f| sql::literal(f)).collect::<Vec<_>>().join(", ")
+        );
+        let cold = match self.config.storage_mode {
+            StorageMode::Wide => {
+                let Some(column) = self.column_for(tag_name)? else {
+                    return Ok(hot);
+                };
+                let conn = self.get_connection()?;
+                let mut stmt = conn.prepare(&format!("DESCRIBE SELECT * FROM {}", scan))?;
+                let columns: Vec<String> = stmt.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?;
+                if !columns.contains(&column) {
+                    return Ok(hot);
+                }
+                sql::Statement {
+                    sql: format!(
+                        "SELECT DateTime AS ts, {col} AS v FROM {scan} WHERE {col} IS NOT NULL",
+                        col = Dialect::DuckDb.quote(&column)
+                    ),
+                    params: Vec::new(),
+                }
+            }
+            StorageMode::Long => sql::Statement {
+                sql: format!("SELECT DateTime AS ts, Value AS v FROM {} WHERE TagName = ? AND Value IS NOT NULL", scan),
+                params: vec![Param::from(tag_name)],
+            },
+        };
+
+        Ok(Some(match hot {
+            Some(mut hot) => {
+                hot.sql = format!("{} UNION ALL {}", hot.sql, cold.sql);
+                hot.params.extend(cold.params);
+                hot
+            }
+            None => cold,
+        }))
+    }
+
+    /// 查询标签在时间范围内的 (数值, 保持秒数) 区间序列
+    fn query_value_spans(
+        &self,
+        tag_name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<(f64, f64)>, Box<dyn std::error::Error + Send + Sync>> {
+        let spans = self.query_timed_spans(tag_name, start_time, end_time)?;
+        Ok(spans.into_iter()
+            .map(|(from, to, value)| (value, (to - from).num_milliseconds() as f64 / 1000.0))
+            .collect())
+    }
+
+    /// 查询标签在 [start_time, end_time) 内的阶梯保持区间 (起始时间, 结束时间, 数值)，按时间升序
+    ///
+    /// 起点之前的最后一个值作为区间初始值，每个值保持到下一个采样点，最后一个值保持到结束时间。
+    fn query_timed_spans(
+        &self,
+        tag_name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<Span>, Box<dyn std::error::Error + Send + Sync>> {
+        if end_time <= start_time {
+            return Ok(Vec::new());
+        }
+
+        let series = match self.tag_series_in_range(tag_name, start_time, end_time)? {
+            Some(series) => series,
+            None => return Ok(Vec::new()),
+        };
+
+        let conn = self.get_connection()?;
+
+        let sql = format!(
+            "WITH series AS ({series}),
+            samples AS (
+                SELECT ts, v FROM series
+                WHERE ts >= CAST(? AS TIMESTAMPTZ) AND ts < CAST(? AS TIMESTAMPTZ)
+                UNION ALL
+                SELECT * FROM (
+                    SELECT CAST(? AS TIMESTAMPTZ) AS ts, v FROM series
+                    WHERE ts < CAST(? AS TIMESTAMPTZ)
+                    ORDER BY series.ts DESC
+                    LIMIT 1
+                )
+            )
+            SELECT CAST(ts AS TIMESTAMP), CAST(COALESCE(LEAD(ts) OVER (ORDER BY ts), CAST(? AS TIMESTAMPTZ)) AS TIMESTAMP), v
+            FROM samples
+            ORDER BY ts",
+            series = series.sql
+        );
+
+        let start_str = format_timestamp(&start_time);
+        let end_str = format_timestamp(&end_time);
+        let mut params = series.params;
+        params.extend([&start_str, &end_str, &start_str, &start_str, &end_str].map(Param::from));
+
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(
+            duckdb::params_from_iter(params.iter()),
+            |row| {
+                let from: chrono::NaiveDateTime = row.get(0)?;
+                let to: chrono::NaiveDateTime = row.get(1)?;
+                Ok((from.and_utc(), to.and_utc(), row.get::<_, f64>(2)?))
+            },
+        )?;
+
+        let mut spans = Vec::new();
+        for row in rows {
+            spans.push(row?);
+        }
+
+        Ok(spans)
+    }
+}
+
+/// 聚合方式对应的 SQL 表达式，作用于 `tag_series_sql` 的 `ts`/`v` 列；按时间跨度计算的聚合方式返回 None
+fn aggregate_expr(aggregation: Aggregation) -> Option<&'static str> {
+    Some(match aggregation {
+        Aggregation::Avg => "AVG(v)",
+        Aggregation::Min => "MIN(v)",
+        Aggregation::Max => "MAX(v)",
+        Aggregation::Sum => "SUM(v)",
+        Aggregation::Stddev => "STDDEV_SAMP(v)",
+        Aggregation::First => "MIN_BY(v, ts)",
+        Aggregation::Last => "MAX_BY(v, ts)",
+        Aggregation::Count => "CAST(COUNT(v) AS DOUBLE)",
+        Aggregation::TimeWeightedAvg | Aggregation::DurationInState => return None,
+    })
+}
+
+/// `time_bucket` 未给出起点时的对齐起点（2000-01-03 00:00:00 UTC），时间加权的时间桶与之保持一致
+const TIME_BUCKET_ORIGIN_SECS: i64 = 946_857_600;
+
+/// 将阶梯保持区间按从 `origin` 开始、宽 `window_secs` 的时间桶切分，计算各桶的时间加权平均值或非零状态秒数
+///
+/// 跨越桶边界的区间按重叠时长分配到各桶；不含任何区间的桶不返回。
+fn time_weighted_buckets(
+    spans: &[Span],
+    origin: DateTime<Utc>,
+    window_secs: u64,
+    aggregation: Aggregation,
+) -> Vec<Point<f64>> {
+    let origin_ms = origin.timestamp_millis();
+    let window_ms = window_secs as i64 * 1000;
+    // 桶序号 -> (值 × 毫秒之和, 总毫秒, 非零毫秒)
+    let mut buckets: std::collections::BTreeMap<i64, (f64, f64, f64)> = std::collections::BTreeMap::new();
+    for &(from, to, value) in spans {
+        let (mut t, end) = (from.timestamp_millis(), to.timestamp_millis());
+        while t < end {
+            let index = (t - origin_ms).div_euclid(window_ms);
+            let bucket_end = origin_ms + (index + 1) * window_ms;
+            let piece = (end.min(bucket_end) - t) as f64;
+            let entry = buckets.entry(index).or_default();
+            entry.0 += value * piece;
+            entry.1 += piece;
+            if value != 0.0 {
+                entry.2 += piece;
+            }
+            t = bucket_end;
+        }
+    }
+
+    buckets.into_iter()
+        .filter(|(_, (_, total, _))| *total > 0.0)
+        .filter_map(|(index, (weighted, total, nonzero))| {
+            let timestamp = DateTime::from_timestamp_millis(origin_ms + index * window_ms)?;
+            let value = match aggregation {
+                Aggregation::DurationInState => nonzero / 1000.0,
+                _ => weighted / total,
+            };
+            Some((timestamp, value))
+        })
+        .collect()
+}
+
+/// 按状态值汇总区间持续时间，返回按状态值排序的 (状态值, 秒) 列表
+fn sum_durations_by_state(spans: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut durations: Vec<(f64, f64)> = Vec::new();
+    for &(value, secs) in spans {
+        match durations.iter_mut().find(|(state, _)| *state == value) {
+            Some((_, total)) => *total += secs,
+            None => durations.push((value, secs)),
+        }
+    }
+
+    durations.sort_by(|a, b| a.0.total_cmp(&b.0));
+    durations
+}
+
+/// 在 CSV 文件开头插入以 `#` 开头的来源信息注释行
+fn prepend_csv_comment(file: &Path, provenance: &[(&str, String)]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use std::io::Write;
+
+    let tmp = file.with_extension("watermark.tmp");
+    {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+        for (key, value) in provenance {
+            writeln!(writer, "# {}: {}", key, value)?;
+        }
+        std::io::copy(&mut std::fs::File::open(file)?, &mut writer)?;
+        writer.flush()?;
+    }
+    std::fs::rename(&tmp, file)?;
+    Ok(())
+}
+
+/// 判断错误是否由表结构不一致引起（列或表不存在）
+fn is_schema_error(error: &(dyn std::error::Error + Send + Sync)) -> bool {
+    let message = error.to_string();
+    message.contains("Binder Error") || message.contains("Catalog Error")
+}
+
+/// 将 UTC 时间格式化为带时区偏移的 DuckDB 时间字面量
+pub fn format_timestamp(timestamp: &DateTime<Utc>) -> String {
+    timestamp.format("%Y-%m-%d %H:%M:%S%.3f+00").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_source::TagChanges;
+
+    /// 临时文件上的缓存，释放时删除文件
+    struct TempDb {
+        db: DatabaseManager,
+        path: std::path::PathBuf,
+    }
+
+    impl TempDb {
+        fn new(name: &str, storage_mode: StorageMode) -> Self {
+            let path = std::env::temp_dir().join(format!("rt_db_db_test_{}_{}.duckdb", name, std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            let db = DatabaseManager::new(Arc::new(AppConfig {
+                db_file_path: path.to_string_lossy().into_owned(),
+                storage_mode,
+                ..Default::default()
+            }));
+            db.initialize().expect("初始化临时缓存失败");
+            Self { db, path }
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+            let _ = std::fs::remove_file(self.path.with_extension("duckdb.wal"));
+        }
+    }
+
+    fn record(tag: &str, timestamp: DateTime<Utc>, value: Option<f64>) -> TimeSeriesRecord {
+        TimeSeriesRecord { tag_name: tag.to_string(), timestamp, value, text: None, quality: None }
+    }
+
+    /// 压缩只删除已移除标签的全空列，已知标签的列即使全为空值也保留，映射不变
+    #[test]
+    fn compact_keeps_all_null_columns_of_known_tags() {
+        let temp = TempDb::new("compact", StorageMode::Wide);
+        let db = &temp.db;
+        let tags = ["Live", "Quiet", "Gone"].map(String::from);
+        db.handle_tag_changes(&TagChanges {
+            added_tags: tags.to_vec(),
+            removed_tags: Vec::new(),
+            current_tags: tags.iter().cloned().collect(),
+        }, None).unwrap();
+
+        let now = Utc::now();
+        let records: Vec<TimeSeriesRecord> = (0..3)
+            .flat_map(|i| {
+                let at = now + chrono::Duration::seconds(i);
+                [record("Live", at, Some(i as f64)), record("Quiet", at, None), record("Gone", at, None)]
+            })
+            .collect();
+        db.convert_and_insert_wide(&records, None).unwrap();
+        db.handle_tag_changes(&TagChanges {
+            added_tags: Vec::new(),
+            removed_tags: vec!["Gone".to_string()],
+            current_tags: ["Live", "Quiet"].map(String::from).into_iter().collect(),
+        }, None).unwrap();
+        let quiet_column = db.column_for("Quiet").unwrap().unwrap();
+
+        let report = db.compact().unwrap();
+        assert_eq!(report.dropped_columns, vec![db.base_column_name("Gone")]);
+        assert_eq!(report.rows, 3);
+        assert!(db.wide_column_exists(&quiet_column).unwrap(), "已知标签的全空列被删除");
+        assert_eq!(db.column_for("Quiet").unwrap(), Some(quiet_column));
+        assert!(db.tag_columns().unwrap().iter().all(|c| c.tag != "Gone"));
+    }
+
+    /// 汇总只处理上次截止时间以后的数据，留在表中的更早数据不会覆盖已完成的桶
+    #[test]
+    fn roll_up_keeps_completed_buckets() {
+        let temp = TempDb::new("rollup", StorageMode::Wide);
+        let db = &temp.db;
+        db.handle_tag_changes(&TagChanges {
+            added_tags: vec!["Flow".to_string()],
+            removed_tags: Vec::new(),
+            current_tags: std::iter::once("Flow".to_string()).collect(),
+        }, None).unwrap();
+
+        let start = DateTime::from_timestamp(1_700_000_040, 0).unwrap();
+        let at = |secs| start + chrono::Duration::seconds(secs);
+        db.convert_and_insert_wide(&[record("Flow", at(0), Some(10.0)), record("Flow", at(30), Some(20.0))], None).unwrap();
+        assert_eq!(db.roll_up_before("ts_rollup_1m", 60, at(60)).unwrap(), 1);
+        assert_eq!(db.roll_up_before("ts_rollup_1m", 60, at(60)).unwrap(), 0);
+
+        // 模拟保留期清理只删除了桶内的一部分数据
+        let conn = db.get_connection().unwrap();
+        conn.execute("DELETE FROM ts_wide WHERE DateTime = CAST(? AS TIMESTAMPTZ)", [format_timestamp(&at(0))]).unwrap();
+        db.roll_up_before("ts_rollup_1m", 60, at(120)).unwrap();
+
+        let (count, avg): (i64, f64) = conn.query_row(
+            "SELECT Count, Avg FROM ts_rollup_1m WHERE TagName = 'Flow' AND DateTime = CAST(? AS TIMESTAMPTZ)",
+            [format_timestamp(&at(0))],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap();
+        assert_eq!((count, avg), (2, 15.0));
+    }
+
+    /// 变更流带有文本值与数据质量，写入副本后与主实例一致
+    #[test]
+    fn changes_carry_text_and_quality() {
+        for (label, mode) in [("wide", StorageMode::Wide), ("long", StorageMode::Long)] {
+            let primary = TempDb::new(&format!("changes_primary_{}", label), mode);
+            let replica = TempDb::new(&format!("changes_replica_{}", label), mode);
+            let at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+            let later = at + chrono::Duration::seconds(1);
+            let records = vec![
+                TimeSeriesRecord { quality: Some("Good".to_string()), ..record("Flow", at, Some(1.5)) },
+                TimeSeriesRecord { text: Some("RUNNING".to_string()), ..record("State", at, None) },
+                TimeSeriesRecord { quality: Some("Bad".to_string()), ..record("Flow", later, None) },
+            ];
+            primary.db.convert_and_insert_wide(&records, None).unwrap();
+
+            let page = primary.db.changes(None, None, None, 10).unwrap();
+            replica.db.convert_and_insert_wide(&page.records, None).unwrap();
+
+            let dump = |db: &DatabaseManager| {
+                let conn = db.get_connection().unwrap();
+                let text: Vec<(String, String)> = conn.prepare("SELECT TagName, Value FROM ts_text ORDER BY ALL").unwrap()
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap()
+                    .collect::<Result<_, _>>().unwrap();
+                let quality: Vec<(String, String)> = conn.prepare("SELECT TagName, Quality FROM ts_quality ORDER BY ALL").unwrap()
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap()
+                    .collect::<Result<_, _>>().unwrap();
+                (text, quality)
+            };
+            let expected = (
+                vec![("State".to_string(), "RUNNING".to_string())],
+                vec![("Flow".to_string(), "Bad".to_string()), ("Flow".to_string(), "Good".to_string())],
+            );
+            assert_eq!(dump(&primary.db), expected, "{}", label);
+            assert_eq!(dump(&replica.db), expected, "{}", label);
+            assert_eq!(page.records.iter().filter(|r| r.value.is_some()).count(), 1, "{}", label);
+        }
+    }
+}
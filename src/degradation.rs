@@ -0,0 +1,179 @@
+//! 资源压力下的分级降级
+//! 定期采样 CPU、内存与 DuckDB 文件所在磁盘的剩余空间，持续超过阈值时按配置的顺序逐级停用次要功能
+//! （默认依次为预计算汇总、推送、重型 API 查询、同步频率），压力解除后按相反顺序逐级恢复。
+//! 各功能在执行前查询 [`Degradation::shedding`]，核心同步始终运行，只是频率可能降低。
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use sysinfo::{Disks, System};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::config::{AppConfig, DegradationConfig, ShedStage};
+
+/// 降级状态
+#[derive(Debug)]
+pub struct Degradation {
+    stages: Vec<ShedStage>,
+    /// 已启用的级数，前 `level` 个阶段处于降级中
+    level: AtomicUsize,
+    sync_slowdown_factor: u32,
+    last_sample: Mutex<Option<ResourceSample>>,
+}
+
+/// 一次资源采样
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ResourceSample {
+    pub cpu_percent: f32,
+    pub memory_percent: f32,
+    /// DuckDB 文件所在磁盘的剩余空间百分比，无法确定磁盘时为 None
+    pub disk_free_percent: Option<f32>,
+}
+
+/// 降级状态报告
+#[derive(Debug, Clone, Serialize)]
+pub struct DegradationStatus {
+    pub enabled: bool,
+    /// 当前处于降级中的阶段，按启用顺序
+    pub shed: Vec<ShedStage>,
+    pub last_sample: Option<ResourceSample>,
+}
+
+static DEGRADATION: OnceLock<Degradation> = OnceLock::new();
+
+impl Degradation {
+    fn from_config(config: &DegradationConfig) -> Self {
+        Self {
+            stages: if config.enabled { config.stages.clone() } else { Vec::new() },
+            level: AtomicUsize::new(0),
+            sync_slowdown_factor: config.sync_slowdown_factor.max(1),
+            last_sample: Mutex::new(None),
+        }
+    }
+
+    /// 该阶段是否处于降级中
+    pub fn shedding(&self, stage: ShedStage) -> bool {
+        let level = self.level.load(Ordering::Relaxed);
+        self.stages[..level.min(self.stages.len())].contains(&stage)
+    }
+
+    /// 降低同步频率时，每多少个更新周期执行一次
+    pub fn sync_slowdown_factor(&self) -> u32 {
+        self.sync_slowdown_factor
+    }
+
+    pub fn status(&self) -> DegradationStatus {
+        let level = self.level.load(Ordering::Relaxed).min(self.stages.len());
+        DegradationStatus {
+            enabled: !self.stages.is_empty(),
+            shed: self.stages[..level].to_vec(),
+            last_sample: *self.last_sample.lock().unwrap(),
+        }
+    }
+}
+
+/// 按配置初始化，需在启动各任务之前调用
+pub fn init(config: &AppConfig) {
+    let _ = DEGRADATION.set(Degradation::from_config(&config.degradation));
+}
+
+/// 当前降级状态；未初始化时（命令行工具）不降级
+pub fn get() -> &'static Degradation {
+    DEGRADATION.get_or_init(|| Degradation::from_config(&DegradationConfig::default()))
+}
+
+/// 启动资源监控任务，未启用时返回 None
+pub fn spawn_monitor(config: Arc<AppConfig>) -> Option<JoinHandle<()>> {
+    let degradation = &config.degradation;
+    if !degradation.enabled {
+        return None;
+    }
+    info!("资源压力降级已启用，检查间隔 {} 秒，降级顺序 {:?}", degradation.check_interval_secs, degradation.stages);
+
+    let db_path = std::fs::canonicalize(&config.db_file_path)
+        .unwrap_or_else(|_| PathBuf::from(&config.db_file_path));
+    Some(tokio::spawn(async move {
+        let settings = &config.degradation;
+        let state = get();
+        let mut system = System::new();
+        let mut disks = Disks::new_with_refreshed_list();
+        let mut pressured_checks = 0u32;
+        let mut calm_checks = 0u32;
+
+        // CPU 使用率需两次采样之间的差值，先采样一次作为基准
+        system.refresh_cpu_usage();
+        let mut ticker = tokio::time::interval(Duration::from_secs(settings.check_interval_secs));
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+
+            system.refresh_cpu_usage();
+            system.refresh_memory();
+            disks.refresh(true);
+            let sample = ResourceSample {
+                cpu_percent: system.global_cpu_usage(),
+                memory_percent: percent(system.total_memory().saturating_sub(system.available_memory()), system.total_memory()),
+                disk_free_percent: disk_free_percent(&disks, &db_path),
+            };
+            *state.last_sample.lock().unwrap() = Some(sample);
+
+            let reasons = pressure_reasons(settings, &sample);
+            let level = state.level.load(Ordering::Relaxed);
+            if reasons.is_empty() {
+                pressured_checks = 0;
+                calm_checks += 1;
+                if level > 0 && calm_checks >= settings.recover_after_checks.max(1) {
+                    calm_checks = 0;
+                    state.level.store(level - 1, Ordering::Relaxed);
+                    info!("资源压力解除，恢复 {:?}", state.stages[level - 1]);
+                }
+            } else {
+                calm_checks = 0;
+                pressured_checks += 1;
+                if level < state.stages.len() && pressured_checks >= settings.escalate_after_checks.max(1) {
+                    pressured_checks = 0;
+                    state.level.store(level + 1, Ordering::Relaxed);
+                    warn!("资源压力（{}），降级: 停用 {:?}", reasons.join("，"), state.stages[level]);
+                } else {
+                    debug!("资源压力（{}），当前降级 {} 级", reasons.join("，"), level);
+                }
+            }
+        }
+    }))
+}
+
+/// 超过阈值的资源说明，无压力时为空
+fn pressure_reasons(settings: &DegradationConfig, sample: &ResourceSample) -> Vec<String> {
+    let mut reasons = Vec::new();
+    if sample.cpu_percent >= settings.cpu_percent {
+        reasons.push(format!("CPU {:.0}%", sample.cpu_percent));
+    }
+    if sample.memory_percent >= settings.memory_percent {
+        reasons.push(format!("内存 {:.0}%", sample.memory_percent));
+    }
+    if let Some(free) = sample.disk_free_percent.filter(|free| *free <= settings.disk_free_percent) {
+        reasons.push(format!("磁盘剩余 {:.1}%", free));
+    }
+    reasons
+}
+
+/// 路径所在磁盘（挂载点为其最长前缀的磁盘）的剩余空间百分比
+fn disk_free_percent(disks: &Disks, path: &Path) -> Option<f32> {
+    disks.list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| percent(disk.available_space(), disk.total_space()))
+}
+
+fn percent(part: u64, total: u64) -> f32 {
+    if total == 0 {
+        0.0
+    } else {
+        (part as f64 / total as f64 * 100.0) as f32
+    }
+}
@@ -0,0 +1,59 @@
+//! 能耗统计模块
+//! 根据累计计数型标签（电表、蒸汽表等）计算时间段内的消耗量
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use tracing::debug;
+
+/// 判定为计数器回绕的阈值：回落前读数超过量程的该比例
+const ROLLOVER_THRESHOLD: f64 = 0.9;
+
+/// 单日消耗量
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyConsumption {
+    pub date: NaiveDate,
+    pub consumption: f64,
+}
+
+/// 计算相邻两个读数之间的增量
+///
+/// 读数回落时：若配置了量程且回落前接近量程上限，按计数器回绕处理；
+/// 否则视为换表，该段增量计为 0。
+fn counter_delta(previous: f64, current: f64, rollover: Option<f64>) -> f64 {
+    if current >= previous {
+        return current - previous;
+    }
+
+    match rollover {
+        Some(max) if previous >= max * ROLLOVER_THRESHOLD => (max - previous) + current,
+        _ => {
+            debug!("计数器读数回落 {} -> {}，按换表处理", previous, current);
+            0.0
+        }
+    }
+}
+
+/// 计算计数器读数序列（按时间升序）的总消耗量
+pub fn counter_consumption(samples: &[(DateTime<Utc>, f64)], rollover: Option<f64>) -> f64 {
+    samples
+        .windows(2)
+        .map(|pair| counter_delta(pair[0].1, pair[1].1, rollover))
+        .sum()
+}
+
+/// 按自然日（UTC）汇总消耗量，增量归入后一个读数所在的日期
+pub fn daily_consumption(samples: &[(DateTime<Utc>, f64)], rollover: Option<f64>) -> Vec<DailyConsumption> {
+    let mut days: Vec<DailyConsumption> = Vec::new();
+
+    for pair in samples.windows(2) {
+        let date = pair[1].0.date_naive();
+        let delta = counter_delta(pair[0].1, pair[1].1, rollover);
+
+        match days.last_mut() {
+            Some(day) if day.date == date => day.consumption += delta,
+            _ => days.push(DailyConsumption { date, consumption: delta }),
+        }
+    }
+
+    days
+}
@@ -0,0 +1,198 @@
+//! 导出文件的投递目标实现
+
+use anyhow::{Result, anyhow};
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::net::tcp::OwnedWriteHalf;
+
+use crate::config::DestinationConfig;
+
+/// 目标的简短描述，用于日志
+pub fn describe(dest: &DestinationConfig) -> String {
+    match dest {
+        DestinationConfig::Local { path } => format!("local:{}", path),
+        DestinationConfig::Smb { path } => format!("smb:{}", path),
+        DestinationConfig::Ftp { host, port, remote_dir, .. } => format!("ftp://{}:{}/{}", host, port, remote_dir),
+        DestinationConfig::Sftp { host, port, remote_dir, .. } => format!("sftp://{}:{}/{}", host, port, remote_dir),
+    }
+}
+
+/// 将文件投递到指定目标
+pub async fn deliver(dest: &DestinationConfig, file: &Path) -> Result<()> {
+    match dest {
+        // SMB 共享在 Windows 上可直接使用 UNC 路径，Linux 上需预先挂载
+        DestinationConfig::Local { path } | DestinationConfig::Smb { path } => copy_to_dir(file, Path::new(path)).await,
+        DestinationConfig::Ftp { host, port, user, password, remote_dir } => {
+            ftp_upload(host, *port, user, password, remote_dir, file).await
+        }
+        DestinationConfig::Sftp { host, port, user, remote_dir, identity_file } => {
+            sftp_upload(host, *port, user, remote_dir, identity_file.as_deref(), file).await
+        }
+    }
+}
+
+/// 目标是否就是本地目录 `dir`（本地或 SMB 目标指向暂存目录本身）
+pub fn is_dir(dest: &DestinationConfig, dir: &Path) -> bool {
+    match dest {
+        DestinationConfig::Local { path } | DestinationConfig::Smb { path } => {
+            match (std::fs::canonicalize(path), std::fs::canonicalize(dir)) {
+                (Ok(path), Ok(dir)) => path == dir,
+                _ => Path::new(path) == dir,
+            }
+        }
+        DestinationConfig::Ftp { .. } | DestinationConfig::Sftp { .. } => false,
+    }
+}
+
+fn file_name(file: &Path) -> Result<String> {
+    file.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow!("无效的导出文件路径: {}", file.display()))
+}
+
+/// 复制到目录（本地或已挂载的共享）
+async fn copy_to_dir(file: &Path, dir: &Path) -> Result<()> {
+    tokio::fs::create_dir_all(dir).await
+        .map_err(|e| anyhow!("创建目录 {} 失败: {}", dir.display(), e))?;
+    tokio::fs::copy(file, dir.join(file_name(file)?)).await
+        .map_err(|e| anyhow!("复制文件到 {} 失败: {}", dir.display(), e))?;
+    Ok(())
+}
+
+/// FTP 控制连接
+struct FtpControl {
+    reader: BufReader<tokio::net::tcp::OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl FtpControl {
+    /// 读取一条（可能多行的）响应，返回响应码与内容
+    async fn read_reply(&mut self) -> Result<(u16, String)> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).await? == 0 {
+            return Err(anyhow!("FTP 连接已关闭"));
+        }
+        let code: u16 = line.get(..3).and_then(|c| c.parse().ok())
+            .ok_or_else(|| anyhow!("无效的 FTP 响应: {}", line.trim_end()))?;
+
+        // 多行响应以 "xyz-" 开始，以 "xyz " 结束
+        if line.as_bytes().get(3) == Some(&b'-') {
+            let end = format!("{} ", code);
+            loop {
+                let mut next = String::new();
+                if self.reader.read_line(&mut next).await? == 0 {
+                    return Err(anyhow!("FTP 连接已关闭"));
+                }
+                if next.starts_with(&end) {
+                    line = next;
+                    break;
+                }
+            }
+        }
+
+        Ok((code, line.trim_end().to_string()))
+    }
+
+    /// 发送命令并校验响应码
+    async fn command(&mut self, cmd: &str, expected: &[u16]) -> Result<String> {
+        self.writer.write_all(format!("{}\r\n", cmd).as_bytes()).await?;
+        self.expect(expected).await
+            .map_err(|e| anyhow!("FTP 命令 {} 失败: {}", cmd.split(' ').next().unwrap_or(cmd), e))
+    }
+
+    async fn expect(&mut self, expected: &[u16]) -> Result<String> {
+        let (code, text) = self.read_reply().await?;
+        if expected.contains(&code) {
+            Ok(text)
+        } else {
+            Err(anyhow!("{}", text))
+        }
+    }
+}
+
+/// 从 PASV 响应中解析数据端口
+fn parse_pasv_port(reply: &str) -> Result<u16> {
+    let start = reply.find('(').ok_or_else(|| anyhow!("无法解析 PASV 响应: {}", reply))?;
+    let end = reply[start..].find(')').ok_or_else(|| anyhow!("无法解析 PASV 响应: {}", reply))? + start;
+    let parts: Vec<u16> = reply[start + 1..end]
+        .split(',')
+        .map(|p| p.trim().parse::<u16>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| anyhow!("无法解析 PASV 响应: {}", reply))?;
+
+    if parts.len() != 6 {
+        return Err(anyhow!("无法解析 PASV 响应: {}", reply));
+    }
+    Ok(parts[4] * 256 + parts[5])
+}
+
+/// 通过 FTP 被动模式上传文件
+async fn ftp_upload(host: &str, port: u16, user: &str, password: &str, remote_dir: &str, file: &Path) -> Result<()> {
+    let stream = TcpStream::connect((host, port)).await
+        .map_err(|e| anyhow!("连接 FTP 服务器 {}:{} 失败: {}", host, port, e))?;
+    let (read_half, write_half) = stream.into_split();
+    let mut ctrl = FtpControl { reader: BufReader::new(read_half), writer: write_half };
+
+    ctrl.expect(&[220]).await?;
+    ctrl.command(&format!("USER {}", user), &[230, 331]).await?;
+    ctrl.command(&format!("PASS {}", password), &[230, 202]).await?;
+    ctrl.command("TYPE I", &[200]).await?;
+    if !remote_dir.is_empty() {
+        ctrl.command(&format!("CWD {}", remote_dir), &[250]).await?;
+    }
+
+    // 数据连接使用控制连接的主机，避免服务器返回内网地址
+    let data_port = parse_pasv_port(&ctrl.command("PASV", &[227]).await?)?;
+    let mut data = TcpStream::connect((host, data_port)).await
+        .map_err(|e| anyhow!("建立 FTP 数据连接失败: {}", e))?;
+
+    ctrl.command(&format!("STOR {}", file_name(file)?), &[125, 150]).await?;
+
+    // 按块转发文件内容，不把整个文件读入内存
+    let mut source = tokio::fs::File::open(file).await?;
+    tokio::io::copy(&mut source, &mut data).await
+        .map_err(|e| anyhow!("FTP 数据传输失败: {}", e))?;
+    data.shutdown().await?;
+    drop(data);
+
+    ctrl.expect(&[226, 250]).await?;
+    let _ = ctrl.command("QUIT", &[221]).await;
+    Ok(())
+}
+
+/// 检查 SFTP 投递依赖的系统 scp 是否可用
+pub async fn scp_available() -> bool {
+    // 不带参数运行只打印用法，能启动即说明已安装
+    tokio::process::Command::new("scp")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .await
+        .is_ok()
+}
+
+/// 通过系统 scp 上传文件（需配置密钥认证）；OpenSSH 9.0 起 scp 默认经 SFTP 协议传输
+async fn sftp_upload(host: &str, port: u16, user: &str, remote_dir: &str, identity_file: Option<&str>, file: &Path) -> Result<()> {
+    let mut cmd = tokio::process::Command::new("scp");
+    cmd.arg("-q")
+        .arg("-P").arg(port.to_string())
+        .arg("-o").arg("BatchMode=yes");
+    if let Some(identity) = identity_file {
+        cmd.arg("-i").arg(identity);
+    }
+
+    let remote_path = if remote_dir.is_empty() {
+        file_name(file)?
+    } else {
+        format!("{}/{}", remote_dir.trim_end_matches('/'), file_name(file)?)
+    };
+    cmd.arg(file).arg(format!("{}@{}:{}", user, host, remote_path));
+
+    let output = cmd.output().await
+        .map_err(|e| anyhow!("执行 scp 失败: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!("scp 返回错误: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
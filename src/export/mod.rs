@@ -0,0 +1,151 @@
+//! 定时导出模块
+//! 按任务配置周期性地将缓存数据导出为 CSV，并投递到本地目录、SMB 共享、FTP 或 SFTP。
+//! SFTP 投递调用系统的 OpenSSH scp，是运行时的硬依赖；任务失败（及按配置的成功）时经告警 webhook 通知。
+
+mod destination;
+
+use anyhow::{Result, anyhow};
+use chrono::{Duration as ChronoDuration, Utc};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, interval};
+use tracing::{info, error, warn, debug};
+
+use crate::alert::Alerter;
+use crate::config::{AppConfig, DestinationConfig, ExportJobConfig, ShedStage};
+use crate::database::DatabaseManager;
+
+/// 启动全部导出任务，每个任务独立按间隔执行，返回任务句柄
+pub fn spawn_jobs(config: Arc<AppConfig>, db_manager: Arc<DatabaseManager>) -> Vec<JoinHandle<()>> {
+    let mut handles = Vec::new();
+    let alerts = Arc::new(Alerter::new(&config));
+
+    let uses_sftp = config.export.jobs.iter()
+        .flat_map(|job| &job.destinations)
+        .any(|dest| matches!(dest, DestinationConfig::Sftp { .. }));
+    if uses_sftp {
+        handles.push(tokio::spawn(async {
+            if !destination::scp_available().await {
+                error!("SFTP 投递需要系统中安装 OpenSSH 客户端（scp），当前未找到，SFTP 目标的投递将失败");
+            }
+        }));
+    }
+
+    for job in config.export.jobs.clone() {
+        if job.interval_secs == 0 {
+            warn!("导出任务 {} 的执行间隔为 0，已跳过", job.name);
+            continue;
+        }
+
+        let output_dir = PathBuf::from(&config.export.output_dir);
+        let db_manager = db_manager.clone();
+        let alerts = alerts.clone();
+
+        info!("导出任务 {} 已启动，间隔 {} 秒，投递目标 {} 个",
+              job.name, job.interval_secs, job.destinations.len());
+
+        handles.push(tokio::spawn(async move {
+            let mut ticker = interval(Duration::from_secs(job.interval_secs));
+            ticker.tick().await; // 跳过第一个立即触发
+
+            let sink = crate::toggles::export_sink(&job.name);
+            loop {
+                ticker.tick().await;
+                if !db_manager.toggles().sink_enabled(&sink) {
+                    debug!("导出任务 {} 已在运行时停用，跳过本次导出", job.name);
+                    continue;
+                }
+                if crate::degradation::get().shedding(ShedStage::Sinks) {
+                    debug!("资源压力降级中，跳过导出任务 {}", job.name);
+                    continue;
+                }
+                match run_job(&job, &output_dir, &db_manager).await {
+                    Ok(files) => {
+                        info!("导出任务 {} 完成，生成 {} 个文件", job.name, files.len());
+                        if job.notify_success {
+                            alerts.export_result(&job.name, files.len(), None).await;
+                        }
+                    }
+                    Err(e) => {
+                        error!("导出任务 {} 失败: {}", job.name, e);
+                        alerts.export_result(&job.name, 0, Some(&e.to_string())).await;
+                    }
+                }
+            }
+        }));
+    }
+
+    handles
+}
+
+/// 执行一次导出：生成 CSV 文件并投递到所有目标，全部投递成功后删除暂存目录中的文件
+async fn run_job(job: &ExportJobConfig, output_dir: &Path, db_manager: &Arc<DatabaseManager>) -> Result<Vec<PathBuf>> {
+    let end_time = Utc::now();
+    let lookback = job.lookback_secs.unwrap_or(job.interval_secs);
+    let start_time = end_time - ChronoDuration::seconds(lookback as i64);
+
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| anyhow!("创建导出目录 {} 失败: {}", output_dir.display(), e))?;
+    let file = output_dir.join(format!("{}_{}.csv", job.name, end_time.format("%Y%m%d_%H%M%S")));
+
+    let (files, rows) = {
+        let db_manager = db_manager.clone();
+        let tags = job.tags.clone();
+        let layout = job.layout;
+        tokio::task::spawn_blocking(move || db_manager.export_csv(&file, start_time, end_time, &tags, layout))
+            .await
+            .map_err(|e| anyhow!("导出任务异常终止: {}", e))?
+            .map_err(|e| anyhow!("导出 CSV 失败: {}", e))?
+    };
+    debug!("导出任务 {} 写出 {} 行到 {} 个文件", job.name, rows, files.len());
+
+    let mut failed = 0;
+    for dest in &job.destinations {
+        for file in &files {
+            if let Err(e) = deliver_with_retry(job, dest, file).await {
+                error!("导出任务 {} 投递 {} 到 {} 失败: {}", job.name, file.display(), destination::describe(dest), e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow!("{} 次投递失败，文件保留在 {}", failed, output_dir.display()));
+    }
+
+    // 全部目标确认后删除暂存文件；没有投递目标或目标就是暂存目录时文件本身即为导出结果，保留
+    if !job.destinations.is_empty() && !job.destinations.iter().any(|dest| destination::is_dir(dest, output_dir)) {
+        for file in &files {
+            if let Err(e) = tokio::fs::remove_file(file).await {
+                warn!("删除已投递的暂存文件 {} 失败: {}", file.display(), e);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// 带重试的投递
+async fn deliver_with_retry(
+    job: &ExportJobConfig,
+    dest: &DestinationConfig,
+    file: &Path,
+) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        match destination::deliver(dest, file).await {
+            Ok(()) => {
+                debug!("文件 {} 已投递到 {}", file.display(), destination::describe(dest));
+                return Ok(());
+            }
+            Err(e) if attempt < job.max_retries => {
+                attempt += 1;
+                warn!("投递到 {} 失败（第 {}/{} 次重试）: {}",
+                      destination::describe(dest), attempt, job.max_retries, e);
+                tokio::time::sleep(Duration::from_secs(job.retry_interval_secs)).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
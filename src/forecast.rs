@@ -0,0 +1,101 @@
+//! 趋势预测模块
+//! 基于最近样本做指数平滑（EWMA 或 Holt 线性趋势），按样本平均间隔外推若干步，
+//! 结果写入 ts_forecast 表，每个标签只保留最近一次预测。
+
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use tracing::{debug, warn};
+
+use crate::config::{ForecastConfig, ForecastMethod};
+use crate::database::DatabaseManager;
+
+/// 对按时间升序的样本做指数平滑并外推 `steps` 步，样本为空时返回空
+pub fn forecast(values: &[f64], method: ForecastMethod, alpha: f64, beta: f64, steps: usize) -> Vec<f64> {
+    let Some(&first) = values.first() else {
+        return Vec::new();
+    };
+
+    let mut level = first;
+    let mut trend = match (method, values.get(1)) {
+        (ForecastMethod::Holt, Some(&second)) => second - first,
+        _ => 0.0,
+    };
+
+    for &value in &values[1..] {
+        let previous = level;
+        match method {
+            ForecastMethod::Ewma => level = alpha * value + (1.0 - alpha) * level,
+            ForecastMethod::Holt => {
+                level = alpha * value + (1.0 - alpha) * (level + trend);
+                trend = beta * (level - previous) + (1.0 - beta) * trend;
+            }
+        }
+    }
+
+    (1..=steps).map(|h| level + h as f64 * trend).collect()
+}
+
+/// 趋势预测器，记录每个标签最后预测所基于的时间点，无新数据时不重复预测
+pub struct Forecaster {
+    config: ForecastConfig,
+    last_forecast: HashMap<String, DateTime<Utc>>,
+}
+
+impl Forecaster {
+    pub fn new(config: ForecastConfig) -> Self {
+        Self {
+            config,
+            last_forecast: HashMap::new(),
+        }
+    }
+
+    /// 对配置的标签各做一次预测并写入预测表，返回更新了预测的标签数
+    ///
+    /// 单个标签读取样本或写入预测失败时记录警告并跳过该标签，不影响其他标签，失败的标签下个周期重试。
+    pub fn evaluate(&mut self, db_manager: &DatabaseManager) -> usize {
+        let issued_at = Utc::now();
+        let mut updated = 0;
+
+        for tag in &self.config.tags {
+            let samples = match db_manager.get_recent_values(tag, self.config.window_size) {
+                Ok(samples) => samples,
+                Err(e) => {
+                    warn!("读取标签 {} 的预测样本失败，跳过: {}", tag, e);
+                    continue;
+                }
+            };
+
+            let (Some(&(first_time, _)), Some(&(latest_time, _))) = (samples.first(), samples.last()) else {
+                continue;
+            };
+            if samples.len() < 2 || latest_time <= first_time {
+                debug!("标签 {} 样本不足，跳过预测", tag);
+                continue;
+            }
+            if self.last_forecast.get(tag).is_some_and(|t| *t >= latest_time) {
+                continue;
+            }
+
+            // 步长取样本的平均间隔
+            let step = (latest_time - first_time) / (samples.len() as i32 - 1);
+            let values: Vec<f64> = samples.iter().map(|(_, v)| *v).collect();
+            let points: Vec<(DateTime<Utc>, f64)> = forecast(
+                &values, self.config.method, self.config.alpha, self.config.beta, self.config.horizon_steps,
+            )
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| (latest_time + step * (i as i32 + 1), value))
+                .filter(|(_, value)| value.is_finite())
+                .collect();
+
+            if let Err(e) = db_manager.replace_forecast(tag, issued_at, &points) {
+                warn!("写入标签 {} 的预测失败，跳过: {}", tag, e);
+                continue;
+            }
+            self.last_forecast.insert(tag.clone(), latest_time);
+            updated += 1;
+        }
+
+        updated
+    }
+}
@@ -0,0 +1,199 @@
+//! MES/ERP REST 推送模块
+//! 按端点配置周期性地聚合标签值，渲染 JSON 负载并 POST 到外部系统，投递结果写入 integration_deliveries 表
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, interval};
+use tracing::{info, error, warn, debug};
+
+use crate::config::{AppConfig, RestAuthConfig, RestEndpointConfig, ShedStage};
+use crate::database::DatabaseManager;
+
+/// 启动全部推送端点，每个端点独立按间隔执行，返回任务句柄
+pub fn spawn_jobs(config: Arc<AppConfig>, db_manager: Arc<DatabaseManager>) -> Vec<JoinHandle<()>> {
+    let mut handles = Vec::new();
+
+    for endpoint in config.integration.endpoints.clone() {
+        if endpoint.interval_secs == 0 {
+            warn!("推送端点 {} 的执行间隔为 0，已跳过", endpoint.name);
+            continue;
+        }
+
+        let client = match build_client(&endpoint) {
+            Ok(client) => client,
+            Err(e) => {
+                error!("推送端点 {} 初始化失败: {}", endpoint.name, e);
+                continue;
+            }
+        };
+        let db_manager = db_manager.clone();
+
+        info!("推送端点 {} 已启动，间隔 {} 秒，目标 {}", endpoint.name, endpoint.interval_secs, endpoint.url);
+
+        handles.push(tokio::spawn(async move {
+            let mut ticker = interval(Duration::from_secs(endpoint.interval_secs));
+            ticker.tick().await; // 跳过第一个立即触发
+
+            let sink = crate::toggles::integration_sink(&endpoint.name);
+            loop {
+                ticker.tick().await;
+                if !db_manager.toggles().sink_enabled(&sink) {
+                    debug!("推送端点 {} 已在运行时停用，跳过本次推送", endpoint.name);
+                    continue;
+                }
+                if crate::degradation::get().shedding(ShedStage::Sinks) {
+                    debug!("资源压力降级中，跳过推送端点 {}", endpoint.name);
+                    continue;
+                }
+                if let Err(e) = run_push(&endpoint, &client, &db_manager).await {
+                    error!("推送端点 {} 失败: {}", endpoint.name, e);
+                }
+            }
+        }));
+    }
+
+    handles
+}
+
+/// 构建 HTTP 客户端
+fn build_client(endpoint: &RestEndpointConfig) -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(endpoint.timeout_secs))
+        .build()
+        .map_err(|e| anyhow!("创建 HTTP 客户端失败: {}", e))
+}
+
+/// 执行一次推送：聚合、渲染负载、带重试投递并记录结果
+async fn run_push(endpoint: &RestEndpointConfig, client: &reqwest::Client, db_manager: &Arc<DatabaseManager>) -> Result<()> {
+    let end_time = Utc::now();
+    let window = endpoint.window_secs.unwrap_or(endpoint.interval_secs);
+    let start_time = end_time - ChronoDuration::seconds(window as i64);
+
+    let values = {
+        let db_manager = db_manager.clone();
+        let tags = endpoint.tags.clone();
+        let aggregation = endpoint.aggregation;
+        tokio::task::spawn_blocking(move || {
+            let mut values = HashMap::new();
+            for tag in tags {
+                let value = db_manager.aggregate_tag_value(&tag, start_time, end_time, aggregation)?;
+                values.insert(tag, value);
+            }
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(values)
+        })
+        .await
+        .map_err(|e| anyhow!("聚合任务异常终止: {}", e))?
+        .map_err(|e| anyhow!("聚合标签值失败: {}", e))?
+    };
+
+    let payload = render_payload(endpoint, &values, start_time, end_time)?;
+
+    let mut attempts = 0;
+    let result = loop {
+        attempts += 1;
+        match send(endpoint, client, &payload).await {
+            Ok(status) => break Ok(status),
+            Err((_, e)) if attempts <= endpoint.max_retries => {
+                warn!("推送到 {} 失败（第 {}/{} 次重试）: {}",
+                      endpoint.name, attempts, endpoint.max_retries, e);
+                tokio::time::sleep(Duration::from_secs(endpoint.retry_interval_secs)).await;
+            }
+            Err(e) => break Err(e),
+        }
+    };
+
+    let (status, error_message) = match &result {
+        Ok(status) => (Some(*status), None),
+        Err((status, e)) => (*status, Some(e.to_string())),
+    };
+
+    {
+        let db_manager = db_manager.clone();
+        let name = endpoint.name.clone();
+        tokio::task::spawn_blocking(move || {
+            db_manager.record_delivery(&name, end_time, attempts, status, error_message.as_deref())
+        })
+        .await
+        .map_err(|e| anyhow!("记录投递结果任务异常终止: {}", e))?
+        .map_err(|e| anyhow!("记录投递结果失败: {}", e))?;
+    }
+
+    match result {
+        Ok(status) => {
+            debug!("推送端点 {} 完成，状态码 {}，{} 个标签", endpoint.name, status, values.len());
+            Ok(())
+        }
+        Err((_, e)) => Err(anyhow!("已尝试 {} 次: {}", attempts, e)),
+    }
+}
+
+/// 发送一次请求，失败时返回可能存在的状态码
+async fn send(endpoint: &RestEndpointConfig, client: &reqwest::Client, payload: &Value) -> Result<u16, (Option<u16>, anyhow::Error)> {
+    let mut request = client.post(&endpoint.url).json(payload);
+
+    request = match &endpoint.auth {
+        RestAuthConfig::None => request,
+        RestAuthConfig::Basic { user, password } => request.basic_auth(user, Some(password)),
+        RestAuthConfig::Bearer { token } => request.bearer_auth(token),
+        RestAuthConfig::ApiKey { header, key } => request.header(header.as_str(), key.as_str()),
+    };
+    for (name, value) in &endpoint.headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+
+    let response = request.send().await
+        .map_err(|e| (None, anyhow!("请求失败: {}", e)))?;
+    let status = response.status();
+
+    if status.is_success() {
+        Ok(status.as_u16())
+    } else {
+        let body = response.text().await.unwrap_or_default();
+        Err((Some(status.as_u16()), anyhow!("服务器返回 {}: {}", status, body.chars().take(200).collect::<String>())))
+    }
+}
+
+/// 渲染 JSON 负载
+fn render_payload(
+    endpoint: &RestEndpointConfig,
+    values: &HashMap<String, Option<f64>>,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Value> {
+    let values_json: Map<String, Value> = values.iter()
+        .map(|(tag, value)| (tag.clone(), json_number(*value)))
+        .collect();
+
+    let Some(template) = &endpoint.payload_template else {
+        return Ok(serde_json::json!({
+            "timestamp": end_time.to_rfc3339(),
+            "start": start_time.to_rfc3339(),
+            "end": end_time.to_rfc3339(),
+            "values": values_json,
+        }));
+    };
+
+    let mut rendered = template
+        .replace("{{timestamp}}", &end_time.to_rfc3339())
+        .replace("{{start}}", &start_time.to_rfc3339())
+        .replace("{{end}}", &end_time.to_rfc3339())
+        .replace("{{values}}", &Value::Object(values_json).to_string());
+    for (tag, value) in values {
+        rendered = rendered.replace(&format!("{{{{value:{}}}}}", tag), &json_number(*value).to_string());
+    }
+
+    serde_json::from_str(&rendered)
+        .map_err(|e| anyhow!("端点 {} 的负载模板渲染结果不是合法 JSON: {}", endpoint.name, e))
+}
+
+/// 将可空数值转换为 JSON 值
+fn json_number(value: Option<f64>) -> Value {
+    value
+        .and_then(serde_json::Number::from_f64)
+        .map(Value::Number)
+        .unwrap_or(Value::Null)
+}
@@ -0,0 +1,200 @@
+//! Kafka 推送模块
+//! 每次写入缓存后，将新记录（标签、时间戳、值、质量）编码为 JSON 或 Avro，以标签名为键发布到配置的主题，
+//! 数据湖可消费与缓存相同的数据流。发布在后台任务中进行，不阻塞同步周期：记录批次先进入有界队列，
+//! Kafka 不可用时按间隔重试当前批次，队列满时丢弃新批次并告警。
+
+use anyhow::{Result, anyhow};
+use rskafka::client::ClientBuilder;
+use rskafka::client::partition::{Compression, PartitionClient, UnknownTopicHandling};
+use rskafka::record::Record;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::config::{KafkaConfig, KafkaFormat};
+use crate::database::TimeSeriesRecord;
+
+/// 每个生产请求的最大记录数
+const PRODUCE_BATCH: usize = 1000;
+
+/// Avro 格式的记录模式，消费端按此模式解码（消息体为不带头部的 Avro 二进制编码）
+const AVRO_SCHEMA: &str = r#"{"type":"record","name":"TagRecord","namespace":"rt_db","fields":[{"name":"tag","type":"string"},{"name":"timestamp","type":{"type":"long","logicalType":"timestamp-millis"}},{"name":"value","type":["null","double"]},{"name":"text","type":["null","string"]},{"name":"quality","type":["null","string"]}]}"#;
+
+/// Kafka 发布端，克隆后共享同一后台任务
+#[derive(Clone)]
+pub struct KafkaSink {
+    sender: mpsc::Sender<Vec<TimeSeriesRecord>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl KafkaSink {
+    /// 启动后台发布任务
+    pub fn spawn(config: KafkaConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(config.queue_batches.max(1));
+        info!("Kafka 推送已启用，主题 {}，格式 {:?}，Broker {}", config.topic, config.format, config.brokers.join(","));
+        if config.format == KafkaFormat::Avro {
+            info!("Kafka 消息的 Avro 模式: {}", AVRO_SCHEMA);
+        }
+        tokio::spawn(run(config, receiver));
+        Self {
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// 将一批新记录加入发布队列，队列满时丢弃并告警
+    pub fn publish(&self, records: &[TimeSeriesRecord]) {
+        if records.is_empty() {
+            return;
+        }
+        if self.sender.try_send(records.to_vec()).is_err() {
+            let dropped = self.dropped.fetch_add(records.len() as u64, Ordering::Relaxed) + records.len() as u64;
+            warn!("Kafka 发布队列已满，丢弃 {} 条记录（累计丢弃 {} 条）", records.len(), dropped);
+        }
+    }
+}
+
+/// 后台发布循环：按需连接，发布失败时断开并在间隔后重试同一批次
+async fn run(config: KafkaConfig, mut receiver: mpsc::Receiver<Vec<TimeSeriesRecord>>) {
+    let retry_interval = Duration::from_secs(config.retry_interval_secs.max(1));
+    let mut partitions: Option<Vec<PartitionClient>> = None;
+
+    while let Some(records) = receiver.recv().await {
+        let messages: Vec<Record> = records.iter()
+            .map(|record| Record {
+                key: Some(record.tag_name.clone().into_bytes()),
+                value: Some(encode(record, config.format)),
+                headers: BTreeMap::new(),
+                timestamp: record.timestamp,
+            })
+            .collect();
+
+        loop {
+            if partitions.is_none() {
+                match connect(&config).await {
+                    Ok(clients) => partitions = Some(clients),
+                    Err(e) => {
+                        warn!("连接 Kafka 失败，{} 秒后重试: {}", retry_interval.as_secs(), e);
+                        tokio::time::sleep(retry_interval).await;
+                        continue;
+                    }
+                }
+            }
+            let Some(clients) = partitions.as_ref() else {
+                continue;
+            };
+
+            match produce(clients, &messages).await {
+                Ok(()) => {
+                    debug!("已发布 {} 条记录到 Kafka 主题 {}", messages.len(), config.topic);
+                    break;
+                }
+                Err(e) => {
+                    warn!("发布到 Kafka 失败，{} 秒后重连重试: {}", retry_interval.as_secs(), e);
+                    partitions = None;
+                    tokio::time::sleep(retry_interval).await;
+                }
+            }
+        }
+    }
+}
+
+/// 连接 Kafka 并为主题的每个分区创建客户端
+async fn connect(config: &KafkaConfig) -> Result<Vec<PartitionClient>> {
+    let client = ClientBuilder::new(config.brokers.clone())
+        .client_id(config.client_id.as_str())
+        .build()
+        .await?;
+    let topic = client.list_topics().await?
+        .into_iter()
+        .find(|topic| topic.name == config.topic)
+        .ok_or_else(|| anyhow!("主题 {} 不存在", config.topic))?;
+
+    let mut clients = Vec::new();
+    for partition in topic.partitions {
+        clients.push(client.partition_client(config.topic.as_str(), partition, UnknownTopicHandling::Retry).await?);
+    }
+    if clients.is_empty() {
+        return Err(anyhow!("主题 {} 没有分区", config.topic));
+    }
+    info!("已连接 Kafka，主题 {} 共 {} 个分区", config.topic, clients.len());
+    Ok(clients)
+}
+
+/// 按标签名散列到分区发布，同一标签的记录保持顺序
+async fn produce(clients: &[PartitionClient], messages: &[Record]) -> Result<()> {
+    let mut by_partition: Vec<Vec<Record>> = vec![Vec::new(); clients.len()];
+    for message in messages {
+        let key = message.key.as_deref().unwrap_or_default();
+        let partition = (fnv1a(key) % clients.len() as u64) as usize;
+        by_partition[partition].push(message.clone());
+    }
+
+    for (client, records) in clients.iter().zip(by_partition) {
+        let mut records = records.into_iter().peekable();
+        while records.peek().is_some() {
+            let batch: Vec<Record> = records.by_ref().take(PRODUCE_BATCH).collect();
+            client.produce(batch, Compression::NoCompression).await?;
+        }
+    }
+    Ok(())
+}
+
+/// 稳定的标签名散列（FNV-1a），重启后同一标签仍落在同一分区
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3))
+}
+
+/// 编码一条记录
+fn encode(record: &TimeSeriesRecord, format: KafkaFormat) -> Vec<u8> {
+    match format {
+        KafkaFormat::Json => serde_json::to_vec(&serde_json::json!({
+            "tag": record.tag_name,
+            "timestamp": record.timestamp.to_rfc3339(),
+            "value": record.value,
+            "text": record.text,
+            "quality": record.quality,
+        })).unwrap_or_default(),
+        KafkaFormat::Avro => {
+            let mut buf = Vec::new();
+            avro_string(&mut buf, &record.tag_name);
+            avro_long(&mut buf, record.timestamp.timestamp_millis());
+            match record.value {
+                Some(value) => {
+                    avro_long(&mut buf, 1);
+                    buf.extend_from_slice(&value.to_le_bytes());
+                }
+                None => avro_long(&mut buf, 0),
+            }
+            for optional in [&record.text, &record.quality] {
+                match optional {
+                    Some(text) => {
+                        avro_long(&mut buf, 1);
+                        avro_string(&mut buf, text);
+                    }
+                    None => avro_long(&mut buf, 0),
+                }
+            }
+            buf
+        }
+    }
+}
+
+/// Avro long：zigzag 编码后按变长整数写出
+fn avro_long(buf: &mut Vec<u8>, value: i64) {
+    let mut n = ((value << 1) ^ (value >> 63)) as u64;
+    while n >= 0x80 {
+        buf.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+/// Avro string：长度（long）加 UTF-8 字节
+fn avro_string(buf: &mut Vec<u8>, value: &str) {
+    avro_long(buf, value.len() as i64);
+    buf.extend_from_slice(value.as_bytes());
+}
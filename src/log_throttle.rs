@@ -0,0 +1,132 @@
+//! 重复告警日志限流
+//! 同一位置的 warn/error 日志在窗口期内重复出现时只输出第一条，其余计数后丢弃（只有数字不同的消息，
+//! 如重试次数，也视为重复）；每个窗口结束时对被丢弃的消息输出一条“重复 N 次”的汇总。
+//! 用于数据源长时间不可用时避免每次重试都写满日志。
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::callsite::Identifier;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber, warn};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+
+use crate::config::LogThrottleConfig;
+
+/// 最多跟踪的消息数，超出后新出现的消息不限流
+const MAX_ENTRIES: usize = 1000;
+
+/// 限流状态
+struct Throttle {
+    window: Duration,
+    entries: Mutex<HashMap<(Identifier, String), Entry>>,
+}
+
+/// 一条被跟踪的消息
+struct Entry {
+    level: Level,
+    /// 窗口内第一次输出的消息
+    message: String,
+    window_start: Instant,
+    /// 窗口内被丢弃的次数
+    suppressed: u64,
+}
+
+static THROTTLE: OnceLock<Throttle> = OnceLock::new();
+
+/// 限流层，需与 [`spawn_reporter`] 一同使用
+pub struct ThrottleLayer;
+
+/// 按配置创建限流层，未启用时返回 None
+pub fn layer(config: &LogThrottleConfig) -> Option<ThrottleLayer> {
+    if !config.enabled {
+        return None;
+    }
+    let _ = THROTTLE.set(Throttle {
+        window: Duration::from_secs(config.window_secs),
+        entries: Mutex::new(HashMap::new()),
+    });
+    Some(ThrottleLayer)
+}
+
+impl<S: Subscriber> Layer<S> for ThrottleLayer {
+    fn event_enabled(&self, event: &Event<'_>, _ctx: Context<'_, S>) -> bool {
+        let metadata = event.metadata();
+        // 汇总消息本身不限流
+        if *metadata.level() > Level::WARN || metadata.target() == module_path!() {
+            return true;
+        }
+        let Some(throttle) = THROTTLE.get() else {
+            return true;
+        };
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let key = (metadata.callsite(), visitor.message.replace(|c: char| c.is_ascii_digit(), ""));
+
+        let now = Instant::now();
+        let mut entries = throttle.entries.lock().unwrap();
+        if let Some(entry) = entries.get_mut(&key) {
+            if now.duration_since(entry.window_start) < throttle.window {
+                entry.suppressed += 1;
+                return false;
+            }
+            entry.window_start = now;
+            entry.message = visitor.message;
+        } else if entries.len() < MAX_ENTRIES {
+            entries.insert(key, Entry { level: *metadata.level(), message: visitor.message, window_start: now, suppressed: 0 });
+        }
+        true
+    }
+}
+
+/// 拼接事件的消息与其他字段
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.message.is_empty() {
+            self.message.push(' ');
+        }
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.message, "{}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// 启动汇总任务：每个窗口输出被丢弃消息的重复次数，并清理已过期的消息；未启用时返回 None
+pub fn spawn_reporter() -> Option<tokio::task::JoinHandle<()>> {
+    let throttle = THROTTLE.get()?;
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(throttle.window);
+        interval.tick().await; // 跳过第一个立即触发
+
+        loop {
+            interval.tick().await;
+            let now = Instant::now();
+            let mut repeated = Vec::new();
+            {
+                let mut entries = throttle.entries.lock().unwrap();
+                entries.retain(|_, entry| {
+                    if entry.suppressed > 0 {
+                        repeated.push((entry.level, entry.suppressed, entry.message.clone()));
+                        entry.suppressed = 0;
+                    }
+                    now.duration_since(entry.window_start) < throttle.window
+                });
+            }
+
+            // 在锁外输出，避免输出汇总时再次进入限流层
+            for (level, count, message) in repeated {
+                warn!("上一个 {} 秒内 {} 日志重复 {} 次（已省略）: {}", throttle.window.as_secs(), level, count, message);
+            }
+        }
+    }))
+}
@@ -0,0 +1,128 @@
+//! 低延迟模式
+//! 亚秒级轮询时，每次轮询的结果先暂存在内存中，每 `commit_every_cycles` 次轮询在一个事务中批量写入，
+//! 减少 DuckDB 事务次数；同时统计每次轮询读取数据源的耗时与从开始读取到数据提交的端到端延迟，
+//! 用于确认缓存作为准实时镜像的实际延迟。
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::database::TimeSeriesRecord;
+
+/// 延迟统计保留的最近样本数
+const MAX_SAMPLES: usize = 1000;
+
+/// 暂存的一次轮询结果
+pub struct StagedPoll {
+    /// 开始读取数据源的时间
+    pub started: Instant,
+    pub records: Vec<TimeSeriesRecord>,
+}
+
+/// 延迟统计
+#[derive(Default)]
+pub struct LatencyTracker {
+    samples: Mutex<LatencySamples>,
+}
+
+#[derive(Default)]
+struct LatencySamples {
+    polls: u64,
+    commits: u64,
+    overruns: u64,
+    fetch: VecDeque<Duration>,
+    end_to_end: VecDeque<Duration>,
+}
+
+/// 延迟统计报告，分位数基于最近的样本
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyReport {
+    /// 成功的轮询次数
+    pub polls: u64,
+    /// 批量提交次数
+    pub commits: u64,
+    /// 耗时超过轮询间隔的轮询次数
+    pub overruns: u64,
+    /// 读取数据源的耗时
+    pub fetch: Percentiles,
+    /// 从开始读取数据源到数据提交的延迟
+    pub end_to_end: Percentiles,
+}
+
+/// 延迟分位数，单位为毫秒
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Percentiles {
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+impl LatencyTracker {
+    /// 记录一次成功的轮询
+    pub fn record_poll(&self, fetch: Duration, overrun: bool) {
+        let mut samples = self.samples.lock().unwrap();
+        samples.polls += 1;
+        if overrun {
+            samples.overruns += 1;
+        }
+        push_sample(&mut samples.fetch, fetch);
+    }
+
+    /// 记录一次批量提交，`started` 为本次提交的各次轮询开始读取的时间
+    pub fn record_commit(&self, started: impl IntoIterator<Item = Instant>) {
+        let committed = Instant::now();
+        let mut samples = self.samples.lock().unwrap();
+        samples.commits += 1;
+        for started in started {
+            push_sample(&mut samples.end_to_end, committed.duration_since(started));
+        }
+    }
+
+    pub fn report(&self) -> LatencyReport {
+        let samples = self.samples.lock().unwrap();
+        LatencyReport {
+            polls: samples.polls,
+            commits: samples.commits,
+            overruns: samples.overruns,
+            fetch: percentiles(&samples.fetch),
+            end_to_end: percentiles(&samples.end_to_end),
+        }
+    }
+}
+
+impl std::fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "轮询 {} 次（超时 {} 次），提交 {} 次；读取耗时 p50 {:.0} / p95 {:.0} / 最大 {:.0} 毫秒，端到端延迟 p50 {:.0} / p95 {:.0} / 最大 {:.0} 毫秒",
+            self.polls, self.overruns, self.commits,
+            self.fetch.p50_ms, self.fetch.p95_ms, self.fetch.max_ms,
+            self.end_to_end.p50_ms, self.end_to_end.p95_ms, self.end_to_end.max_ms
+        )
+    }
+}
+
+fn push_sample(samples: &mut VecDeque<Duration>, sample: Duration) {
+    if samples.len() == MAX_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(sample);
+}
+
+fn percentiles(samples: &VecDeque<Duration>) -> Percentiles {
+    if samples.is_empty() {
+        return Percentiles::default();
+    }
+    let mut sorted: Vec<Duration> = samples.iter().copied().collect();
+    sorted.sort();
+    let at = |q: f64| {
+        let index = ((sorted.len() - 1) as f64 * q).round() as usize;
+        sorted[index].as_secs_f64() * 1000.0
+    };
+    Percentiles {
+        p50_ms: at(0.5),
+        p95_ms: at(0.95),
+        max_ms: at(1.0),
+    }
+}
@@ -1,14 +1,46 @@
+mod aad;
+mod alert;
+mod api;
+mod archive;
+mod backup;
+mod capture;
+mod chaos;
 mod config;
 mod database;
+mod degradation;
 mod data_source;
+mod energy;
+mod export;
+mod forecast;
+mod integration;
+mod kafka;
+mod log_throttle;
+mod low_latency;
+mod metrics;
+mod normalize;
+mod opcua;
+mod proxy;
+mod replica;
+mod rollup;
+mod s3;
+mod schema_doc;
+mod slow_log;
+mod spc;
+mod sql;
+mod stale;
+mod startup;
 mod sync_service;
+mod telemetry;
+mod toggles;
+mod verify;
 
 use anyhow::Result;
 use std::sync::Arc;
-use tracing::{info, error, warn, debug};
+use tracing::{info, error, warn, debug, Instrument};
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 use tracing_appender::{rolling, non_blocking};
 use std::fs;
+use tokio::sync::Notify;
 
 use config::AppConfig;
 use database::DatabaseManager;
@@ -23,7 +55,8 @@ async fn check_table_structure(data_source: &SqlServerDataSource) -> Result<()>
     
     // 检查TagDatabase表结构
     debug!("检查TagDatabase表结构:");
-    let stream = client.query("SELECT COLUMN_NAME, DATA_TYPE FROM INFORMATION_SCHEMA.COLUMNS WHERE TABLE_NAME = 'TagDatabase'", &[]).await?;
+    let stream = data_source.checked_query("SELECT COLUMN_NAME, DATA_TYPE FROM INFORMATION_SCHEMA.COLUMNS WHERE TABLE_NAME = 'TagDatabase'")?
+        .query(&mut client).await?;
     let rows = stream.into_first_result().await?;
     
     for row in rows {
@@ -34,7 +67,8 @@ async fn check_table_structure(data_source: &SqlServerDataSource) -> Result<()>
     
     // 检查历史表结构
     debug!("检查历史表结构:");
-    let stream = client.query("SELECT COLUMN_NAME, DATA_TYPE FROM INFORMATION_SCHEMA.COLUMNS WHERE TABLE_NAME = '历史表'", &[]).await?;
+    let stream = data_source.checked_query("SELECT COLUMN_NAME, DATA_TYPE FROM INFORMATION_SCHEMA.COLUMNS WHERE TABLE_NAME = '历史表'")?
+        .query(&mut client).await?;
     let rows = stream.into_first_result().await?;
     
     for row in rows {
@@ -47,53 +81,32 @@ async fn check_table_structure(data_source: &SqlServerDataSource) -> Result<()>
     Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // 检查命令行参数
-    let args: Vec<String> = std::env::args().collect();
-    
-    // 如果参数包含 --test-config，运行配置测试// 检查是否运行测试
-    if args.len() > 1 && args[1] == "--test-config" {
-        println!("配置测试功能已移除");
-        return Ok(());
-    }
-    
-    // 加载配置
-    let config = match AppConfig::load("config.toml") {
-        Ok(config) => {
-            Arc::new(config)
-        }
-        Err(e) => {
-            eprintln!("配置加载失败: {}", e);
-            eprintln!("提示: 可以运行 'cargo run -- --test-config' 来测试配置解析功能");
-            return Err(e);
-        }
-    };
-    
-    // 初始化日志系统
-    init_logging(&config);
-    
-    info!("=== 实时数据缓存服务启动 ===");
-    info!("配置加载成功");
-    
-    // 初始化数据库管理器
-    let db_manager = Arc::new(DatabaseManager::new(config.db_file_path.clone()));
-    
-    // 初始化数据库结构
-    if let Err(e) = db_manager.initialize() {
-        error!("数据库初始化失败: {}", e);
-        return Err(anyhow::anyhow!("数据库初始化失败: {}", e));
-    }
-    
+/// 连接 SQL Server 数据源，执行初始加载并启动周期更新与状态报告任务
+async fn start_source_sync(
+    config: &Arc<AppConfig>,
+    db_manager: &Arc<DatabaseManager>,
+    sync_trigger: &Arc<Notify>,
+) -> Result<(Arc<SyncService>, Vec<tokio::task::JoinHandle<()>>)> {
     // 初始化数据源
-    let data_source = Arc::new(SqlServerDataSource::new((*config).clone()));
+    let data_source = Arc::new(SqlServerDataSource::new((**config).clone()).with_toggles(db_manager.toggles().clone()));
     
-    // 测试数据源连接
-    if let Err(e) = data_source.test_connection().await {
+    // 测试数据源连接，按启动配置等待数据源就绪
+    if let Err(e) = startup::wait_for_source(&config.startup, &data_source).await {
         error!("数据源连接测试失败: {}", e);
         return Err(anyhow::anyhow!("数据源连接测试失败: {}", e));
     }
     
+    // 没有历史表的站点降级为仅快照模式，只同步TagDatabase的当前值；先于权限检查判断，
+    // 否则不存在的历史表在权限检查中被当作缺少权限
+    let snapshot_only = !data_source.has_history_table().await?;
+    if snapshot_only {
+        warn!("历史表 {} 不存在，以仅快照模式运行：跳过历史数据加载与缺口回填，只同步TagDatabase的当前值",
+              config.tables.history_table);
+    }
+    
+    // 检查账号权限
+    data_source.check_permissions(snapshot_only).await?;
+    
     // 检查表结构
     check_table_structure(&data_source).await?;
     
@@ -113,12 +126,14 @@ async fn main() -> Result<()> {
     //     }
     // }
     
-    // 创建同步服务
-    let mut sync_service = SyncService::new(
+    // 创建同步服务，初始加载、周期更新与状态报告共享同一实例
+    let sync_service = Arc::new(SyncService::new(
         config.clone(),
         db_manager.clone(),
         data_source.clone(),
-    );
+        sync_trigger.clone(),
+        snapshot_only,
+    ));
     
     // 执行初始数据加载
     debug!("开始初始数据加载...");
@@ -128,32 +143,46 @@ async fn main() -> Result<()> {
     }
     
     // 显示初始状态
-    if let Ok(status) = sync_service.get_status().await {
+    if let Ok(status) = sync_service.get_status() {
         debug!("\n{}", status);
     }
     
     // 启动周期性更新任务
     let update_handle = {
-        let mut service = SyncService::new(
-            config.clone(),
-            db_manager.clone(),
-            data_source.clone(),
-        );
+        let service = sync_service.clone();
         
         tokio::spawn(async move {
             if let Err(e) = service.start_periodic_update().await {
                 error!("周期性更新任务失败: {}", e);
             }
-        })
+        }.in_current_span())
     };
     
+    // 启动快速组轮询任务（低延迟模式已按更短的间隔轮询全部标签）
+    let fast_handle = (!config.polling.fast_tags.is_empty() && !config.low_latency.enabled).then(|| {
+        let service = sync_service.clone();
+        
+        tokio::spawn(async move {
+            if let Err(e) = service.start_fast_polling().await {
+                error!("快速组轮询任务失败: {}", e);
+            }
+        }.in_current_span())
+    });
+    
+    // 启动低延迟模式轮询任务
+    let low_latency_handle = config.low_latency.enabled.then(|| {
+        let service = sync_service.clone();
+        
+        tokio::spawn(async move {
+            if let Err(e) = service.start_low_latency_sync().await {
+                error!("低延迟轮询任务失败: {}", e);
+            }
+        }.in_current_span())
+    });
+    
     // 启动状态报告任务
     let status_handle = {
-        let service = SyncService::new(
-            config.clone(),
-            db_manager.clone(),
-            data_source.clone(),
-        );
+        let service = sync_service.clone();
         
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(300)); // 5分钟
@@ -161,41 +190,591 @@ async fn main() -> Result<()> {
             
             loop {
                 interval.tick().await;
-                if let Ok(status) = service.get_status().await {
+                if let Ok(status) = service.get_status() {
                     debug!("定期状态报告:\n{}", status);
                 }
             }
-        })
+        }.in_current_span())
     };
     
+    let handles = [Some(update_handle), fast_handle, low_latency_handle, Some(status_handle)].into_iter().flatten().collect();
+    Ok((sync_service, handles))
+}
+
+/// 生成缓存数据字典：`rt_db schema-doc [--format md|html] [--output 文件]`，未指定输出文件时写到标准输出
+///
+/// 直接读取 DuckDB 文件，需在服务停止时运行；服务运行中可通过 HTTP API `GET /schema-doc` 获取。
+fn write_schema_doc(config: &Arc<AppConfig>, args: &[String]) -> Result<()> {
+    let mut format = schema_doc::DocFormat::Markdown;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = args.next().ok_or_else(|| anyhow::anyhow!("--format 缺少参数"))?;
+                format = value.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            }
+            "--output" => output = Some(args.next().ok_or_else(|| anyhow::anyhow!("--output 缺少参数"))?),
+            other => anyhow::bail!("未知参数: {}", other),
+        }
+    }
+    
+    if !std::path::Path::new(&config.db_file_path).exists() {
+        anyhow::bail!("DuckDB 文件不存在: {}", config.db_file_path);
+    }
+    let db_manager = DatabaseManager::new(config.clone());
+    let doc = schema_doc::generate(&db_manager, config, format)
+        .map_err(|e| anyhow::anyhow!("生成数据字典失败: {}", e))?;
+    
+    match output {
+        Some(path) => fs::write(path, doc)?,
+        None => print!("{}", doc),
+    }
+    Ok(())
+}
+
+/// 导出 Parquet 或 Arrow IPC 流文件：`rt_db export parquet|arrow --output 文件 [--from 时间] [--to 时间] [--tags a,b]`，
+/// 或 CSV 文件：`rt_db export csv --output 文件 --from 时间 --to 时间 [--tags a,b] [--time local|utc]`
+///
+/// CSV 供工艺工程师在 Excel 中打开，DateTime 列默认为数据源本地时间（`YYYY-MM-DD HH:MM:SS`）。
+/// 时间为 RFC 3339 格式（如 `2024-01-01T00:00:00+08:00`），未给出时不限；与 `schema-doc` 相同，
+/// 直接读取 DuckDB 文件，需在服务停止时运行。
+fn write_export(config: &Arc<AppConfig>, args: &[String]) -> Result<()> {
+    let mut args = args.iter();
+    let format = match args.next().map(String::as_str) {
+        Some(format @ ("parquet" | "arrow" | "csv")) => format,
+        Some(other) => anyhow::bail!("不支持的导出格式: {}（可选 parquet、arrow、csv）", other),
+        None => anyhow::bail!(
+            "用法: rt_db export parquet|arrow --output 文件 [--from 时间] [--to 时间] [--tags a,b]\n      \
+             rt_db export csv --output 文件 --from 时间 --to 时间 [--tags a,b] [--time local|utc]"
+        ),
+    };
+    
+    let mut output = None;
+    let mut from = None;
+    let mut to = None;
+    let mut tags = Vec::new();
+    let mut timestamps = config::ExportTimestamps::Local;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow::anyhow!("{} 缺少参数", arg));
+        match arg.as_str() {
+            "--output" => output = Some(value()?.clone()),
+            "--from" => from = Some(value()?.parse::<chrono::DateTime<chrono::Utc>>()
+                .map_err(|e| anyhow::anyhow!("--from 时间格式无效: {}", e))?),
+            "--to" => to = Some(value()?.parse::<chrono::DateTime<chrono::Utc>>()
+                .map_err(|e| anyhow::anyhow!("--to 时间格式无效: {}", e))?),
+            "--tags" => tags = value()?.split(',')
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            "--time" if format == "csv" => timestamps = match value()?.as_str() {
+                "local" => config::ExportTimestamps::Local,
+                "utc" => config::ExportTimestamps::Utc,
+                other => anyhow::bail!("--time 可选 local、utc，而不是 {}", other),
+            },
+            other => anyhow::bail!("未知参数: {}", other),
+        }
+    }
+    let output = output.ok_or_else(|| anyhow::anyhow!("必须通过 --output 指定输出文件"))?;
+    if let (Some(from), Some(to)) = (from, to)
+        && to <= from {
+        anyhow::bail!("--to 必须晚于 --from");
+    }
+    
+    if !std::path::Path::new(&config.db_file_path).exists() {
+        anyhow::bail!("DuckDB 文件不存在: {}", config.db_file_path);
+    }
+    let db_manager = DatabaseManager::new(config.clone());
+    let path = std::path::Path::new(&output);
+    if format == "csv" {
+        let (Some(from), Some(to)) = (from, to) else {
+            anyhow::bail!("导出 CSV 必须通过 --from 与 --to 指定时间范围");
+        };
+        let layout = config::ExportLayout { timestamps, ..Default::default() };
+        let (files, rows) = db_manager.export_csv(path, from, to, &tags, layout)
+            .map_err(|e| anyhow::anyhow!("导出 CSV 失败: {}", e))?;
+        for file in &files {
+            println!("已写入 {}", file.display());
+        }
+        println!("已导出 {} 行", rows);
+    } else if format == "arrow" {
+        let file = std::io::BufWriter::new(fs::File::create(path)?);
+        let rows = db_manager.export_arrow(file, from, to, &tags)
+            .map_err(|e| anyhow::anyhow!("导出 Arrow 失败: {}", e))?;
+        println!("已导出 {} 行到 {}", rows, output);
+    } else {
+        let rows = db_manager.export_parquet(path, from, to, &tags)
+            .map_err(|e| anyhow::anyhow!("导出 Parquet 失败: {}", e))?;
+        println!("已导出 {} 行到 {}", rows, output);
+    }
+    Ok(())
+}
+
+/// 备份缓存：`rt_db backup [--dir 目录] [--keep 份数]`，未给出时取 `[backup]` 配置
+///
+/// 与 `schema-doc` 相同，直接读取 DuckDB 文件，需在服务停止时运行；服务运行中使用定时备份或 `POST /admin/backup`。
+async fn write_backup(config: &Arc<AppConfig>, args: &[String]) -> Result<()> {
+    let mut dir = config.backup.dir.clone();
+    let mut keep = config.backup.keep;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow::anyhow!("{} 缺少参数", arg));
+        match arg.as_str() {
+            "--dir" => dir = value()?.clone(),
+            "--keep" => keep = value()?.parse()
+                .ok()
+                .filter(|keep| *keep > 0)
+                .ok_or_else(|| anyhow::anyhow!("--keep 必须为正整数"))?,
+            other => anyhow::bail!("未知参数: {}", other),
+        }
+    }
+    
+    if !std::path::Path::new(&config.db_file_path).exists() {
+        anyhow::bail!("DuckDB 文件不存在: {}", config.db_file_path);
+    }
+    let db_manager = DatabaseManager::new(config.clone());
+    let target = backup::run(&db_manager, config, std::path::Path::new(&dir), keep)?;
+    println!("已备份到 {}", target.display());
+    let uploaded = backup::upload(config, &target).await?;
+    if uploaded > 0 {
+        println!("已上传 {} 个文件", uploaded);
+    }
+    Ok(())
+}
+
+/// 从备份恢复缓存：`rt_db restore <备份目录>`，需在服务停止时运行
+fn write_restore(config: &Arc<AppConfig>, args: &[String]) -> Result<()> {
+    let [source] = args else {
+        anyhow::bail!("用法: rt_db restore <备份目录>");
+    };
+    
+    match backup::restore(config, std::path::Path::new(source))? {
+        Some(last_synced) => println!("已恢复到 {}，服务启动后从 {} 回填之后的数据", config.db_file_path, last_synced),
+        None => println!("已恢复到 {}（备份中没有数据）", config.db_file_path),
+    }
+    Ok(())
+}
+
+/// 压缩数据表：`rt_db compact`，删除已移除标签的全空列并按时间重新排序写入，回收空间
+///
+/// 与 `schema-doc` 相同，直接读取 DuckDB 文件，需在服务停止时运行。
+fn write_compact(config: &Arc<AppConfig>, args: &[String]) -> Result<()> {
+    if let Some(arg) = args.first() {
+        anyhow::bail!("未知参数: {}", arg);
+    }
+    if !std::path::Path::new(&config.db_file_path).exists() {
+        anyhow::bail!("DuckDB 文件不存在: {}", config.db_file_path);
+    }
+    
+    let db_manager = DatabaseManager::new(config.clone());
+    let report = db_manager.compact()
+        .map_err(|e| anyhow::anyhow!("压缩数据表失败: {}", e))?;
+    if !report.dropped_columns.is_empty() {
+        println!("已删除 {} 个全空列: {}", report.dropped_columns.len(), report.dropped_columns.join(", "));
+    }
+    println!(
+        "已重写 {} 行，文件大小 {:.2} MB -> {:.2} MB",
+        report.rows,
+        report.size_before as f64 / 1024.0 / 1024.0,
+        report.size_after as f64 / 1024.0 / 1024.0
+    );
+    Ok(())
+}
+
+/// 对账：`rt_db verify --from 时间 --to 时间 [--tags a,b]`，比较数据源与缓存中各标签的样本数
+///
+/// 存在不一致的标签时返回错误（退出码非零）；与 `schema-doc` 相同，直接读取 DuckDB 文件，需在服务停止时运行。
+async fn write_verify(config: &Arc<AppConfig>, args: &[String]) -> Result<()> {
+    let mut from = None;
+    let mut to = None;
+    let mut tags = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow::anyhow!("{} 缺少参数", arg));
+        match arg.as_str() {
+            "--from" => from = Some(value()?.parse::<chrono::DateTime<chrono::Utc>>()
+                .map_err(|e| anyhow::anyhow!("--from 时间格式无效: {}", e))?),
+            "--to" => to = Some(value()?.parse::<chrono::DateTime<chrono::Utc>>()
+                .map_err(|e| anyhow::anyhow!("--to 时间格式无效: {}", e))?),
+            "--tags" => tags = value()?.split(',')
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            other => anyhow::bail!("未知参数: {}", other),
+        }
+    }
+    let (Some(from), Some(to)) = (from, to) else {
+        anyhow::bail!("用法: rt_db verify --from 时间 --to 时间 [--tags a,b]");
+    };
+    if to <= from {
+        anyhow::bail!("--to 必须晚于 --from");
+    }
+    if !std::path::Path::new(&config.db_file_path).exists() {
+        anyhow::bail!("DuckDB 文件不存在: {}", config.db_file_path);
+    }
+    
+    let db_manager = DatabaseManager::new(config.clone());
+    let report = verify::run(config, &db_manager, from, to, &tags).await?;
+    
+    println!("比较 {} 个标签：数据源 {} 个样本，缓存 {} 个样本", report.tags, report.source_samples, report.cache_samples);
+    if report.mismatches.is_empty() {
+        println!("一致");
+        return Ok(());
+    }
+    println!("{:<40} {:>12} {:>12} {:>12}", "标签", "数据源", "缓存", "差值");
+    for (tag, source, cache) in &report.mismatches {
+        println!("{:<40} {:>12} {:>12} {:>12}", tag, source, cache, *cache as i64 - *source as i64);
+    }
+    if config.deadband.enabled {
+        println!("注意: 已启用死区，未变化的值不写入缓存，缓存样本数少于数据源属于预期");
+    }
+    anyhow::bail!("{} 个标签的样本数不一致", report.mismatches.len())
+}
+
+/// 查询运行中服务的状态：`rt_db status [--json]`
+///
+/// 经本机的 HTTP API（`GET /status`）读取，需启用 `[api]`；`--json` 输出机器可读的 JSON，默认输出文本报告。
+async fn write_status(config: &Arc<AppConfig>, args: &[String]) -> Result<()> {
+    let mut json = false;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            other => anyhow::bail!("未知参数: {}", other),
+        }
+    }
+    if !config.api.enabled {
+        anyhow::bail!("status 通过 HTTP API 查询服务状态，需启用 [api]");
+    }
+    
+    // 监听所有地址时经本机回环地址访问
+    let addr: std::net::SocketAddr = config.api.bind_addr.parse()
+        .map_err(|e| anyhow::anyhow!("api.bind_addr 无效: {}", e))?;
+    let ip = match addr.ip() {
+        std::net::IpAddr::V4(ip) if ip.is_unspecified() => std::net::Ipv4Addr::LOCALHOST.into(),
+        std::net::IpAddr::V6(ip) if ip.is_unspecified() => std::net::Ipv6Addr::LOCALHOST.into(),
+        ip => ip,
+    };
+    let url = format!(
+        "http://{}/status?format={}",
+        std::net::SocketAddr::new(ip, addr.port()),
+        if json { "json" } else { "text" }
+    );
+    
+    let response = reqwest::get(&url).await
+        .map_err(|e| anyhow::anyhow!("无法连接服务（{}），服务是否在运行: {}", url, e))?;
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        anyhow::bail!("查询服务状态失败（{}）: {}", status, body);
+    }
+    
+    if json {
+        let value: serde_json::Value = serde_json::from_str(&body)?;
+        println!("{}", serde_json::to_string_pretty(&value)?);
+    } else {
+        print!("{}", body);
+    }
+    Ok(())
+}
+
+/// 额外同步配置启动失败或任务退出后的首次重试间隔
+const PIPELINE_RETRY_MIN: tokio::time::Duration = tokio::time::Duration::from_secs(5);
+/// 额外同步配置重试间隔的上限
+const PIPELINE_RETRY_MAX: tokio::time::Duration = tokio::time::Duration::from_secs(300);
+
+/// 启动一个额外同步配置：独立的 DuckDB 文件与同步任务，日志带有配置名称
+async fn start_pipeline(
+    config: &AppConfig,
+    pipeline: &config::PipelineConfig,
+    sync_trigger: &Arc<Notify>,
+) -> Result<Vec<tokio::task::JoinHandle<()>>> {
+    let pipeline_config = Arc::new(config.pipeline_config(pipeline)?);
+    
+    async {
+        info!("启动同步配置，DuckDB 文件: {}", pipeline_config.db_file_path);
+        
+        let db_manager = Arc::new(DatabaseManager::new(pipeline_config.clone()));
+        db_manager.initialize()
+            .map_err(|e| anyhow::anyhow!("数据库初始化失败: {}", e))?;
+        
+        let (_, handles) = start_source_sync(&pipeline_config, &db_manager, sync_trigger).await?;
+        Ok(handles)
+    }
+    .instrument(tracing::info_span!("pipeline", name = %pipeline.name))
+    .await
+}
+
+/// 离开作用域时中止持有的任务
+struct AbortOnDrop(Vec<tokio::task::AbortHandle>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        for handle in &self.0 {
+            handle.abort();
+        }
+    }
+}
+
+/// 监督一个额外同步配置：启动失败或任务退出时按指数退避重新启动，监督任务被中止时一并中止同步任务
+fn spawn_pipeline_supervisor(
+    config: Arc<AppConfig>,
+    pipeline: config::PipelineConfig,
+    sync_trigger: Arc<Notify>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut retry = PIPELINE_RETRY_MIN;
+        loop {
+            match start_pipeline(&config, &pipeline, &sync_trigger).await {
+                Ok(handles) if !handles.is_empty() => {
+                    retry = PIPELINE_RETRY_MIN;
+                    let _tasks = AbortOnDrop(handles.iter().map(|h| h.abort_handle()).collect());
+                    let (result, _, _) = futures::future::select_all(handles).await;
+                    match result {
+                        Ok(()) => error!("同步配置 {} 的任务已退出，{} 秒后重新启动", pipeline.name, retry.as_secs()),
+                        Err(e) => error!("同步配置 {} 的任务异常终止: {}，{} 秒后重新启动", pipeline.name, e, retry.as_secs()),
+                    }
+                }
+                Ok(_) => return,
+                Err(e) => error!("同步配置 {} 启动失败: {}，{} 秒后重试", pipeline.name, e, retry.as_secs()),
+            }
+            tokio::time::sleep(retry).await;
+            retry = (retry * 2).min(PIPELINE_RETRY_MAX);
+        }
+    })
+}
+
+/// 将立即同步请求转发给每个同步服务（各服务在自己的 Notify 上等待）
+fn spawn_sync_trigger_fanout(sync_trigger: Arc<Notify>, targets: Vec<Arc<Notify>>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            sync_trigger.notified().await;
+            for target in &targets {
+                target.notify_one();
+            }
+        }
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // 检查命令行参数
+    let args: Vec<String> = std::env::args().collect();
+    
+    // 如果参数包含 --test-config，运行配置测试// 检查是否运行测试
+    if args.len() > 1 && args[1] == "--test-config" {
+        println!("配置测试功能已移除");
+        return Ok(());
+    }
+    
+    // 加载配置
+    let config = match AppConfig::load("config.toml") {
+        Ok(config) => {
+            Arc::new(config)
+        }
+        Err(e) => {
+            eprintln!("配置加载失败: {}", e);
+            eprintln!("提示: 可以运行 'cargo run -- --test-config' 来测试配置解析功能");
+            return Err(e);
+        }
+    };
+    
+    match args.get(1).map(String::as_str) {
+        Some("schema-doc") => return write_schema_doc(&config, &args[2..]),
+        Some("export") => return write_export(&config, &args[2..]),
+        Some("backup") => return write_backup(&config, &args[2..]).await,
+        Some("restore") => return write_restore(&config, &args[2..]),
+        Some("compact") => return write_compact(&config, &args[2..]),
+        Some("verify") => return write_verify(&config, &args[2..]).await,
+        Some("status") => return write_status(&config, &args[2..]).await,
+        _ => {}
+    }
+    
+    // 初始化日志系统
+    init_logging(&config);
+    
+    info!("=== 实时数据缓存服务启动 ===");
+    info!("配置加载成功");
+    chaos::warn_if_enabled();
+    degradation::init(&config);
+    
+    // 等待数据盘挂载
+    if let Err(e) = startup::wait_for_paths(&config.startup).await {
+        error!("{}", e);
+        return Err(e);
+    }
+    
+    // 初始化数据库管理器
+    let db_manager = Arc::new(DatabaseManager::new(config.clone()));
+    
+    // 初始化数据库结构
+    if let Err(e) = db_manager.initialize() {
+        error!("数据库初始化失败: {}", e);
+        return Err(anyhow::anyhow!("数据库初始化失败: {}", e));
+    }
+    
+    // 立即同步请求，由 SIGUSR1 与管理接口触发，转发给主同步与每个额外同步配置
+    let sync_trigger = Arc::new(Notify::new());
+    let main_trigger = Arc::new(Notify::new());
+    let pipeline_triggers: Vec<Arc<Notify>> = config.pipelines.iter().map(|_| Arc::new(Notify::new())).collect();
+    let fanout_handle = spawn_sync_trigger_fanout(
+        sync_trigger.clone(),
+        std::iter::once(main_trigger.clone()).chain(pipeline_triggers.iter().cloned()).collect(),
+    );
+    
+    // 启动数据同步：跟随模式从主实例拉取变更，否则从 SQL Server 同步
+    let (sync_service, mut sync_handles) = if config.replica.enabled {
+        (None, vec![replica::spawn_follower(config.clone(), db_manager.clone(), main_trigger)])
+    } else {
+        let (sync_service, handles) = start_source_sync(&config, &db_manager, &main_trigger).await?;
+        (Some(sync_service), handles)
+    };
+    sync_handles.push(fanout_handle);
+    
+    // 启动额外同步配置，单个配置启动失败不影响其余配置，失败的配置按退避间隔重试
+    for (pipeline, trigger) in config.pipelines.iter().zip(pipeline_triggers) {
+        sync_handles.push(spawn_pipeline_supervisor(config.clone(), pipeline.clone(), trigger));
+    }
+    
+    // 收到 SIGUSR1 时立即执行一次同步
+    #[cfg(unix)]
+    sync_handles.push(spawn_sync_signal_listener(sync_trigger.clone()));
+    
+    // 启动数据库维护任务
+    let maintenance_handle = if config.maintenance.enabled && config.maintenance.interval_secs > 0 {
+        let db_manager = db_manager.clone();
+        let interval_secs = config.maintenance.interval_secs;
+        
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(interval_secs));
+            interval.tick().await; // 跳过第一个立即触发
+            
+            loop {
+                interval.tick().await;
+                let db_manager = db_manager.clone();
+                match tokio::task::spawn_blocking(move || db_manager.checkpoint()).await {
+                    Ok(Ok((before, after))) => info!(
+                        "数据库维护完成: 文件大小 {:.2} MB -> {:.2} MB",
+                        before as f64 / 1024.0 / 1024.0,
+                        after as f64 / 1024.0 / 1024.0
+                    ),
+                    Ok(Err(e)) => warn!("数据库维护失败: {}", e),
+                    Err(e) => error!("数据库维护任务异常终止: {}", e),
+                }
+            }
+        }))
+    } else {
+        None
+    };
+    
+    // 启动定时备份
+    let backup_handle = backup::spawn_scheduler(config.clone(), db_manager.clone());
+    
+    // 启动资源压力监控
+    let degradation_handle = degradation::spawn_monitor(config.clone());
+    
+    // 启动 HTTP API
+    let api_handle = if config.api.enabled {
+        let state = Arc::new(api::ApiState::new(config.clone(), db_manager.clone(), sync_trigger.clone(), sync_service.clone()));
+        
+        Some(tokio::spawn(async move {
+            if let Err(e) = api::serve(state).await {
+                error!("HTTP API 服务失败: {}", e);
+            }
+        }))
+    } else {
+        None
+    };
+    
+    // 启动定时导出任务
+    let export_handles = export::spawn_jobs(config.clone(), db_manager.clone());
+    
+    // 启动 MES/ERP REST 推送任务
+    let integration_handles = integration::spawn_jobs(config.clone(), db_manager.clone());
+    
     info!("服务启动完成，等待终止信号...");
     
     // 等待终止信号
     wait_for_shutdown_signal().await;
     
     info!("收到终止信号，开始停机...");
+    let shutdown_timeout = tokio::time::Duration::from_secs(5);
+    
+    // 等待进行中的同步周期提交后再取消任务，避免丢弃本周期已获取的数据
+    wait_for_cycle(&db_manager, shutdown_timeout).await;
     
     // 取消任务
-    update_handle.abort();
-    status_handle.abort();
+    for handle in &sync_handles {
+        handle.abort();
+    }
+    if let Some(handle) = &api_handle {
+        handle.abort();
+    }
+    for handle in maintenance_handle.iter().chain(&backup_handle).chain(&degradation_handle) {
+        handle.abort();
+    }
+    for handle in export_handles.iter().chain(&integration_handles) {
+        handle.abort();
+    }
     
     // 等待任务完成（最多等待5秒）
-    let shutdown_timeout = tokio::time::Duration::from_secs(5);
-    if let Err(_) = tokio::time::timeout(shutdown_timeout, async {
-        let _ = update_handle.await;
-        let _ = status_handle.await;
-    }).await {
+    if tokio::time::timeout(shutdown_timeout, async {
+        for handle in sync_handles {
+            let _ = handle.await;
+        }
+    }).await.is_err() {
         warn!("任务停止超时，强制退出");
     }
     
+    // 保存缓存，下次启动（persist_cache）可直接复用
+    let shutdown_config = config.clone();
+    let result = tokio::task::spawn_blocking(move || save_cache_on_shutdown(&shutdown_config, &db_manager)).await;
+    match result {
+        Ok(Ok(Some(target))) => {
+            if let Err(e) = backup::upload(&config, &target).await {
+                error!("上传停机备份 {} 失败: {}", target.display(), e);
+            }
+        }
+        Ok(Ok(None)) => {}
+        Ok(Err(e)) => error!("停机时保存缓存失败: {}", e),
+        Err(e) => error!("停机保存任务异常终止: {}", e),
+    }
+    
+    // 发送尚未导出的追踪
+    if let Err(e) = tokio::task::spawn_blocking(telemetry::shutdown).await {
+        error!("关闭追踪导出任务异常终止: {}", e);
+    }
+    
     info!("服务已停止");
     Ok(())
 }
 
+/// 等待进行中的同步周期完成，最多等待 `timeout`
+async fn wait_for_cycle(db_manager: &DatabaseManager, timeout: tokio::time::Duration) {
+    let deadline = tokio::time::Instant::now() + timeout;
+    while db_manager.cycle_in_progress() {
+        if tokio::time::Instant::now() >= deadline {
+            warn!("等待同步周期完成超时");
+            return;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+}
+
+/// 停机时执行 CHECKPOINT，配置 `backup.on_shutdown` 时再备份一次，返回备份目录
+fn save_cache_on_shutdown(config: &AppConfig, db_manager: &DatabaseManager) -> Result<Option<std::path::PathBuf>> {
+    db_manager.shutdown_checkpoint()
+        .map_err(|e| anyhow::anyhow!("CHECKPOINT 失败: {}", e))?;
+    if !config.backup.on_shutdown {
+        return Ok(None);
+    }
+    let target = backup::run(db_manager, config, std::path::Path::new(&config.backup.dir), config.backup.keep)?;
+    Ok(Some(target))
+}
+
 /// 初始化日志系统
 fn init_logging(config: &AppConfig) {
     let filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new(&format!("{},tiberius=warn,tokio_util=warn", &config.log_level)));
+        .unwrap_or_else(|_| EnvFilter::new(config.log_filter()));
     
     // 创建logs目录（如果不存在）
     fs::create_dir_all("logs").expect("无法创建logs目录");
@@ -215,7 +794,7 @@ fn init_logging(config: &AppConfig) {
         .with_line_number(false)
         .with_timer(fmt::time::OffsetTime::new(
             time::UtcOffset::from_hms(8, 0, 0).unwrap(),
-            time::format_description::parse("[year]-[month]-[day] [hour]:[minute]:[second]").unwrap()
+            time::macros::format_description!("[year]-[month]-[day] [hour]:[minute]:[second]")
         ));
     
     // 创建文件输出层 - 精简格式，使用北京时间
@@ -226,20 +805,44 @@ fn init_logging(config: &AppConfig) {
         .with_line_number(false)
         .with_timer(fmt::time::OffsetTime::new(
             time::UtcOffset::from_hms(8, 0, 0).unwrap(),
-            time::format_description::parse("[year]-[month]-[day] [hour]:[minute]:[second]").unwrap()
+            time::macros::format_description!("[year]-[month]-[day] [hour]:[minute]:[second]")
         ))
         .with_writer(non_blocking_appender);
     
-    // 注册所有层
+    // 注册所有层；启用 [telemetry] 时另外经 OTLP 导出 span
     tracing_subscriber::registry()
         .with(filter)
         .with(console_layer)
         .with(file_layer)
+        .with(telemetry::layer(&config.telemetry))
+        .with(log_throttle::layer(&config.log_throttle))
         .init();
+    log_throttle::spawn_reporter();
     
     info!("日志系统初始化完成，日志文件保存在 logs/rt_db.log");
 }
 
+/// 监听 SIGUSR1，收到后请求立即同步
+#[cfg(unix)]
+fn spawn_sync_signal_listener(sync_trigger: Arc<Notify>) -> tokio::task::JoinHandle<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    
+    tokio::spawn(async move {
+        let mut sigusr1 = match signal(SignalKind::user_defined1()) {
+            Ok(sigusr1) => sigusr1,
+            Err(e) => {
+                warn!("注册 SIGUSR1 信号失败，无法通过信号触发立即同步: {}", e);
+                return;
+            }
+        };
+        
+        while sigusr1.recv().await.is_some() {
+            info!("收到 SIGUSR1 信号，请求立即同步");
+            sync_trigger.notify_one();
+        }
+    })
+}
+
 /// 等待停机信号
 async fn wait_for_shutdown_signal() {
     #[cfg(unix)]
@@ -0,0 +1,164 @@
+//! Prometheus 指标
+//! 同步流水线内部的直方图与计数器：数据源查询耗时、DuckDB 写入耗时、批大小、同步周期耗时、加列与重试次数，
+//! 均以 `op` 标签区分操作（如 history、latest、backfill）。指标保存在进程内，由 `GET /metrics`
+//! 以 Prometheus 文本格式导出，进程重启后清零。
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// 耗时直方图的桶上界（秒）
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+/// 批大小直方图的桶上界（记录数）
+const SIZE_BUCKETS: &[f64] = &[1.0, 10.0, 100.0, 1_000.0, 10_000.0, 100_000.0, 1_000_000.0];
+
+/// 指标定义；`buckets` 为 None 时是计数器
+pub struct Metric {
+    name: &'static str,
+    help: &'static str,
+    buckets: Option<&'static [f64]>,
+}
+
+/// SQL Server 查询耗时（含读取结果），op: history、incremental、latest
+pub static SOURCE_QUERY_SECONDS: Metric = Metric {
+    name: "rt_db_source_query_seconds",
+    help: "SQL Server 查询耗时（秒）",
+    buckets: Some(LATENCY_BUCKETS),
+};
+
+/// DuckDB 写入耗时，op: history、latest
+pub static DUCKDB_INSERT_SECONDS: Metric = Metric {
+    name: "rt_db_duckdb_insert_seconds",
+    help: "DuckDB 写入耗时（秒）",
+    buckets: Some(LATENCY_BUCKETS),
+};
+
+/// 每批从数据源读取的记录数，op: initial_load、backfill、latest
+pub static BATCH_RECORDS: Metric = Metric {
+    name: "rt_db_batch_records",
+    help: "每批从数据源读取的记录数",
+    buckets: Some(SIZE_BUCKETS),
+};
+
+/// 同步周期耗时，op: update、backfill（每个回填段）
+pub static SYNC_CYCLE_SECONDS: Metric = Metric {
+    name: "rt_db_sync_cycle_seconds",
+    help: "同步周期耗时（秒）",
+    buckets: Some(LATENCY_BUCKETS),
+};
+
+/// 为新标签添加的列数，op: ts_wide
+pub static COLUMN_ADDS: Metric = Metric {
+    name: "rt_db_column_adds_total",
+    help: "为新标签添加的列数",
+    buckets: None,
+};
+
+/// 重试次数，op: source_connect、wide_insert
+pub static RETRIES: Metric = Metric {
+    name: "rt_db_retries_total",
+    help: "失败后重试的次数",
+    buckets: None,
+};
+
+/// 导出顺序
+static METRICS: [&Metric; 6] = [
+    &SOURCE_QUERY_SECONDS,
+    &DUCKDB_INSERT_SECONDS,
+    &BATCH_RECORDS,
+    &SYNC_CYCLE_SECONDS,
+    &COLUMN_ADDS,
+    &RETRIES,
+];
+
+/// 单个 (指标, op) 的累计值；计数器只使用 `count`
+#[derive(Default)]
+struct Series {
+    /// 各桶的（非累计）样本数，最后一个为超出全部上界的样本
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+static SERIES: Mutex<BTreeMap<(&'static str, &'static str), Series>> = Mutex::new(BTreeMap::new());
+
+/// 记录一个直方图样本
+pub fn observe(metric: &'static Metric, op: &'static str, value: f64) {
+    let Some(bounds) = metric.buckets else {
+        return;
+    };
+    let mut series = SERIES.lock().unwrap();
+    let series = series.entry((metric.name, op)).or_default();
+    if series.buckets.is_empty() {
+        series.buckets = vec![0; bounds.len() + 1];
+    }
+    let bucket = bounds.iter().position(|&bound| value <= bound).unwrap_or(bounds.len());
+    series.buckets[bucket] += 1;
+    series.sum += value;
+    series.count += 1;
+}
+
+/// 计数器加 n
+pub fn add(metric: &'static Metric, op: &'static str, n: u64) {
+    if n == 0 {
+        return;
+    }
+    SERIES.lock().unwrap().entry((metric.name, op)).or_default().count += n;
+}
+
+/// 计时器，析构时将经过的秒数记录到直方图（失败的调用同样计入）
+pub struct Timer {
+    metric: &'static Metric,
+    op: &'static str,
+    started: Instant,
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        observe(self.metric, self.op, self.started.elapsed().as_secs_f64());
+    }
+}
+
+/// 开始计时，返回的计时器离开作用域时记录耗时
+pub fn timer(metric: &'static Metric, op: &'static str) -> Timer {
+    Timer { metric, op, started: Instant::now() }
+}
+
+/// 以 Prometheus 文本格式导出一个由调用方在导出时读取的仪表盘（gauge），每个值一条 `label` 区分的序列
+pub fn render_gauge(name: &str, help: &str, label: &str, values: &[(&str, f64)]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    for (value_label, value) in values {
+        let _ = writeln!(out, "{}{{{}=\"{}\"}} {}", name, label, value_label, value);
+    }
+    out
+}
+
+/// 以 Prometheus 文本格式（0.0.4）导出全部指标
+pub fn render() -> String {
+    let series = SERIES.lock().unwrap();
+    let mut out = String::new();
+    for metric in METRICS {
+        let kind = if metric.buckets.is_some() { "histogram" } else { "counter" };
+        let _ = writeln!(out, "# HELP {} {}", metric.name, metric.help);
+        let _ = writeln!(out, "# TYPE {} {}", metric.name, kind);
+
+        for ((_, op), values) in series.range((metric.name, "")..).take_while(|((name, _), _)| *name == metric.name) {
+            let Some(bounds) = metric.buckets else {
+                let _ = writeln!(out, "{}{{op=\"{}\"}} {}", metric.name, op, values.count);
+                continue;
+            };
+            let mut cumulative = 0;
+            for (bound, count) in bounds.iter().zip(&values.buckets) {
+                cumulative += count;
+                let _ = writeln!(out, "{}_bucket{{op=\"{}\",le=\"{}\"}} {}", metric.name, op, bound, cumulative);
+            }
+            let _ = writeln!(out, "{}_bucket{{op=\"{}\",le=\"+Inf\"}} {}", metric.name, op, values.count);
+            let _ = writeln!(out, "{}_sum{{op=\"{}\"}} {}", metric.name, op, values.sum);
+            let _ = writeln!(out, "{}_count{{op=\"{}\"}} {}", metric.name, op, values.count);
+        }
+    }
+    out
+}
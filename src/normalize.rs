@@ -0,0 +1,175 @@
+//! 标签规范化目录
+//! 将各数据源的原始标签映射为统一的工厂标签（规范标签）并换算单位，使不同数据源、不同命名与单位的
+//! 同一物理测点写入同一列。同一规范标签有多个来源时，按配置顺序取第一个有值的来源。
+//! 目录属于主配置，各同步配置（pipelines）共用，生成一致的规范列集合。
+
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use tracing::debug;
+
+use crate::config::NormalizationConfig;
+use crate::database::TimeSeriesRecord;
+
+/// 原始标签到规范标签的映射
+#[derive(Debug, Clone)]
+struct SourceMapping {
+    canonical: String,
+    /// 在规范标签来源列表中的位置，越小越优先
+    priority: usize,
+    scale: f64,
+    offset: f64,
+}
+
+/// 标签规范化器
+#[derive(Debug, Clone, Default)]
+pub struct TagNormalizer {
+    enabled: bool,
+    drop_unmapped: bool,
+    sources: HashMap<String, SourceMapping>,
+}
+
+impl TagNormalizer {
+    /// 按配置构建；配置已由 `NormalizationConfig::validate` 校验，无法换算的单位按原值处理
+    pub fn new(config: &NormalizationConfig) -> Self {
+        let mut sources = HashMap::new();
+        if config.enabled {
+            for tag in &config.tags {
+                for (priority, source) in tag.sources.iter().enumerate() {
+                    let (unit_scale, unit_offset) = match (source.unit.as_deref(), tag.unit.as_deref()) {
+                        (Some(from), Some(to)) => unit_conversion(from, to).unwrap_or((1.0, 0.0)),
+                        _ => (1.0, 0.0),
+                    };
+                    sources.insert(source.tag.clone(), SourceMapping {
+                        canonical: tag.name.clone(),
+                        priority,
+                        scale: unit_scale * source.scale,
+                        offset: unit_offset * source.scale + source.offset,
+                    });
+                }
+            }
+        }
+
+        Self {
+            enabled: config.enabled,
+            drop_unmapped: config.drop_unmapped,
+            sources,
+        }
+    }
+
+    /// 原始标签对应的规范标签名，未在目录中时为原名
+    pub fn canonical_name<'a>(&'a self, tag: &'a str) -> &'a str {
+        self.sources.get(tag).map_or(tag, |m| m.canonical.as_str())
+    }
+
+    /// 原始标签是否保留（未在目录中且配置了 `drop_unmapped` 时丢弃）
+    pub fn keeps(&self, tag: &str) -> bool {
+        !self.enabled || !self.drop_unmapped || self.sources.contains_key(tag)
+    }
+
+    /// 规范化一批记录：重命名、换算单位，并在同一时间点的多个来源中保留优先级最高且有值的一条
+    pub fn normalize(&self, records: Vec<TimeSeriesRecord>) -> Vec<TimeSeriesRecord> {
+        if !self.enabled {
+            return records;
+        }
+
+        let input = records.len();
+        let mut output: Vec<TimeSeriesRecord> = Vec::with_capacity(input);
+        // (时间, 规范标签) -> (输出位置, 来源优先级)
+        let mut chosen: HashMap<(DateTime<Utc>, String), (usize, usize)> = HashMap::new();
+
+        for mut record in records {
+            let Some(mapping) = self.sources.get(&record.tag_name) else {
+                if !self.drop_unmapped {
+                    output.push(record);
+                }
+                continue;
+            };
+
+            record.tag_name = mapping.canonical.clone();
+            record.value = record.value.map(|v| v * mapping.scale + mapping.offset);
+
+            let key = (record.timestamp, mapping.canonical.clone());
+            match chosen.get(&key) {
+                None => {
+                    chosen.insert(key, (output.len(), mapping.priority));
+                    output.push(record);
+                }
+                Some(&(index, priority)) => {
+                    let existing_has_value = has_value(&output[index]);
+                    let better = if has_value(&record) == existing_has_value {
+                        mapping.priority < priority
+                    } else {
+                        has_value(&record)
+                    };
+                    if better {
+                        output[index] = record;
+                        chosen.insert(key, (index, mapping.priority));
+                    }
+                }
+            }
+        }
+
+        if output.len() != input {
+            debug!("标签规范化: {} 条原始记录合并为 {} 条", input, output.len());
+        }
+        output
+    }
+}
+
+fn has_value(record: &TimeSeriesRecord) -> bool {
+    record.value.is_some() || record.text.is_some()
+}
+
+/// 单位换算系数：`目标值 = 原值 * scale + offset`，量纲不同或单位未知时返回 None
+///
+/// 单位名不区分大小写。
+pub fn unit_conversion(from: &str, to: &str) -> Option<(f64, f64)> {
+    let (from_dimension, from_factor, from_offset) = unit(from)?;
+    let (to_dimension, to_factor, to_offset) = unit(to)?;
+    if from_dimension != to_dimension {
+        return None;
+    }
+    // 先换算到基准单位，再换算到目标单位
+    Some((from_factor / to_factor, (from_offset - to_offset) / to_factor))
+}
+
+/// 单位的量纲与到基准单位的换算：`基准值 = 值 * factor + offset`
+fn unit(name: &str) -> Option<(&'static str, f64, f64)> {
+    let unit = match name.trim().to_lowercase().as_str() {
+        // 温度，基准 °C
+        "°c" | "c" | "degc" => ("temperature", 1.0, 0.0),
+        "°f" | "f" | "degf" => ("temperature", 5.0 / 9.0, -32.0 * 5.0 / 9.0),
+        "k" => ("temperature", 1.0, -273.15),
+        // 压力，基准 Pa
+        "pa" => ("pressure", 1.0, 0.0),
+        "kpa" => ("pressure", 1e3, 0.0),
+        "mpa" => ("pressure", 1e6, 0.0),
+        "mbar" => ("pressure", 100.0, 0.0),
+        "bar" => ("pressure", 1e5, 0.0),
+        "psi" => ("pressure", 6894.757, 0.0),
+        "atm" => ("pressure", 101_325.0, 0.0),
+        "mmh2o" => ("pressure", 9.80665, 0.0),
+        // 质量流量，基准 kg/h
+        "kg/h" => ("mass_flow", 1.0, 0.0),
+        "kg/s" => ("mass_flow", 3600.0, 0.0),
+        "t/h" => ("mass_flow", 1000.0, 0.0),
+        "lb/h" => ("mass_flow", 0.453_592_37, 0.0),
+        // 体积流量，基准 m3/h
+        "m3/h" => ("volume_flow", 1.0, 0.0),
+        "m3/min" => ("volume_flow", 60.0, 0.0),
+        "l/min" => ("volume_flow", 0.06, 0.0),
+        "l/s" => ("volume_flow", 3.6, 0.0),
+        // 功率，基准 kW
+        "w" => ("power", 1e-3, 0.0),
+        "kw" => ("power", 1.0, 0.0),
+        "mw" => ("power", 1e3, 0.0),
+        // 能量，基准 kWh
+        "wh" => ("energy", 1e-3, 0.0),
+        "kwh" => ("energy", 1.0, 0.0),
+        "mwh" => ("energy", 1e3, 0.0),
+        "mj" => ("energy", 1.0 / 3.6, 0.0),
+        "gj" => ("energy", 1000.0 / 3.6, 0.0),
+        _ => return None,
+    };
+    Some(unit)
+}
@@ -0,0 +1,265 @@
+//! OPC UA 服务模块
+//! 内嵌 OPC UA 服务器，按缓存中的标签生成地址空间：`Objects/rt_db/Tags` 下每个标签一个文件夹，包含最新值 `Value`
+//! 与最近若干个数值样本 `HistoryValues`/`HistoryTimestamps`，OPC 客户端可像浏览历史库一样浏览缓存。
+//! 变量均为只读，随每次写入缓存更新并通知订阅；新标签在首次收到数据时加入，TagDatabase 中删除的标签随之移除。
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use opcua::server::address_space::Variable;
+use opcua::server::diagnostics::NamespaceMetadata;
+use opcua::server::node_manager::memory::{SimpleNodeManager, simple_node_manager};
+use opcua::server::{ServerBuilder, ServerHandle};
+use opcua::types::{DataTypeId, DataValue, NodeId, QualifiedName, StatusCode, Variant};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, error, info, warn};
+
+use crate::config::{OpcUaConfig, QualityConfig};
+use crate::database::{DatabaseManager, Point, TimeSeriesRecord};
+
+/// 根文件夹与标签文件夹的节点 ID
+const ROOT_NODE: &str = "rt_db";
+const TAGS_NODE: &str = "rt_db.Tags";
+
+/// 内嵌的 OPC UA 服务器，克隆后共享同一地址空间
+#[derive(Clone)]
+pub struct OpcUaServer {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    handle: ServerHandle,
+    node_manager: Arc<SimpleNodeManager>,
+    namespace: u16,
+    history_points: usize,
+    quality: QualityConfig,
+    /// 已加入地址空间的标签及其最近的数值样本
+    tags: Mutex<HashMap<String, VecDeque<Point<f64>>>>,
+}
+
+impl OpcUaServer {
+    /// 创建地址空间并在后台启动服务器
+    pub fn spawn(config: &OpcUaConfig, quality: QualityConfig) -> Result<Self> {
+        let (server, handle) = ServerBuilder::new_anonymous("rt_db")
+            .application_uri(config.namespace_uri.as_str())
+            .product_uri(config.namespace_uri.as_str())
+            .host(config.host.as_str())
+            .port(config.port)
+            .pki_dir(config.pki_dir.as_str())
+            .create_sample_keypair(true)
+            .with_node_manager(simple_node_manager(
+                NamespaceMetadata {
+                    namespace_uri: config.namespace_uri.clone(),
+                    ..Default::default()
+                },
+                "rt_db",
+            ))
+            .build()
+            .map_err(|e| anyhow!("创建 OPC UA 服务器失败: {}", e))?;
+
+        let node_manager = handle.node_managers()
+            .get_of_type::<SimpleNodeManager>()
+            .ok_or_else(|| anyhow!("OPC UA 节点管理器不存在"))?;
+        let namespace = handle.get_namespace_index(&config.namespace_uri)
+            .ok_or_else(|| anyhow!("OPC UA 命名空间 {} 未注册", config.namespace_uri))?;
+
+        {
+            let mut address_space = node_manager.address_space().write();
+            address_space.add_folder(
+                &NodeId::new(namespace, ROOT_NODE),
+                QualifiedName::new(namespace, "rt_db"),
+                "rt_db",
+                &NodeId::objects_folder_id(),
+            );
+            address_space.add_folder(
+                &NodeId::new(namespace, TAGS_NODE),
+                QualifiedName::new(namespace, "Tags"),
+                "Tags",
+                &NodeId::new(namespace, ROOT_NODE),
+            );
+        }
+
+        info!("OPC UA 服务已启用，监听 opc.tcp://{}:{}/，命名空间 {}", config.host, config.port, config.namespace_uri);
+        tokio::spawn(async move {
+            if let Err(e) = server.run().await {
+                error!("OPC UA 服务失败: {}", e);
+            }
+        });
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                handle,
+                node_manager,
+                namespace,
+                history_points: config.history_points,
+                quality,
+                tags: Mutex::new(HashMap::new()),
+            }),
+        })
+    }
+
+    /// 为缓存中已有的标签创建节点，并以最近的数值样本填充历史窗口
+    pub fn seed(&self, db_manager: &DatabaseManager) {
+        let mut tags: Vec<String> = db_manager.get_known_tags().into_iter().collect();
+        tags.sort();
+
+        for tag in &tags {
+            let samples = match db_manager.get_recent_values(tag, self.inner.history_points) {
+                Ok(samples) => samples,
+                Err(e) => {
+                    warn!("读取标签 {} 的最近样本失败，OPC UA 历史窗口从空开始: {}", tag, e);
+                    Vec::new()
+                }
+            };
+            let records: Vec<TimeSeriesRecord> = samples.into_iter()
+                .map(|(timestamp, value)| TimeSeriesRecord {
+                    tag_name: tag.clone(),
+                    timestamp,
+                    value: Some(value),
+                    text: None,
+                    quality: None,
+                })
+                .collect();
+            if records.is_empty() {
+                self.ensure_tag(tag);
+            } else {
+                self.publish(&records);
+            }
+        }
+        info!("OPC UA 地址空间已生成，共 {} 个标签", tags.len());
+    }
+
+    /// 用新写入缓存的记录更新标签节点
+    pub fn publish(&self, records: &[TimeSeriesRecord]) {
+        if records.is_empty() {
+            return;
+        }
+        for record in records {
+            self.ensure_tag(&record.tag_name);
+        }
+
+        let namespace = self.inner.namespace;
+        let mut updates: Vec<(NodeId, DataValue)> = Vec::new();
+        let mut changed: Vec<&str> = Vec::new();
+        {
+            let mut tags = self.inner.tags.lock().unwrap();
+            for record in records {
+                let status = self.status(record);
+                let value = match (&record.text, record.value) {
+                    (Some(text), _) => Variant::from(text.clone()),
+                    (None, Some(value)) => Variant::from(value),
+                    (None, None) => Variant::Empty,
+                };
+                let timestamp = record.timestamp.into();
+                updates.push((value_node(namespace, &record.tag_name), DataValue::new_at_status(value, timestamp, status)));
+
+                if let (Some(value), Some(history)) = (record.value, tags.get_mut(&record.tag_name)) {
+                    if history.back().is_some_and(|(t, _)| *t >= record.timestamp) {
+                        continue;
+                    }
+                    history.push_back((record.timestamp, value));
+                    while history.len() > self.inner.history_points {
+                        history.pop_front();
+                    }
+                    if !changed.contains(&record.tag_name.as_str()) {
+                        changed.push(&record.tag_name);
+                    }
+                }
+            }
+
+            for tag in changed {
+                let history = &tags[tag];
+                let values: Vec<f64> = history.iter().map(|(_, v)| *v).collect();
+                let timestamps: Vec<DateTime<Utc>> = history.iter().map(|(t, _)| *t).collect();
+                updates.push((history_values_node(namespace, tag), DataValue::new_now(values)));
+                updates.push((history_timestamps_node(namespace, tag), DataValue::new_now(timestamps)));
+            }
+        }
+
+        let result = self.inner.node_manager.set_values(
+            self.inner.handle.subscriptions(),
+            updates.iter().map(|(id, value)| (id, None, value.clone())),
+        );
+        if let Err(status) = result {
+            warn!("更新 OPC UA 节点失败: {}", status);
+        }
+    }
+
+    /// 从地址空间移除已删除的标签
+    pub fn remove_tags(&self, tags: &[String]) {
+        let namespace = self.inner.namespace;
+        let mut known = self.inner.tags.lock().unwrap();
+        let mut address_space = self.inner.node_manager.address_space().write();
+        for tag in tags {
+            if known.remove(tag).is_none() {
+                continue;
+            }
+            for id in [
+                value_node(namespace, tag),
+                history_values_node(namespace, tag),
+                history_timestamps_node(namespace, tag),
+                tag_node(namespace, tag),
+            ] {
+                address_space.delete(&id, true);
+            }
+            debug!("已从 OPC UA 地址空间移除标签 {}", tag);
+        }
+    }
+
+    /// 标签节点不存在时创建：标签文件夹及其 Value、HistoryValues、HistoryTimestamps 变量
+    fn ensure_tag(&self, tag: &str) {
+        let mut known = self.inner.tags.lock().unwrap();
+        if known.contains_key(tag) {
+            return;
+        }
+        known.insert(tag.to_string(), VecDeque::with_capacity(self.inner.history_points));
+
+        let namespace = self.inner.namespace;
+        let folder = tag_node(namespace, tag);
+        let name = |n: &str| QualifiedName::new(namespace, n);
+
+        let mut value = Variable::new_data_value(
+            &value_node(namespace, tag), name("Value"), "Value", DataTypeId::BaseDataType, None, None, Variant::Empty,
+        );
+        value.set_data_value(DataValue::new_now_status(Variant::Empty, StatusCode::BadWaitingForInitialData));
+        let history_values = Variable::new_data_value(
+            &history_values_node(namespace, tag), name("HistoryValues"), "HistoryValues",
+            DataTypeId::Double, Some(1), Some(0), Vec::<f64>::new(),
+        );
+        let history_timestamps = Variable::new_data_value(
+            &history_timestamps_node(namespace, tag), name("HistoryTimestamps"), "HistoryTimestamps",
+            DataTypeId::DateTime, Some(1), Some(0), Vec::<DateTime<Utc>>::new(),
+        );
+
+        let mut address_space = self.inner.node_manager.address_space().write();
+        address_space.add_folder(&folder, name(tag), tag, &NodeId::new(namespace, TAGS_NODE));
+        address_space.add_variables(vec![value, history_values, history_timestamps], &folder);
+    }
+
+    /// 记录的 OPC UA 状态码：缺少值为 Bad，质量不在良好值列表中为 Uncertain
+    fn status(&self, record: &TimeSeriesRecord) -> StatusCode {
+        if record.value.is_none() && record.text.is_none() {
+            StatusCode::BadNoData
+        } else if record.quality.as_deref().is_some_and(|q| !self.inner.quality.is_good(q)) {
+            StatusCode::Uncertain
+        } else {
+            StatusCode::Good
+        }
+    }
+}
+
+fn tag_node(namespace: u16, tag: &str) -> NodeId {
+    NodeId::new(namespace, format!("{}.{}", TAGS_NODE, tag))
+}
+
+fn value_node(namespace: u16, tag: &str) -> NodeId {
+    NodeId::new(namespace, format!("{}.{}.Value", TAGS_NODE, tag))
+}
+
+fn history_values_node(namespace: u16, tag: &str) -> NodeId {
+    NodeId::new(namespace, format!("{}.{}.HistoryValues", TAGS_NODE, tag))
+}
+
+fn history_timestamps_node(namespace: u16, tag: &str) -> NodeId {
+    NodeId::new(namespace, format!("{}.{}.HistoryTimestamps", TAGS_NODE, tag))
+}
@@ -0,0 +1,213 @@
+//! 数据源连接的代理
+//! 历史库位于隔离网络、只能经跳板机访问时，通过 SOCKS5 代理或 SSH 隧道建立到 SQL Server 的 TCP 连接。
+//! SSH 隧道调用系统中的 OpenSSH 客户端做本地端口转发（`ssh -N -L`），每个目标地址一个常驻进程，
+//! 进程退出后下次连接时重新建立。经代理连接时不支持命名实例（SQL Browser 使用 UDP）。
+
+use anyhow::{Result, anyhow, bail};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::{info, warn};
+
+use crate::config::ConnectionConfig;
+
+/// 代理配置
+#[derive(Debug, Clone)]
+pub enum Proxy {
+    /// SOCKS5 代理，可选用户名/密码认证
+    Socks5 { addr: String, auth: Option<(String, String)> },
+    /// SSH 跳板机，`destination` 为 `user@host` 或 `host`
+    Ssh { destination: String, port: u16 },
+}
+
+impl Proxy {
+    /// 解析 `socks5://[user:password@]host[:port]` 或 `ssh://[user@]host[:port]`
+    pub fn parse(proxy: &str) -> Result<Self> {
+        let url = reqwest::Url::parse(proxy).map_err(|e| anyhow!("无法解析代理地址 {}: {}", proxy, e))?;
+        let host = url.host_str().filter(|h| !h.is_empty())
+            .ok_or_else(|| anyhow!("代理地址 {} 缺少主机名", proxy))?;
+        let decode = |value: &str| urlencoding::decode(value).map(|v| v.into_owned())
+            .map_err(|e| anyhow!("代理地址 {} 的用户信息解码失败: {}", proxy, e));
+
+        match url.scheme() {
+            "socks5" => {
+                let auth = if url.username().is_empty() {
+                    None
+                } else {
+                    let user = decode(url.username())?;
+                    let password = decode(url.password().unwrap_or(""))?;
+                    if user.len() > 255 || password.len() > 255 {
+                        bail!("SOCKS5 代理的用户名与密码不能超过 255 字节");
+                    }
+                    Some((user, password))
+                };
+                Ok(Proxy::Socks5 { addr: format!("{}:{}", host, url.port().unwrap_or(1080)), auth })
+            }
+            "ssh" => {
+                let destination = if url.username().is_empty() {
+                    host.to_string()
+                } else {
+                    format!("{}@{}", decode(url.username())?, host)
+                };
+                Ok(Proxy::Ssh { destination, port: url.port().unwrap_or(22) })
+            }
+            other => bail!("不支持的代理类型 {}（可选 socks5、ssh）", other),
+        }
+    }
+}
+
+/// 一条 SSH 本地端口转发
+struct SshTunnel {
+    /// ssh 进程，析构时结束
+    child: tokio::process::Child,
+    local_port: u16,
+}
+
+/// 经代理建立数据源连接
+pub struct ProxyConnector {
+    proxy: Proxy,
+    ssh_identity_file: Option<String>,
+    timeout: Duration,
+    /// 按目标地址复用的 SSH 隧道
+    tunnels: tokio::sync::Mutex<HashMap<(String, u16), SshTunnel>>,
+}
+
+impl ProxyConnector {
+    /// 按 `[connection]` 配置创建，未配置代理时返回 None（代理地址已在配置校验时检查）
+    pub fn new(config: &ConnectionConfig) -> Option<Self> {
+        let proxy = Proxy::parse(config.proxy.as_deref()?)
+            .map_err(|e| warn!("忽略无效的代理配置: {}", e))
+            .ok()?;
+        Some(Self {
+            proxy,
+            ssh_identity_file: config.ssh_identity_file.clone(),
+            timeout: Duration::from_secs(config.connection_timeout_secs.max(1)),
+            tunnels: tokio::sync::Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// 经代理建立到 `host:port` 的 TCP 连接
+    pub async fn connect(&self, host: &str, port: u16) -> Result<TcpStream> {
+        match &self.proxy {
+            Proxy::Socks5 { addr, auth } => {
+                tokio::time::timeout(self.timeout, socks5_connect(addr, auth.as_ref(), host, port))
+                    .await
+                    .map_err(|_| anyhow!("经 SOCKS5 代理 {} 连接 {}:{} 超时", addr, host, port))?
+            }
+            Proxy::Ssh { destination, port: ssh_port } => {
+                self.ssh_connect(destination, *ssh_port, host, port).await
+            }
+        }
+    }
+
+    /// 经 SSH 隧道连接，隧道不存在或已断开时重新建立
+    async fn ssh_connect(&self, destination: &str, ssh_port: u16, host: &str, port: u16) -> Result<TcpStream> {
+        let mut tunnels = self.tunnels.lock().await;
+        let key = (host.to_string(), port);
+        if let Some(tunnel) = tunnels.get_mut(&key) {
+            if tunnel.child.try_wait()?.is_none()
+                && let Ok(stream) = TcpStream::connect(("127.0.0.1", tunnel.local_port)).await {
+                return Ok(stream);
+            }
+            warn!("到 {}:{} 的 SSH 隧道已断开，重新建立", host, port);
+            tunnels.remove(&key);
+        }
+
+        let (tunnel, stream) = self.open_tunnel(destination, ssh_port, host, port).await?;
+        tunnels.insert(key, tunnel);
+        Ok(stream)
+    }
+
+    /// 启动 ssh 本地端口转发，等待本地端口可连接后返回隧道与第一条连接
+    async fn open_tunnel(&self, destination: &str, ssh_port: u16, host: &str, port: u16) -> Result<(SshTunnel, TcpStream)> {
+        // 由系统分配空闲的本地端口
+        let local_port = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+
+        let mut command = tokio::process::Command::new("ssh");
+        command
+            .args(["-N", "-o", "BatchMode=yes", "-o", "ExitOnForwardFailure=yes", "-o", "ServerAliveInterval=30"])
+            .arg("-L").arg(format!("127.0.0.1:{}:{}:{}", local_port, host, port))
+            .arg("-p").arg(ssh_port.to_string());
+        if let Some(identity) = &self.ssh_identity_file {
+            command.arg("-i").arg(identity);
+        }
+        let mut child = command
+            .arg(destination)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow!("启动 ssh 失败（需要系统中安装 OpenSSH 客户端）: {}", e))?;
+        info!("经 SSH 跳板机 {} 建立到 {}:{} 的隧道，本地端口 {}", destination, host, port, local_port);
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                bail!("SSH 隧道进程已退出（{}），请检查跳板机地址与认证配置", status);
+            }
+            if let Ok(stream) = TcpStream::connect(("127.0.0.1", local_port)).await {
+                return Ok((SshTunnel { child, local_port }, stream));
+            }
+            if Instant::now() >= deadline {
+                bail!("等待到 {}:{} 的 SSH 隧道建立超时", host, port);
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+}
+
+/// 经 SOCKS5 代理（RFC 1928，用户名/密码认证见 RFC 1929）连接 `host:port`，目标地址交由代理解析
+async fn socks5_connect(addr: &str, auth: Option<&(String, String)>, host: &str, port: u16) -> Result<TcpStream> {
+    if host.len() > 255 {
+        bail!("目标主机名 {} 过长", host);
+    }
+    let mut stream = TcpStream::connect(addr).await
+        .map_err(|e| anyhow!("无法连接到 SOCKS5 代理 {}: {}", addr, e))?;
+
+    // 协商认证方式：无认证（0x00）或用户名/密码（0x02）
+    let method = if auth.is_some() { 0x02 } else { 0x00 };
+    stream.write_all(&[0x05, 0x01, method]).await?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply != [0x05, method] {
+        bail!("SOCKS5 代理 {} 不接受所配置的认证方式", addr);
+    }
+
+    if let Some((user, password)) = auth {
+        let mut request = vec![0x01, user.len() as u8];
+        request.extend_from_slice(user.as_bytes());
+        request.push(password.len() as u8);
+        request.extend_from_slice(password.as_bytes());
+        stream.write_all(&request).await?;
+        stream.read_exact(&mut reply).await?;
+        if reply[1] != 0x00 {
+            bail!("SOCKS5 代理 {} 用户名或密码错误", addr);
+        }
+    }
+
+    let mut request = vec![0x05, 0x01, 0x00, 0x03, host.len() as u8];
+    request.extend_from_slice(host.as_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+    if header[1] != 0x00 {
+        bail!("SOCKS5 代理 {} 拒绝连接 {}:{}（错误码 {}）", addr, host, port, header[1]);
+    }
+    // 跳过代理返回的绑定地址与端口
+    let addr_len = match header[3] {
+        0x01 => 4,
+        0x04 => 16,
+        0x03 => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await?;
+            usize::from(len[0])
+        }
+        other => bail!("SOCKS5 代理 {} 返回了未知的地址类型 {}", addr, other),
+    };
+    let mut bound = vec![0u8; addr_len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(stream)
+}
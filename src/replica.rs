@@ -0,0 +1,149 @@
+//! 只读副本（跟随模式）
+//! 按主实例的变更日志通过 HTTP(S) 拉取增量数据（含回填），维护本地 DuckDB 副本，并按数据窗口清理过期数据。
+//! 变更流中的记录带有字符串标签的文本值与数据质量，与数值一起写入副本的 ts_text 与 ts_quality。
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, interval};
+use tracing::{info, error, debug, warn};
+
+use crate::config::{AppConfig, ReplicaConfig};
+use crate::database::{ChangeLog, ChangesPage, DatabaseManager};
+
+/// 启动跟随任务
+pub fn spawn_follower(config: Arc<AppConfig>, db_manager: Arc<DatabaseManager>, sync_trigger: Arc<Notify>) -> JoinHandle<()> {
+    let replica = config.replica.clone();
+
+    tokio::spawn(async move {
+        let client = match reqwest::Client::builder().timeout(Duration::from_secs(60)).build() {
+            Ok(client) => client,
+            Err(e) => {
+                error!("创建 HTTP 客户端失败，跟随任务未启动: {}", e);
+                return;
+            }
+        };
+
+        info!("以跟随模式运行，主实例: {}，拉取间隔 {} 秒", replica.primary_url, replica.poll_interval_secs);
+
+        let mut ticker = interval(Duration::from_secs(replica.poll_interval_secs.max(1)));
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = sync_trigger.notified() => {
+                    info!("收到立即同步请求，提前从主实例拉取");
+                    ticker.reset();
+                }
+            }
+            match pull_changes(&replica, &client, &db_manager).await {
+                Ok(0) => debug!("主实例没有新数据"),
+                Ok(count) => info!("已从主实例同步 {} 条记录", count),
+                Err(e) => error!("从主实例同步失败: {}", e),
+            }
+            if let Err(e) = apply_retention(&config, &db_manager).await {
+                warn!("{}", e);
+            }
+        }
+    })
+}
+
+/// 按主实例的变更日志拉取新写入（含回填的较早数据）的时间范围，写入后保存日志序号，返回写入的记录数
+async fn pull_changes(replica: &ReplicaConfig, client: &reqwest::Client, db_manager: &Arc<DatabaseManager>) -> Result<usize> {
+    let base = replica.primary_url.trim_end_matches('/');
+    let position = db_manager.replica_position()
+        .map_err(|e| anyhow!("读取副本拉取位置失败: {}", e))?;
+
+    let mut request = client.get(format!("{}/replication/log", base));
+    if let Some(after) = position {
+        request = request.query(&[("after", after)]);
+    }
+    let log: ChangeLog = fetch(replica, request).await
+        .map_err(|e| anyhow!("请求主实例变更日志失败: {}", e))?;
+
+    let ranges = if log.full {
+        info!("从主实例全量拉取数据窗口");
+        vec![(None, None)]
+    } else {
+        log.ranges.iter().map(|(from, to)| (Some(*from), Some(*to))).collect()
+    };
+
+    let mut total = 0;
+    for (from, to) in ranges {
+        total += pull_range(replica, client, db_manager, from, to).await?;
+    }
+
+    db_manager.save_replica_position(log.seq)
+        .map_err(|e| anyhow!("保存副本拉取位置失败: {}", e))?;
+    Ok(total)
+}
+
+/// 分页拉取并写入时间范围 [from, to] 内的数据，返回写入的记录数
+async fn pull_range(
+    replica: &ReplicaConfig,
+    client: &reqwest::Client,
+    db_manager: &Arc<DatabaseManager>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<usize> {
+    let url = format!("{}/replication/changes", replica.primary_url.trim_end_matches('/'));
+    let mut after = None;
+    let mut total = 0;
+
+    loop {
+        let mut request = client.get(&url).query(&[("limit", replica.batch_size.to_string())]);
+        for (name, bound) in [("from", from), ("to", to), ("after", after)] {
+            if let Some(bound) = bound {
+                request = request.query(&[(name, bound.to_rfc3339())]);
+            }
+        }
+
+        let page: ChangesPage = fetch(replica, request).await
+            .map_err(|e| anyhow!("请求主实例变更流失败: {}", e))?;
+        let count = page.records.len();
+        if count > 0 {
+            let db_manager = db_manager.clone();
+            let records = page.records;
+            // 文本值与数据质量随记录写入 ts_text 与 ts_quality
+            tokio::task::spawn_blocking(move || db_manager.convert_and_insert_wide(&records, None))
+                .await
+                .map_err(|e| anyhow!("写入任务异常终止: {}", e))?
+                .map_err(|e| anyhow!("写入副本数据失败: {}", e))?;
+            total += count;
+        }
+
+        match page.next {
+            Some(next) => after = Some(next),
+            None => break,
+        }
+    }
+
+    Ok(total)
+}
+
+/// 发送请求（附带复制令牌）并解析 JSON 响应
+async fn fetch<T: serde::de::DeserializeOwned>(replica: &ReplicaConfig, mut request: reqwest::RequestBuilder) -> Result<T> {
+    if let Some(token) = &replica.token {
+        request = request.bearer_auth(token);
+    }
+
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow!("主实例返回 {}: {}", status, body));
+    }
+
+    Ok(response.json().await?)
+}
+
+/// 删除超出数据窗口的本地数据
+async fn apply_retention(config: &AppConfig, db_manager: &Arc<DatabaseManager>) -> Result<usize> {
+    let cutoff = Utc::now() - chrono::Duration::days(i64::from(config.data_window_days));
+    let db_manager = db_manager.clone();
+    tokio::task::spawn_blocking(move || db_manager.delete_data_before_time(cutoff))
+        .await
+        .map_err(|e| anyhow!("清理任务异常终止: {}", e))?
+        .map_err(|e| anyhow!("清理过期数据失败: {}", e))
+}
@@ -0,0 +1,57 @@
+//! 分级保留
+//! 保留窗口清理删除原始数据之前，先将其降采样为 1 分钟与 1 小时汇总（平均值、最小值、最大值、样本数、
+//! 时间加权平均值与非零状态秒数），分别写入 `ts_rollup_1m` 与 `ts_rollup_1h` 并按各自的保留期（远长于原始数据窗口）清理。
+//! 清理截止时间向下对齐到整点，使写入的每个汇总桶都包含完整的原始数据；每次只汇总上次截止时间以后的数据，
+//! 保留期豁免留下的更早数据不会覆盖已完成的桶。
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use tracing::{debug, info};
+
+use crate::config::{AppConfig, RollupConfig};
+use crate::database::{DatabaseManager, ROLLUP_TABLES};
+
+/// 各汇总表的桶宽度（秒），与 [`ROLLUP_TABLES`] 一一对应
+const BUCKET_SECS: [u64; 2] = [60, 3600];
+
+/// 分级保留汇总器
+pub struct Rollups {
+    config: RollupConfig,
+}
+
+impl Rollups {
+    pub fn new(config: &AppConfig) -> Self {
+        Self { config: config.rollup.clone() }
+    }
+
+    /// 启用时将清理截止时间向下对齐到整点，未启用时原样返回
+    pub fn align_cutoff(&self, cutoff_time: DateTime<Utc>) -> DateTime<Utc> {
+        if !self.config.enabled {
+            return cutoff_time;
+        }
+        cutoff_time.duration_trunc(Duration::hours(1)).unwrap_or(cutoff_time)
+    }
+
+    /// 汇总截止时间以前的原始数据并清理超出保留期的汇总，未启用时不做任何事
+    pub fn roll_up(&self, db_manager: &DatabaseManager, cutoff_time: DateTime<Utc>) -> Result<()> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        let retention_days = [self.config.minute_retention_days, self.config.hour_retention_days];
+        for ((table, bucket_secs), days) in ROLLUP_TABLES.into_iter().zip(BUCKET_SECS).zip(retention_days) {
+            let rows = db_manager.roll_up_before(table, bucket_secs, cutoff_time)
+                .map_err(|e| anyhow!("汇总到 {} 失败: {}", table, e))?;
+            let expired = db_manager.delete_rollups_before(table, now - Duration::days(i64::from(days)))
+                .map_err(|e| anyhow!("清理 {} 失败: {}", table, e))?;
+
+            if rows > 0 || expired > 0 {
+                info!("{}: 写入 {} 个汇总，删除 {} 个超过 {} 天的汇总", table, rows, expired, days);
+            } else {
+                debug!("{}: 没有需要汇总或清理的数据", table);
+            }
+        }
+        Ok(())
+    }
+}
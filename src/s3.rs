@@ -0,0 +1,148 @@
+//! S3 兼容对象存储上传
+//! 以 AWS Signature V4 签名的 PUT 请求上传文件（路径风格地址，兼容 AWS S3 与 MinIO），失败时按配置重试。
+//! 一批文件全部上传后再上传一份清单（JSON），列出各文件的对象键、大小与 SHA-256，清单存在即表示这批文件已完整上传。
+
+use anyhow::{Result, anyhow};
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::config::S3Config;
+
+/// 上传清单中的一个文件
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestEntry {
+    pub key: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// 上传清单
+#[derive(Serialize)]
+pub struct Manifest<'a> {
+    pub source: &'a str,
+    pub uploaded_at: DateTime<Utc>,
+    pub files: &'a [ManifestEntry],
+}
+
+/// S3 客户端
+pub struct S3Client {
+    config: S3Config,
+    http: reqwest::Client,
+}
+
+impl S3Client {
+    pub fn new(config: S3Config) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// 对象键：配置的前缀加相对键
+    pub fn key(&self, relative: &str) -> String {
+        format!("{}{}", self.config.prefix, relative)
+    }
+
+    /// 上传本地文件，失败时按配置重试；返回文件内容的 SHA-256（十六进制）
+    pub async fn upload_file(&self, file: &Path, key: &str) -> Result<String> {
+        let body = tokio::fs::read(file).await
+            .map_err(|e| anyhow!("读取文件 {} 失败: {}", file.display(), e))?;
+        self.put_with_retry(key, body.into()).await
+    }
+
+    /// 上传对象，失败时按配置重试；返回内容的 SHA-256（十六进制）
+    ///
+    /// 内容只读取并计算摘要一次，重试时共享同一份 `Bytes`，不复制数据。
+    pub async fn put_with_retry(&self, key: &str, body: Bytes) -> Result<String> {
+        let payload_hash = hex(&Sha256::digest(&body));
+        let attempts = self.config.max_retries + 1;
+        let mut attempt = 1;
+        loop {
+            match self.put_object(key, body.clone(), &payload_hash).await {
+                Ok(()) => return Ok(payload_hash),
+                Err(e) if attempt < attempts => {
+                    warn!("上传 {} 失败（第 {}/{} 次），{} 秒后重试: {}",
+                          key, attempt, attempts, self.config.retry_interval_secs, e);
+                    tokio::time::sleep(Duration::from_secs(self.config.retry_interval_secs)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(anyhow!("上传 {} 失败（共尝试 {} 次）: {}", key, attempts, e)),
+            }
+        }
+    }
+
+    /// 以签名的 PUT 请求上传一个对象，`payload_hash` 为内容的 SHA-256（十六进制）
+    async fn put_object(&self, key: &str, body: Bytes, payload_hash: &str) -> Result<()> {
+        let path = format!(
+            "/{}/{}",
+            uri_encode(&self.config.bucket),
+            key.split('/').map(uri_encode).collect::<Vec<_>>().join("/")
+        );
+        let url = reqwest::Url::parse(&format!("{}{}", self.config.endpoint.trim_end_matches('/'), path))
+            .map_err(|e| anyhow!("无效的 S3 地址 {}: {}", self.config.endpoint, e))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(anyhow!("S3 地址缺少主机名: {}", self.config.endpoint)),
+        };
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "PUT\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            url.path(), host, payload_hash, amz_date, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date, scope, hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let signing_key = [date.as_str(), self.config.region.as_str(), "s3", "aws4_request"]
+            .iter()
+            .fold(format!("AWS4{}", self.config.secret_key).into_bytes(), |key, part| hmac(&key, part.as_bytes()));
+        let signature = hex(&hmac(&signing_key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.config.access_key, scope, signed_headers, signature
+        );
+
+        let response = self.http.put(url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", &amz_date)
+            .header(reqwest::header::AUTHORIZATION, authorization)
+            .timeout(Duration::from_secs(300))
+            .body(body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow!("S3 返回 {}: {}", status, text.trim()));
+        }
+        Ok(())
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC 接受任意长度的密钥");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// SigV4 的 URI 编码：除非保留字符（字母、数字、`-_.~`）外全部百分号编码
+fn uri_encode(segment: &str) -> String {
+    urlencoding::encode(segment).into_owned()
+}
@@ -0,0 +1,189 @@
+//! 缓存数据字典
+//! 根据 DuckDB 目录、标签列名映射与配置生成人类可读的结构说明（Markdown 或 HTML），
+//! 包括各表的列与行数、标签（列名、单位、说明）、保留策略与预计算汇总。
+
+use chrono::Utc;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use crate::config::{AppConfig, StorageMode};
+use crate::database::DatabaseManager;
+
+/// 文档格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+impl std::str::FromStr for DocFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Ok(DocFormat::Markdown),
+            "html" => Ok(DocFormat::Html),
+            other => Err(format!("不支持的文档格式: {}（可选 md、html）", other)),
+        }
+    }
+}
+
+/// 一张表的说明
+struct TableDoc {
+    name: String,
+    description: &'static str,
+    rows: i64,
+    columns: Vec<(String, String)>,
+}
+
+/// 一个标签的说明
+struct TagDoc {
+    tag: String,
+    column: String,
+    unit: String,
+    description: String,
+}
+
+/// 内置表的用途说明
+fn table_description(name: &str) -> &'static str {
+    match name {
+        "ts_wide" => "宽表：每个时间点一行，每个标签一列",
+        "ts_long" => "窄表：(DateTime, TagName, Value)",
+        "ts_text" => "字符串标签的文本值",
+        "ts_quality" => "数据质量（TagQuality）",
+        "tag_columns" => "标签到宽表列名的映射",
+        "sync_checkpoint" => "同步检查点（单行）",
+        "sync_log" => "同步周期统计",
+        "spc_events" => "SPC 规则告警事件",
+        "integration_deliveries" => "MES/ERP 推送记录",
+        "retention_holds" => "保留期豁免（法律保全）",
+        "ts_forecast" => "标签趋势预测（每个标签最近一次）",
+        "ts_latest" => "各标签最新的非空数值",
+        "tag_meta" => "标签元数据（单位、描述、量程、输入/输出标志），每个同步周期从 TagDatabase 更新",
+        "ts_rollup_1m" => "1 分钟汇总（平均、最小、最大值、样本数、时间加权平均与运行秒数），保留窗口清理前降采样",
+        "cold_partitions" => "冷存储目录：归档写出的 Parquet 分区文件（来源表、日期、时间范围与行数）",
+        "share_revocations" => "已吊销的分享链接 ID（链接过期后清除）",
+        "ts_rollup_1h" => "1 小时汇总（平均、最小、最大值、样本数、时间加权平均与运行秒数），保留窗口清理前降采样",
+        "rollup_watermarks" => "各汇总表已汇总到的截止时间",
+        "size_cap_stall" => "大小上限清理未能降到上限以下时的状态（单行），已用空间回到上限以下或上限变更后清除",
+        _ => "",
+    }
+}
+
+/// 生成数据字典
+pub fn generate(db_manager: &DatabaseManager, config: &AppConfig, format: DocFormat) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let conn = db_manager.get_connection()?;
+
+    let mut tables = Vec::new();
+    let mut stmt = conn.prepare("SELECT table_name FROM information_schema.tables WHERE table_schema = 'main' ORDER BY table_name")?;
+    let names: Vec<String> = stmt.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?;
+    for name in names {
+        let rows: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM \"{}\"", name.replace('"', "\"\"")), [], |row| row.get(0))?;
+        let mut stmt = conn.prepare(
+            "SELECT column_name, data_type FROM information_schema.columns WHERE table_name = ? ORDER BY ordinal_position"
+        )?;
+        let mut columns: Vec<(String, String)> = stmt.query_map([&name], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        // 宽表的标签列在标签一节列出
+        if name == "ts_wide" && columns.len() > 1 {
+            let tag_columns = columns.len() - 1;
+            columns.truncate(1);
+            columns.push((format!("（{} 个标签列）", tag_columns), "DOUBLE".to_string()));
+        }
+        tables.push(TableDoc { description: table_description(&name), name, rows, columns });
+    }
+
+    // 标签单位与说明取自规范化目录
+    let catalog: HashMap<&str, (&str, &str)> = config.normalization.tags.iter()
+        .map(|t| (t.name.as_str(), (t.unit.as_deref().unwrap_or(""), t.description.as_deref().unwrap_or(""))))
+        .collect();
+    let tag_names: Vec<(String, String)> = match config.storage_mode {
+        StorageMode::Wide => db_manager.tag_columns()?.into_iter().map(|c| (c.tag, c.column)).collect(),
+        StorageMode::Long => {
+            let mut stmt = conn.prepare("SELECT DISTINCT TagName FROM ts_long ORDER BY TagName")?;
+            stmt.query_map([], |row| row.get::<_, String>(0))?
+                .map(|tag| tag.map(|tag| (tag, "Value".to_string())))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    let tags: Vec<TagDoc> = tag_names.into_iter()
+        .map(|(tag, column)| {
+            let (unit, description) = catalog.get(tag.as_str()).copied().unwrap_or(("", ""));
+            TagDoc { unit: unit.to_string(), description: description.to_string(), tag, column }
+        })
+        .collect();
+
+    let mut policies = vec![
+        ("存储模式", format!("{:?}", config.storage_mode)),
+        ("数据保留窗口", format!("{} 天，超出窗口的数据在每个更新周期清理", config.data_window_days)),
+        ("更新周期", format!("{} 秒", config.update_interval_secs)),
+        ("时间戳", "以 UTC 存储（TIMESTAMPTZ）".to_string()),
+        ("死区过滤", if config.deadband.enabled { "启用".to_string() } else { "未启用".to_string() }),
+    ];
+    policies.push(("预计算汇总", if config.sparkline.enabled {
+        format!("缩略趋势：最近 {} 小时每个标签 {} 个桶均值（内存中，GET /tags/sparklines）",
+                config.sparkline.window_hours, config.sparkline.points)
+    } else {
+        "无".to_string()
+    }));
+
+    let title = format!("rt_db 数据字典（{}）", config.db_file_path);
+    let generated = format!("生成时间: {}", Utc::now().to_rfc3339());
+    Ok(match format {
+        DocFormat::Markdown => render_markdown(&title, &generated, &policies, &tables, &tags),
+        DocFormat::Html => render_html(&title, &generated, &policies, &tables, &tags),
+    })
+}
+
+fn render_markdown(title: &str, generated: &str, policies: &[(&str, String)], tables: &[TableDoc], tags: &[TagDoc]) -> String {
+    let cell = |s: &str| s.replace('|', "\\|");
+    let mut out = String::new();
+    let _ = writeln!(out, "# {}\n\n{}\n\n## 保留与汇总\n", title, generated);
+    for (name, value) in policies {
+        let _ = writeln!(out, "- **{}**: {}", name, value);
+    }
+
+    let _ = writeln!(out, "\n## 表\n");
+    for table in tables {
+        let _ = writeln!(out, "### {}\n\n{}（{} 行）\n\n| 列名 | 类型 |\n|------|------|", table.name, table.description, table.rows);
+        for (column, data_type) in &table.columns {
+            let _ = writeln!(out, "| {} | {} |", cell(column), cell(data_type));
+        }
+        out.push('\n');
+    }
+
+    let _ = writeln!(out, "## 标签（{} 个）\n\n| 标签 | 列名 | 单位 | 说明 |\n|------|------|------|------|", tags.len());
+    for tag in tags {
+        let _ = writeln!(out, "| {} | {} | {} | {} |", cell(&tag.tag), cell(&tag.column), cell(&tag.unit), cell(&tag.description));
+    }
+    out
+}
+
+fn render_html(title: &str, generated: &str, policies: &[(&str, String)], tables: &[TableDoc], tags: &[TagDoc]) -> String {
+    let esc = |s: &str| s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;");
+    let mut out = String::new();
+    let _ = writeln!(out, "<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head><meta charset=\"utf-8\"><title>{0}</title></head>\n<body>\n<h1>{0}</h1>\n<p>{1}</p>", esc(title), esc(generated));
+
+    let _ = writeln!(out, "<h2>保留与汇总</h2>\n<ul>");
+    for (name, value) in policies {
+        let _ = writeln!(out, "<li><b>{}</b>: {}</li>", esc(name), esc(value));
+    }
+    let _ = writeln!(out, "</ul>\n<h2>表</h2>");
+
+    for table in tables {
+        let _ = writeln!(out, "<h3>{}</h3>\n<p>{}（{} 行）</p>\n<table border=\"1\">\n<tr><th>列名</th><th>类型</th></tr>",
+                         esc(&table.name), esc(table.description), table.rows);
+        for (column, data_type) in &table.columns {
+            let _ = writeln!(out, "<tr><td>{}</td><td>{}</td></tr>", esc(column), esc(data_type));
+        }
+        let _ = writeln!(out, "</table>");
+    }
+
+    let _ = writeln!(out, "<h2>标签（{} 个）</h2>\n<table border=\"1\">\n<tr><th>标签</th><th>列名</th><th>单位</th><th>说明</th></tr>", tags.len());
+    for tag in tags {
+        let _ = writeln!(out, "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                         esc(&tag.tag), esc(&tag.column), esc(&tag.unit), esc(&tag.description));
+    }
+    let _ = writeln!(out, "</table>\n</body>\n</html>");
+    out
+}
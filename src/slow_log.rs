@@ -0,0 +1,60 @@
+//! 慢操作日志
+//! SQL Server 查询或 DuckDB 批量写入耗时超过 `[slow_log]` 配置的阈值时输出一条结构化警告
+//! （target 为 `slow_op`，字段为 kind、op、rows、sql、elapsed_ms、threshold_ms），
+//! 其中 sql 为语句摘要，与审计日志中的摘要一致，用于及早发现逐渐变慢的查询与写入。
+
+use std::time::Instant;
+use tracing::warn;
+
+use crate::config::SlowLogConfig;
+use crate::data_source::sql_digest;
+
+/// 被计时的操作类型
+#[derive(Debug, Clone, Copy)]
+pub enum SlowOpKind {
+    /// SQL Server 查询（含读取结果）
+    SourceQuery,
+    /// DuckDB 批量写入
+    DuckDbInsert,
+}
+
+impl SlowOpKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            SlowOpKind::SourceQuery => "source_query",
+            SlowOpKind::DuckDbInsert => "duckdb_insert",
+        }
+    }
+
+    /// 对应的阈值（毫秒），0 表示不记录
+    fn threshold_ms(self, config: &SlowLogConfig) -> u64 {
+        match self {
+            SlowOpKind::SourceQuery => config.source_query_ms,
+            SlowOpKind::DuckDbInsert => config.duckdb_insert_ms,
+        }
+    }
+}
+
+/// 检查从 `started` 起的耗时，超过阈值时输出慢操作警告
+pub fn check(config: &SlowLogConfig, kind: SlowOpKind, op: &str, sql: &str, rows: usize, started: Instant) {
+    let threshold_ms = kind.threshold_ms(config);
+    if threshold_ms == 0 {
+        return;
+    }
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    if elapsed_ms < threshold_ms {
+        return;
+    }
+
+    warn!(
+        target: "slow_op",
+        kind = kind.as_str(),
+        op,
+        rows,
+        sql = %sql_digest(sql),
+        elapsed_ms,
+        threshold_ms,
+        "慢操作: {} {} 耗时 {} ms（阈值 {} ms），{} 行",
+        kind.as_str(), op, elapsed_ms, threshold_ms, rows
+    );
+}
@@ -0,0 +1,237 @@
+//! 长时间浸泡测试工具
+//! 按固定间隔采样运行中 rt_db 进程的内存（RSS）、文件句柄数、DuckDB 文件大小与 HTTP API 延迟，
+//! 结束时按趋势判断是否存在资源泄漏或无界增长，发现问题时以非零状态码退出。
+//! 可选地以模拟数据源的方式向测试用 SQL Server 的 TagDatabase 表高频更新模拟标签的最新值。
+//!
+//! 通过环境变量配置：
+//! - `RT_DB_SOAK_PID`：被测 rt_db 进程号（必填）
+//! - `RT_DB_SOAK_DB_FILE`：DuckDB 文件路径，默认 `./realtime_data.duckdb`
+//! - `RT_DB_SOAK_API_URL`：HTTP API 地址（如 `http://127.0.0.1:8080`），配置后采样 `/status/sync-log` 延迟
+//! - `RT_DB_SOAK_DURATION_SECS`：测试时长，默认 14400（4 小时）
+//! - `RT_DB_SOAK_SAMPLE_SECS`：采样间隔，默认 60
+//! - `RT_DB_SOAK_SOURCE`：测试用 SQL Server 的 ADO 连接字符串，配置后启用模拟写入
+//! - `RT_DB_SOAK_TAGS` / `RT_DB_SOAK_RATE_HZ`：模拟标签数（默认 500）与每秒写入轮数（默认 10）
+//! - `RT_DB_SOAK_MAX_RSS_MB_PER_HOUR`：允许的 RSS 增长斜率，默认 20
+//! - `RT_DB_SOAK_MAX_FILE_MB_PER_HOUR`：允许的 DuckDB 文件增长斜率，默认 500
+//! - `RT_DB_SOAK_MAX_FD_GROWTH`：允许的文件句柄数增长，默认 50
+//! - `RT_DB_SOAK_MAX_LATENCY_RATIO`：末段与首段 API 延迟中位数之比上限，默认 3
+
+use anyhow::{Result, anyhow};
+use std::time::{Duration, Instant};
+use tokio_util::compat::TokioAsyncWriteCompatExt;
+use tracing::{info, warn, error};
+
+/// 一次采样
+struct Sample {
+    /// 距测试开始的小时数
+    hours: f64,
+    rss_mb: Option<f64>,
+    open_fds: Option<usize>,
+    file_mb: Option<f64>,
+    latency_ms: Option<f64>,
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name).ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .init();
+
+    let pid: u32 = std::env::var("RT_DB_SOAK_PID").ok()
+        .and_then(|v| v.trim().parse().ok())
+        .ok_or_else(|| anyhow!("必须通过 RT_DB_SOAK_PID 指定被测进程号"))?;
+    let db_file = std::env::var("RT_DB_SOAK_DB_FILE").unwrap_or_else(|_| "./realtime_data.duckdb".to_string());
+    let api_url = std::env::var("RT_DB_SOAK_API_URL").ok();
+    let duration = Duration::from_secs(env_or("RT_DB_SOAK_DURATION_SECS", 14400));
+    let sample_interval = Duration::from_secs(env_or("RT_DB_SOAK_SAMPLE_SECS", 60u64).max(1));
+
+    if let Ok(source) = std::env::var("RT_DB_SOAK_SOURCE") {
+        let tags = env_or("RT_DB_SOAK_TAGS", 500usize).max(1);
+        let rate_hz = env_or("RT_DB_SOAK_RATE_HZ", 10.0f64).max(0.1);
+        tokio::spawn(async move {
+            if let Err(e) = simulate_source(&source, tags, rate_hz).await {
+                error!("模拟数据源写入失败: {}", e);
+            }
+        });
+    }
+
+    info!("浸泡测试开始: 进程 {}，时长 {} 秒，采样间隔 {} 秒", pid, duration.as_secs(), sample_interval.as_secs());
+
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?;
+    let started = Instant::now();
+    let mut samples = Vec::new();
+    let mut ticker = tokio::time::interval(sample_interval);
+
+    while started.elapsed() < duration {
+        ticker.tick().await;
+        if !std::path::Path::new(&format!("/proc/{}", pid)).exists() && cfg!(target_os = "linux") {
+            error!("被测进程 {} 已退出", pid);
+            std::process::exit(2);
+        }
+
+        let sample = Sample {
+            hours: started.elapsed().as_secs_f64() / 3600.0,
+            rss_mb: read_rss_mb(pid),
+            open_fds: std::fs::read_dir(format!("/proc/{}/fd", pid)).ok().map(|d| d.count()),
+            file_mb: std::fs::metadata(&db_file).ok().map(|m| m.len() as f64 / 1_048_576.0),
+            latency_ms: match &api_url {
+                Some(url) => probe_latency(&client, url).await,
+                None => None,
+            },
+        };
+        info!("采样: RSS {:?} MB，句柄 {:?}，文件 {:?} MB，API 延迟 {:?} ms",
+              sample.rss_mb.map(|v| v.round()), sample.open_fds, sample.file_mb.map(|v| v.round()),
+              sample.latency_ms.map(|v| v.round()));
+        samples.push(sample);
+    }
+
+    let failures = evaluate(&samples);
+    if failures.is_empty() {
+        info!("浸泡测试通过，共 {} 个采样", samples.len());
+        Ok(())
+    } else {
+        for failure in &failures {
+            error!("浸泡测试失败: {}", failure);
+        }
+        std::process::exit(1);
+    }
+}
+
+/// 读取进程常驻内存（Linux /proc），单位 MB
+fn read_rss_mb(pid: u32) -> Option<f64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let kb: f64 = status.lines()
+        .find(|line| line.starts_with("VmRSS:"))?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()?;
+    Some(kb / 1024.0)
+}
+
+/// 请求一次同步日志接口，返回耗时（毫秒）
+async fn probe_latency(client: &reqwest::Client, api_url: &str) -> Option<f64> {
+    let url = format!("{}/status/sync-log?limit=1", api_url.trim_end_matches('/'));
+    let started = Instant::now();
+    match client.get(&url).send().await {
+        Ok(response) if response.status().is_success() => Some(started.elapsed().as_secs_f64() * 1000.0),
+        Ok(response) => {
+            warn!("API 返回 {}", response.status());
+            None
+        }
+        Err(e) => {
+            warn!("API 请求失败: {}", e);
+            None
+        }
+    }
+}
+
+/// 按趋势判断是否存在泄漏，返回失败原因
+fn evaluate(samples: &[Sample]) -> Vec<String> {
+    let mut failures = Vec::new();
+    // 跳过前 10% 的预热阶段（初始加载、缓存建立）
+    let steady = &samples[samples.len() / 10..];
+    if steady.len() < 4 {
+        failures.push(format!("有效采样数 {} 过少，无法判断趋势", steady.len()));
+        return failures;
+    }
+
+    let max_rss_slope = env_or("RT_DB_SOAK_MAX_RSS_MB_PER_HOUR", 20.0);
+    if let Some(slope) = slope(steady, |s| s.rss_mb) {
+        info!("RSS 增长斜率 {:.2} MB/小时", slope);
+        if slope > max_rss_slope {
+            failures.push(format!("RSS 持续增长 {:.2} MB/小时，超过 {} MB/小时", slope, max_rss_slope));
+        }
+    }
+
+    let max_file_slope = env_or("RT_DB_SOAK_MAX_FILE_MB_PER_HOUR", 500.0);
+    if let Some(slope) = slope(steady, |s| s.file_mb) {
+        info!("DuckDB 文件增长斜率 {:.2} MB/小时", slope);
+        if slope > max_file_slope {
+            failures.push(format!("DuckDB 文件持续增长 {:.2} MB/小时，超过 {} MB/小时", slope, max_file_slope));
+        }
+    }
+
+    let max_fd_growth = env_or("RT_DB_SOAK_MAX_FD_GROWTH", 50usize);
+    let fds: Vec<usize> = steady.iter().filter_map(|s| s.open_fds).collect();
+    if let (Some(first), Some(last)) = (fds.first(), fds.last())
+        && last.saturating_sub(*first) > max_fd_growth {
+        failures.push(format!("文件句柄数从 {} 增长到 {}，可能存在连接泄漏", first, last));
+    }
+
+    let max_latency_ratio = env_or("RT_DB_SOAK_MAX_LATENCY_RATIO", 3.0);
+    let latencies: Vec<f64> = steady.iter().filter_map(|s| s.latency_ms).collect();
+    if latencies.len() >= 8 {
+        let quarter = latencies.len() / 4;
+        let head = median(&latencies[..quarter]);
+        let tail = median(&latencies[latencies.len() - quarter..]);
+        info!("API 延迟中位数: 首段 {:.1} ms，末段 {:.1} ms", head, tail);
+        if head > 0.0 && tail / head > max_latency_ratio {
+            failures.push(format!("API 延迟从 {:.1} ms 增长到 {:.1} ms", head, tail));
+        }
+    }
+
+    failures
+}
+
+/// 最小二乘斜率（每小时变化量）
+fn slope(samples: &[Sample], value: impl Fn(&Sample) -> Option<f64>) -> Option<f64> {
+    let points: Vec<(f64, f64)> = samples.iter()
+        .filter_map(|s| value(s).map(|v| (s.hours, v)))
+        .collect();
+    if points.len() < 2 {
+        return None;
+    }
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let covariance: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    (variance > 0.0).then(|| covariance / variance)
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    sorted[sorted.len() / 2]
+}
+
+/// 模拟数据源：按频率更新 TagDatabase 表中 `tags` 个标签的最新值
+///
+/// 与真实数据源一样每个标签只有一行（MERGE：已有则更新，没有则插入），表不会随测试时长增长。
+/// 仅用于测试库，写入的标签名以 `SOAK_` 开头。
+async fn simulate_source(connection_string: &str, tags: usize, rate_hz: f64) -> Result<()> {
+    let config = tiberius::Config::from_ado_string(connection_string)?;
+    let tcp = tokio::net::TcpStream::connect(config.get_addr()).await?;
+    let mut client = tiberius::Client::connect(config, tcp.compat_write()).await?;
+
+    info!("模拟数据源已启动: {} 个标签，每秒 {} 轮", tags, rate_hz);
+    let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / rate_hz));
+    let mut round: u64 = 0;
+
+    loop {
+        ticker.tick().await;
+        round += 1;
+
+        // 每条语句最多 1000 行（SQL Server VALUES 子句上限）
+        for chunk_start in (0..tags).step_by(1000) {
+            let values: Vec<String> = (chunk_start..(chunk_start + 1000).min(tags))
+                .map(|i| format!("('SOAK_{:05}', {})", i, ((round + i as u64) as f64 / 10.0).sin()))
+                .collect();
+            let sql = format!(
+                "MERGE [TagDatabase] AS t USING (VALUES {}) AS s ([TagName], [TagVal]) ON t.[TagName] = s.[TagName] \
+                 WHEN MATCHED THEN UPDATE SET t.[DataTime] = SYSDATETIME(), t.[TagVal] = s.[TagVal] \
+                 WHEN NOT MATCHED THEN INSERT ([DataTime], [TagName], [TagVal]) VALUES (SYSDATETIME(), s.[TagName], s.[TagVal]);",
+                values.join(", ")
+            );
+            client.execute(sql, &[]).await?;
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! SPC（统计过程控制）监控模块
+//! 基于滑动窗口计算控制限，按 Western Electric 判异规则检测违规点
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use tracing::{debug, warn};
+
+use crate::config::SpcConfig;
+use crate::database::DatabaseManager;
+
+/// 规则 2-4 判定所需的最少点数
+const RULE_POINTS: usize = 8;
+
+/// Western Electric 判异规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpcRule {
+    /// 1 个点落在 3σ 之外
+    Beyond3Sigma,
+    /// 连续 3 点中有 2 点落在同侧 2σ 之外
+    TwoOfThreeBeyond2Sigma,
+    /// 连续 5 点中有 4 点落在同侧 1σ 之外
+    FourOfFiveBeyond1Sigma,
+    /// 连续 8 点落在中心线同一侧
+    EightOnOneSide,
+}
+
+impl SpcRule {
+    /// 规则编号，写入事件表
+    pub fn code(&self) -> &'static str {
+        match self {
+            SpcRule::Beyond3Sigma => "WE1",
+            SpcRule::TwoOfThreeBeyond2Sigma => "WE2",
+            SpcRule::FourOfFiveBeyond1Sigma => "WE3",
+            SpcRule::EightOnOneSide => "WE4",
+        }
+    }
+}
+
+/// 控制限（中心线与标准差）
+#[derive(Debug, Clone, Copy)]
+pub struct ControlLimits {
+    pub mean: f64,
+    pub sigma: f64,
+}
+
+impl ControlLimits {
+    /// 由样本计算控制限，样本不足或标准差为 0 时返回 None
+    pub fn from_samples(values: &[f64]) -> Option<Self> {
+        if values.len() < 2 {
+            return None;
+        }
+
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        let sigma = variance.sqrt();
+
+        if sigma > 0.0 && sigma.is_finite() {
+            Some(Self { mean, sigma })
+        } else {
+            None
+        }
+    }
+
+    fn z_score(&self, value: f64) -> f64 {
+        (value - self.mean) / self.sigma
+    }
+}
+
+/// 检查最新点触发的判异规则，`recent` 按时间升序，最后一个为最新点
+pub fn check_rules(limits: &ControlLimits, recent: &[f64]) -> Vec<SpcRule> {
+    let z: Vec<f64> = recent.iter().map(|v| limits.z_score(*v)).collect();
+    let Some(&last) = z.last() else {
+        return Vec::new();
+    };
+
+    let mut violations = Vec::new();
+
+    if last.abs() > 3.0 {
+        violations.push(SpcRule::Beyond3Sigma);
+    }
+
+    // 统计最近 n 个点中与最新点同侧且超过 k·σ 的点数
+    let same_side_beyond = |n: usize, k: f64| -> usize {
+        z.iter()
+            .rev()
+            .take(n)
+            .filter(|v| v.signum() == last.signum() && v.abs() > k)
+            .count()
+    };
+
+    if z.len() >= 3 && last.abs() > 2.0 && same_side_beyond(3, 2.0) >= 2 {
+        violations.push(SpcRule::TwoOfThreeBeyond2Sigma);
+    }
+
+    if z.len() >= 5 && last.abs() > 1.0 && same_side_beyond(5, 1.0) >= 4 {
+        violations.push(SpcRule::FourOfFiveBeyond1Sigma);
+    }
+
+    if z.len() >= RULE_POINTS && last != 0.0 && same_side_beyond(RULE_POINTS, 0.0) == RULE_POINTS {
+        violations.push(SpcRule::EightOnOneSide);
+    }
+
+    violations
+}
+
+/// SPC 违规事件
+#[derive(Debug, Clone)]
+pub struct SpcEvent {
+    pub timestamp: DateTime<Utc>,
+    pub tag_name: String,
+    pub rule: SpcRule,
+    pub value: f64,
+    pub mean: f64,
+    pub sigma: f64,
+}
+
+/// SPC 监控器，记录每个标签最后检查的时间点以避免重复报警
+pub struct SpcMonitor {
+    config: SpcConfig,
+    last_checked: HashMap<String, DateTime<Utc>>,
+}
+
+impl SpcMonitor {
+    /// 创建新的 SPC 监控器
+    pub fn new(config: SpcConfig) -> Self {
+        Self {
+            config,
+            last_checked: HashMap::new(),
+        }
+    }
+
+    /// 对配置的标签执行一次判异，将违规事件写入事件表并返回
+    ///
+    /// 单个标签读取样本失败时记录警告并跳过该标签，不影响其他标签。
+    pub fn evaluate(&mut self, db_manager: &DatabaseManager) -> Result<Vec<SpcEvent>> {
+        let mut events = Vec::new();
+
+        for tag in &self.config.tags {
+            // 取窗口样本计算控制限，另加最新点参与判异
+            let samples = match db_manager.get_recent_values(tag, self.config.window_size + 1) {
+                Ok(samples) => samples,
+                Err(e) => {
+                    warn!("读取标签 {} 的 SPC 样本失败，跳过: {}", tag, e);
+                    continue;
+                }
+            };
+
+            let Some(&(latest_time, latest_value)) = samples.last() else {
+                continue;
+            };
+
+            if self.last_checked.get(tag).is_some_and(|t| *t >= latest_time) {
+                continue;
+            }
+            self.last_checked.insert(tag.clone(), latest_time);
+
+            let values: Vec<f64> = samples.iter().map(|(_, v)| *v).collect();
+            let Some(limits) = ControlLimits::from_samples(&values[..values.len() - 1]) else {
+                debug!("标签 {} 样本不足或无波动，跳过 SPC 判异", tag);
+                continue;
+            };
+
+            let recent_start = values.len().saturating_sub(RULE_POINTS);
+            for rule in check_rules(&limits, &values[recent_start..]) {
+                warn!("SPC 判异: 标签 {} 在 {} 触发规则 {}，值 {:.4}，均值 {:.4}，σ {:.4}",
+                      tag, latest_time, rule.code(), latest_value, limits.mean, limits.sigma);
+
+                events.push(SpcEvent {
+                    timestamp: latest_time,
+                    tag_name: tag.clone(),
+                    rule,
+                    value: latest_value,
+                    mean: limits.mean,
+                    sigma: limits.sigma,
+                });
+            }
+        }
+
+        if !events.is_empty() {
+            db_manager.insert_spc_events(&events)
+                .map_err(|e| anyhow!("写入 SPC 事件失败: {}", e))?;
+        }
+
+        Ok(events)
+    }
+}
@@ -0,0 +1,374 @@
+//! 内部 SQL 生成
+//! 发往 SQL Server 与 DuckDB 的语句统一由此构建：标识符按方言引用（SQL Server 方括号、DuckDB 双引号，
+//! 支持中文等任意字符并转义结束引号），值一律通过参数绑定（SQL Server `@P1`、DuckDB `?`），
+//! 无法绑定参数的位置（如 COPY 的目标路径）使用 [`literal`] 转义。
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+/// SQL 方言
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    SqlServer,
+    DuckDb,
+}
+
+impl Dialect {
+    /// 引用单个标识符
+    pub fn quote(self, name: &str) -> String {
+        match self {
+            Dialect::SqlServer => format!("[{}]", name.replace(']', "]]")),
+            Dialect::DuckDb => format!("\"{}\"", name.replace('"', "\"\"")),
+        }
+    }
+
+    /// 引用可带架构名的标识符（`schema.table`），各部分分别引用
+    pub fn quote_qualified(self, name: &str) -> String {
+        name.split('.').map(|part| self.quote(part)).collect::<Vec<_>>().join(".")
+    }
+
+    /// 第 `n` 个参数（从 1 开始）的占位符
+    fn placeholder(self, n: usize) -> String {
+        match self {
+            Dialect::SqlServer => format!("@P{}", n),
+            Dialect::DuckDb => "?".to_string(),
+        }
+    }
+}
+
+/// 字符串字面量（单引号转义），仅用于无法绑定参数的位置
+pub fn literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// 绑定参数
+#[derive(Debug, Clone, PartialEq)]
+pub enum Param {
+    Null,
+    Text(String),
+    Float(f64),
+    Date(NaiveDate),
+    /// 不带时区的时间（SQL Server 中为数据源本地时间）
+    DateTime(NaiveDateTime),
+}
+
+impl From<&str> for Param {
+    fn from(value: &str) -> Self {
+        Param::Text(value.to_string())
+    }
+}
+
+impl From<String> for Param {
+    fn from(value: String) -> Self {
+        Param::Text(value)
+    }
+}
+
+impl From<&String> for Param {
+    fn from(value: &String) -> Self {
+        Param::Text(value.clone())
+    }
+}
+
+impl From<f64> for Param {
+    fn from(value: f64) -> Self {
+        Param::Float(value)
+    }
+}
+
+impl From<NaiveDate> for Param {
+    fn from(value: NaiveDate) -> Self {
+        Param::Date(value)
+    }
+}
+
+impl From<NaiveDateTime> for Param {
+    fn from(value: NaiveDateTime) -> Self {
+        Param::DateTime(value)
+    }
+}
+
+impl<T: Into<Param>> From<Option<T>> for Param {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Param::Null, Into::into)
+    }
+}
+
+impl Param {
+    /// 绑定到 SQL Server 查询
+    pub fn bind_to(self, query: &mut tiberius::Query<'_>) {
+        match self {
+            Param::Null => query.bind(Option::<String>::None),
+            Param::Text(value) => query.bind(value),
+            Param::Float(value) => query.bind(value),
+            Param::Date(value) => query.bind(value),
+            Param::DateTime(value) => query.bind(value),
+        }
+    }
+}
+
+impl duckdb::ToSql for Param {
+    fn to_sql(&self) -> duckdb::Result<duckdb::types::ToSqlOutput<'_>> {
+        match self {
+            Param::Null => duckdb::types::Null.to_sql(),
+            Param::Text(value) => value.to_sql(),
+            Param::Float(value) => value.to_sql(),
+            Param::Date(value) => value.to_sql(),
+            Param::DateTime(value) => value.to_sql(),
+        }
+    }
+}
+
+/// 构建完成的语句与按顺序绑定的参数
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub sql: String,
+    pub params: Vec<Param>,
+}
+
+impl Statement {
+    /// 不带参数的语句
+    pub fn new(sql: impl Into<String>) -> Self {
+        Self { sql: sql.into(), params: Vec::new() }
+    }
+
+    /// 以 UNION ALL 连接各语句，参数按顺序合并（仅适用于按位置绑定的 DuckDB 占位符）
+    pub fn union_all(parts: impl IntoIterator<Item = Statement>) -> Self {
+        let mut sql = Vec::new();
+        let mut params = Vec::new();
+        for part in parts {
+            sql.push(part.sql);
+            params.extend(part.params);
+        }
+        Self { sql: sql.join(" UNION ALL "), params }
+    }
+}
+
+/// 比较运算符
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl Op {
+    fn as_sql(self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+        }
+    }
+}
+
+/// SELECT 语句构建器
+#[derive(Debug, Clone)]
+pub struct Select {
+    dialect: Dialect,
+    distinct: bool,
+    columns: Vec<String>,
+    column_params: Vec<Param>,
+    from: String,
+    filters: Vec<String>,
+    group_by: Vec<String>,
+    order_by: Vec<String>,
+    params: Vec<Param>,
+}
+
+impl Select {
+    /// 从表（可带架构名）查询
+    pub fn from(dialect: Dialect, table: &str) -> Self {
+        Self {
+            dialect,
+            distinct: false,
+            columns: Vec::new(),
+            column_params: Vec::new(),
+            from: dialect.quote_qualified(table),
+            filters: Vec::new(),
+            group_by: Vec::new(),
+            order_by: Vec::new(),
+            params: Vec::new(),
+        }
+    }
+
+    /// 从子查询查询，子查询的参数排在之后添加的参数之前
+    pub fn from_query(dialect: Dialect, query: Statement) -> Self {
+        Self {
+            dialect,
+            distinct: false,
+            columns: Vec::new(),
+            column_params: Vec::new(),
+            from: format!("({})", query.sql),
+            filters: Vec::new(),
+            group_by: Vec::new(),
+            order_by: Vec::new(),
+            params: query.params,
+        }
+    }
+
+    pub fn distinct(mut self) -> Self {
+        self.distinct = true;
+        self
+    }
+
+    /// 查询列
+    pub fn column(mut self, name: &str) -> Self {
+        self.columns.push(self.dialect.quote(name));
+        self
+    }
+
+    /// 查询表达式，表达式中的标识符需由调用方按方言引用
+    pub fn expr(mut self, expr: impl Into<String>) -> Self {
+        self.columns.push(expr.into());
+        self
+    }
+
+    /// 带参数的查询表达式，占位符写作 `?`，参数排在 FROM 与条件的参数之前（仅用于 DuckDB）
+    pub fn expr_bound<P: Into<Param>>(mut self, expr: impl Into<String>, params: impl IntoIterator<Item = P>) -> Self {
+        self.columns.push(expr.into());
+        self.column_params.extend(params.into_iter().map(Into::into));
+        self
+    }
+
+    /// 列与参数比较
+    pub fn filter(self, column: &str, op: Op, value: impl Into<Param>) -> Self {
+        let column = self.dialect.quote(column);
+        self.filter_expr(column, op, value)
+    }
+
+    /// 表达式与参数比较，表达式中的标识符需由调用方按方言引用
+    pub fn filter_expr(mut self, expr: impl Into<String>, op: Op, value: impl Into<Param>) -> Self {
+        self.params.push(value.into());
+        let placeholder = self.dialect.placeholder(self.params.len());
+        self.filters.push(format!("{} {} {}", expr.into(), op.as_sql(), placeholder));
+        self
+    }
+
+    /// 列取值在参数列表中；列表为空时不匹配任何行
+    pub fn filter_in<P: Into<Param>>(mut self, column: &str, values: impl IntoIterator<Item = P>) -> Self {
+        let mut placeholders = Vec::new();
+        for value in values {
+            self.params.push(value.into());
+            placeholders.push(self.dialect.placeholder(self.params.len()));
+        }
+        let filter = if placeholders.is_empty() {
+            "1 = 0".to_string()
+        } else {
+            format!("{} IN ({})", self.dialect.quote(column), placeholders.join(", "))
+        };
+        self.filters.push(filter);
+        self
+    }
+
+    /// 列不为 NULL
+    pub fn not_null(mut self, column: &str) -> Self {
+        self.filters.push(format!("{} IS NOT NULL", self.dialect.quote(column)));
+        self
+    }
+
+    /// 不带参数的条件表达式，表达式中的标识符需由调用方按方言引用
+    pub fn condition(mut self, expr: impl Into<String>) -> Self {
+        self.filters.push(expr.into());
+        self
+    }
+
+    pub fn group_by(mut self, column: &str) -> Self {
+        self.group_by.push(self.dialect.quote(column));
+        self
+    }
+
+    pub fn order_by(mut self, column: &str) -> Self {
+        self.order_by.push(self.dialect.quote(column));
+        self
+    }
+
+    pub fn build(self) -> Statement {
+        let columns = if self.columns.is_empty() { "*".to_string() } else { self.columns.join(", ") };
+        let mut sql = format!(
+            "SELECT {}{} FROM {}",
+            if self.distinct { "DISTINCT " } else { "" },
+            columns,
+            self.from
+        );
+        if !self.filters.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&self.filters.join(" AND "));
+        }
+        if !self.group_by.is_empty() {
+            sql.push_str(" GROUP BY ");
+            sql.push_str(&self.group_by.join(", "));
+        }
+        if !self.order_by.is_empty() {
+            sql.push_str(" ORDER BY ");
+            sql.push_str(&self.order_by.join(", "));
+        }
+        let mut params = self.column_params;
+        params.extend(self.params);
+        Statement { sql, params }
+    }
+}
+
+/// 多行 INSERT 语句构建器
+#[derive(Debug, Clone)]
+pub struct Insert {
+    dialect: Dialect,
+    table: String,
+    columns: Vec<String>,
+    or_replace: bool,
+}
+
+impl Insert {
+    pub fn into(dialect: Dialect, table: &str) -> Self {
+        Self {
+            dialect,
+            table: dialect.quote_qualified(table),
+            columns: Vec::new(),
+            or_replace: false,
+        }
+    }
+
+    /// 主键冲突时覆盖（DuckDB `INSERT OR REPLACE`）
+    pub fn or_replace(mut self) -> Self {
+        self.or_replace = true;
+        self
+    }
+
+    pub fn column(mut self, name: &str) -> Self {
+        self.columns.push(self.dialect.quote(name));
+        self
+    }
+
+    pub fn columns<S: AsRef<str>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        for name in names {
+            self.columns.push(self.dialect.quote(name.as_ref()));
+        }
+        self
+    }
+
+    /// 插入 `rows` 行的语句，参数按行依次绑定
+    pub fn sql(&self, rows: usize) -> String {
+        let mut n = 0;
+        let values: Vec<String> = (0..rows)
+            .map(|_| {
+                let row: Vec<String> = self.columns.iter()
+                    .map(|_| {
+                        n += 1;
+                        self.dialect.placeholder(n)
+                    })
+                    .collect();
+                format!("({})", row.join(", "))
+            })
+            .collect();
+        format!(
+            "INSERT {}INTO {} ({}) VALUES {}",
+            if self.or_replace { "OR REPLACE " } else { "" },
+            self.table,
+            self.columns.join(", "),
+            values.join(", ")
+        )
+    }
+}
@@ -0,0 +1,136 @@
+//! 停滞与过期标签跟踪
+//! 同一个跟踪器记录两类问题：值超过 `stale_tags.threshold_secs` 未变化（停滞，仪表冻结）与
+//! 数据源 DataTime 超过 `timestamps.stale_after_secs` 未更新（过期，仅源时间戳模式）。
+//! 状态报告、`GET /status/stale-tags` 与 `/metrics` 都从这里读取；同步周期回滚或提交失败时随死区基准值一起重置。
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// 标签被列出的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StaleReason {
+    /// 值长时间未变化
+    Frozen,
+    /// 数据源 DataTime 长时间未更新
+    SourceTime,
+}
+
+/// 停滞或过期的标签
+#[derive(Debug, Clone, Serialize)]
+pub struct StaleTag {
+    pub tag: String,
+    pub reason: StaleReason,
+    /// 最后的值（过期标签为空）
+    pub value: Option<f64>,
+    /// 停滞标签为值最后变化的时间，过期标签为最后的数据源时间
+    pub last_changed: DateTime<Utc>,
+    /// 已停滞或过期的秒数
+    pub stale_secs: i64,
+}
+
+/// 单个标签的跟踪状态
+#[derive(Debug, Default)]
+struct TagState {
+    /// 最后的值与值最后变化的时间（停滞检测）
+    value: Option<(Option<f64>, DateTime<Utc>)>,
+    frozen: bool,
+    /// 最后读取到的数据源时间
+    source_time: Option<DateTime<Utc>>,
+    /// 最后写入的数据源时间，用于跳过 DataTime 未更新的记录
+    written_source_time: Option<DateTime<Utc>>,
+    source_stale: bool,
+}
+
+/// 停滞与过期标签跟踪器
+#[derive(Debug, Default)]
+pub struct StaleTracker {
+    tags: Mutex<HashMap<String, TagState>>,
+}
+
+impl StaleTracker {
+    /// 停滞检测：值变化时以记录时间 `at` 为最后变化时间，标签开始或结束停滞时记录日志，返回该标签当前是否停滞
+    pub fn observe_value(&self, tag: &str, value: Option<f64>, at: DateTime<Utc>, now: DateTime<Utc>, threshold: chrono::Duration) -> bool {
+        let mut tags = self.tags.lock().unwrap();
+        let state = tags.entry(tag.to_string()).or_default();
+        let changed_at = match state.value {
+            Some((last, changed_at)) if last == value => changed_at,
+            _ => at,
+        };
+        state.value = Some((value, changed_at));
+
+        let frozen = now - changed_at > threshold;
+        if frozen && !state.frozen {
+            warn!("标签 {} 的值 {:?} 已 {} 秒未变化，标记为停滞", tag, value, (now - changed_at).num_seconds());
+        } else if !frozen && state.frozen {
+            info!("标签 {} 的值恢复变化", tag);
+        }
+        state.frozen = frozen;
+        frozen
+    }
+
+    /// 源时间检查：DataTime 超过 `stale_after` 未更新的标签标记为过期，返回记录是否应写入
+    /// （未过期且 DataTime 比上次写入的新）；`stale_after` 为 None 时不检查过期
+    pub fn observe_source_time(&self, tag: &str, source_time: DateTime<Utc>, now: DateTime<Utc>, stale_after: Option<chrono::Duration>) -> bool {
+        let mut tags = self.tags.lock().unwrap();
+        let state = tags.entry(tag.to_string()).or_default();
+        state.source_time = Some(source_time);
+
+        let stale = stale_after.is_some_and(|stale_after| now - source_time > stale_after);
+        if stale && !state.source_stale {
+            warn!("标签 {} 的数据源时间 {} 已超过 {} 秒未更新", tag, source_time,
+                  stale_after.map_or(0, |d| d.num_seconds()));
+        } else if !stale && state.source_stale {
+            info!("标签 {} 的数据源时间已恢复更新", tag);
+        }
+        state.source_stale = stale;
+        if stale || state.written_source_time.is_some_and(|last| source_time <= last) {
+            return false;
+        }
+        state.written_source_time = Some(source_time);
+        true
+    }
+
+    /// 当前停滞与过期的标签，按时长从长到短排序
+    pub fn stale_tags(&self) -> Vec<StaleTag> {
+        let now = Utc::now();
+        let tags = self.tags.lock().unwrap();
+        let mut stale = Vec::new();
+        for (tag, state) in tags.iter() {
+            if let (true, Some((value, changed_at))) = (state.frozen, state.value) {
+                stale.push(StaleTag {
+                    tag: tag.clone(),
+                    reason: StaleReason::Frozen,
+                    value,
+                    last_changed: changed_at,
+                    stale_secs: (now - changed_at).num_seconds(),
+                });
+            }
+            if let (true, Some(source_time)) = (state.source_stale, state.source_time) {
+                stale.push(StaleTag {
+                    tag: tag.clone(),
+                    reason: StaleReason::SourceTime,
+                    value: None,
+                    last_changed: source_time,
+                    stale_secs: (now - source_time).num_seconds(),
+                });
+            }
+        }
+        stale.sort_by(|a, b| b.stale_secs.cmp(&a.stale_secs).then_with(|| a.tag.cmp(&b.tag)));
+        stale
+    }
+
+    /// 停滞与过期的标签数
+    pub fn counts(&self) -> (usize, usize) {
+        let tags = self.tags.lock().unwrap();
+        (tags.values().filter(|s| s.frozen).count(), tags.values().filter(|s| s.source_stale).count())
+    }
+
+    /// 清空全部状态：回滚后已写入的值与数据源时间不再可信，之后的周期重新检测
+    pub fn reset(&self) {
+        self.tags.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,104 @@
+//! 启动顺序控制
+//! 工厂上电时 SQL Server 或数据盘可能晚于 rt_db 就绪。启动前按配置等待依赖：
+//! 数据盘挂载（路径存在，可选要求为挂载点）与数据源可连接，超过最长等待时间才放弃启动，
+//! 不必依赖操作系统服务之间的启动依赖关系。
+
+use anyhow::{Result, bail};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::StartupConfig;
+use crate::data_source::SqlServerDataSource;
+
+/// 等待配置的路径就绪（数据盘挂载）
+pub async fn wait_for_paths(config: &StartupConfig) -> Result<()> {
+    if config.wait_for_paths.is_empty() {
+        return Ok(());
+    }
+
+    let started = Instant::now();
+    let max_wait = Duration::from_secs(config.paths_max_wait_secs);
+    let interval = Duration::from_secs(config.retry_interval_secs.max(1));
+    let mut logged = false;
+
+    loop {
+        let pending: Vec<&String> = config.wait_for_paths.iter()
+            .filter(|path| !path_ready(Path::new(path), config.require_mount))
+            .collect();
+        if pending.is_empty() {
+            if logged {
+                info!("等待的路径已就绪，耗时 {} 秒", started.elapsed().as_secs());
+            }
+            return Ok(());
+        }
+
+        if config.paths_max_wait_secs > 0 && started.elapsed() >= max_wait {
+            bail!("等待路径就绪超时（{} 秒）: {:?}", config.paths_max_wait_secs, pending);
+        }
+        if !logged {
+            warn!("路径尚未就绪，每 {} 秒检查一次: {:?}", interval.as_secs(), pending);
+            logged = true;
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// 路径是否就绪：存在，且 `require_mount` 时为挂载点（与上级目录不在同一设备上）
+fn path_ready(path: &Path, require_mount: bool) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+    if !require_mount {
+        return true;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let parent = path.canonicalize().ok()
+            .and_then(|p| p.parent().map(Path::to_path_buf))
+            .and_then(|p| std::fs::metadata(p).ok());
+        match parent {
+            Some(parent) => parent.dev() != metadata.dev(),
+            // 根目录总是已挂载
+            None => true,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        true
+    }
+}
+
+/// 等待数据源可连接，未启用 `wait_for_source` 时只测试一次
+pub async fn wait_for_source(config: &StartupConfig, data_source: &SqlServerDataSource) -> Result<()> {
+    if !config.wait_for_source {
+        return data_source.test_connection().await;
+    }
+
+    let started = Instant::now();
+    let max_wait = Duration::from_secs(config.source_max_wait_secs);
+    let interval = Duration::from_secs(config.retry_interval_secs.max(1));
+    let mut attempts = 0u32;
+
+    loop {
+        attempts += 1;
+        match data_source.test_connection().await {
+            Ok(()) => {
+                if attempts > 1 {
+                    info!("数据源已就绪，等待 {} 秒，共尝试 {} 次", started.elapsed().as_secs(), attempts);
+                }
+                return Ok(());
+            }
+            Err(e) => {
+                if config.source_max_wait_secs > 0 && started.elapsed() >= max_wait {
+                    bail!("等待数据源超时（{} 秒，共尝试 {} 次）: {}", config.source_max_wait_secs, attempts, e);
+                }
+                warn!("数据源尚未就绪（第 {} 次），{} 秒后重试: {}", attempts, interval.as_secs(), e);
+                tokio::time::sleep(interval).await;
+            }
+        }
+    }
+}
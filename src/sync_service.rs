@@ -1,14 +1,33 @@
 use anyhow::{Result, anyhow};
 use chrono::{DateTime, Utc, Duration};
+use serde::Serialize;
 use tokio::time::{interval, Duration as TokioDuration};
 use tracing::{info, debug, error, warn};
-use crate::config::AppConfig;
-use crate::database::DatabaseManager;
+use crate::alert::Alerter;
+use crate::config::{AppConfig, HeartbeatMode, ShedStage};
+use crate::database::{Cycle, DatabaseManager, SyncCycleStats, TagMeta};
+use crate::stale::{StaleReason, StaleTag};
 use crate::data_source::SqlServerDataSource;
-use std::sync::Arc;
+use crate::capture::Recorder;
+use crate::archive::Archiver;
+use crate::forecast::Forecaster;
+use crate::kafka::KafkaSink;
+use crate::low_latency::{LatencyReport, StagedPoll};
+use crate::metrics;
+use crate::opcua::OpcUaServer;
+use crate::rollup::Rollups;
+use crate::spc::SpcMonitor;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use tokio::sync::Notify;
+
+/// 状态中保留的最近同步错误条数
+const ERROR_HISTORY_LEN: usize = 20;
 
 /// 标签配置信息
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 pub struct TagConfig {
     pub tag_name: String,
     pub max_records: Option<usize>,
@@ -16,11 +35,42 @@ pub struct TagConfig {
 }
 
 /// 数据同步服务
+///
+/// 初始加载、周期更新与状态报告共享同一实例（通过 `Arc`），可变状态使用内部可变性。
 pub struct SyncService {
     config: Arc<AppConfig>,
     db_manager: Arc<DatabaseManager>,
     data_source: Arc<SqlServerDataSource>,
-    last_seen_timestamp: Option<DateTime<Utc>>,
+    last_seen_timestamp: Mutex<Option<DateTime<Utc>>>,
+    spc_monitor: Mutex<Option<SpcMonitor>>,
+    forecaster: Mutex<Option<Forecaster>>,
+    /// 立即同步请求（SIGUSR1 或管理接口触发），唤醒周期更新提前执行
+    sync_trigger: Arc<Notify>,
+    /// 串行化常规周期、缺口回填与快速组轮询的写事务
+    write_lock: tokio::sync::Mutex<()>,
+    /// 数据源熔断是否开启
+    circuit_open: AtomicBool,
+    /// 数据源会话录制（启用 `[capture]` 时）
+    capture: Option<Recorder>,
+    /// 清理前的 Parquet 归档
+    archiver: Archiver,
+    rollups: Rollups,
+    /// 仅快照模式：数据源没有历史表，不加载历史数据、不回填缺口
+    snapshot_only: bool,
+    /// 写入缓存的新记录发布到 Kafka
+    kafka: Option<KafkaSink>,
+    /// 内嵌 OPC UA 服务器，随写入缓存的新记录更新
+    opcua: Option<OpcUaServer>,
+    /// 最近一次写入 tag_meta 表的标签元数据，未变化时不重写
+    tag_meta: Mutex<Vec<TagMeta>>,
+    /// 服务创建时间，用于计算运行时长
+    started: std::time::Instant,
+    /// 更新周期的失败统计
+    errors: Mutex<CycleErrors>,
+    /// 同步失败与数据滞后告警
+    alerts: Alerter,
+    /// 已回写的心跳次数（计数器心跳）
+    heartbeats: AtomicU64,
 }
 
 impl SyncService {
@@ -29,28 +79,81 @@ impl SyncService {
         config: Arc<AppConfig>,
         db_manager: Arc<DatabaseManager>,
         data_source: Arc<SqlServerDataSource>,
+        sync_trigger: Arc<Notify>,
+        snapshot_only: bool,
     ) -> Self {
+        let spc_monitor = config.spc.enabled
+            .then(|| SpcMonitor::new(config.spc.clone()));
+        let capture = Recorder::open(&config.capture);
+        let forecaster = config.forecast.enabled
+            .then(|| Forecaster::new(config.forecast.clone()));
+        let archiver = Archiver::new(&config);
+        let rollups = Rollups::new(&config);
+        let alerts = Alerter::new(&config);
+        let kafka = config.kafka.enabled
+            .then(|| KafkaSink::spawn(config.kafka.clone()));
+        let opcua = if config.opcua.enabled {
+            OpcUaServer::spawn(&config.opcua, config.quality.clone())
+                .map_err(|e| error!("OPC UA 服务启动失败: {}", e))
+                .ok()
+        } else {
+            None
+        };
+        
         Self {
             config,
             db_manager,
             data_source,
-            last_seen_timestamp: None,
+            last_seen_timestamp: Mutex::new(None),
+            spc_monitor: Mutex::new(spc_monitor),
+            forecaster: Mutex::new(forecaster),
+            sync_trigger,
+            write_lock: tokio::sync::Mutex::new(()),
+            circuit_open: AtomicBool::new(false),
+            capture,
+            archiver,
+            rollups,
+            snapshot_only,
+            kafka,
+            opcua,
+            tag_meta: Mutex::new(Vec::new()),
+            started: std::time::Instant::now(),
+            errors: Mutex::new(CycleErrors::default()),
+            alerts,
+            heartbeats: AtomicU64::new(0),
+        }
+    }
+    
+    /// 将已提交到缓存的新记录发布到 Kafka（未启用或运行时停用时不做任何事），并更新 OPC UA 地址空间
+    fn publish(&self, records: &[crate::database::TimeSeriesRecord]) {
+        if let Some(opcua) = &self.opcua {
+            opcua.publish(records);
+        }
+        if let Some(kafka) = &self.kafka
+            && self.db_manager.toggles().sink_enabled(crate::toggles::KAFKA_SINK)
+            && !crate::degradation::get().shedding(ShedStage::Sinks) {
+            kafka.publish(records);
         }
     }
     
-    /// 初始数据加载 - 查询过去1小时的历史数据
-    pub async fn initial_load(&mut self) -> Result<()> {
+    /// 初始数据加载 - 查询过去1小时（或自缓存最新时间戳起）的历史数据
+    pub async fn initial_load(&self) -> Result<()> {
         info!("开始初始数据加载...");
         
         let now = Utc::now();
-        // 固定查询过去1小时的数据
-        let one_hour_ago = now - Duration::hours(1);
+        // 默认查询过去1小时的数据，复用缓存时从已存储的最新时间戳继续
+        let start_time = self.initial_load_start(now)?;
         
-        info!("历史数据时间范围: {} 到 {} (过去1小时)", one_hour_ago, now);
-        
-        // 查询过去1小时的历史数据
-        let history_data = self.data_source.load_data_in_range(one_hour_ago, now).await
-            .map_err(|e| anyhow!("加载历史数据失败: {}", e))?;
+        // 查询历史数据（仅快照模式下没有历史表）
+        let history_data = if self.snapshot_only {
+            info!("仅快照模式，跳过历史数据加载");
+            Vec::new()
+        } else {
+            info!("历史数据时间范围: {} 到 {}", start_time, now);
+            self.data_source.load_data_in_range(start_time, now).await
+                .map_err(|e| anyhow!("加载历史数据失败: {}", e))?
+        };
+        metrics::observe(&metrics::BATCH_RECORDS, "initial_load", history_data.len() as f64);
         
         let mut total_loaded = 0;
         let mut latest_timestamp: Option<DateTime<Utc>> = None;
@@ -61,7 +164,7 @@ impl SyncService {
             // 分批处理数据以避免内存溢出
             let max_memory_records = self.config.batch.max_memory_records;
             for chunk in history_data.chunks(max_memory_records) {
-                self.db_manager.convert_and_insert_wide(chunk)
+                self.db_manager.convert_and_insert_wide(chunk, None)
                     .map_err(|e| anyhow!("转换并插入宽表数据失败: {}", e))?;
                 
                 total_loaded += chunk.len();
@@ -74,7 +177,7 @@ impl SyncService {
                 info!("已加载 {} 条记录，累计: {}", chunk.len(), total_loaded);
             }
         } else {
-            info!("过去1小时内无历史数据");
+            info!("时间范围内无历史数据");
         }
         
         // 查询TagDatabase中的当前数据
@@ -88,14 +191,20 @@ impl SyncService {
             // 分批处理TagDatabase数据
             let max_memory_records = self.config.batch.max_memory_records;
             for chunk in tagdb_data.chunks(max_memory_records) {
-                self.db_manager.convert_and_insert_wide(chunk)
-                    .map_err(|e| anyhow!("转换并插入TagDatabase数据失败: {}", e))?;
+                if self.config.timestamps.use_source_time {
+                    // 各标签时间不同，按拼接方式写入：同一时间点只写入该时刻有值的标签，不覆盖已加载的历史数据
+                    self.db_manager.append_latest_tagdb_data(chunk, None)
+                        .map_err(|e| anyhow!("拼接TagDatabase数据失败: {}", e))?;
+                } else {
+                    self.db_manager.convert_and_insert_wide(chunk, None)
+                        .map_err(|e| anyhow!("转换并插入TagDatabase数据失败: {}", e))?;
+                }
                 
                 total_loaded += chunk.len();
                 
                 // 更新最新时间戳
-                if let Some(last_record) = chunk.last() {
-                    latest_timestamp = Some(last_record.timestamp);
+                if let Some(chunk_latest) = chunk.iter().map(|r| r.timestamp).max() {
+                    latest_timestamp = Some(latest_timestamp.map_or(chunk_latest, |t| t.max(chunk_latest)));
                 }
                 
                 info!("已加载 {} 条TagDatabase记录，累计: {}", chunk.len(), total_loaded);
@@ -106,9 +215,9 @@ impl SyncService {
         
         // 更新最后见到的时间戳
         if let Some(timestamp) = latest_timestamp {
-            self.last_seen_timestamp = Some(timestamp);
+            self.set_last_seen_timestamp(timestamp);
         } else {
-            self.last_seen_timestamp = Some(now);
+            self.set_last_seen_timestamp(now);
         }
         
         // 初始化标签变化检测（建立基线）
@@ -117,13 +226,22 @@ impl SyncService {
         let tag_changes = self.data_source.detect_tag_changes(&known_tags).await
             .map_err(|e| anyhow!("初始标签检测失败: {}", e))?;
         
-        // 处理初始标签变化（主要是新增标签）
-        if !tag_changes.added_tags.is_empty() {
-            info!("初始化时发现新标签: {:?}", tag_changes.added_tags);
-            self.db_manager.handle_tag_changes(&tag_changes)
+        // 处理初始标签变化（主要是新增标签，从检查点恢复时也可能有删除的标签）
+        if !tag_changes.added_tags.is_empty() || !tag_changes.removed_tags.is_empty() {
+            info!("初始化时发现标签变化: 新增 {:?}, 删除 {:?}", tag_changes.added_tags, tag_changes.removed_tags);
+            self.db_manager.handle_tag_changes(&tag_changes, None)
                 .map_err(|e| anyhow!("处理初始标签变化失败: {}", e))?;
         }
         
+        // 按缓存中的标签生成 OPC UA 地址空间
+        if let Some(opcua) = &self.opcua {
+            opcua.seed(&self.db_manager);
+        }
+        
+        // 记录同步检查点
+        self.db_manager.save_checkpoint(now, None)
+            .map_err(|e| anyhow!("保存同步检查点失败: {}", e))?;
+        
         // 清理超过3天的旧数据
         info!("开始清理超过3天的旧数据...");
         self.cleanup_old_data().await
@@ -142,8 +260,43 @@ impl SyncService {
         Ok(())
     }
     
+    /// 计算初始加载的起始时间
+    ///
+    /// 启用 `persist_cache` 时优先从同步检查点继续（并恢复标签基线），其次从缓存中的
+    /// 最新时间戳继续，均不早于数据窗口；否则查询过去1小时。
+    fn initial_load_start(&self, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        let one_hour_ago = now - Duration::hours(1);
+        
+        if !self.config.persist_cache {
+            return Ok(one_hour_ago);
+        }
+        
+        let window_start = now - Duration::seconds(self.config.data_window_duration_secs());
+        
+        let checkpoint = self.db_manager.load_checkpoint()
+            .map_err(|e| anyhow!("读取同步检查点失败: {}", e))?;
+        if let Some(checkpoint) = checkpoint {
+            self.db_manager.restore_known_tags(&checkpoint.known_tags);
+            let start_time = checkpoint.last_synced.max(window_start).min(now);
+            info!("从同步检查点继续加载: {}，恢复已知标签 {} 个", start_time, checkpoint.known_tags.len());
+            return Ok(start_time);
+        }
+        
+        let latest = self.db_manager.get_latest_timestamp()
+            .map_err(|e| anyhow!("获取最新时间戳失败: {}", e))?;
+        
+        match latest {
+            Some(latest) => {
+                let start_time = latest.max(window_start).min(now);
+                info!("从缓存中的最新时间戳继续加载: {}", start_time);
+                Ok(start_time)
+            }
+            None => Ok(one_hour_ago),
+        }
+    }
+    
     /// 启动周期性更新任务
-    pub async fn start_periodic_update(&mut self) -> Result<()> {
+    pub async fn start_periodic_update(&self) -> Result<()> {
         debug!("启动周期性更新任务，更新间隔: {} 秒", self.config.update_interval_secs);
         
         let mut interval_timer = interval(TokioDuration::from_secs(self.config.update_interval_secs));
@@ -151,18 +304,275 @@ impl SyncService {
         // 跳过第一个立即触发的tick
         interval_timer.tick().await;
         
+        let breaker = &self.config.circuit_breaker;
+        let mut consecutive_failures = 0u32;
+        let mut slowed_ticks = 0u32;
+        
+        loop {
+            if self.circuit_open.load(Ordering::Relaxed) {
+                // 熔断期间按探测间隔执行，不再按常规周期访问数据源
+                tokio::select! {
+                    _ = tokio::time::sleep(TokioDuration::from_secs(breaker.probe_interval_secs)) => {}
+                    _ = self.sync_trigger.notified() => info!("收到立即同步请求，提前执行熔断探测"),
+                }
+            } else {
+                let triggered = tokio::select! {
+                    _ = interval_timer.tick() => false,
+                    _ = self.sync_trigger.notified() => {
+                        info!("收到立即同步请求，提前执行更新周期");
+                        // 从本次执行起重新计时，避免紧接着再执行一次
+                        interval_timer.reset();
+                        true
+                    }
+                };
+                
+                // 资源压力降级时每 sync_slowdown_factor 个周期执行一次，立即同步请求不受影响
+                let degradation = crate::degradation::get();
+                if !triggered && degradation.shedding(ShedStage::SyncFrequency) {
+                    slowed_ticks += 1;
+                    if slowed_ticks < degradation.sync_slowdown_factor() {
+                        continue;
+                    }
+                }
+                slowed_ticks = 0;
+            }
+            
+            let result = self.update_cycle().await;
+            let cycle_error = result.as_ref().err().map(|e| e.to_string());
+            match result {
+                Ok(()) => {
+                    if self.circuit_open.swap(false, Ordering::Relaxed) {
+                        info!("数据源已恢复，熔断关闭（此前连续失败 {} 个周期）", consecutive_failures);
+                        self.alerts.circuit(false, consecutive_failures, None).await;
+                        // 熔断期间错过的周期不补发
+                        interval_timer.reset();
+                    }
+                    consecutive_failures = 0;
+                    self.write_heartbeat().await;
+                }
+                Err(e) => {
+                    consecutive_failures += 1;
+                    if self.circuit_open.load(Ordering::Relaxed) {
+                        debug!("熔断探测失败（连续失败 {} 个周期）: {}", consecutive_failures, e);
+                    } else if breaker.enabled && consecutive_failures >= breaker.failure_threshold.max(1) {
+                        self.circuit_open.store(true, Ordering::Relaxed);
+                        error!("连续 {} 个更新周期失败，数据源熔断，暂停同步并每 {} 秒探测一次: {}",
+                               consecutive_failures, breaker.probe_interval_secs, e);
+                        self.alerts.circuit(true, consecutive_failures, Some(&e.to_string())).await;
+                    } else {
+                        error!("更新周期执行失败: {}", e);
+                        // 继续下一个周期，不退出服务
+                    }
+                }
+            }
+            
+            let latest = self.db_manager.get_latest_timestamp().ok().flatten();
+            self.alerts.after_cycle(consecutive_failures, cycle_error.as_deref(), latest).await;
+        }
+    }
+    
+    /// 更新周期成功后向数据源回写心跳（未启用时不做任何事），失败只记录日志
+    async fn write_heartbeat(&self) {
+        if !self.config.heartbeat.enabled {
+            return;
+        }
+        
+        let value = match self.config.heartbeat.mode {
+            HeartbeatMode::Counter => (self.heartbeats.fetch_add(1, Ordering::Relaxed) + 1) as f64,
+            HeartbeatMode::Timestamp => Utc::now().timestamp() as f64,
+        };
+        match self.data_source.write_heartbeat(value).await {
+            Ok(()) => debug!("已回写心跳 {} = {}", self.config.heartbeat.tag_name, value),
+            Err(e) => warn!("回写心跳失败: {}", e),
+        }
+    }
+    
+    /// 启动快速组轮询任务：按 `polling.fast_interval_secs` 只查询并写入快速组标签
+    pub async fn start_fast_polling(&self) -> Result<()> {
+        let polling = &self.config.polling;
+        info!("启动快速组轮询，{} 个标签，间隔 {} 秒", polling.fast_tags.len(), polling.fast_interval_secs);
+        
+        let mut interval_timer = interval(TokioDuration::from_secs(polling.fast_interval_secs.max(1)));
+        // 上一轮未完成时不补发错过的轮询
+        interval_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        
         loop {
             interval_timer.tick().await;
             
-            if let Err(e) = self.update_cycle().await {
-                error!("更新周期执行失败: {}", e);
-                // 继续下一个周期，不退出服务
+            // 数据源熔断期间暂停快速轮询
+            if self.circuit_open.load(Ordering::Relaxed) {
+                continue;
+            }
+            
+            if let Err(e) = self.fast_poll_cycle().await {
+                error!("快速组轮询失败: {}", e);
+                self.record_error("fast_poll", &e);
+            }
+        }
+    }
+    
+    /// 执行一次快速组轮询
+    async fn fast_poll_cycle(&self) -> Result<()> {
+        let records = self.data_source.get_specific_tags_data(&self.config.polling.fast_tags).await
+            .map_err(|e| anyhow!("获取快速组标签数据失败: {}", e))?;
+        if records.is_empty() {
+            return Ok(());
+        }
+        
+        let _write = self.write_lock.lock().await;
+        let cycle = self.db_manager.begin_cycle()
+            .map_err(|e| anyhow!("开始快速组写入事务失败: {}", e))?;
+        
+        if let Err(e) = self.db_manager.append_latest_tagdb_data(&records, Some(&cycle)) {
+            if let Err(rollback_err) = self.db_manager.rollback_cycle(cycle) {
+                error!("回滚快速组写入事务失败: {}", rollback_err);
             }
+            return Err(anyhow!("写入快速组标签数据失败: {}", e));
         }
+        
+        self.db_manager.commit_cycle(cycle)
+            .map_err(|e| anyhow!("提交快速组写入事务失败: {}", e))?;
+        self.publish(&records);
+        
+        debug!("快速组轮询写入 {} 个标签", records.len());
+        Ok(())
     }
     
-    /// 执行一次更新周期
-    async fn update_cycle(&mut self) -> Result<()> {
+    /// 启动低延迟模式轮询：按 `low_latency.interval_ms` 读取全部标签的最新值并暂存，
+    /// 每 `commit_every_cycles` 次轮询在一个事务中批量写入
+    pub async fn start_low_latency_sync(&self) -> Result<()> {
+        let low_latency = &self.config.low_latency;
+        let period = TokioDuration::from_millis(low_latency.interval_ms);
+        info!("启动低延迟模式，轮询间隔 {} 毫秒，每 {} 次轮询提交一次", low_latency.interval_ms, low_latency.commit_every_cycles);
+        
+        let mut interval_timer = interval(period);
+        // 上一轮未完成时不补发错过的轮询
+        interval_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        
+        let mut staged: Vec<StagedPoll> = Vec::new();
+        let mut cycles = 0u32;
+        let mut consecutive_failures = 0u32;
+        let mut last_report = std::time::Instant::now();
+        
+        loop {
+            interval_timer.tick().await;
+            
+            // 数据源熔断期间暂停轮询
+            if self.circuit_open.load(Ordering::Relaxed) {
+                continue;
+            }
+            
+            let started = std::time::Instant::now();
+            match self.data_source.poll_latest_tagdb_data().await {
+                Ok(records) => {
+                    if consecutive_failures > 0 {
+                        info!("低延迟轮询已恢复（此前连续失败 {} 次）", consecutive_failures);
+                        consecutive_failures = 0;
+                    }
+                    let elapsed = started.elapsed();
+                    self.db_manager.latency().record_poll(elapsed, elapsed > period);
+                    if !records.is_empty() {
+                        staged.push(StagedPoll { started, records });
+                    }
+                }
+                Err(e) => {
+                    consecutive_failures += 1;
+                    // 亚秒级间隔下每次失败都记录会刷屏，只在首次失败时告警
+                    if consecutive_failures == 1 {
+                        warn!("低延迟轮询失败，下次轮询时重新连接: {}", e);
+                        self.record_error("low_latency_poll", &e);
+                    } else {
+                        debug!("低延迟轮询失败（连续 {} 次）: {}", consecutive_failures, e);
+                    }
+                }
+            }
+            
+            cycles += 1;
+            if cycles >= low_latency.commit_every_cycles {
+                cycles = 0;
+                if let Err(e) = self.commit_staged(&mut staged).await {
+                    error!("提交低延迟暂存数据失败: {}", e);
+                    self.record_error("low_latency_commit", &e);
+                }
+            }
+            
+            if low_latency.report_interval_secs > 0
+                && last_report.elapsed() >= TokioDuration::from_secs(low_latency.report_interval_secs) {
+                info!("低延迟模式统计: {}", self.db_manager.latency().report());
+                last_report = std::time::Instant::now();
+            }
+        }
+    }
+    
+    /// 在一个事务中写入暂存的轮询结果并记录端到端延迟；写入失败时丢弃暂存数据（下次轮询读取的仍是最新值）
+    async fn commit_staged(&self, staged: &mut Vec<StagedPoll>) -> Result<()> {
+        if staged.is_empty() {
+            return Ok(());
+        }
+        let polls = std::mem::take(staged);
+        
+        let _write = self.write_lock.lock().await;
+        let cycle = self.db_manager.begin_cycle()
+            .map_err(|e| anyhow!("开始低延迟写入事务失败: {}", e))?;
+        
+        let mut written = 0;
+        for poll in &polls {
+            match self.db_manager.append_latest_tagdb_data(&poll.records, Some(&cycle)) {
+                Ok(count) => written += count,
+                Err(e) => {
+                    if let Err(rollback_err) = self.db_manager.rollback_cycle(cycle) {
+                        error!("回滚低延迟写入事务失败: {}", rollback_err);
+                    }
+                    return Err(anyhow!("写入暂存数据失败，丢弃 {} 次轮询的数据: {}", polls.len(), e));
+                }
+            }
+        }
+        
+        self.db_manager.commit_cycle(cycle)
+            .map_err(|e| anyhow!("提交低延迟写入事务失败: {}", e))?;
+        for poll in &polls {
+            self.publish(&poll.records);
+        }
+        
+        self.db_manager.latency().record_commit(polls.iter().map(|poll| poll.started));
+        self.set_last_seen_timestamp(Utc::now());
+        debug!("低延迟模式提交 {} 次轮询，写入 {} 条记录", polls.len(), written);
+        Ok(())
+    }
+    
+    /// 执行一次更新周期，并将周期统计记录到 sync_log 表
+    #[tracing::instrument(skip_all, fields(rows_fetched, rows_written, new_columns))]
+    async fn update_cycle(&self) -> Result<()> {
+        let mut stats = SyncCycleStats {
+            started_at: Utc::now(),
+            finished_at: Utc::now(),
+            rows_fetched: 0,
+            rows_written: 0,
+            new_columns: 0,
+            error: None,
+        };
+        
+        let result = {
+            let _timer = metrics::timer(&metrics::SYNC_CYCLE_SECONDS, "update");
+            self.run_update_cycle(&mut stats).await
+        };
+        
+        stats.finished_at = Utc::now();
+        stats.error = result.as_ref().err().map(|e| e.to_string());
+        tracing::Span::current()
+            .record("rows_fetched", stats.rows_fetched)
+            .record("rows_written", stats.rows_written)
+            .record("new_columns", stats.new_columns);
+        self.errors.lock().unwrap().record(stats.finished_at, stats.error.as_deref());
+        if let Err(e) = self.db_manager.record_sync_cycle(&stats) {
+            warn!("记录同步周期统计失败: {}", e);
+        }
+        
+        result
+    }
+    
+    /// 更新周期的各个步骤，执行过程中累计统计
+    async fn run_update_cycle(&self, stats: &mut SyncCycleStats) -> Result<()> {
         debug!("开始执行更新周期");
         
         // 1. 检测标签变化（加点/少点）
@@ -177,17 +587,206 @@ impl SyncService {
               tag_changes.removed_tags.len(), 
               tag_changes.current_tags.len());
         
-        // 2. 处理标签变化
+        // 读取标签元数据失败不影响数据同步，下个周期重试；与上次写入相同时不重写
+        let tag_meta = match self.data_source.get_tag_metadata().await {
+            Ok(meta) => Some(meta).filter(|meta| *self.tag_meta.lock().unwrap() != *meta),
+            Err(e) => {
+                warn!("读取标签元数据失败: {}", e);
+                None
+            }
+        };
+        
+        // 2. 检测数据缺口并从历史表回填（仅快照模式下没有历史表可回填）
+        if self.config.backfill.enabled && !self.snapshot_only {
+            let backfilled = self.backfill_gap().await?;
+            if backfilled > 0 {
+                self.db_manager.reset_sparklines();
+            }
+            stats.rows_fetched += backfilled;
+            stats.rows_written += backfilled;
+        }
+        
+        // 3. 获取TagDatabase的最新数据（在事务外完成网络读取，缩短事务时间）；低延迟模式下由低延迟轮询写入
+        let latest_data = if self.config.low_latency.enabled {
+            Vec::new()
+        } else {
+            self.fetch_incremental_data().await?
+        };
+        stats.rows_fetched += latest_data.len();
+        if let Some(capture) = &self.capture {
+            capture.record(&tag_changes.current_tags, &latest_data);
+        }
+        metrics::observe(&metrics::BATCH_RECORDS, "latest", latest_data.len() as f64);
+        
+        // 4. 在单个事务中处理标签变化并写入最新数据，崩溃时不会留下写了一半的时间点
+        {
+            let _write = self.write_lock.lock().await;
+            let cycle = self.db_manager.begin_cycle()
+                .map_err(|e| anyhow!("开始同步周期事务失败: {}", e))?;
+            
+            let written = match self.apply_cycle_writes(&cycle, &tag_changes, &latest_data, tag_meta.as_deref()) {
+                Ok(written) => written,
+                Err(e) => {
+                    if let Err(rollback_err) = self.db_manager.rollback_cycle(cycle) {
+                        error!("回滚同步周期事务失败: {}", rollback_err);
+                    }
+                    return Err(e);
+                }
+            };
+            
+            self.db_manager.commit_cycle(cycle)
+                .map_err(|e| anyhow!("提交同步周期事务失败: {}", e))?;
+            self.publish(&latest_data);
+            if let Some(meta) = tag_meta {
+                *self.tag_meta.lock().unwrap() = meta;
+            }
+            
+            stats.rows_written += written;
+            stats.new_columns += tag_changes.added_tags.len();
+        }
+        
+        if !latest_data.is_empty() {
+            // 更新最后见到的时间戳为当前时间
+            self.set_last_seen_timestamp(Utc::now());
+            
+            info!("更新成功: {} 条记录", latest_data.len());
+        } else {
+            debug!("TagDatabase表中没有数据");
+        }
+        
+        // 5. 增量刷新缩略趋势：周期已提交，失败只记录日志
+        if !tag_changes.removed_tags.is_empty() {
+            self.db_manager.reset_sparklines();
+        }
+        if self.db_manager.toggles().sparklines() && !crate::degradation::get().shedding(ShedStage::Rollups) {
+            let since = latest_data.iter().map(|record| record.timestamp).min().unwrap_or_else(Utc::now);
+            match self.db_manager.refresh_sparklines(self.config.sparkline.window_hours, self.config.sparkline.points, since) {
+                Ok(count) => debug!("已刷新 {} 个标签的缩略趋势", count),
+                Err(e) => warn!("刷新缩略趋势失败: {}", e),
+            }
+        }
+        
+        // 6. SPC 判异：失败只记录日志，不影响数据同步
+        let spc_events = {
+            let mut spc_monitor = self.spc_monitor.lock().unwrap();
+            match spc_monitor.as_mut().map(|monitor| monitor.evaluate(&self.db_manager)) {
+                Some(Ok(events)) => events,
+                Some(Err(e)) => {
+                    warn!("SPC 判异失败: {}", e);
+                    Vec::new()
+                }
+                None => Vec::new(),
+            }
+        };
+        if !spc_events.is_empty() {
+            warn!("本周期产生 {} 条 SPC 违规事件", spc_events.len());
+            self.alerts.spc_violations(&spc_events).await;
+        }
+        
+        // 7. 趋势预测：单个标签失败只记录日志，不影响数据同步
+        {
+            let mut forecaster = self.forecaster.lock().unwrap();
+            if let Some(forecaster) = forecaster.as_mut() {
+                let count = forecaster.evaluate(&self.db_manager);
+                debug!("已更新 {} 个标签的趋势预测", count);
+            }
+        }
+        
+        // 8. 清理数据保留窗口以前的数据以维持数据库大小
+        self.cleanup_old_data().await
+            .map_err(|e| anyhow!("清理旧数据失败: {}", e))?;
+        
+        debug!("更新周期完成");
+        Ok(())
+    }
+    
+    /// 检测最后一次成功同步与当前时间之间的缺口，按段从历史表回填，返回回填的记录数
+    ///
+    /// 以同步检查点（没有检查点时为缓存中的最新时间戳）判断缺口，死区或数据源时间戳模式下
+    /// 标签长时间没有新数据不会被误判为缺口。每段在独立事务中写入并更新检查点，回填中断后下次从已完成的位置继续。
+    #[tracing::instrument(skip_all)]
+    async fn backfill_gap(&self) -> Result<usize> {
+        let checkpoint = self.db_manager.load_checkpoint()
+            .map_err(|e| anyhow!("读取同步检查点失败: {}", e))?;
+        let latest = match checkpoint {
+            Some(checkpoint) => Some(checkpoint.last_synced),
+            None => self.db_manager.get_latest_timestamp()
+                .map_err(|e| anyhow!("获取最新时间戳失败: {}", e))?,
+        };
+        let Some(latest) = latest else {
+            return Ok(0);
+        };
+        
+        let now = Utc::now();
+        let threshold_secs = self.config.backfill.threshold_secs(self.config.update_interval_secs);
+        if now - latest <= Duration::seconds(threshold_secs as i64) {
+            return Ok(0);
+        }
+        
+        // 不回填数据窗口之外的数据
+        let window_start = now - Duration::seconds(self.config.data_window_duration_secs());
+        let mut chunk_start = latest.max(window_start);
+        let chunk = Duration::hours(i64::from(self.config.backfill.chunk_hours.max(1)));
+        
+        warn!("检测到数据缺口: {} 到 {}，开始从历史表回填", chunk_start, now);
+        
+        let mut total = 0;
+        while chunk_start < now {
+            let chunk_end = (chunk_start + chunk).min(now);
+            let _timer = metrics::timer(&metrics::SYNC_CYCLE_SECONDS, "backfill");
+            let records = self.data_source.load_data_in_range(chunk_start, chunk_end).await
+                .map_err(|e| anyhow!("回填 {} 到 {} 的历史数据失败: {}", chunk_start, chunk_end, e))?;
+            metrics::observe(&metrics::BATCH_RECORDS, "backfill", records.len() as f64);
+            
+            {
+                let _write = self.write_lock.lock().await;
+                let cycle = self.db_manager.begin_cycle()
+                    .map_err(|e| anyhow!("开始回填事务失败: {}", e))?;
+                let result = records.chunks(self.config.batch.max_memory_records.max(1))
+                    .try_for_each(|batch| self.db_manager.convert_and_insert_wide(batch, Some(&cycle)))
+                    .and_then(|_| self.db_manager.save_checkpoint(chunk_end, Some(&cycle)));
+                if let Err(e) = result {
+                    if let Err(rollback_err) = self.db_manager.rollback_cycle(cycle) {
+                        error!("回滚回填事务失败: {}", rollback_err);
+                    }
+                    return Err(anyhow!("写入回填数据失败: {}", e));
+                }
+                self.db_manager.commit_cycle(cycle)
+                    .map_err(|e| anyhow!("提交回填事务失败: {}", e))?;
+            }
+            self.publish(&records);
+            
+            total += records.len();
+            debug!("已回填 {} 到 {}: {} 条记录", chunk_start, chunk_end, records.len());
+            chunk_start = chunk_end;
+        }
+        
+        info!("数据缺口回填完成，共 {} 条记录", total);
+        Ok(total)
+    }
+    
+    /// 同步周期内的写操作：处理标签变化、拼接最新数据并更新变化了的标签元数据，均写入 `cycle` 的事务，返回写入的记录数
+    #[tracing::instrument(skip_all)]
+    fn apply_cycle_writes(
+        &self,
+        cycle: &Cycle,
+        tag_changes: &crate::data_source::TagChanges,
+        latest_data: &[crate::database::TimeSeriesRecord],
+        tag_meta: Option<&[TagMeta]>,
+    ) -> Result<usize> {
         if !tag_changes.added_tags.is_empty() || !tag_changes.removed_tags.is_empty() {
             info!("处理标签变化: 新增标签 {:?}, 删除标签 {:?}", 
                   tag_changes.added_tags, tag_changes.removed_tags);
             
-            self.db_manager.handle_tag_changes(&tag_changes)
+            self.db_manager.handle_tag_changes(tag_changes, Some(cycle))
                 .map_err(|e| anyhow!("处理标签变化失败: {}", e))?;
             
             // 如果有删除的标签，可选择清理其数据
             if !tag_changes.removed_tags.is_empty() {
-                let cleaned_count = self.db_manager.cleanup_removed_tag_data(&tag_changes.removed_tags)
+                if let Some(opcua) = &self.opcua {
+                    opcua.remove_tags(&tag_changes.removed_tags);
+                }
+                let cleaned_count = self.db_manager.cleanup_removed_tag_data(&tag_changes.removed_tags, Some(cycle))
                     .map_err(|e| anyhow!("清理已删除标签数据失败: {}", e))?;
                 if cleaned_count > 0 {
                     info!("已清理 {} 条已删除标签的数据记录", cleaned_count);
@@ -195,37 +794,37 @@ impl SyncService {
             }
         }
         
-        // 3. 获取TagDatabase的最新数据并拼接到宽表
-        let latest_data = self.fetch_incremental_data().await?;
+        let written = self.db_manager.append_latest_tagdb_data(latest_data, Some(cycle))
+            .map_err(|e| anyhow!("拼接最新TagDB数据失败: {}", e))?;
         
-        if !latest_data.is_empty() {
-            self.db_manager.append_latest_tagdb_data(&latest_data)
-                .map_err(|e| anyhow!("拼接最新TagDB数据失败: {}", e))?;
-            
-            // 更新最后见到的时间戳为当前时间
-            self.last_seen_timestamp = Some(Utc::now());
-            
-            info!("更新成功: {} 条记录", latest_data.len());
-        } else {
-            debug!("TagDatabase表中没有数据");
+        if let Some(meta) = tag_meta {
+            self.db_manager.replace_tag_meta(meta, Some(cycle))
+                .map_err(|e| anyhow!("更新标签元数据失败: {}", e))?;
         }
         
-        // 4. 清理3天前的数据以维持数据库大小
-        self.cleanup_old_data().await
-            .map_err(|e| anyhow!("清理旧数据失败: {}", e))?;
+        // 检查点与数据在同一事务中提交
+        self.db_manager.save_checkpoint(Utc::now(), Some(cycle))
+            .map_err(|e| anyhow!("保存同步检查点失败: {}", e))?;
         
-        debug!("更新周期完成");
-        Ok(())
+        Ok(written)
     }
     
-    /// 从TagDatabase获取最新数据
-    async fn fetch_incremental_data(&mut self) -> Result<Vec<crate::database::TimeSeriesRecord>> {
+    /// 从TagDatabase获取最新数据（快速组标签由快速轮询单独写入，此处排除）
+    async fn fetch_incremental_data(&self) -> Result<Vec<crate::database::TimeSeriesRecord>> {
         debug!("开始获取TagDatabase最新数据...");
         
         // 获取TagDatabase的最新数据
-        let latest_data = self.data_source.get_latest_tagdb_data().await
+        let mut latest_data = self.data_source.get_latest_tagdb_data().await
             .map_err(|e| anyhow!("获取TagDatabase数据失败: {}", e))?;
         
+        // 快速组配置的是原始标签名，数据已规范化，按规范标签名比较
+        let fast_tags: std::collections::HashSet<String> = self.config.polling.fast_tags.iter()
+            .map(|tag| self.data_source.canonical_tag_name(tag))
+            .collect();
+        if !fast_tags.is_empty() {
+            latest_data.retain(|record| !fast_tags.contains(&record.tag_name));
+        }
+        
         if !latest_data.is_empty() {
             info!("从TagDatabase获取到 {} 条最新数据", latest_data.len());
             debug!("TagDatabase数据更新完成");
@@ -236,12 +835,24 @@ impl SyncService {
         Ok(latest_data)
     }
     
-    /// 清理3天前的数据以维持数据库大小
+    /// 清理数据保留窗口（data_window_days）以前的数据以维持数据库大小
+    ///
+    /// 启用分级保留时先将待删除的数据汇总到 1 分钟与 1 小时汇总表（截止时间对齐到整点），
+    /// 启用归档时先将待删除的数据写出为 Parquet 文件，汇总或归档失败时不删除；配置上传时随后上传归档文件。
+    /// 随后检查 DuckDB 已用空间上限（`max_db_size_mb`）。
     pub async fn cleanup_old_data(&self) -> Result<()> {
-        info!("开始清理3天前的数据...");
+        if self.archiver.due() {
+            self.cleanup_expired_data().await?;
+        }
+        self.trim_to_size_cap().await
+    }
+    
+    /// 清理保留窗口以前的数据（汇总、归档后删除）
+    async fn cleanup_expired_data(&self) -> Result<()> {
+        info!("开始清理{}天前的数据...", self.config.data_window_days);
         
-        let deleted_count = self.db_manager.delete_data_older_than_days(3)
-            .map_err(|e| anyhow!("删除旧数据失败: {}", e))?;
+        let cutoff_time = self.rollups.align_cutoff(Utc::now() - Duration::seconds(self.config.data_window_duration_secs()));
+        let deleted_count = self.expire_before(cutoff_time)?;
         
         if deleted_count > 0 {
             let total_records = self.db_manager.get_record_count()
@@ -251,10 +862,114 @@ impl SyncService {
             debug!("没有需要清理的旧数据");
         }
         
+        self.upload_archives().await;
+        Ok(())
+    }
+    
+    /// 汇总、归档截止时间以前的数据后将其删除，返回删除的行数；汇总或归档失败时不删除
+    fn expire_before(&self, cutoff_time: DateTime<Utc>) -> Result<usize> {
+        self.rollups.roll_up(&self.db_manager, cutoff_time)?;
+        self.archiver.archive(&self.db_manager, cutoff_time)?;
+        self.db_manager.delete_data_before_time(cutoff_time)
+            .map_err(|e| anyhow!("删除旧数据失败: {}", e))
+    }
+    
+    /// 上传待上传的归档文件
+    async fn upload_archives(&self) {
+        // 上传失败不影响清理，文件保留在本地并在下次归档时重试
+        if let Err(e) = self.archiver.upload().await {
+            warn!("上传归档文件失败: {}", e);
+        }
+    }
+    
+    /// DuckDB 已用空间超过大小上限时从最旧的数据开始逐步汇总、归档并删除，每步后执行 CHECKPOINT，
+    /// 直到低于上限或没有可删除的数据
+    ///
+    /// 上限按已用块计算：删除释放的块在 CHECKPOINT 后可复用，文件本身通常不会缩小。每步删除当前数据时间跨度的 1/20；
+    /// 保留期豁免命中的数据不删除。删除后已用空间未减少时停止并把状态记入数据库，此后的周期不再删除，
+    /// 直到已用空间回到上限以下（例如保留窗口清理之后）或上限变更。
+    async fn trim_to_size_cap(&self) -> Result<()> {
+        let Some(max_mb) = self.config.max_db_size_mb else {
+            return Ok(());
+        };
+        let cap = max_mb * 1024 * 1024;
+        let mut used = self.db_manager.used_size()
+            .map_err(|e| anyhow!("获取数据库已用空间失败: {}", e))?;
+        let stall = self.db_manager.size_cap_stall()
+            .map_err(|e| anyhow!("读取大小上限清理状态失败: {}", e))?;
+        if used <= cap {
+            if stall.is_some() {
+                self.db_manager.set_size_cap_stall(None)
+                    .map_err(|e| anyhow!("清除大小上限清理状态失败: {}", e))?;
+            }
+            return Ok(());
+        }
+        if let Some((stalled_cap, stalled_used)) = stall
+            && stalled_cap == cap
+        {
+            debug!(
+                "数据库已用空间 {:.2} MB 超过上限 {} MB，上次删除后已用空间未减少（{:.2} MB），不再删除",
+                used as f64 / 1024.0 / 1024.0, max_mb, stalled_used as f64 / 1024.0 / 1024.0
+            );
+            return Ok(());
+        }
+        warn!("数据库已用空间 {:.2} MB 超过上限 {} MB，开始删除最旧的数据", used as f64 / 1024.0 / 1024.0, max_mb);
+        
+        let mut total_deleted = 0;
+        let mut trimmed_to = None;
+        while used > cap {
+            let earliest = self.db_manager.get_earliest_timestamp()
+                .map_err(|e| anyhow!("获取最早时间戳失败: {}", e))?;
+            let latest = self.db_manager.get_latest_timestamp()
+                .map_err(|e| anyhow!("获取最新时间戳失败: {}", e))?;
+            let (Some(earliest), Some(latest)) = (earliest, latest) else {
+                break;
+            };
+            
+            let step = ((latest - earliest) / 20).max(Duration::minutes(1));
+            // 与保留窗口清理一样对齐截止时间，对齐后不晚于最早数据时前进到下一个整点
+            let mut cutoff_time = self.rollups.align_cutoff(earliest + step);
+            if cutoff_time <= earliest {
+                cutoff_time = self.rollups.align_cutoff(earliest) + Duration::hours(1);
+            }
+            let deleted = self.expire_before(cutoff_time)?;
+            self.db_manager.checkpoint()
+                .map_err(|e| anyhow!("回收数据库空间失败: {}", e))?;
+            let after = self.db_manager.used_size()
+                .map_err(|e| anyhow!("获取数据库已用空间失败: {}", e))?;
+            
+            total_deleted += deleted;
+            trimmed_to = Some(cutoff_time);
+            let shrunk = after < used;
+            used = after;
+            if deleted == 0 || !shrunk {
+                break;
+            }
+        }
+        if trimmed_to.is_some() {
+            self.upload_archives().await;
+        }
+        
+        let used_mb = used as f64 / 1024.0 / 1024.0;
+        match trimmed_to {
+            Some(cutoff_time) if used <= cap => info!(
+                "已删除 {} 以前的 {} 条数据，数据库已用空间 {:.2} MB", cutoff_time, total_deleted, used_mb
+            ),
+            _ => {
+                warn!(
+                    "删除 {} 条数据后数据库已用空间仍为 {:.2} MB，超过上限 {} MB（剩余数据受保留期豁免或空间未能回收），\
+                     在已用空间回到上限以下或上限变更前不再删除",
+                    total_deleted, used_mb, max_mb
+                );
+                self.db_manager.set_size_cap_stall(Some((cap, used)))
+                    .map_err(|e| anyhow!("记录大小上限清理状态失败: {}", e))?;
+            }
+        }
         Ok(())
     }
     
     /// 删除给定时间以前的数据
+    #[allow(dead_code)]
     pub async fn delete_data_before_time(&self, cutoff_time: DateTime<Utc>) -> Result<()> {
         info!("开始删除{}以前的数据...", cutoff_time);
         
@@ -288,31 +1003,124 @@ impl SyncService {
         })
     }
     
+    /// 将周期之外的同步错误（快速组轮询、低延迟模式）记入最近错误
+    fn record_error(&self, phase: &'static str, error: &anyhow::Error) {
+        self.errors.lock().unwrap().push(phase, Utc::now(), error.to_string());
+    }
+    
+    /// 记录最后一次成功同步的时间
+    fn set_last_seen_timestamp(&self, timestamp: DateTime<Utc>) {
+        *self.last_seen_timestamp.lock().unwrap() = Some(timestamp);
+    }
+    
     /// 获取服务状态信息
-    pub async fn get_status(&self) -> Result<ServiceStatus> {
+    pub fn get_status(&self) -> Result<ServiceStatus> {
         let total_records = self.db_manager.get_record_count()
             .map_err(|e| anyhow!("获取记录总数失败: {}", e))?;
         let latest_timestamp = self.db_manager.get_latest_timestamp()
             .map_err(|e| anyhow!("获取最新时间戳失败: {}", e))?;
         
+        let last_cycle = self.db_manager.recent_sync_cycles(1)
+            .map_err(|e| anyhow!("获取同步周期统计失败: {}", e))?
+            .pop();
+        // 内存模式下没有 DuckDB 文件
+        let db_file_size = self.db_manager.file_size().ok();
+        
         Ok(ServiceStatus {
             total_records,
             latest_timestamp,
-            last_seen_timestamp: self.last_seen_timestamp,
+            last_seen_timestamp: *self.last_seen_timestamp.lock().unwrap(),
             data_window_days: self.config.data_window_days,
             update_interval_secs: self.config.update_interval_secs,
+            circuit_open: self.circuit_open.load(Ordering::Relaxed),
+            snapshot_only: self.snapshot_only,
+            last_cycle,
+            stale_tags: self.db_manager.staleness().stale_tags(),
+            latency: self.config.low_latency.enabled.then(|| self.db_manager.latency().report()),
+            uptime_secs: self.started.elapsed().as_secs(),
+            db_file_size,
+            tag_count: self.db_manager.get_known_tags().len(),
+            errors: self.errors.lock().unwrap().clone(),
+            source_server: self.data_source.active_server(),
         })
     }
 }
 
 /// 服务状态信息
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct ServiceStatus {
     pub total_records: i64,
     pub latest_timestamp: Option<DateTime<Utc>>,
     pub last_seen_timestamp: Option<DateTime<Utc>>,
     pub data_window_days: u32,
     pub update_interval_secs: u64,
+    /// 数据源熔断是否开启
+    pub circuit_open: bool,
+    /// 是否以仅快照模式运行（数据源没有历史表）
+    pub snapshot_only: bool,
+    /// 最近一次更新周期的统计
+    pub last_cycle: Option<SyncCycleStats>,
+    /// 停滞标签
+    pub stale_tags: Vec<StaleTag>,
+    /// 低延迟模式的延迟统计（未启用时为 None）
+    pub latency: Option<LatencyReport>,
+    /// 服务运行时长，单位为秒
+    pub uptime_secs: u64,
+    /// DuckDB 文件大小（字节），文件不存在时为 None
+    pub db_file_size: Option<u64>,
+    /// 已知标签数
+    pub tag_count: usize,
+    /// 更新周期的失败统计
+    pub errors: CycleErrors,
+    /// 当前使用的数据源服务器（配置了备用服务器时连接失败会轮换）
+    pub source_server: Option<String>,
+}
+
+/// 同步失败统计（自服务启动起）
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CycleErrors {
+    /// 失败的更新周期总数
+    pub failed_cycles: u64,
+    /// 连续失败的更新周期数，成功后清零
+    pub consecutive_failures: u32,
+    /// 最近一次失败的时间与错误信息
+    pub last_error_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// 最近的同步错误（最多 [`ERROR_HISTORY_LEN`] 条），按时间先后排列
+    pub recent: VecDeque<SyncError>,
+}
+
+/// 一次同步错误
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncError {
+    pub at: DateTime<Utc>,
+    /// 出错的环节：update_cycle、fast_poll、low_latency_poll 或 low_latency_commit
+    pub phase: &'static str,
+    pub message: String,
+}
+
+impl CycleErrors {
+    /// 记录一个更新周期的结果，`error` 为 None 表示成功
+    fn record(&mut self, finished_at: DateTime<Utc>, error: Option<&str>) {
+        match error {
+            Some(error) => {
+                self.failed_cycles += 1;
+                self.consecutive_failures += 1;
+                self.push("update_cycle", finished_at, error.to_string());
+            }
+            None => self.consecutive_failures = 0,
+        }
+    }
+    
+    /// 记入最近错误，超出保留条数时丢弃最早的一条
+    fn push(&mut self, phase: &'static str, at: DateTime<Utc>, message: String) {
+        self.last_error_at = Some(at);
+        self.last_error = Some(message.clone());
+        if self.recent.len() == ERROR_HISTORY_LEN {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(SyncError { at, phase, message });
+    }
 }
 
 impl std::fmt::Display for ServiceStatus {
@@ -323,6 +1131,303 @@ impl std::fmt::Display for ServiceStatus {
         writeln!(f, "最后同步时间: {:?}", self.last_seen_timestamp)?;
         writeln!(f, "数据窗口: {} 天", self.data_window_days)?;
         writeln!(f, "更新间隔: {} 秒", self.update_interval_secs)?;
+        if let Some(server) = &self.source_server {
+            writeln!(f, "数据源服务器: {}", server)?;
+        }
+        writeln!(f, "运行时长: {} 秒", self.uptime_secs)?;
+        writeln!(f, "标签数: {}", self.tag_count)?;
+        if let Some(size) = self.db_file_size {
+            writeln!(f, "DuckDB 文件大小: {:.2} MB", size as f64 / 1024.0 / 1024.0)?;
+        }
+        if self.errors.failed_cycles > 0 {
+            writeln!(f, "失败周期: 共 {} 个，当前连续 {} 个", self.errors.failed_cycles, self.errors.consecutive_failures)?;
+        }
+        if !self.errors.recent.is_empty() {
+            writeln!(f, "最近错误（{} 条）:", self.errors.recent.len())?;
+            for error in self.errors.recent.iter().rev() {
+                writeln!(f, "  {} [{}] {}", error.at, error.phase, error.message)?;
+            }
+        }
+        if self.snapshot_only {
+            writeln!(f, "运行模式: 仅快照（数据源没有历史表，不加载历史数据、不回填缺口）")?;
+        }
+        if self.circuit_open {
+            writeln!(f, "数据源熔断: 已开启")?;
+        }
+        if let Some(cycle) = &self.last_cycle {
+            writeln!(f, "最近周期: {} 耗时 {} 毫秒，获取 {} 条，写入 {} 条，新增列 {} 个{}",
+                     cycle.started_at,
+                     (cycle.finished_at - cycle.started_at).num_milliseconds(),
+                     cycle.rows_fetched,
+                     cycle.rows_written,
+                     cycle.new_columns,
+                     cycle.error.as_deref().map(|e| format!("，错误: {}", e)).unwrap_or_default())?;
+        }
+        for (reason, label) in [(StaleReason::Frozen, "停滞标签"), (StaleReason::SourceTime, "数据源时间过期标签")] {
+            let tags: Vec<&str> = self.stale_tags.iter().filter(|t| t.reason == reason).map(|t| t.tag.as_str()).collect();
+            if !tags.is_empty() {
+                let names = &tags[..tags.len().min(10)];
+                writeln!(f, "{}: {} 个（{}{}）", label, tags.len(), names.join(", "),
+                         if tags.len() > names.len() { " 等" } else { "" })?;
+            }
+        }
+        if let Some(latency) = &self.latency {
+            writeln!(f, "低延迟模式: {}", latency)?;
+        }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    //! 录制会话的回放测试：以 `tests/replay/*.jsonl` 代替数据源，经更新周期逐周期回放，与 golden 文件对比缓存内容；
+    //! 故障测试在回放的同时注入断连、查询延迟、数值损坏与磁盘写满，检查故障周期不留下部分写入且恢复后结果不变
+
+    use super::*;
+    use crate::capture::Replay;
+    use crate::config::StorageMode;
+
+    /// 临时缓存文件上以录制会话为数据源的同步服务，释放时删除文件
+    struct Harness {
+        service: SyncService,
+        db: Arc<DatabaseManager>,
+        path: std::path::PathBuf,
+    }
+
+    impl Harness {
+        fn new(name: &str, storage_mode: StorageMode, replay: Replay) -> Self {
+            Self::with_config(name, AppConfig { storage_mode, ..Default::default() }, replay)
+        }
+
+        /// 以给定配置创建，缓存文件路径由名称决定
+        fn with_config(name: &str, config: AppConfig, replay: Replay) -> Self {
+            let path = std::env::temp_dir().join(format!("rt_db_test_{}_{}.duckdb", name, std::process::id()));
+            let _ = std::fs::remove_file(&path);
+
+            let config = Arc::new(AppConfig {
+                db_file_path: path.to_string_lossy().into_owned(),
+                ..config
+            });
+            let db = Arc::new(DatabaseManager::new(config.clone()));
+            db.initialize().expect("初始化临时缓存失败");
+            let data_source = Arc::new(SqlServerDataSource::new((*config).clone()).with_replay(replay));
+            let service = SyncService::new(config, db.clone(), data_source, Arc::new(Notify::new()), false);
+            Self { service, db, path }
+        }
+
+        fn watermark(&self) -> Option<DateTime<Utc>> {
+            self.db.load_checkpoint().unwrap().map(|checkpoint| checkpoint.last_synced)
+        }
+
+        /// 与存储模式无关的缓存内容：全部非空数据与已知标签，每项一行
+        ///
+        /// 常规周期以写入时间为时间戳，数据按时间点的先后编号，同一时间点内按标签名排序。
+        fn dump(&self) -> String {
+            let mut records = self.db.changes(None, None, None, usize::MAX >> 1).unwrap().records;
+            records.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.tag_name.cmp(&b.tag_name)));
+
+            let mut lines = vec!["# data".to_string()];
+            let mut point = 0;
+            let mut last = None;
+            for record in &records {
+                if last != Some(record.timestamp) {
+                    point += 1;
+                    last = Some(record.timestamp);
+                }
+                lines.push(format!("{} {} {:?}", point, record.tag_name, record.value));
+            }
+
+            let mut tags: Vec<String> = self.db.get_known_tags().into_iter().collect();
+            tags.sort();
+            lines.push("# known_tags".to_string());
+            lines.extend(tags);
+            lines.join("\n") + "\n"
+        }
+    }
+
+    impl Drop for Harness {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+            let _ = std::fs::remove_file(self.path.with_extension("duckdb.wal"));
+        }
+    }
+
+    /// 回放 `tests/replay/<name>.jsonl` 到两种存储模式，缓存内容都应与 `<name>.golden` 一致；
+    /// 设置 `RT_DB_UPDATE_GOLDEN=1` 时以宽表模式的结果重写 golden 文件
+    async fn assert_replay_matches_golden(name: &str) {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replay");
+        let golden_path = dir.join(format!("{}.golden", name));
+
+        for (label, mode) in [("wide", StorageMode::Wide), ("long", StorageMode::Long)] {
+            let replay = Replay::open(&dir.join(format!("{}.jsonl", name))).unwrap();
+            let cycles = replay.cycle_count();
+            let harness = Harness::new(&format!("replay_{}_{}", name, label), mode, replay);
+
+            let mut watermark = None;
+            for index in 1..=cycles {
+                harness.service.update_cycle().await
+                    .unwrap_or_else(|e| panic!("{} 第 {} 个周期回放失败: {}", name, index, e));
+                // 检查点与数据在同一事务中提交，每个周期都前进
+                let checkpoint = harness.watermark();
+                assert!(checkpoint.is_some() && checkpoint > watermark, "{} 第 {} 个周期后水位未前进", name, index);
+                watermark = checkpoint;
+            }
+            assert!(harness.service.update_cycle().await.is_err(), "{} 回放结束后数据源应报错", name);
+            let actual = harness.dump();
+
+            if std::env::var("RT_DB_UPDATE_GOLDEN").is_ok() && mode == StorageMode::Wide {
+                std::fs::write(&golden_path, &actual).unwrap();
+                continue;
+            }
+            let expected = std::fs::read_to_string(&golden_path).unwrap();
+            assert_eq!(actual, expected, "{} 在{}模式下的回放结果与 golden 文件不一致", name, label);
+        }
+    }
+
+    #[tokio::test]
+    async fn replay_steady_session() {
+        assert_replay_matches_golden("steady").await;
+    }
+
+    #[tokio::test]
+    async fn replay_tag_churn_session() {
+        assert_replay_matches_golden("tag_churn").await;
+    }
+
+    /// 回放 `tests/replay/<name>.jsonl` 的全部周期，返回缓存内容
+    async fn replay_dump(name: &str, mode: StorageMode, replay: Replay) -> String {
+        let cycles = replay.cycle_count();
+        let harness = Harness::new(name, mode, replay);
+        for index in 1..=cycles {
+            harness.service.update_cycle().await
+                .unwrap_or_else(|e| panic!("{} 第 {} 个周期回放失败: {}", name, index, e));
+        }
+        harness.dump()
+    }
+
+    /// 回放 `tests/replay/<name>.jsonl`，第 `fault_cycle` 个周期在 `fault` 故障注入下执行：
+    /// 故障周期失败时不得改变缓存内容与同步检查点，之后的周期正常回放。
+    ///
+    /// 故障发生在读取数据源之前时，最终缓存内容应与 golden 文件一致；`source_read` 表示故障发生在
+    /// 读取最新值之后（如写入阶段），这些值与真实数据源一样不会被重读，对照结果为跳过该周期的回放。
+    async fn assert_recovers_from(name: &str, fault_cycle: usize, fault: crate::chaos::ChaosSettings, source_read: bool) {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replay");
+        let session = dir.join(format!("{}.jsonl", name));
+
+        for (label, mode) in [("wide", StorageMode::Wide), ("long", StorageMode::Long)] {
+            let expected = if source_read {
+                let replay = Replay::open(&session).unwrap().without_cycle(fault_cycle - 1);
+                replay_dump(&format!("fault_{}_{}_{}_expected", name, fault_cycle, label), mode, replay).await
+            } else {
+                std::fs::read_to_string(dir.join(format!("{}.golden", name))).unwrap()
+            };
+
+            let replay = Replay::open(&session).unwrap();
+            let cycles = replay.cycle_count();
+            let harness = Harness::new(&format!("fault_{}_{}_{}", name, fault_cycle, label), mode, replay);
+
+            for index in 1..fault_cycle {
+                harness.service.update_cycle().await
+                    .unwrap_or_else(|e| panic!("{} 第 {} 个周期回放失败: {}", name, index, e));
+            }
+
+            let (watermark, before) = (harness.watermark(), harness.dump());
+            crate::chaos::set_for_test(Some(fault.clone()));
+            let result = harness.service.update_cycle().await;
+            crate::chaos::set_for_test(None);
+            assert!(result.is_err(), "{} 在{}模式下故障周期应失败", name, label);
+            assert_eq!(harness.watermark(), watermark, "{} 在{}模式下故障周期推进了同步检查点", name, label);
+            assert_eq!(harness.dump(), before, "{} 在{}模式下故障周期留下了部分写入", name, label);
+            assert!(!harness.db.cycle_in_progress(), "{} 在{}模式下故障周期的事务未结束", name, label);
+
+            let resumed = if source_read { fault_cycle + 1 } else { fault_cycle };
+            for index in resumed..=cycles {
+                harness.service.update_cycle().await
+                    .unwrap_or_else(|e| panic!("{} 恢复后第 {} 个周期回放失败: {}", name, index, e));
+            }
+            assert_eq!(harness.dump(), expected, "{} 在{}模式下恢复后的结果与 golden 文件不一致", name, label);
+        }
+    }
+
+    #[tokio::test]
+    async fn recovers_from_dropped_connection() {
+        let fault = crate::chaos::ChaosSettings { drop_connection_pct: 100.0, ..Default::default() };
+        assert_recovers_from("steady", 2, fault, false).await;
+    }
+
+    #[tokio::test]
+    async fn recovers_from_disk_full() {
+        let fault = crate::chaos::ChaosSettings { disk_full_pct: 100.0, ..Default::default() };
+        assert_recovers_from("tag_churn", 2, fault, true).await;
+    }
+
+    #[tokio::test]
+    async fn slow_source_still_completes_cycles() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replay");
+        let replay = Replay::open(&dir.join("steady.jsonl")).unwrap();
+        let cycles = replay.cycle_count();
+        let harness = Harness::new("fault_slow_source", StorageMode::Wide, replay);
+
+        crate::chaos::set_for_test(Some(crate::chaos::ChaosSettings { query_delay_ms: 20, ..Default::default() }));
+        for index in 1..=cycles {
+            let started = std::time::Instant::now();
+            let result = harness.service.update_cycle().await;
+            assert!(result.is_ok(), "第 {} 个周期在查询延迟下失败: {:?}", index, result);
+            assert!(started.elapsed() >= std::time::Duration::from_millis(20), "查询延迟未生效");
+        }
+        crate::chaos::set_for_test(None);
+
+        let expected = std::fs::read_to_string(dir.join("steady.golden")).unwrap();
+        assert_eq!(harness.dump(), expected);
+    }
+
+    #[tokio::test]
+    async fn corrupt_values_are_stored_as_missing() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replay");
+        let replay = Replay::open(&dir.join("steady.jsonl")).unwrap();
+        let harness = Harness::new("fault_corrupt_values", StorageMode::Wide, replay);
+
+        harness.service.update_cycle().await.unwrap();
+        let before = harness.dump();
+        crate::chaos::set_for_test(Some(crate::chaos::ChaosSettings { corrupt_rows_pct: 100.0, ..Default::default() }));
+        let result = harness.service.update_cycle().await;
+        crate::chaos::set_for_test(None);
+
+        // 损坏的数值按缺失处理：周期成功、水位前进，但不写入任何 NaN 或新的数值
+        assert!(result.is_ok(), "数值损坏的周期失败: {:?}", result);
+        assert!(harness.watermark().is_some());
+        let after = harness.dump();
+        assert!(!after.contains("NaN"), "缓存中写入了 NaN:\n{}", after);
+        assert_eq!(after, before);
+    }
+
+    /// 删除数据后已用空间仍超过上限时记录状态，之后的清理不再删除数据
+    #[tokio::test]
+    async fn size_cap_trim_stops_once_space_is_not_reclaimed() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replay");
+        let replay = Replay::open(&dir.join("steady.jsonl")).unwrap();
+        let cycles = replay.cycle_count();
+        // 空表占用的块已超过 1 MB，删除全部数据也无法降到上限以下
+        let config = AppConfig { max_db_size_mb: Some(1), ..Default::default() };
+        let harness = Harness::with_config("size_cap_stall", config, replay);
+        for index in 1..=cycles {
+            harness.service.update_cycle().await
+                .unwrap_or_else(|e| panic!("第 {} 个周期回放失败: {}", index, e));
+        }
+
+        harness.db.checkpoint().unwrap();
+        harness.service.trim_to_size_cap().await.unwrap();
+        let (cap, _) = harness.db.size_cap_stall().unwrap().expect("未记录大小上限清理状态");
+        assert_eq!(cap, 1024 * 1024);
+
+        let now = Utc::now();
+        let record = crate::database::TimeSeriesRecord {
+            tag_name: "Late".to_string(), timestamp: now, value: Some(1.0), text: None, quality: None,
+        };
+        harness.db.convert_and_insert_wide(&[record], None).unwrap();
+        let records = harness.db.get_record_count().unwrap();
+        assert!(records > 0);
+        harness.service.trim_to_size_cap().await.unwrap();
+        assert_eq!(harness.db.get_record_count().unwrap(), records, "记录状态后仍删除了数据");
+    }
+}
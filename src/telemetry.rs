@@ -0,0 +1,63 @@
+//! OpenTelemetry 追踪
+//! 启用 `[telemetry]` 时将 tracing 的 span 经 OTLP/HTTP 导出到采集器。更新周期 `update_cycle` 为根 span，
+//! 其下为标签变化检测、读取最新数据与 DuckDB 写入等步骤的子 span，用于定位慢周期的耗时所在。
+//! 导出在后台线程中分批进行，采集器不可用时丢弃 span，不影响同步。
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{Protocol, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use std::sync::OnceLock;
+use tracing::{Subscriber, warn};
+use tracing_subscriber::Layer;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::config::TelemetryConfig;
+
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// 按配置创建导出层，未启用时返回 None
+///
+/// 在日志系统初始化之前调用，创建导出器失败时只能打印到标准错误，并且不导出追踪。
+pub fn layer<S>(config: &TelemetryConfig) -> Option<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    if !config.enabled {
+        return None;
+    }
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_protocol(Protocol::HttpBinary)
+        .with_endpoint(config.endpoint.as_str())
+        .build();
+    let exporter = match exporter {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            eprintln!("创建 OTLP 导出器失败，不导出追踪: {}", e);
+            return None;
+        }
+    };
+
+    // 上游已采样的 span 跟随上游的决定，根 span 按比例采样
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio))))
+        .with_resource(Resource::builder().with_service_name(config.service_name.clone()).build())
+        .build();
+    let tracer = provider.tracer("rt_db");
+    let _ = PROVIDER.set(provider);
+
+    Some(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// 发送尚未导出的 span 并关闭导出器，停机时调用（阻塞）
+pub fn shutdown() {
+    let Some(provider) = PROVIDER.get() else {
+        return;
+    };
+    if let Err(e) = provider.shutdown() {
+        warn!("关闭 OpenTelemetry 导出器失败: {}", e);
+    }
+}
@@ -0,0 +1,132 @@
+//! 运行时功能开关
+//! 死区过滤、缩略趋势预计算、导出/推送目标与解析详细日志可通过管理接口在运行时开关，
+//! 故障排查时无需修改配置并重启。开关只保存在内存中，重启后恢复为配置值。
+//!
+//! 每个同步配置（主配置与各 `[[pipelines]]`）有各自的开关，由其 `DatabaseManager` 持有；管理接口修改主配置的开关。
+//! 死区过滤与缩略趋势的运行时开关只能停用配置中已启用的功能，生效值为配置与开关同时启用。
+//!
+//! 推送目标的名称为 `export:<导出任务名>`、`integration:<推送端点名>` 与 `kafka`。
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::AppConfig;
+
+/// 一个同步配置的功能开关
+#[derive(Debug)]
+pub struct FeatureToggles {
+    /// 配置中是否启用死区过滤（`deadband.enabled`）
+    deadband_configured: bool,
+    /// 配置中是否启用缩略趋势预计算（`sparkline.enabled`）
+    sparklines_configured: bool,
+    deadband: AtomicBool,
+    sparklines: AtomicBool,
+    parse_logging: AtomicBool,
+    disabled_sinks: Mutex<HashSet<String>>,
+}
+
+/// 开关当前状态（生效值）
+#[derive(Debug, Clone, Serialize)]
+pub struct ToggleState {
+    pub deadband: bool,
+    pub sparklines: bool,
+    pub parse_logging: bool,
+    /// 推送目标名称 -> 是否启用
+    pub sinks: BTreeMap<String, bool>,
+}
+
+impl FeatureToggles {
+    /// 按配置创建开关，运行时开关初始均不停用任何功能
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            deadband_configured: config.deadband.enabled,
+            sparklines_configured: config.sparkline.enabled,
+            deadband: AtomicBool::new(true),
+            sparklines: AtomicBool::new(true),
+            parse_logging: AtomicBool::new(false),
+            disabled_sinks: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// 是否启用死区过滤
+    pub fn deadband(&self) -> bool {
+        self.deadband_configured && self.deadband.load(Ordering::Relaxed)
+    }
+
+    /// 是否刷新缩略趋势
+    pub fn sparklines(&self) -> bool {
+        self.sparklines_configured && self.sparklines.load(Ordering::Relaxed)
+    }
+
+    /// 是否逐行记录数据源解析详情
+    pub fn parse_logging(&self) -> bool {
+        self.parse_logging.load(Ordering::Relaxed)
+    }
+
+    /// 推送目标是否启用
+    pub fn sink_enabled(&self, sink: &str) -> bool {
+        !self.disabled_sinks.lock().unwrap().contains(sink)
+    }
+
+    /// 运行时启用或停用死区过滤，配置中未启用时启用不生效
+    pub fn set_deadband(&self, enabled: bool) {
+        self.deadband.store(enabled, Ordering::Relaxed);
+    }
+
+    /// 运行时启用或停用缩略趋势刷新，配置中未启用时启用不生效
+    pub fn set_sparklines(&self, enabled: bool) {
+        self.sparklines.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn set_parse_logging(&self, enabled: bool) {
+        self.parse_logging.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn set_sink(&self, sink: &str, enabled: bool) {
+        let mut disabled = self.disabled_sinks.lock().unwrap();
+        if enabled {
+            disabled.remove(sink);
+        } else {
+            disabled.insert(sink.to_string());
+        }
+    }
+
+    /// 当前状态，推送目标列出配置中的全部导出任务与推送端点
+    pub fn state(&self, config: &AppConfig) -> ToggleState {
+        ToggleState {
+            deadband: self.deadband(),
+            sparklines: self.sparklines(),
+            parse_logging: self.parse_logging(),
+            sinks: sink_names(config).into_iter()
+                .map(|sink| {
+                    let enabled = self.sink_enabled(&sink);
+                    (sink, enabled)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// 配置中的全部推送目标名称
+pub fn sink_names(config: &AppConfig) -> Vec<String> {
+    config.export.jobs.iter()
+        .map(|job| export_sink(&job.name))
+        .chain(config.integration.endpoints.iter().map(|endpoint| integration_sink(&endpoint.name)))
+        .chain(config.kafka.enabled.then(|| KAFKA_SINK.to_string()))
+        .collect()
+}
+
+/// Kafka 推送的推送目标名称
+pub const KAFKA_SINK: &str = "kafka";
+
+/// 导出任务的推送目标名称
+pub fn export_sink(job: &str) -> String {
+    format!("export:{}", job)
+}
+
+/// 推送端点的推送目标名称
+pub fn integration_sink(endpoint: &str) -> String {
+    format!("integration:{}", endpoint)
+}